@@ -0,0 +1,514 @@
+//! `#[derive(Vertex)]` and `#[derive(Uniforms)]` for miniquad.
+//!
+//! Use through miniquad's "derive" feature, the generated code refers to `::miniquad`.
+//! See the `miniquad::Vertex` and `miniquad::Uniforms` traits for the attributes.
+//!
+//! ```
+//! #[repr(C)]
+//! #[derive(miniquad::Vertex, miniquad::Uniforms)]
+//! struct Quad {
+//!     pos: [f32; 2],
+//!     size: [f32; 2],
+//! }
+//! ```
+//!
+//! Field types with no vertex format or uniform type are rejected:
+//!
+//! ```compile_fail
+//! #[repr(C)]
+//! #[derive(miniquad::Vertex)]
+//! struct Quad {
+//!     pos: [f32; 2],
+//!     label: String,
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! #[repr(C)]
+//! #[derive(miniquad::Uniforms)]
+//! struct Quad {
+//!     pos: [f32; 2],
+//!     visible: bool,
+//! }
+//! ```
+//!
+//! So is a format that does not match the field size, and padding between the fields:
+//!
+//! ```compile_fail
+//! #[repr(C)]
+//! #[derive(miniquad::Vertex)]
+//! struct Quad {
+//!     #[vertex(format = Float3)]
+//!     pos: [f32; 2],
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! #[repr(C)]
+//! #[derive(miniquad::Vertex)]
+//! struct Quad {
+//!     color: [u8; 3],
+//!     pos: [f32; 2],
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, Field, Fields, Ident, Lit, Type};
+
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Uniforms, attributes(uniform))]
+pub fn derive_uniforms(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    uniforms(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn vertex(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let ident = &input.ident;
+    let fields = repr_c_fields(input, "Vertex")?;
+
+    let mut buffer_index = 0usize;
+    let mut per_instance = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("vertex"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("buffer_index") {
+                buffer_index = int(&meta.value()?.parse()?)?;
+            } else if meta.path.is_ident("per_instance") {
+                per_instance = true;
+            } else {
+                return Err(meta.error("expected `buffer_index = N` or `per_instance`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut attributes = vec![];
+    let mut checks = vec![];
+    let mut offset = quote!(0usize);
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let mut name = field_ident.to_string();
+        let mut format = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("vertex"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = meta.value()?.parse::<syn::LitStr>()?.value();
+                } else if meta.path.is_ident("format") {
+                    format = Some(meta.value()?.parse::<Ident>()?);
+                } else {
+                    return Err(meta.error("expected `name = \"...\"` or `format = ...`"));
+                }
+                Ok(())
+            })?;
+        }
+        let format = match format {
+            Some(format) => format,
+            None => vertex_format(&field.ty).ok_or_else(|| {
+                Error::new_spanned(
+                    &field.ty,
+                    "unknown vertex format, add #[vertex(format = Float2)] or alike",
+                )
+            })?,
+        };
+
+        let ty = &field.ty;
+        let size_message = format!("field `{}` is not the size of {}", field_ident, format);
+        let offset_message = format!(
+            "padding before field `{}`, vertex attributes are read one right after another",
+            field_ident
+        );
+        checks.push(quote! {
+            assert!(
+                ::core::mem::size_of::<#ty>()
+                    == ::miniquad::VertexFormat::#format.size_bytes() as usize,
+                #size_message
+            );
+            assert!(::core::mem::offset_of!(#ident, #field_ident) == #offset, #offset_message);
+        });
+        offset = quote!(#offset + ::core::mem::size_of::<#ty>());
+        attributes.push(quote! {
+            ::miniquad::VertexAttribute::with_buffer(
+                #name,
+                ::miniquad::VertexFormat::#format,
+                #buffer_index,
+            )
+        });
+    }
+
+    let step_func = if per_instance {
+        quote!(PerInstance)
+    } else {
+        quote!(PerVertex)
+    };
+    Ok(quote! {
+        impl ::miniquad::Vertex for #ident {
+            const ATTRIBUTES: &'static [::miniquad::VertexAttribute] = &[#(#attributes),*];
+
+            fn buffer_layout() -> ::miniquad::BufferLayout {
+                ::miniquad::BufferLayout {
+                    stride: ::core::mem::size_of::<#ident>() as i32,
+                    step_func: ::miniquad::VertexStep::#step_func,
+                    step_rate: 1,
+                }
+            }
+        }
+
+        const _: () = {
+            #(#checks)*
+        };
+    })
+}
+
+fn uniforms(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let ident = &input.ident;
+    let fields = repr_c_fields(input, "Uniforms")?;
+
+    let mut uniforms = vec![];
+    let mut checks = vec![];
+    let mut offset = quote!(0usize);
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let mut name = field_ident.to_string();
+        let mut uniform_type = None;
+        let mut array_count = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("uniform"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = meta.value()?.parse::<syn::LitStr>()?.value();
+                } else if meta.path.is_ident("uniform_type") {
+                    uniform_type = Some(meta.value()?.parse::<Ident>()?);
+                } else if meta.path.is_ident("array_count") {
+                    array_count = Some(int(&meta.value()?.parse()?)?);
+                } else {
+                    return Err(meta.error(
+                        "expected `name = \"...\"`, `uniform_type = ...` or `array_count = N`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        let (uniform_type, array_count) = match (uniform_type, uniform_type_of(&field.ty)) {
+            (Some(uniform_type), _) => (uniform_type, array_count.unwrap_or(1)),
+            (None, Some((uniform_type, count))) => (uniform_type, array_count.unwrap_or(count)),
+            (None, None) => {
+                return Err(Error::new_spanned(
+                    &field.ty,
+                    "unknown uniform type, add #[uniform(uniform_type = Mat4)] or alike",
+                ))
+            }
+        };
+
+        let ty = &field.ty;
+        let size_message = format!(
+            "field `{}` is not the size of {} x {}",
+            field_ident, array_count, uniform_type
+        );
+        let offset_message = format!(
+            "padding before field `{}`, uniforms are read one right after another",
+            field_ident
+        );
+        checks.push(quote! {
+            assert!(
+                ::core::mem::size_of::<#ty>()
+                    == ::miniquad::UniformType::#uniform_type.size() * #array_count,
+                #size_message
+            );
+            assert!(::core::mem::offset_of!(#ident, #field_ident) == #offset, #offset_message);
+        });
+        offset = quote!(#offset + ::core::mem::size_of::<#ty>());
+        uniforms.push(quote! {
+            ::miniquad::UniformDesc::new(#name, ::miniquad::UniformType::#uniform_type)
+                .array(#array_count)
+        });
+    }
+
+    Ok(quote! {
+        impl ::miniquad::Uniforms for #ident {
+            fn uniform_layout() -> ::miniquad::UniformBlockLayout {
+                ::miniquad::UniformBlockLayout {
+                    uniforms: vec![#(#uniforms),*],
+                }
+            }
+        }
+
+        const _: () = {
+            #(#checks)*
+        };
+    })
+}
+
+/// Named fields of a non-generic `#[repr(C)]` struct.
+fn repr_c_fields<'a>(input: &'a DeriveInput, derive: &str) -> Result<Vec<&'a Field>, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+    if fields.is_empty() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!("derive({}) needs a struct with named fields", derive),
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            format!("derive({}) does not support generic structs", derive),
+        ));
+    }
+    let repr_c = input.attrs.iter().any(|attr| {
+        attr.path().is_ident("repr")
+            && attr.meta.require_list().is_ok_and(|list| {
+                list.tokens
+                    .clone()
+                    .into_iter()
+                    .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "C"))
+            })
+    });
+    if !repr_c {
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!(
+                "derive({}) needs #[repr(C)], Rust may reorder the fields otherwise",
+                derive
+            ),
+        ));
+    }
+    Ok(fields)
+}
+
+fn int(expr: &Expr) -> Result<usize, Error> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse(),
+            _ => Err(Error::new_spanned(expr, "expected an integer")),
+        },
+        _ => Err(Error::new_spanned(expr, "expected an integer")),
+    }
+}
+
+/// `f32` to `("f32", 1)`, `[u8; 4]` to `("u8", 4)`.
+fn scalars(ty: &Type) -> Option<(String, usize)> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => Some((path.path.get_ident()?.to_string(), 1)),
+        Type::Array(array) => match scalars(&array.elem)? {
+            (scalar, 1) => Some((scalar, int(&array.len).ok()?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_mat4(ty: &Type) -> bool {
+    match ty {
+        Type::Array(array) => {
+            int(&array.len).ok() == Some(4) && scalars(&array.elem) == Some(("f32".into(), 4))
+        }
+        _ => false,
+    }
+}
+
+fn vertex_format(ty: &Type) -> Option<Ident> {
+    if is_mat4(ty) {
+        return Some(Ident::new("Mat4", Span::call_site()));
+    }
+    let (scalar, count) = scalars(ty)?;
+    let prefix = match scalar.as_str() {
+        "f32" => "Float",
+        "u8" => "Byte",
+        "u16" => "Short",
+        "u32" => "Int",
+        _ => return None,
+    };
+    if !(1..=4).contains(&count) {
+        return None;
+    }
+    Some(format_ident!("{}{}", prefix, count))
+}
+
+/// Uniform type and array count: `[f32; 2]` is a `Float2`, `[[f32; 2]; 3]` three of them
+/// and `[f32; 8]` eight `Float1`.
+fn uniform_type_of(ty: &Type) -> Option<(Ident, usize)> {
+    fn single(ty: &Type) -> Option<Ident> {
+        if is_mat4(ty) {
+            return Some(Ident::new("Mat4", Span::call_site()));
+        }
+        let (scalar, count) = scalars(ty)?;
+        let prefix = match scalar.as_str() {
+            "f32" => "Float",
+            "i32" => "Int",
+            _ => return None,
+        };
+        if !(1..=4).contains(&count) {
+            return None;
+        }
+        Some(format_ident!("{}{}", prefix, count))
+    }
+
+    if let Some(uniform_type) = single(ty) {
+        return Some((uniform_type, 1));
+    }
+    match ty {
+        Type::Array(array) => Some((single(&array.elem)?, int(&array.len).ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+fn error(result: Result<TokenStream2, Error>) -> String {
+    result.map(drop).unwrap_err().to_string()
+}
+
+#[test]
+fn test_vertex_rejected() {
+    let unsupported: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Vertex {
+            pos: [f32; 2],
+            label: String,
+        }
+    };
+    assert_eq!(
+        error(vertex(&unsupported)),
+        "unknown vertex format, add #[vertex(format = Float2)] or alike"
+    );
+    let too_wide: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Vertex {
+            weights: [f32; 5],
+        }
+    };
+    assert_eq!(
+        error(vertex(&too_wide)),
+        "unknown vertex format, add #[vertex(format = Float2)] or alike"
+    );
+    let signed: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Vertex {
+            color: [i8; 4],
+        }
+    };
+    assert_eq!(
+        error(vertex(&signed)),
+        "unknown vertex format, add #[vertex(format = Float2)] or alike"
+    );
+
+    let not_repr_c: DeriveInput = syn::parse_quote! {
+        struct Vertex {
+            pos: [f32; 2],
+        }
+    };
+    assert_eq!(
+        error(vertex(&not_repr_c)),
+        "derive(Vertex) needs #[repr(C)], Rust may reorder the fields otherwise"
+    );
+    let generic: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Vertex<T> {
+            pos: T,
+        }
+    };
+    assert_eq!(
+        error(vertex(&generic)),
+        "derive(Vertex) does not support generic structs"
+    );
+    let tuple: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Vertex([f32; 2]);
+    };
+    assert_eq!(
+        error(vertex(&tuple)),
+        "derive(Vertex) needs a struct with named fields"
+    );
+    let unknown_attribute: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        #[vertex(step_rate = 2)]
+        struct Vertex {
+            pos: [f32; 2],
+        }
+    };
+    assert_eq!(
+        error(vertex(&unknown_attribute)),
+        "expected `buffer_index = N` or `per_instance`"
+    );
+}
+
+#[test]
+fn test_uniforms_rejected() {
+    let unsupported: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Uniforms {
+            time: f32,
+            enabled: bool,
+        }
+    };
+    assert_eq!(
+        error(uniforms(&unsupported)),
+        "unknown uniform type, add #[uniform(uniform_type = Mat4)] or alike"
+    );
+    // vertex formats with no uniform counterpart
+    let bytes: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Uniforms {
+            color: [u8; 4],
+        }
+    };
+    assert_eq!(
+        error(uniforms(&bytes)),
+        "unknown uniform type, add #[uniform(uniform_type = Mat4)] or alike"
+    );
+
+    let not_repr_c: DeriveInput = syn::parse_quote! {
+        #[repr(packed)]
+        struct Uniforms {
+            time: f32,
+        }
+    };
+    assert_eq!(
+        error(uniforms(&not_repr_c)),
+        "derive(Uniforms) needs #[repr(C)], Rust may reorder the fields otherwise"
+    );
+    let unit: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Uniforms;
+    };
+    assert_eq!(
+        error(uniforms(&unit)),
+        "derive(Uniforms) needs a struct with named fields"
+    );
+    let unknown_attribute: DeriveInput = syn::parse_quote! {
+        #[repr(C)]
+        struct Uniforms {
+            #[uniform(format = Float1)]
+            time: f32,
+        }
+    };
+    assert_eq!(
+        error(uniforms(&unknown_attribute)),
+        "expected `name = \"...\"`, `uniform_type = ...` or `array_count = N`"
+    );
+}
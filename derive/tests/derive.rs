@@ -0,0 +1,84 @@
+use miniquad::{UniformType, Uniforms, Vertex, VertexFormat, VertexStep};
+
+#[repr(C)]
+#[derive(Vertex)]
+struct Sprite {
+    // not sorted by name or size, attributes must keep the declaration order
+    uv: [f32; 2],
+    color: [u8; 4],
+    pos: [f32; 3],
+    #[vertex(format = Short2)]
+    frame: [u16; 2],
+    model: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Uniforms)]
+struct Light {
+    intensity: f32,
+    direction: [f32; 3],
+    range: f32,
+    weights: [f32; 8],
+    offsets: [[f32; 2]; 3],
+    mvp: [[f32; 4]; 4],
+}
+
+#[test]
+fn test_vertex_field_order() {
+    let attributes: Vec<_> = Sprite::ATTRIBUTES
+        .iter()
+        .map(|attribute| (attribute.name, attribute.format, attribute.buffer_index))
+        .collect();
+    assert_eq!(
+        attributes,
+        [
+            ("uv", VertexFormat::Float2, 0),
+            ("color", VertexFormat::Byte4, 0),
+            ("pos", VertexFormat::Float3, 0),
+            ("frame", VertexFormat::Short2, 0),
+            ("model", VertexFormat::Mat4, 0),
+        ]
+    );
+
+    let layout = Sprite::buffer_layout();
+    assert_eq!(layout.stride, 8 + 4 + 12 + 4 + 64);
+    assert_eq!(layout.step_func, VertexStep::PerVertex);
+    assert_eq!(layout.step_rate, 1);
+    let sizes: i32 = Sprite::ATTRIBUTES
+        .iter()
+        .map(|attribute| attribute.format.size_bytes())
+        .sum();
+    assert_eq!(sizes, layout.stride);
+}
+
+#[test]
+fn test_uniforms_std140_layout() {
+    let layout = Light::uniform_layout();
+    let uniforms: Vec<_> = layout
+        .uniforms
+        .iter()
+        .map(|uniform| {
+            (
+                uniform.name.as_str(),
+                uniform.uniform_type,
+                uniform.array_count,
+            )
+        })
+        .collect();
+    assert_eq!(
+        uniforms,
+        [
+            ("intensity", UniformType::Float1, 1),
+            ("direction", UniformType::Float3, 1),
+            ("range", UniformType::Float1, 1),
+            ("weights", UniformType::Float1, 8),
+            ("offsets", UniformType::Float2, 3),
+            ("mvp", UniformType::Mat4, 1),
+        ]
+    );
+
+    // the struct itself is tightly packed, the std140 block pads vec3s and array elements
+    assert_eq!(std::mem::size_of::<Light>(), 4 + 12 + 4 + 32 + 24 + 64);
+    assert_eq!(layout.std140_offsets(), [0, 16, 28, 32, 160, 208]);
+    assert_eq!(layout.std140_size(), 272);
+}
@@ -47,7 +47,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![vertex_buffer],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer,
+            index_buffer_offset: 0,
             images: vec![],
         };
 
@@ -333,6 +335,7 @@ mod shader {
                     UniformDesc::new("blobs_positions", UniformType::Float2).array(32),
                 ],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -58,7 +58,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer,
+            index_buffer_offset: 0,
             images: vec![],
         };
 
@@ -254,6 +256,7 @@ mod shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -262,3 +265,24 @@ mod shader {
         pub mvp: glam::Mat4,
     }
 }
+
+#[cfg(all(feature = "test-support", target_os = "linux"))]
+#[test]
+fn golden() {
+    use miniquad::test_support::{assert_golden, capture_frames};
+
+    let conf = conf::Conf {
+        window_width: 128,
+        window_height: 128,
+        ..Default::default()
+    };
+    let image = capture_frames(conf, 60, || Box::new(Stage::new()));
+    assert_golden(
+        &image,
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/golden/instancing.png"
+        ),
+        2,
+    );
+}
@@ -90,13 +90,17 @@ impl Stage {
 
         let offscreen_bind = Bindings {
             vertex_buffers: vec![vertex_buffer.clone()],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer.clone(),
+            index_buffer_offset: 0,
             images: vec![],
         };
 
         let display_bind = Bindings {
             vertex_buffers: vec![vertex_buffer],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer,
+            index_buffer_offset: 0,
             images: vec![color_img],
         };
 
@@ -300,6 +304,7 @@ mod display_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -376,6 +381,25 @@ mod offscreen_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 }
+
+#[cfg(all(feature = "test-support", target_os = "linux"))]
+#[test]
+fn golden() {
+    use miniquad::test_support::{assert_golden, capture_frames};
+
+    let conf = conf::Conf {
+        window_width: 128,
+        window_height: 128,
+        ..Default::default()
+    };
+    let image = capture_frames(conf, 10, || Box::new(Stage::new()));
+    assert_golden(
+        &image,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/examples/golden/offscreen.png"),
+        2,
+    );
+}
@@ -22,12 +22,14 @@ impl Stage {
             width: w as _,
             height: h as _,
             format: TextureFormat::RGBA8,
+            sample_count: 4,
             ..Default::default()
         });
         let depth_img = ctx.new_render_texture(TextureParams {
             width: w as _,
             height: h as _,
             format: TextureFormat::Depth,
+            sample_count: 4,
             ..Default::default()
         });
 
@@ -91,7 +93,9 @@ impl Stage {
 
         let offscreen_bind = Bindings {
             vertex_buffers: vec![vertex_buffer.clone()],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer.clone(),
+            index_buffer_offset: 0,
             images: vec![],
         };
 
@@ -120,7 +124,9 @@ impl Stage {
 
         let post_processing_bind = Bindings {
             vertex_buffers: vec![vertex_buffer],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer,
+            index_buffer_offset: 0,
             images: vec![color_img],
         };
 
@@ -195,12 +201,14 @@ impl EventHandler for Stage {
             width: width as _,
             height: height as _,
             format: TextureFormat::RGBA8,
+            sample_count: 4,
             ..Default::default()
         });
         let depth_img = self.ctx.new_render_texture(TextureParams {
             width: width as _,
             height: height as _,
             format: TextureFormat::Depth,
+            sample_count: 4,
             ..Default::default()
         });
 
@@ -307,6 +315,7 @@ mod post_processing_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("resolution", UniformType::Float2)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -348,6 +357,7 @@ mod offscreen_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -356,3 +366,24 @@ mod offscreen_shader {
         pub mvp: glam::Mat4,
     }
 }
+
+#[cfg(all(feature = "test-support", target_os = "linux"))]
+#[test]
+fn golden() {
+    use miniquad::test_support::{assert_golden, capture_frames};
+
+    let conf = conf::Conf {
+        window_width: 128,
+        window_height: 128,
+        ..Default::default()
+    };
+    let image = capture_frames(conf, 10, || Box::new(Stage::new()));
+    assert_golden(
+        &image,
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/golden/post_processing.png"
+        ),
+        2,
+    );
+}
@@ -53,7 +53,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![vertex_buffer],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer,
+            index_buffer_offset: 0,
             images: vec![texture],
         };
 
@@ -90,12 +92,8 @@ impl Stage {
     }
 }
 
-impl EventHandler for Stage {
-    fn update(&mut self) {}
-
-    fn draw(&mut self) {
-        let t = date::now();
-
+impl Stage {
+    fn draw_at(&mut self, t: f64) {
         self.ctx.begin_default_pass(Default::default());
 
         self.ctx.apply_pipeline(&self.pipeline);
@@ -115,6 +113,14 @@ impl EventHandler for Stage {
     }
 }
 
+impl EventHandler for Stage {
+    fn update(&mut self) {}
+
+    fn draw(&mut self) {
+        self.draw_at(date::now());
+    }
+}
+
 fn main() {
     let mut conf = conf::Conf::default();
     let metal = std::env::args().nth(1).as_deref() == Some("metal");
@@ -198,6 +204,7 @@ mod shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("offset", UniformType::Float2)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -206,3 +213,30 @@ mod shader {
         pub offset: (f32, f32),
     }
 }
+
+#[cfg(all(feature = "test-support", target_os = "linux"))]
+#[test]
+fn golden() {
+    use miniquad::test_support::{assert_golden, capture_frames};
+
+    // the quads move with the time
+    struct FixedTime(Stage);
+    impl EventHandler for FixedTime {
+        fn update(&mut self) {}
+        fn draw(&mut self) {
+            self.0.draw_at(1.0);
+        }
+    }
+
+    let conf = conf::Conf {
+        window_width: 128,
+        window_height: 128,
+        ..Default::default()
+    };
+    let image = capture_frames(conf, 1, || Box::new(FixedTime(Stage::new())));
+    assert_golden(
+        &image,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/examples/golden/quad.png"),
+        2,
+    );
+}
@@ -0,0 +1,50 @@
+//! Replays a `.mqtrace` recorded with `TracingBackend`, one frame per `draw`:
+//!
+//! cargo run --example replay -- bug.mqtrace
+//! cargo run --example replay -- bug.mqtrace --headless
+
+use miniquad::*;
+
+struct Stage {
+    ctx: Box<dyn RenderingBackend>,
+    replay: Replay,
+    frame: usize,
+}
+
+impl EventHandler for Stage {
+    fn update(&mut self) {}
+
+    fn draw(&mut self) {
+        match self.replay.replay_frame(&mut *self.ctx) {
+            Ok(true) => self.frame += 1,
+            Ok(false) => {
+                println!("Replayed {} frames", self.frame + 1);
+                window::order_quit();
+            }
+            Err(err) => {
+                eprintln!("Replay failed at frame {}: {}", self.frame, err);
+                window::order_quit();
+            }
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("usage: replay <trace.mqtrace> [--headless]");
+    let replay = Replay::new(std::fs::read(path).unwrap()).unwrap();
+
+    let mut conf = conf::Conf::default();
+    if args.any(|arg| arg == "--headless") {
+        conf.platform.linux_backend = conf::LinuxBackend::Headless;
+    }
+    miniquad::start(conf, move || {
+        Box::new(Stage {
+            ctx: window::new_rendering_backend(),
+            replay,
+            frame: 0,
+        })
+    });
+}
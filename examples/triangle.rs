@@ -37,7 +37,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![vertex_buffer],
+            vertex_buffer_offsets: vec![],
             index_buffer: index_buffer,
+            index_buffer_offset: 0,
             images: vec![],
         };
 
@@ -158,6 +160,7 @@ mod shader {
         ShaderMeta {
             images: vec![],
             uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
         }
     }
 }
@@ -89,9 +89,20 @@ function init_webgl(version) {
         }
     } else {
         gl = canvas.getContext("webgl2");
+        if (gl !== null) {
+            gl.getExtension('EXT_disjoint_timer_query_webgl2');
+            gl['getQueryObject'] = gl['getQueryParameter'];
+        }
     }
     if (gl === null) {
         alert("Unable to initialize WebGL. Your browser or machine may not support it.");
+    } else {
+        // compressed formats, float render targets and filtering may only be used after the extension was enabled
+        ["WEBGL_compressed_texture_s3tc", "EXT_texture_compression_rgtc", "EXT_texture_compression_bptc",
+            "WEBGL_compressed_texture_etc", "WEBGL_compressed_texture_astc",
+            "EXT_color_buffer_float", "OES_texture_float_linear"].forEach(function (name) {
+                gl.getExtension(name);
+            });
     }
 }
 
@@ -213,6 +224,9 @@ var GL = {
     shaders: [],
     vaos: [],
     timerQueries: [],
+    syncs: [],
+    // glReadPixels takes an offset instead of a pointer with a GL_PIXEL_PACK_BUFFER bound
+    packBufferBound: false,
     contexts: {},
     programInfos: {},
 
@@ -305,6 +319,29 @@ function _glGenObject(n, buffers, createFunction, objectTable, functionName) {
     }
 }
 
+// Write a nul-terminated, possibly truncated string into a bufSize bytes buffer
+function _webglWriteName(str, bufSize, length, name) {
+    var written = 0;
+    if (bufSize > 0) {
+        var heap = getArray(name, Uint8Array, bufSize);
+        written = stringToUTF8(str, heap, 0, bufSize - 1);
+        heap[written] = 0;
+    }
+    if (length) {
+        getArray(length, Int32Array, 1)[0] = written;
+    }
+}
+
+function _webglActiveInfo(info, bufSize, length, size, type, name) {
+    if (!info) {
+        GL.recordError(0x501 /* GL_INVALID_VALUE */);
+        return;
+    }
+    _webglWriteName(info.name, bufSize, length, name);
+    getArray(size, Int32Array, 1)[0] = info.size;
+    getArray(type, Uint32Array, 1)[0] = info.type;
+}
+
 function _webglGet(name_, p, type) {
     // Guard against user passing a null pointer.
     // Note that GLES2 spec does not say anything about how passing a null pointer should be treated.
@@ -368,6 +405,7 @@ function _webglGet(name_, p, type) {
                         case 0x8B8D: // CURRENT_PROGRAM
                         case 0x8895: // ELEMENT_ARRAY_BUFFER_BINDING
                         case 0x8CA6: // FRAMEBUFFER_BINDING
+                        case 0x8CAA: // READ_FRAMEBUFFER_BINDING
                         case 0x8CA7: // RENDERBUFFER_BINDING
                         case 0x8069: // TEXTURE_BINDING_2D
                         case 0x85B5: // WebGL 2 GL_VERTEX_ARRAY_BINDING, or WebGL 1 extension OES_vertex_array_object GL_VERTEX_ARRAY_BINDING_OES
@@ -595,17 +633,25 @@ function dpi_scale() {
     }
 }
 
-function texture_size(internalFormat, width, height) {
-    if (internalFormat == gl.ALPHA) {
-        return width * height;
+// WebGL wants the typed array of the pixel data to match its type,
+// Float32Array for gl.FLOAT, Uint16Array for gl.HALF_FLOAT and so on
+function pixel_array(pixels, format, type, count) {
+    var components = 1; // ALPHA, RED, RED_INTEGER, DEPTH_COMPONENT, DEPTH_STENCIL
+    if (format == 0x8227 /* RG */) {
+        components = 2;
+    } else if (format == 0x1907 /* RGB */) {
+        components = 3;
+    } else if (format == 0x1908 /* RGBA */ || format == 0x8D99 /* RGBA_INTEGER */) {
+        components = 4;
     }
-    else if (internalFormat == gl.RGB) {
-        return width * height * 3;
-    } else if (internalFormat == gl.RGBA) {
-        return width * height * 4;
-    } else { // TextureFormat::RGB565 | TextureFormat::RGBA4 | TextureFormat::RGBA5551
-        return width * height * 3;
+    if (type == 0x1406 /* FLOAT */) {
+        return getArray(pixels, Float32Array, count * components);
+    } else if (type == 0x140B /* HALF_FLOAT */ || type == 0x1403 /* UNSIGNED_SHORT */) {
+        return getArray(pixels, Uint16Array, count * components);
+    } else if (type == 0x1405 /* UNSIGNED_INT */ || type == 0x84FA /* UNSIGNED_INT_24_8 */) {
+        return getArray(pixels, Uint32Array, count * components);
     }
+    return getArray(pixels, Uint8Array, count * components);
 }
 
 function mouse_relative_position(clientX, clientY) {
@@ -685,14 +731,45 @@ var importObject = {
         },
         glTexImage2D: function (target, level, internalFormat, width, height, border, format, type, pixels) {
             gl.texImage2D(target, level, internalFormat, width, height, border, format, type,
-                pixels ? getArray(pixels, Uint8Array, texture_size(internalFormat, width, height)) : null);
+                pixels ? pixel_array(pixels, format, type, width * height) : null);
         },
         glTexSubImage2D: function (target, level, xoffset, yoffset, width, height, format, type, pixels) {
             gl.texSubImage2D(target, level, xoffset, yoffset, width, height, format, type,
-                pixels ? getArray(pixels, Uint8Array, texture_size(format, width, height)) : null);
+                pixels ? pixel_array(pixels, format, type, width * height) : null);
+        },
+        glTexImage3D: function (target, level, internalFormat, width, height, depth, border, format, type, pixels) {
+            gl.texImage3D(target, level, internalFormat, width, height, depth, border, format, type,
+                pixels ? pixel_array(pixels, format, type, width * height * depth) : null);
+        },
+        glTexSubImage3D: function (target, level, xoffset, yoffset, zoffset, width, height, depth, format, type, pixels) {
+            gl.texSubImage3D(target, level, xoffset, yoffset, zoffset, width, height, depth, format, type,
+                pixels ? pixel_array(pixels, format, type, width * height * depth) : null);
+        },
+        glCompressedTexImage2D: function (target, level, internalFormat, width, height, border, imageSize, data) {
+            gl.compressedTexImage2D(target, level, internalFormat, width, height, border,
+                getArray(data, Uint8Array, imageSize));
+        },
+        glCompressedTexSubImage2D: function (target, level, xoffset, yoffset, width, height, format, imageSize, data) {
+            gl.compressedTexSubImage2D(target, level, xoffset, yoffset, width, height, format,
+                getArray(data, Uint8Array, imageSize));
+        },
+        glCompressedTexImage3D: function (target, level, internalFormat, width, height, depth, border, imageSize, data) {
+            gl.compressedTexImage3D(target, level, internalFormat, width, height, depth, border,
+                getArray(data, Uint8Array, imageSize));
+        },
+        glCompressedTexSubImage3D: function (target, level, xoffset, yoffset, zoffset, width, height, depth, format, imageSize, data) {
+            gl.compressedTexSubImage3D(target, level, xoffset, yoffset, zoffset, width, height, depth, format,
+                getArray(data, Uint8Array, imageSize));
+        },
+        glReadBuffer: function (src) {
+            gl.readBuffer(src);
         },
         glReadPixels: function (x, y, width, height, format, type, pixels) {
-            var pixelData = getArray(pixels, Uint8Array, texture_size(format, width, height));
+            if (GL.packBufferBound) {
+                gl.readPixels(x, y, width, height, format, type, pixels);
+                return;
+            }
+            var pixelData = pixel_array(pixels, format, type, width * height);
             gl.readPixels(x, y, width, height, format, type, pixelData);
         },
         glTexParameteri: function (target, pname, param) {
@@ -829,8 +906,29 @@ var importObject = {
         },
         glBindBuffer: function (target, buffer) {
             GL.validateGLObjectID(GL.buffers, buffer, 'glBindBuffer', 'buffer');
+            if (target == 0x88EB /* GL_PIXEL_PACK_BUFFER */) {
+                GL.packBufferBound = buffer != 0;
+            }
             gl.bindBuffer(target, GL.buffers[buffer]);
         },
+        glGetBufferSubData: function (target, offset, size, data) {
+            gl.getBufferSubData(target, offset, getArray(data, Uint8Array, size));
+        },
+        glFenceSync: function (condition, flags) {
+            var id = GL.getNewId(GL.syncs);
+            GL.syncs[id] = gl.fenceSync(condition, flags);
+            return id;
+        },
+        glClientWaitSync: function (sync, flags, timeout) {
+            GL.validateGLObjectID(GL.syncs, sync, 'glClientWaitSync', 'sync');
+            // WebGL does not allow blocking, the only valid timeout is 0
+            return gl.clientWaitSync(GL.syncs[sync], flags, 0);
+        },
+        glDeleteSync: function (sync) {
+            if (!GL.syncs[sync]) return;
+            gl.deleteSync(GL.syncs[sync]);
+            GL.syncs[sync] = null;
+        },
         glBufferData: function (target, size, data, usage) {
             gl.bufferData(target, data ? getArray(data, Uint8Array, size) : size, usage);
         },
@@ -890,6 +988,10 @@ var importObject = {
             GL.validateGLObjectID(GL.textures, texture, 'glFramebufferTexture2D', 'texture');
             gl.framebufferTexture2D(target, attachment, textarget, GL.textures[texture], level);
         },
+        glFramebufferTextureLayer: function (target, attachment, texture, level, layer) {
+            GL.validateGLObjectID(GL.textures, texture, 'glFramebufferTextureLayer', 'texture');
+            gl.framebufferTextureLayer(target, attachment, GL.textures[texture], level, layer);
+        },
         glGetProgramiv: function (program, pname, p) {
             assert(p);
             GL.validateGLObjectID(GL.programs, program, 'glGetProgramiv', 'program');
@@ -940,6 +1042,9 @@ var importObject = {
         glCullFace: function (mode) {
             gl.cullFace(mode);
         },
+        glCopyTexSubImage2D: function (target, level, xoffset, yoffset, x, y, width, height) {
+            gl.copyTexSubImage2D(target, level, xoffset, yoffset, x, y, width, height);
+        },
         glCopyTexImage2D: function (target, level, internalformat, x, y, width, height, border) {
             gl.copyTexImage2D(target, level, internalformat, x, y, width, height, border);
         },
@@ -992,7 +1097,9 @@ var importObject = {
         glGetString: function (id) {
             // getParameter returns "any": it could be GLenum, String or whatever,
             // depending on the id.
-            var parameter = gl.getParameter(id).toString();
+            var parameter = id == 0x1F03 /* GL_EXTENSIONS */
+                ? gl.getSupportedExtensions().join(' ')
+                : gl.getParameter(id).toString();
             var len = parameter.length + 1;
             var msg = wasm_exports.allocate_vec_u8(len);
             var array = new Uint8Array(wasm_memory.buffer, msg, len);
@@ -1039,6 +1146,16 @@ var importObject = {
         glDrawElementsInstanced: function (mode, count, type, indices, primcount) {
             gl.drawElementsInstanced(mode, count, type, indices, primcount);
         },
+        // not a part of WebGL, Features::draw_base_vertex and draw_indirect are false there
+        glDrawElementsInstancedBaseVertex: function (mode, count, type, indices, primcount, basevertex) {
+            assert(false, "glDrawElementsInstancedBaseVertex is not supported on WebGL");
+        },
+        glDrawElementsIndirect: function (mode, type, indirect) {
+            assert(false, "glDrawElementsIndirect is not supported on WebGL");
+        },
+        glMultiDrawElementsIndirect: function (mode, type, indirect, drawcount, stride) {
+            assert(false, "glMultiDrawElementsIndirect is not supported on WebGL");
+        },
         glDeleteShader: function (shader) {
             var id = GL.shaders[shader];
             if (id == null) { return }
@@ -1079,6 +1196,34 @@ var importObject = {
                 GL.framebuffers[id] = null;
             }
         },
+        glGenRenderbuffers: function (n, ids) {
+            _glGenObject(n, ids, 'createRenderbuffer', GL.renderbuffers, 'glGenRenderbuffers');
+        },
+        glBindRenderbuffer: function (target, renderbuffer) {
+            GL.validateGLObjectID(GL.renderbuffers, renderbuffer, 'glBindRenderbuffer', 'renderbuffer');
+            gl.bindRenderbuffer(target, GL.renderbuffers[renderbuffer]);
+        },
+        glDeleteRenderbuffers: function (n, renderbuffers) {
+            for (var i = 0; i < n; i++) {
+                var id = getArray(renderbuffers + i * 4, Uint32Array, 1)[0];
+                var renderbuffer = GL.renderbuffers[id];
+                if (!renderbuffer) continue;
+
+                gl.deleteRenderbuffer(renderbuffer);
+                renderbuffer.name = 0;
+                GL.renderbuffers[id] = null;
+            }
+        },
+        glRenderbufferStorageMultisample: function (target, samples, internalformat, width, height) {
+            gl.renderbufferStorageMultisample(target, samples, internalformat, width, height);
+        },
+        glFramebufferRenderbuffer: function (target, attachment, renderbuffertarget, renderbuffer) {
+            GL.validateGLObjectID(GL.renderbuffers, renderbuffer, 'glFramebufferRenderbuffer', 'renderbuffer');
+            gl.framebufferRenderbuffer(target, attachment, renderbuffertarget, GL.renderbuffers[renderbuffer]);
+        },
+        glBlitFramebuffer: function (srcX0, srcY0, srcX1, srcY1, dstX0, dstY0, dstX1, dstY1, mask, filter) {
+            gl.blitFramebuffer(srcX0, srcY0, srcX1, srcY1, dstX0, dstY0, dstX1, dstY1, mask, filter);
+        },
         glDeleteTextures: function (n, textures) {
             for (var i = 0; i < n; i++) {
                 var id = getArray(textures + i * 4, Uint32Array, 1)[0];
@@ -1094,7 +1239,7 @@ var importObject = {
         },
         glDeleteQueries: function (n, ids) {
             for (var i = 0; i < n; i++) {
-                var id = getArray(textures + i * 4, Uint32Array, 1)[0];
+                var id = getArray(ids + i * 4, Uint32Array, 1)[0];
                 var query = GL.timerQueries[id];
                 if (!query) {
                     continue;
@@ -1116,6 +1261,11 @@ var importObject = {
             let result = gl.getQueryObject(GL.timerQueries[id], pname);
             getArray(ptr, Uint32Array, 1)[0] = result;
         },
+        glGetQueryObjectuiv: function (id, pname, ptr) {
+            GL.validateGLObjectID(GL.timerQueries, id, 'glGetQueryObjectuiv', 'id');
+            let result = gl.getQueryObject(GL.timerQueries[id], pname);
+            getArray(ptr, Uint32Array, 1)[0] = result;
+        },
         glGetQueryObjectui64v: function (id, pname, ptr) {
             GL.validateGLObjectID(GL.timerQueries, id, 'glGetQueryObjectui64v', 'id');
             let result = gl.getQueryObject(GL.timerQueries[id], pname);
@@ -1123,6 +1273,54 @@ var importObject = {
             heap[0] = result;
             heap[1] = (result - heap[0]) / 4294967296;
         },
+        glBindBufferRange: function (target, index, buffer, offset, size) {
+            GL.validateGLObjectID(GL.buffers, buffer, 'glBindBufferRange', 'buffer');
+            gl.bindBufferRange(target, index, GL.buffers[buffer], offset, size);
+        },
+        glGetUniformBlockIndex: function (program, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetUniformBlockIndex', 'program');
+            return gl.getUniformBlockIndex(GL.programs[program], UTF8ToString(name));
+        },
+        glUniformBlockBinding: function (program, index, binding) {
+            GL.validateGLObjectID(GL.programs, program, 'glUniformBlockBinding', 'program');
+            gl.uniformBlockBinding(GL.programs[program], index, binding);
+        },
+        glGetActiveUniformBlockiv: function (program, index, pname, ptr) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniformBlockiv', 'program');
+            let result = gl.getActiveUniformBlockParameter(GL.programs[program], index, pname);
+            getArray(ptr, Int32Array, 1)[0] = result;
+        },
+        glGetUniformIndices: function (program, count, names, ptr) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetUniformIndices', 'program');
+            var name_ptrs = getArray(names, Uint32Array, count);
+            var js_names = [];
+            for (var i = 0; i < count; i++) {
+                js_names.push(UTF8ToString(name_ptrs[i]));
+            }
+            let result = gl.getUniformIndices(GL.programs[program], js_names);
+            getArray(ptr, Uint32Array, count).set(result);
+        },
+        glGetActiveUniformsiv: function (program, count, indices, pname, ptr) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniformsiv', 'program');
+            var js_indices = Array.from(getArray(indices, Uint32Array, count));
+            let result = gl.getActiveUniforms(GL.programs[program], js_indices, pname);
+            getArray(ptr, Int32Array, count).set(result);
+        },
+        glGetActiveUniform: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniform', 'program');
+            let info = gl.getActiveUniform(GL.programs[program], index);
+            _webglActiveInfo(info, bufSize, length, size, type, name);
+        },
+        glGetActiveAttrib: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveAttrib', 'program');
+            let info = gl.getActiveAttrib(GL.programs[program], index);
+            _webglActiveInfo(info, bufSize, length, size, type, name);
+        },
+        glGetActiveUniformBlockName: function (program, index, bufSize, length, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniformBlockName', 'program');
+            let result = gl.getActiveUniformBlockName(GL.programs[program], index);
+            _webglWriteName(result, bufSize, length, name);
+        },
         glGenerateMipmap: function (index) {
             gl.generateMipmap(index);
         },
@@ -60,6 +60,11 @@ pub enum LinuxBackend {
     WaylandOnly,
     X11WithWaylandFallback,
     WaylandWithX11Fallback,
+    /// No display server: EGL on the surfaceless platform or with a pbuffer.
+    /// The default pass renders into an offscreen framebuffer of
+    /// `window_width` x `window_height` and there are no input events.
+    /// See `Platform::headless_frames`.
+    Headless,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -126,6 +131,15 @@ pub struct Platform {
     /// Whether to draw the default window decorations on Wayland.
     /// Only works when using the Wayland backend.
     pub wayland_use_fallback_decorations: bool,
+
+    /// How many frames to run with `LinuxBackend::Headless` before returning
+    /// from `miniquad::start`. With None it runs until the quit is ordered.
+    pub headless_frames: Option<usize>,
+
+    /// Request a debug GL context and forward the driver messages (KHR_debug) to the log.
+    /// Driver checks make debug contexts slower, this is for development.
+    /// Ignored on WebGL, Metal and Apple GL, and when the driver has no KHR_debug.
+    pub gl_debug: bool,
 }
 
 impl Default for Platform {
@@ -139,6 +153,8 @@ impl Default for Platform {
             swap_interval: None,
             framebuffer_alpha: false,
             wayland_use_fallback_decorations: true,
+            headless_frames: None,
+            gl_debug: false,
         }
     }
 }
@@ -1,6 +1,7 @@
 //mod texture;
 
 use crate::native::gl::*;
+use crate::ResourceId;
 
 use std::{error::Error, fmt::Display};
 
@@ -13,12 +14,25 @@ pub use gl::raw_gl;
 #[cfg(target_vendor = "apple")]
 mod metal;
 
+pub mod recording;
+pub mod software;
+pub mod trace;
+pub mod validation;
+
+mod diagnostics;
+pub use diagnostics::{DiagnosticSeverity, ShaderDiagnostic};
+
 pub use gl::GlContext;
 
+pub use recording::RecordingContext;
+pub use software::SoftwareContext;
+pub use trace::{Replay, TraceError, TracingBackend};
+pub use validation::ValidatingBackend;
+
 #[cfg(target_vendor = "apple")]
 pub use metal::MetalContext;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UniformType {
     /// One 32-bit wide float (equivalent to `f32`)
     Float1,
@@ -42,7 +56,7 @@ pub enum UniformType {
 
 impl UniformType {
     /// Byte size for a given UniformType
-    pub fn size(&self) -> usize {
+    pub const fn size(&self) -> usize {
         match self {
             UniformType::Float1 => 4,
             UniformType::Float2 => 8,
@@ -55,20 +69,94 @@ impl UniformType {
             UniformType::Mat4 => 64,
         }
     }
+
+    /// Base alignment in std140 layout
+    pub fn std140_alignment(&self) -> usize {
+        match self {
+            UniformType::Float1 | UniformType::Int1 => 4,
+            UniformType::Float2 | UniformType::Int2 => 8,
+            _ => 16,
+        }
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct UniformDesc {
     pub name: String,
     pub uniform_type: UniformType,
     pub array_count: usize,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Default)]
 pub struct UniformBlockLayout {
     pub uniforms: Vec<UniformDesc>,
 }
 
+impl UniformBlockLayout {
+    /// Byte offset of each uniform when the block is laid out by std140 rules.
+    pub fn std140_offsets(&self) -> Vec<usize> {
+        self.std140().0
+    }
+
+    /// Byte size of the block in std140 layout, rounded up to 16 bytes.
+    /// Buffer ranges bound to the block are exactly this long.
+    pub fn std140_size(&self) -> usize {
+        self.std140().1
+    }
+
+    fn std140(&self) -> (Vec<usize>, usize) {
+        let mut offsets = Vec::with_capacity(self.uniforms.len());
+        let mut offset = 0;
+        for uniform in &self.uniforms {
+            let (alignment, size) = if uniform.array_count > 1 {
+                // array elements are padded to vec4
+                let stride = round_up(uniform.uniform_type.size(), 16);
+                (16, stride * uniform.array_count)
+            } else {
+                (
+                    uniform.uniform_type.std140_alignment(),
+                    uniform.uniform_type.size(),
+                )
+            };
+            offset = round_up(offset, alignment);
+            offsets.push(offset);
+            offset += size;
+        }
+        (offsets, round_up(offset, 16))
+    }
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+/// `usize::is_multiple_of` is stable only since Rust 1.87, newer clippy asks for it anyway.
+#[allow(clippy::manual_is_multiple_of)]
+pub(crate) fn is_multiple_of(value: usize, divisor: usize) -> bool {
+    value % divisor == 0
+}
+
+/// Named uniform block, `layout(std140) uniform Name { ... };` in GLSL.
+///
+/// Block members are described the same way as plain uniforms, the data for the block
+/// is read from a `BufferType::UniformBuffer` buffer bound with
+/// `RenderingBackend::apply_uniform_block`. The buffer contents should follow
+/// `UniformBlockLayout::std140_offsets`: `vec3` and arrays elements are padded to 16 bytes.
+#[derive(Clone, Debug)]
+pub struct UniformBlockDesc {
+    pub name: String,
+    pub layout: UniformBlockLayout,
+}
+
+impl UniformBlockDesc {
+    pub fn new(name: &str, uniforms: Vec<UniformDesc>) -> UniformBlockDesc {
+        UniformBlockDesc {
+            name: name.to_string(),
+            layout: UniformBlockLayout { uniforms },
+        }
+    }
+}
+
 impl UniformDesc {
     pub fn new(name: &str, uniform_type: UniformType) -> UniformDesc {
         UniformDesc {
@@ -86,12 +174,193 @@ impl UniformDesc {
     }
 }
 
-#[derive(Clone)]
+/// `#[repr(C)]` struct for `apply_uniforms`, matching `ShaderMeta::uniforms`.
+///
+/// With the "derive" feature it may be derived:
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Uniforms)]
+/// struct Uniforms {
+///     mvp: [[f32; 4]; 4],
+///     #[uniform(uniform_type = Float4, array_count = 4)]
+///     lights: [glam::Vec4; 4],
+///     time: f32,
+/// }
+///
+/// let meta = ShaderMeta {
+///     uniforms: Uniforms::uniform_layout(),
+///     uniform_blocks: vec![],
+///     images: vec![],
+/// };
+/// ```
+/// Uniforms are named after the fields. `f32` and `i32` fields and arrays of up to 4
+/// of them get the `UniformType` of the same type and count, `[[f32; 4]; 4]` is a `Mat4`,
+/// and an array of any of those is an array uniform. Other types need a `uniform_type`.
+///
+/// Uniforms are read packed one right after another: a field which size does
+/// not match its type, or padding between the fields, fails to compile.
+pub trait Uniforms {
+    fn uniform_layout() -> UniformBlockLayout;
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct ShaderMeta {
     pub uniforms: UniformBlockLayout,
+    /// Uniform blocks, the index in this list is the `block` argument of
+    /// `RenderingBackend::apply_uniform_block`.
+    pub uniform_blocks: Vec<UniformBlockDesc>,
     pub images: Vec<String>,
 }
 
+impl ShaderMeta {
+    /// Compare against a meta reflected from the linked program, see
+    /// `RenderingBackend::reflect_shader`.
+    ///
+    /// Order of the uniforms can't be reflected and is not checked.
+    pub fn mismatches(&self, reflected: &ShaderMeta) -> Vec<ShaderMetaMismatch> {
+        let mut mismatches = vec![];
+
+        let uniforms = &self.uniforms.uniforms;
+        let reflected_uniforms = &reflected.uniforms.uniforms;
+        for uniform in uniforms {
+            match reflected_uniforms.iter().find(|u| u.name == uniform.name) {
+                None => mismatches.push(ShaderMetaMismatch::UnknownUniform {
+                    name: uniform.name.clone(),
+                }),
+                Some(reflected)
+                    if reflected.uniform_type != uniform.uniform_type
+                        || reflected.array_count != uniform.array_count =>
+                {
+                    mismatches.push(ShaderMetaMismatch::UniformType {
+                        name: uniform.name.clone(),
+                        meta: (uniform.uniform_type, uniform.array_count),
+                        shader: (reflected.uniform_type, reflected.array_count),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for reflected in reflected_uniforms {
+            if !uniforms.iter().any(|u| u.name == reflected.name) {
+                mismatches.push(ShaderMetaMismatch::MissingUniform {
+                    name: reflected.name.clone(),
+                    uniform_type: reflected.uniform_type,
+                    array_count: reflected.array_count,
+                });
+            }
+        }
+
+        for image in &self.images {
+            if !reflected.images.contains(image) {
+                mismatches.push(ShaderMetaMismatch::UnknownImage {
+                    name: image.clone(),
+                });
+            }
+        }
+        for image in &reflected.images {
+            if !self.images.contains(image) {
+                mismatches.push(ShaderMetaMismatch::MissingImage {
+                    name: image.clone(),
+                });
+            }
+        }
+
+        for block in &self.uniform_blocks {
+            match reflected
+                .uniform_blocks
+                .iter()
+                .find(|b| b.name == block.name)
+            {
+                None => mismatches.push(ShaderMetaMismatch::UnknownUniformBlock {
+                    name: block.name.clone(),
+                }),
+                // members of a block are never optimized out and their order
+                // is the layout, so the lists should be equal
+                Some(reflected_block)
+                    if reflected_block.layout.uniforms != block.layout.uniforms =>
+                {
+                    mismatches.push(ShaderMetaMismatch::UniformBlockMembers {
+                        name: block.name.clone(),
+                        meta: block.layout.uniforms.clone(),
+                        shader: reflected_block.layout.uniforms.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for block in &reflected.uniform_blocks {
+            if !self.uniform_blocks.iter().any(|b| b.name == block.name) {
+                mismatches.push(ShaderMetaMismatch::MissingUniformBlock {
+                    name: block.name.clone(),
+                });
+            }
+        }
+
+        mismatches
+    }
+}
+
+/// A single difference between a hand written `ShaderMeta` and the program.
+///
+/// Drivers optimize out everything the shader does not use, so "unknown"
+/// uniforms and images are either typos or are unused by the shader.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShaderMetaMismatch {
+    /// Listed in the meta, but not an active uniform of the program.
+    UnknownUniform {
+        name: String,
+    },
+    /// Active uniform of the program that is missing from the meta.
+    MissingUniform {
+        name: String,
+        uniform_type: UniformType,
+        array_count: usize,
+    },
+    /// Different type or array length, as `(UniformType, array_count)`.
+    UniformType {
+        name: String,
+        meta: (UniformType, usize),
+        shader: (UniformType, usize),
+    },
+    UnknownImage {
+        name: String,
+    },
+    MissingImage {
+        name: String,
+    },
+    UnknownUniformBlock {
+        name: String,
+    },
+    MissingUniformBlock {
+        name: String,
+    },
+    /// Block members differ in name, type, array length or order.
+    UniformBlockMembers {
+        name: String,
+        meta: Vec<UniformDesc>,
+        shader: Vec<UniformDesc>,
+    },
+}
+
+/// Active vertex attribute of a linked program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderAttribute {
+    pub name: String,
+    pub format: VertexFormat,
+}
+
+/// Interface of a linked program, see `RenderingBackend::reflect_shader`.
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    /// Uniforms are ordered by their location, most drivers assign locations in
+    /// declaration order, but it is worth double checking against the uniforms struct.
+    /// Uniform block members are ordered by their offset.
+    /// Uniforms of types without `UniformType` counterpart are skipped.
+    pub meta: ShaderMeta,
+    /// Vertex attributes, ordered by their location.
+    pub attributes: Vec<ShaderAttribute>,
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum VertexFormat {
     /// One 32-bit wide float (equivalent to `f32`)
@@ -157,7 +426,7 @@ impl VertexFormat {
     }
 
     /// Size in bytes
-    pub fn size_bytes(&self) -> i32 {
+    pub const fn size_bytes(&self) -> i32 {
         match self {
             VertexFormat::Float1 => 1 * 4,
             VertexFormat::Float2 => 2 * 4,
@@ -256,13 +525,47 @@ impl VertexAttribute {
     }
 }
 
+/// `#[repr(C)]` vertex struct, matching a `BufferLayout` and a list of `VertexAttribute`s.
+///
+/// With the "derive" feature it may be derived:
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Vertex)]
+/// struct Vertex {
+///     #[vertex(name = "in_pos")]
+///     pos: [f32; 2],
+///     #[vertex(name = "in_uv", format = Float2)]
+///     uv: glam::Vec2,
+/// }
+///
+/// let pipeline = ctx.new_pipeline(
+///     &[Vertex::buffer_layout()],
+///     Vertex::ATTRIBUTES,
+///     shader,
+///     PipelineParams::default(),
+/// );
+/// ```
+/// Attributes are named after the fields. `f32`, `u8`, `u16` and `u32` fields and arrays
+/// of up to 4 of them get the `VertexFormat` of the same type and count, `[[f32; 4]; 4]`
+/// is a `Mat4`, other types need a `format`.
+/// `#[vertex(buffer_index = 1, per_instance)]` on the struct is for instance data
+/// in a second vertex buffer.
+///
+/// Pipelines read the attributes packed one right after another: a field which size does
+/// not match its format, or padding between the fields, fails to compile.
+pub trait Vertex {
+    const ATTRIBUTES: &'static [VertexAttribute];
+    /// Stride of the whole struct.
+    fn buffer_layout() -> BufferLayout;
+}
+
 #[derive(Clone, Debug)]
 pub struct PipelineLayout {
     pub buffers: &'static [BufferLayout],
     pub attributes: &'static [VertexAttribute],
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum ShaderType {
     Vertex,
     Fragment,
@@ -272,9 +575,29 @@ pub enum ShaderType {
 pub enum ShaderError {
     CompilationError {
         shader_type: ShaderType,
+        /// The log as the driver gave it
         error_message: String,
+        /// `error_message` parsed, see `ShaderDiagnostic::parse_log`
+        diagnostics: Vec<ShaderDiagnostic>,
     },
     LinkError(String),
+    /// Uniform block from `ShaderMeta::uniform_blocks` is laid out differently in the shader.
+    /// The block should be declared with `layout(std140)` and match the `UniformDesc` list.
+    UniformBlockLayoutMismatch {
+        block: String,
+        message: String,
+    },
+    /// `ShaderMeta` given to `new_shader_validated` does not match the program.
+    MetaMismatch(Vec<ShaderMetaMismatch>),
+    /// `ShaderMeta::uniform_blocks` is not empty, but `Features::uniform_buffers` is off.
+    UniformBuffersUnsupported,
+    /// The backend can't inspect compiled programs.
+    ReflectionUnsupported,
+    /// The backend can't replace the program behind a `ShaderId`.
+    ReplaceUnsupported,
+    /// The backend or the driver did not take the `ShaderBinary`, e.g. after a driver update.
+    /// Compile the shader from source instead.
+    ProgramBinaryRejected,
     /// Shader strings should never contains \00 in the middle
     FFINulError(std::ffi::NulError),
 }
@@ -298,7 +621,11 @@ impl Error for ShaderError {
 }
 
 /// List of all the possible formats of input data when uploading to texture.
-/// The list is built by intersection of texture formats supported by 3.3 core profile and webgl1.
+/// `RGB8` to `Alpha` are the intersection of texture formats supported by 3.3 core profile and webgl1
+/// and are always available.
+///
+/// The rest is optional, check `Features::texture_format_supported` first.
+/// Compressed formats can't be rendered to or read back.
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum TextureFormat {
@@ -308,10 +635,79 @@ pub enum TextureFormat {
     Depth,
     Depth32,
     Alpha,
+    /// `Features::texture_rg`.
+    R8,
+    /// `Features::texture_rg`.
+    RG8,
+    /// Half floats. `Features::texture_float`.
+    R16F,
+    /// Half floats. `Features::texture_float`.
+    RG16F,
+    /// `Features::texture_float`.
+    R32F,
+    /// `Features::texture_float`.
+    RGBA32F,
+    /// Unsigned integers, sampled with `usampler2D` and only with `FilterMode::Nearest`.
+    /// `Features::texture_integer`.
+    R32UI,
+    /// RGBA8 with sRGB encoded color, converted to linear when sampled. `Features::texture_srgb`.
+    SRGBA8,
+    /// Depth with a stencil, both get attached to a render pass. `Features::depth_stencil_texture`.
+    Depth24Stencil8,
+    /// S3TC DXT1, RGB with 1 bit alpha. `Features::texture_compression_s3tc`.
+    BC1,
+    /// S3TC DXT3, RGBA with 4 bit alpha. `Features::texture_compression_s3tc`.
+    BC2,
+    /// S3TC DXT5, RGBA. `Features::texture_compression_s3tc`.
+    BC3,
+    /// RGTC1, single red channel. `Features::texture_compression_rgtc`.
+    BC4,
+    /// RGTC2, red and green channels. `Features::texture_compression_rgtc`.
+    BC5,
+    /// BPTC unsigned float RGB. `Features::texture_compression_bptc`.
+    BC6H,
+    /// BPTC RGBA. `Features::texture_compression_bptc`.
+    BC7,
+    /// `Features::texture_compression_etc2`.
+    ETC2RGB,
+    /// ETC2 color with EAC alpha. `Features::texture_compression_etc2`.
+    ETC2RGBA,
+    /// `Features::texture_compression_astc`.
+    ASTC4x4,
+    /// `Features::texture_compression_astc`.
+    ASTC5x5,
+    /// `Features::texture_compression_astc`.
+    ASTC6x6,
+    /// `Features::texture_compression_astc`.
+    ASTC8x8,
+    /// The sRGB encoded variants of the compressed formats, converted to linear when sampled.
+    /// `Features::texture_compression_s3tc_srgb`.
+    BC1SRGB,
+    /// `Features::texture_compression_s3tc_srgb`.
+    BC2SRGB,
+    /// `Features::texture_compression_s3tc_srgb`.
+    BC3SRGB,
+    /// `Features::texture_compression_bptc`.
+    BC7SRGB,
+    /// `Features::texture_compression_etc2`.
+    ETC2SRGB,
+    /// `Features::texture_compression_etc2`.
+    ETC2SRGBA,
+    /// `Features::texture_compression_astc`.
+    ASTC4x4SRGB,
+    /// `Features::texture_compression_astc`.
+    ASTC5x5SRGB,
+    /// `Features::texture_compression_astc`.
+    ASTC6x6SRGB,
+    /// `Features::texture_compression_astc`.
+    ASTC8x8SRGB,
 }
 impl TextureFormat {
     /// Returns the size in bytes of texture with `dimensions`.
     pub fn size(self, width: u32, height: u32) -> u32 {
+        if let Some((block_width, block_height, block_size)) = self.block() {
+            return width.div_ceil(block_width) * height.div_ceil(block_height) * block_size;
+        }
         let square = width * height;
         match self {
             TextureFormat::RGB8 => 3 * square,
@@ -320,8 +716,67 @@ impl TextureFormat {
             TextureFormat::Depth => 2 * square,
             TextureFormat::Depth32 => 4 * square,
             TextureFormat::Alpha => 1 * square,
+            TextureFormat::R8 => square,
+            TextureFormat::RG8 => 2 * square,
+            TextureFormat::R16F => 2 * square,
+            TextureFormat::RG16F => 4 * square,
+            TextureFormat::R32F => 4 * square,
+            TextureFormat::RGBA32F => 16 * square,
+            TextureFormat::R32UI => 4 * square,
+            TextureFormat::SRGBA8 => 4 * square,
+            TextureFormat::Depth24Stencil8 => 4 * square,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Width, height and size in bytes of a block of a compressed format.
+    pub fn block(self) -> Option<(u32, u32, u32)> {
+        match self {
+            TextureFormat::BC1
+            | TextureFormat::BC1SRGB
+            | TextureFormat::BC4
+            | TextureFormat::ETC2RGB
+            | TextureFormat::ETC2SRGB => Some((4, 4, 8)),
+            TextureFormat::BC2
+            | TextureFormat::BC2SRGB
+            | TextureFormat::BC3
+            | TextureFormat::BC3SRGB
+            | TextureFormat::BC5
+            | TextureFormat::BC6H
+            | TextureFormat::BC7
+            | TextureFormat::BC7SRGB
+            | TextureFormat::ETC2RGBA
+            | TextureFormat::ETC2SRGBA
+            | TextureFormat::ASTC4x4
+            | TextureFormat::ASTC4x4SRGB => Some((4, 4, 16)),
+            TextureFormat::ASTC5x5 | TextureFormat::ASTC5x5SRGB => Some((5, 5, 16)),
+            TextureFormat::ASTC6x6 | TextureFormat::ASTC6x6SRGB => Some((6, 6, 16)),
+            TextureFormat::ASTC8x8 | TextureFormat::ASTC8x8SRGB => Some((8, 8, 16)),
+            _ => None,
         }
     }
+
+    pub fn is_compressed(self) -> bool {
+        self.block().is_some()
+    }
+
+    pub fn is_depth(self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Depth | TextureFormat::Depth32 | TextureFormat::Depth24Stencil8
+        )
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(
+            self,
+            TextureFormat::RGBA16F
+                | TextureFormat::R16F
+                | TextureFormat::RG16F
+                | TextureFormat::R32F
+                | TextureFormat::RGBA32F
+        )
+    }
 }
 
 /// Sets the wrap parameter for texture.
@@ -360,6 +815,10 @@ pub enum TextureAccess {
 pub enum TextureKind {
     Texture2D,
     CubeMap,
+    /// `TextureParams::depth` layers of `width * height`, sampled with `sampler2DArray`.
+    Texture2DArray,
+    /// `TextureParams::depth` slices of `width * height`, sampled with `sampler3D`.
+    Texture3D,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -372,11 +831,17 @@ pub struct TextureParams {
     pub mipmap_filter: MipmapFilterMode,
     pub width: u32,
     pub height: u32,
+    /// Amount of layers of `Texture2DArray` or depth of `Texture3D`, ignored otherwise.
+    pub depth: u32,
     // All miniquad API could work without this flag being explicit.
     // We can decide if mipmaps are required by the data provided
     // And reallocate non-mipmapped texture(on metal) on generateMipmaps call
     // But! Reallocating cubemaps is too much struggle, so leave it for later.
     pub allocate_mipmaps: bool,
+    /// Render passes with this texture attached draw into multisampled buffers with this many
+    /// samples and resolve them into the texture at `end_render_pass`. 1 for no multisampling,
+    /// clamped to `Features::max_sample_count`. All the attachments of a pass should agree on it.
+    pub sample_count: i32,
 }
 
 impl Default for TextureParams {
@@ -390,18 +855,31 @@ impl Default for TextureParams {
             mipmap_filter: MipmapFilterMode::None,
             width: 0,
             height: 0,
+            depth: 1,
             allocate_mipmaps: false,
+            sample_count: 1,
+        }
+    }
+}
+
+impl TextureParams {
+    /// Amount of `width * height` images in level 0: cubemap faces, array layers or 3D slices.
+    pub fn layers(&self) -> u32 {
+        match self.kind {
+            TextureKind::Texture2D => 1,
+            TextureKind::CubeMap => 6,
+            TextureKind::Texture2DArray | TextureKind::Texture3D => self.depth,
         }
     }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
-pub struct ShaderId(usize);
+pub struct ShaderId(ResourceId);
 
 // Inner hence we can't have private data in enum fields
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum TextureIdInner {
-    Managed(usize),
+    Managed(ResourceId),
     Raw(RawId),
 }
 
@@ -513,6 +991,7 @@ pub enum CompareFunc {
 
 type ColorMask = (bool, bool, bool, bool);
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PassAction {
     Nothing,
     Clear {
@@ -542,8 +1021,8 @@ impl Default for PassAction {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct RenderPass(usize);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderPass(ResourceId);
 
 pub const MAX_VERTEX_ATTRIBUTES: usize = 16;
 pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
@@ -551,11 +1030,141 @@ pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
 #[derive(Clone, Debug)]
 pub struct Features {
     pub instancing: bool,
+    /// `QueryType::TimeElapsed` queries are supported.
+    pub elapsed_query: bool,
+    /// `QueryType::AnySamplesPassed` and `QueryType::SamplesPassed` queries are supported.
+    pub occlusion_query: bool,
+    /// `BufferType::UniformBuffer` and `ShaderMeta::uniform_blocks` are supported.
+    pub uniform_buffers: bool,
+    /// Required alignment of the `offset` given to `apply_uniform_block`.
+    pub uniform_buffer_offset_alignment: usize,
+    /// `TextureKind::Texture2DArray` and `TextureKind::Texture3D` are supported.
+    pub texture_arrays: bool,
+    /// `TextureFormat::BC1`, `BC2` and `BC3` are supported.
+    pub texture_compression_s3tc: bool,
+    /// `TextureFormat::BC1SRGB`, `BC2SRGB` and `BC3SRGB` are supported, it is a separate
+    /// extension on GLES and WebGL.
+    pub texture_compression_s3tc_srgb: bool,
+    /// `TextureFormat::BC4` and `BC5` are supported.
+    pub texture_compression_rgtc: bool,
+    /// `TextureFormat::BC6H` and `BC7` are supported.
+    pub texture_compression_bptc: bool,
+    /// `TextureFormat::ETC2RGB` and `ETC2RGBA` are supported.
+    pub texture_compression_etc2: bool,
+    /// `TextureFormat::ASTC4x4` to `ASTC8x8` are supported.
+    pub texture_compression_astc: bool,
+    /// `TextureFormat::R8` and `RG8` are supported.
+    pub texture_rg: bool,
+    /// `TextureFormat::R16F`, `RG16F`, `R32F` and `RGBA32F` are supported.
+    pub texture_float: bool,
+    /// `FilterMode::Linear` works on `TextureFormat::R32F` and `RGBA32F`.
+    pub texture_float_linear: bool,
+    /// Float formats, `RGBA16F` included, can be render pass attachments.
+    pub color_buffer_float: bool,
+    /// `TextureFormat::R32UI` is supported.
+    pub texture_integer: bool,
+    /// `TextureFormat::SRGBA8` is supported.
+    pub texture_srgb: bool,
+    /// `TextureFormat::Depth24Stencil8` is supported.
+    pub depth_stencil_texture: bool,
+    /// `draw_base_vertex` is supported.
+    pub draw_base_vertex: bool,
+    /// `draw_indirect` and `BufferType::IndirectBuffer` are supported.
+    pub draw_indirect: bool,
+    /// `multi_draw_indirect` is supported.
+    pub multi_draw_indirect: bool,
+    /// Highest `TextureParams::sample_count` of render pass attachments,
+    /// 1 if multisampled render passes are not supported.
+    pub max_sample_count: i32,
+    /// `begin_read_pixels` does not wait for the GPU. Otherwise it reads the pixels right away.
+    pub async_readback: bool,
+    /// `shader_binary` and `new_shader_from_binary` are supported.
+    pub program_binary: bool,
+}
+
+impl Features {
+    pub fn texture_format_supported(&self, format: TextureFormat) -> bool {
+        match format {
+            TextureFormat::BC1 | TextureFormat::BC2 | TextureFormat::BC3 => {
+                self.texture_compression_s3tc
+            }
+            TextureFormat::BC1SRGB | TextureFormat::BC2SRGB | TextureFormat::BC3SRGB => {
+                self.texture_compression_s3tc_srgb
+            }
+            TextureFormat::BC4 | TextureFormat::BC5 => self.texture_compression_rgtc,
+            TextureFormat::BC6H | TextureFormat::BC7 | TextureFormat::BC7SRGB => {
+                self.texture_compression_bptc
+            }
+            TextureFormat::ETC2RGB
+            | TextureFormat::ETC2RGBA
+            | TextureFormat::ETC2SRGB
+            | TextureFormat::ETC2SRGBA => self.texture_compression_etc2,
+            TextureFormat::ASTC4x4
+            | TextureFormat::ASTC5x5
+            | TextureFormat::ASTC6x6
+            | TextureFormat::ASTC8x8
+            | TextureFormat::ASTC4x4SRGB
+            | TextureFormat::ASTC5x5SRGB
+            | TextureFormat::ASTC6x6SRGB
+            | TextureFormat::ASTC8x8SRGB => self.texture_compression_astc,
+            TextureFormat::R8 | TextureFormat::RG8 => self.texture_rg,
+            TextureFormat::R16F
+            | TextureFormat::RG16F
+            | TextureFormat::R32F
+            | TextureFormat::RGBA32F => self.texture_float,
+            TextureFormat::R32UI => self.texture_integer,
+            TextureFormat::SRGBA8 => self.texture_srgb,
+            TextureFormat::Depth24Stencil8 => self.depth_stencil_texture,
+            TextureFormat::RGB8
+            | TextureFormat::RGBA8
+            | TextureFormat::RGBA16F
+            | TextureFormat::Depth
+            | TextureFormat::Depth32
+            | TextureFormat::Alpha => true,
+        }
+    }
+
+    /// Whether a texture of this format can be used in `new_render_pass`.
+    pub fn texture_format_renderable(&self, format: TextureFormat) -> bool {
+        if format.is_compressed() {
+            return false;
+        }
+        if format.is_float() {
+            return self.color_buffer_float;
+        }
+        self.texture_format_supported(format)
+    }
 }
 
 impl Default for Features {
     fn default() -> Features {
-        Features { instancing: true }
+        Features {
+            instancing: true,
+            elapsed_query: false,
+            occlusion_query: false,
+            uniform_buffers: false,
+            uniform_buffer_offset_alignment: 256,
+            texture_arrays: false,
+            texture_compression_s3tc: false,
+            texture_compression_s3tc_srgb: false,
+            texture_compression_rgtc: false,
+            texture_compression_bptc: false,
+            texture_compression_etc2: false,
+            texture_compression_astc: false,
+            texture_rg: false,
+            texture_float: false,
+            texture_float_linear: false,
+            color_buffer_float: false,
+            texture_integer: false,
+            texture_srgb: false,
+            depth_stencil_texture: false,
+            draw_base_vertex: false,
+            draw_indirect: false,
+            multi_draw_indirect: false,
+            max_sample_count: 1,
+            async_readback: false,
+            program_binary: false,
+        }
     }
 }
 
@@ -709,7 +1318,7 @@ pub struct PipelineParams {
 
 // TODO(next major version bump): should be PipelineId
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-pub struct Pipeline(usize);
+pub struct Pipeline(ResourceId);
 
 impl Default for PipelineParams {
     fn default() -> PipelineParams {
@@ -738,19 +1347,60 @@ pub struct Bindings {
     /// vertex in 3d space, as well as `(u,v)` coordinates that map the vertex
     /// to some position in the corresponding `Texture`.
     pub vertex_buffers: Vec<BufferId>,
+    /// Byte offsets of the vertex data in each of `vertex_buffers`, for many meshes
+    /// sharing a buffer. Missing offsets are 0, so it may be left empty.
+    pub vertex_buffer_offsets: Vec<usize>,
     /// Index buffer which instructs the GPU in which order to draw vertices
     /// from a vertex buffer, with each subsequent 3 indices forming a
     /// triangle.
     pub index_buffer: BufferId,
+    /// Byte offset of the indices in `index_buffer`, a multiple of the index size.
+    /// `base_element` of `draw` counts from there. Indirect draws ignore it,
+    /// their commands always address the whole buffer.
+    pub index_buffer_offset: usize,
     /// Textures to be used with when drawing the geometry in the fragment
     /// shader.
     pub images: Vec<TextureId>,
 }
 
+impl Bindings {
+    /// Bindings of whole buffers, both offsets are 0.
+    pub fn new(
+        vertex_buffers: Vec<BufferId>,
+        index_buffer: BufferId,
+        images: Vec<TextureId>,
+    ) -> Bindings {
+        Bindings {
+            vertex_buffers,
+            vertex_buffer_offsets: vec![],
+            index_buffer,
+            index_buffer_offset: 0,
+            images,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BufferType {
     VertexBuffer,
     IndexBuffer,
+    /// Data for `ShaderMeta::uniform_blocks`, `features.uniform_buffers` check is required.
+    UniformBuffer,
+    /// `DrawIndirectCommand`s for `draw_indirect`, `features.draw_indirect` check is required.
+    IndirectBuffer,
+}
+
+/// Arguments of a single indirect draw, laid out as GL `DrawElementsIndirectCommand`
+/// and Metal `MTLDrawIndexedPrimitivesIndirectArguments`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawIndirectCommand {
+    pub num_elements: u32,
+    pub num_instances: u32,
+    pub base_element: u32,
+    pub base_vertex: i32,
+    /// Should be 0 on GLES and before GL 4.2.
+    pub base_instance: u32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -764,6 +1414,8 @@ fn gl_buffer_target(buffer_type: &BufferType) -> GLenum {
     match buffer_type {
         BufferType::VertexBuffer => GL_ARRAY_BUFFER,
         BufferType::IndexBuffer => GL_ELEMENT_ARRAY_BUFFER,
+        BufferType::UniformBuffer => GL_UNIFORM_BUFFER,
+        BufferType::IndirectBuffer => GL_DRAW_INDIRECT_BUFFER,
     }
 }
 
@@ -776,7 +1428,7 @@ fn gl_usage(usage: &BufferUsage) -> GLenum {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-pub struct BufferId(usize);
+pub struct BufferId(ResourceId);
 
 /// `ElapsedQuery` is used to measure duration of GPU operations.
 ///
@@ -885,17 +1537,15 @@ impl ElapsedQuery {
     ///
     /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
     pub fn get_result(&self) -> u64 {
-        // let mut time: GLuint64 = 0;
-        // assert!(self.gl_query != 0);
-        // unsafe { glGetQueryObjectui64v(self.gl_query, GL_QUERY_RESULT, &mut time) };
-        // time
-        0
+        let mut time: GLuint64 = 0;
+        assert!(self.gl_query != 0);
+        unsafe { glGetQueryObjectui64v(self.gl_query, GL_QUERY_RESULT, &mut time) };
+        time
     }
 
     /// Reports whenever elapsed timer is supported and other methods can be invoked.
     pub fn is_supported() -> bool {
-        unimplemented!();
-        //unsafe { sapp_is_elapsed_timer_supported() }
+        gl::elapsed_query_supported()
     }
 
     /// Reports whenever result of submitted query is available for retrieval with
@@ -906,17 +1556,15 @@ impl ElapsedQuery {
     ///
     /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
     pub fn is_available(&self) -> bool {
-        // let mut available: GLint = 0;
-
-        // // begin_query was not called yet
-        // if self.gl_query == 0 {
-        //     return false;
-        // }
+        let mut available: GLuint = 0;
 
-        //unsafe { glGetQueryObjectiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
-        //available != 0
+        // begin_query was not called yet
+        if self.gl_query == 0 {
+            return false;
+        }
 
-        false
+        unsafe { glGetQueryObjectuiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
     }
 
     /// Delete query.
@@ -925,11 +1573,123 @@ impl ElapsedQuery {
     ///
     /// Implemented as `glDeleteQueries(...)` on OpenGL/WebGL platforms.
     pub fn delete(&mut self) {
-        unsafe { glDeleteQueries(1, &mut self.gl_query) }
+        unsafe { glDeleteQueries(1, &self.gl_query) }
         self.gl_query = 0;
     }
 }
 
+/// `OcclusionQuery` counts samples passing depth and stencil tests between
+/// [`OcclusionQuery::begin_query()`] and [`OcclusionQuery::end_query()`].
+///
+/// Usually used to skip drawing of expensive objects hidden behind something else:
+/// draw a cheap bounding box with color and depth writes disabled inside the query and check
+/// the result a frame later.
+///
+/// The API is the same as [`ElapsedQuery`]: results are available a couple frames later and
+/// only one occlusion query may be active at any moment in time.
+///
+/// [`RenderingBackend::new_query`] provides the same functionality for any backend.
+#[derive(Clone, Copy)]
+pub struct OcclusionQuery {
+    gl_query: GLuint,
+    target: GLenum,
+}
+
+impl OcclusionQuery {
+    /// The result is 1 if any sample passed, 0 otherwise.
+    /// Implemented with `GL_ANY_SAMPLES_PASSED`.
+    pub fn any_samples_passed() -> OcclusionQuery {
+        OcclusionQuery {
+            gl_query: 0,
+            target: gl::query_target(QueryType::AnySamplesPassed),
+        }
+    }
+
+    /// The result is the amount of samples passed.
+    /// Implemented with `GL_SAMPLES_PASSED` on desktop GL, on GLES and WebGL there is only
+    /// `GL_ANY_SAMPLES_PASSED` and the result is 0 or 1.
+    pub fn samples_passed() -> OcclusionQuery {
+        OcclusionQuery {
+            gl_query: 0,
+            target: gl::query_target(QueryType::SamplesPassed),
+        }
+    }
+
+    /// Submit a beginning of occlusion query.
+    ///
+    /// Use [`OcclusionQuery::is_supported()`] to check if functionality is available and the method can be called.
+    pub fn begin_query(&mut self) {
+        if self.gl_query == 0 {
+            unsafe { glGenQueries(1, &mut self.gl_query) };
+        }
+        unsafe { glBeginQuery(self.target, self.gl_query) };
+    }
+
+    /// Submit an end of occlusion query that can be read later when rendering is complete.
+    pub fn end_query(&mut self) {
+        unsafe { glEndQuery(self.target) };
+    }
+
+    /// Retrieve the amount of passed samples, or 0/1 for `any_samples_passed` queries.
+    ///
+    /// Use [`OcclusionQuery::is_available()`] to check if the result is available for retrieval.
+    pub fn get_result(&self) -> u64 {
+        let mut samples: GLuint = 0;
+        assert!(self.gl_query != 0);
+        unsafe { glGetQueryObjectuiv(self.gl_query, GL_QUERY_RESULT, &mut samples) };
+        samples as u64
+    }
+
+    /// Reports whenever occlusion queries are supported and other methods can be invoked.
+    pub fn is_supported() -> bool {
+        gl::occlusion_query_supported()
+    }
+
+    /// Reports whenever result of submitted query is available for retrieval with
+    /// [`OcclusionQuery::get_result()`].
+    pub fn is_available(&self) -> bool {
+        let mut available: GLuint = 0;
+
+        // begin_query was not called yet
+        if self.gl_query == 0 {
+            return false;
+        }
+
+        unsafe { glGetQueryObjectuiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
+    }
+
+    /// Delete query.
+    ///
+    /// Note that the query is not deleted automatically when dropped.
+    pub fn delete(&mut self) {
+        unsafe { glDeleteQueries(1, &self.gl_query) }
+        self.gl_query = 0;
+    }
+}
+
+/// What a query created with [`RenderingBackend::new_query`] measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryType {
+    /// GPU time in nanoseconds, see [`ElapsedQuery`].
+    /// Requires `features.elapsed_query`.
+    TimeElapsed,
+    /// 1 if any sample passed depth and stencil tests, 0 otherwise.
+    /// Requires `features.occlusion_query`.
+    AnySamplesPassed,
+    /// Amount of samples passed depth and stencil tests.
+    /// On GLES and WebGL works as `AnySamplesPassed`.
+    /// Requires `features.occlusion_query`.
+    SamplesPassed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryId(ResourceId);
+
+/// Pixel transfer started with [`RenderingBackend::begin_read_pixels`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadbackId(ResourceId);
+
 /// A vtable-erased generic argument.
 /// Basically, the same thing as `fn f<U>(a: &U)`, but
 /// trait-object friendly.
@@ -943,8 +1703,11 @@ pub struct Arg<'a> {
 
 pub enum TextureSource<'a> {
     Empty,
+    /// Level 0 only, with `Texture2DArray` and `Texture3D` layers one after another.
     Bytes(&'a [u8]),
-    /// Array of `[cubemap_face][mipmap_level][bytes]`
+    /// Array of `[cubemap_face][mipmap_level][bytes]`, or `[layer][mipmap_level][bytes]`
+    /// for `Texture2DArray` and `Texture3D`.
+    /// `Texture3D` slices that do not exist in a smaller mipmap level skip that level.
     Array(&'a [&'a [&'a [u8]]]),
 }
 
@@ -1003,7 +1766,15 @@ impl<'a> UniformsSource<'a> {
     }
 }
 
-#[derive(Debug)]
+/// Linked program in a driver-specific format, see `RenderingBackend::shader_binary`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderBinary {
+    /// GL binary format enum
+    pub format: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub enum ShaderSource<'a> {
     Glsl { vertex: &'a str, fragment: &'a str },
     Msl { program: &'a str },
@@ -1038,6 +1809,9 @@ pub struct ContextInfo {
     pub backend: Backend,
     /// GL_VERSION_STRING from OpenGL. Would be empty on metal.
     pub gl_version_string: String,
+    /// GL_VENDOR and GL_RENDERER from OpenGL. Would be empty on metal.
+    pub gl_vendor_string: String,
+    pub gl_renderer_string: String,
     /// OpenGL provides an enumeration over GL_SHADING_LANGUAGE_VERSION,
     /// allowing to see which glsl versions are actually supported.
     /// Unfortunately, it only works on GL4.3+... and even there it is not quite correct.
@@ -1050,6 +1824,8 @@ pub struct ContextInfo {
     pub features: Features,
 }
 
+/// Methods are `#[track_caller]`, so a panic on misuse, e.g. from `ValidatingBackend`,
+/// reports the line calling the backend, even through `dyn RenderingBackend`.
 pub trait RenderingBackend {
     fn info(&self) -> ContextInfo;
     /// For metal context's ShaderSource should contain MSL source string, for GL - glsl.
@@ -1074,20 +1850,55 @@ pub trait RenderingBackend {
     /// let shader = ctx.new_shader(ShaderSource::Glsl {...}, ...);
     /// ```
     /// for GL-only.
+    #[track_caller]
     fn new_shader(
         &mut self,
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError>;
+
+    /// Compile and link the program just to list its active uniforms, images,
+    /// uniform blocks and vertex attributes.
+    ///
+    /// May be used to build `ShaderMeta` instead of writing it by hand.
+    /// Returns `ShaderError::ReflectionUnsupported` on backends that can't do it.
+    #[track_caller]
+    fn reflect_shader(&mut self, shader: ShaderSource) -> Result<ShaderReflection, ShaderError>;
+
+    /// The same as `new_shader`, but fails with `ShaderError::MetaMismatch` listing every
+    /// uniform and image that does not match the program, see `ShaderMeta::mismatches`.
+    ///
+    /// On backends without reflection support the meta is taken as is.
+    #[track_caller]
+    fn new_shader_validated(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        match self.reflect_shader(shader) {
+            Ok(reflection) => {
+                let mismatches = meta.mismatches(&reflection.meta);
+                if !mismatches.is_empty() {
+                    return Err(ShaderError::MetaMismatch(mismatches));
+                }
+            }
+            Err(ShaderError::ReflectionUnsupported) => {}
+            Err(err) => return Err(err),
+        }
+        self.new_shader(shader, meta)
+    }
+    #[track_caller]
     fn new_texture(
         &mut self,
         access: TextureAccess,
         data: TextureSource,
         params: TextureParams,
     ) -> TextureId;
+    #[track_caller]
     fn new_render_texture(&mut self, params: TextureParams) -> TextureId {
         self.new_texture(TextureAccess::RenderTarget, TextureSource::Empty, params)
     }
+    #[track_caller]
     fn new_texture_from_data_and_format(
         &mut self,
         bytes: &[u8],
@@ -1095,6 +1906,7 @@ pub trait RenderingBackend {
     ) -> TextureId {
         self.new_texture(TextureAccess::Static, TextureSource::Bytes(bytes), params)
     }
+    #[track_caller]
     fn new_texture_from_rgba8(&mut self, width: u16, height: u16, bytes: &[u8]) -> TextureId {
         assert_eq!(width as usize * height as usize * 4, bytes.len());
 
@@ -1109,25 +1921,32 @@ pub trait RenderingBackend {
                 min_filter: FilterMode::Linear,
                 mag_filter: FilterMode::Linear,
                 mipmap_filter: MipmapFilterMode::None,
+                depth: 1,
                 allocate_mipmaps: false,
+                sample_count: 1,
             },
         )
     }
+    #[track_caller]
     fn texture_params(&self, texture: TextureId) -> TextureParams;
+    #[track_caller]
     fn texture_size(&self, texture: TextureId) -> (u32, u32) {
         let params = self.texture_params(texture);
         (params.width, params.height)
     }
 
     /// Get OpenGL's GLuint texture ID or metals ObjcId
+    #[track_caller]
     unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId;
 
     /// Update whole texture content
     /// bytes should be width * height * 4 size - non rgba8 textures are not supported yet anyway
+    #[track_caller]
     fn texture_update(&mut self, texture: TextureId, bytes: &[u8]) {
         let (width, height) = self.texture_size(texture);
         self.texture_update_part(texture, 0 as _, 0 as _, width as _, height as _, bytes)
     }
+    #[track_caller]
     fn texture_set_filter(
         &mut self,
         texture: TextureId,
@@ -1137,22 +1956,42 @@ pub trait RenderingBackend {
         self.texture_set_min_filter(texture, filter, mipmap_filter);
         self.texture_set_mag_filter(texture, filter);
     }
+    #[track_caller]
     fn texture_set_min_filter(
         &mut self,
         texture: TextureId,
         filter: FilterMode,
         mipmap_filter: MipmapFilterMode,
     );
+    #[track_caller]
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode);
+    /// `Texture3D` slices wrap the same as `wrap_x`, like with `TextureParams::wrap`.
+    #[track_caller]
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap);
     /// Metal-specific note: if texture was created without `params.generate_mipmaps`
     /// `generate_mipmaps` will do nothing.
     ///
     /// Also note that if MipmapFilter is set to None, mipmaps will not be visible, even if
     /// generated.
+    #[track_caller]
     fn texture_generate_mipmaps(&mut self, texture: TextureId);
+    /// Render passes the texture is attached to draw at the new size, multisampled ones too.
+    #[track_caller]
     fn texture_resize(&mut self, texture: TextureId, width: u32, height: u32, bytes: Option<&[u8]>);
+    /// `Texture2DArray` and `Texture3D` layers are read one after another.
+    #[track_caller]
     fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]);
+    /// Start reading back `texture` without stalling until the GPU is done rendering into it.
+    /// Pick the pixels up with `try_finish_read_pixels`, usually a couple frames later.
+    #[track_caller]
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId;
+    /// Copy the pixels of a finished readback into `bytes`, laid out the same as by
+    /// `texture_read_pixels`, and free the readback.
+    /// Returns `false` and leaves `bytes` untouched while the transfer is still in flight.
+    /// Panics if the finished transfer can't be mapped, on out of memory or a lost context.
+    #[track_caller]
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool;
+    #[track_caller]
     fn texture_update_part(
         &mut self,
         texture: TextureId,
@@ -1161,7 +2000,38 @@ pub trait RenderingBackend {
         width: i32,
         height: i32,
         bytes: &[u8],
+    ) {
+        self.texture_update_layer_part(texture, 0, x_offset, y_offset, width, height, bytes)
+    }
+    /// Same as "texture_update_part", but for a single cubemap face,
+    /// `Texture2DArray` layer or `Texture3D` slice.
+    #[allow(clippy::too_many_arguments)]
+    #[track_caller]
+    fn texture_update_layer_part(
+        &mut self,
+        texture: TextureId,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
     );
+    /// Copy the `(x, y, width, height)` `src_rect` of `src` into `dst` at `dst_pos`, on the GPU.
+    /// Formats of the textures should match, there is no scaling or conversion.
+    /// Only the first cubemap face, array layer or 3D slice is copied from and into.
+    /// Depth textures can't be copied on GLES2 and WebGL1.
+    ///
+    /// Should be called outside of a render pass.
+    #[track_caller]
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    );
+    #[track_caller]
     fn new_render_pass(
         &mut self,
         color_img: TextureId,
@@ -1170,14 +2040,28 @@ pub trait RenderingBackend {
         self.new_render_pass_mrt(&[color_img], depth_img)
     }
     /// Same as "new_render_pass", but allows multiple color attachments.
+    #[track_caller]
     fn new_render_pass_mrt(
         &mut self,
         color_img: &[TextureId],
         depth_img: Option<TextureId>,
+    ) -> RenderPass {
+        self.new_render_pass_layer(color_img, depth_img, 0)
+    }
+    /// Same as "new_render_pass_mrt", but renders into a single cubemap face,
+    /// `Texture2DArray` layer or `Texture3D` slice of each attachment.
+    /// `Texture2D` attachments are attached as a whole.
+    #[track_caller]
+    fn new_render_pass_layer(
+        &mut self,
+        color_img: &[TextureId],
+        depth_img: Option<TextureId>,
+        layer: u32,
     ) -> RenderPass;
     /// panics for depth-only or multiple color attachment render pass
     /// This function is, mostly, legacy. Using "render_pass_color_attachments"
     /// is recommended instead.
+    #[track_caller]
     fn render_pass_texture(&self, render_pass: RenderPass) -> TextureId {
         let textures = self.render_pass_color_attachments(render_pass);
         if textures.len() == 0 {
@@ -1189,8 +2073,23 @@ pub trait RenderingBackend {
         return textures[0];
     }
     /// For depth-only render pass returns empty slice.
+    #[track_caller]
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId];
+    #[track_caller]
     fn delete_render_pass(&mut self, render_pass: RenderPass);
+    /// Copy the attachments of `src` into `dst`, the default framebuffer for `None`,
+    /// stretching them over the whole `dst` with `filter`.
+    /// Color attachments are copied into the attachments with the same index, depth and stencil
+    /// when both passes have a depth attachment of the same format, always with nearest filtering.
+    ///
+    /// Without `glBlitFramebuffer`, on GLES2 and WebGL1, only the first color attachment
+    /// is copied by drawing a quad.
+    /// On Metal the passes should be of the same size, `filter` is ignored and `dst` can't
+    /// be the default framebuffer.
+    /// Should be called outside of a render pass.
+    #[track_caller]
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode);
+    #[track_caller]
     fn new_pipeline(
         &mut self,
         buffer_layout: &[BufferLayout],
@@ -1198,7 +2097,9 @@ pub trait RenderingBackend {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline;
+    #[track_caller]
     fn apply_pipeline(&mut self, pipeline: &Pipeline);
+    #[track_caller]
     fn delete_pipeline(&mut self, pipeline: Pipeline);
 
     /// Create a buffer resource object.
@@ -1220,12 +2121,21 @@ pub trait RenderingBackend {
     ///        BufferSource::slice(&vertices),
     ///    );
     /// ```
+    #[track_caller]
     fn new_buffer(&mut self, type_: BufferType, usage: BufferUsage, data: BufferSource)
         -> BufferId;
-    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource);
+    #[track_caller]
+    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+        self.buffer_update_range(buffer, 0, data)
+    }
+    /// Same as `buffer_update`, but writes `data` at `offset` bytes into the buffer.
+    /// The rest of the buffer keeps its content.
+    #[track_caller]
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource);
 
     /// Size of buffer in bytes.
     /// For 1 element, u16 buffer this will return 2.
+    #[track_caller]
     fn buffer_size(&mut self, buffer: BufferId) -> usize;
 
     /// Delete GPU buffer, leaving handle unmodified.
@@ -1233,8 +2143,8 @@ pub trait RenderingBackend {
     /// More high-level code on top of miniquad probably is going to call this in Drop implementation of some
     /// more RAII buffer object.
     ///
-    /// There is no protection against using deleted buffers later. However its not an UB in OpenGl and thats why
-    /// this function is not marked as unsafe
+    /// Using the handle after deletion panics, even once a new buffer took over its slot.
+    #[track_caller]
     fn delete_buffer(&mut self, buffer: BufferId);
 
     /// Delete GPU texture, leaving handle unmodified.
@@ -1242,8 +2152,9 @@ pub trait RenderingBackend {
     /// More high-level code on top of miniquad probably is going to call this in Drop implementation of some
     /// more RAII buffer object.
     ///
-    /// There is no protection against using deleted textures later. However its not a CPU-level UB and thats why
-    /// this function is not marked as unsafe
+    /// Using the handle after deletion panics, even once a new texture took over its slot.
+    /// Raw textures, made with `TextureId::from_raw_id`, are not checked.
+    #[track_caller]
     fn delete_texture(&mut self, texture: TextureId);
 
     /// Delete GPU program, leaving handle unmodified.
@@ -1251,38 +2162,118 @@ pub trait RenderingBackend {
     /// More high-level code on top of miniquad probably is going to call this in Drop implementation of some
     /// more RAII buffer object.
     ///
-    /// There is no protection against using deleted programs later. However its not a CPU-level Porgram and thats why
-    /// this function is not marked as unsafe
+    /// Using the handle after deletion panics, even once a new shader took over its slot.
+    #[track_caller]
     fn delete_shader(&mut self, program: ShaderId);
 
+    /// Compile `shader` and swap it in for the program behind `program`, every pipeline
+    /// created with `program` uses the new one from then on. On error the old program stays.
+    ///
+    /// Returns `ShaderError::ReplaceUnsupported` on backends that can't do it.
+    #[track_caller]
+    fn replace_shader(
+        &mut self,
+        _program: ShaderId,
+        _shader: ShaderSource,
+        _meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        Err(ShaderError::ReplaceUnsupported)
+    }
+
+    /// The linked program of the shader, to give to `new_shader_from_binary` on the next
+    /// run and skip compiling. Only works with the same driver, on the same GPU.
+    ///
+    /// `None` without `features.program_binary`.
+    #[track_caller]
+    fn shader_binary(&mut self, _shader: ShaderId) -> Option<ShaderBinary> {
+        None
+    }
+
+    /// Create a shader from `shader_binary` output.
+    ///
+    /// Fails with `ShaderError::ProgramBinaryRejected` without `features.program_binary`
+    /// and when the driver does not take the binary anymore, then compile from source.
+    #[track_caller]
+    fn new_shader_from_binary(
+        &mut self,
+        _binary: &ShaderBinary,
+        _meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        Err(ShaderError::ProgramBinaryRejected)
+    }
+
+    /// Name the texture for graphics debuggers and driver messages.
+    /// Does nothing on backends without debug labels, on GL they need KHR_debug
+    /// (GL 4.3, GLES 3.2) and are not available on WebGL.
+    #[track_caller]
+    fn texture_set_label(&mut self, _texture: TextureId, _label: &str) {}
+    /// Same as `texture_set_label`, for buffers.
+    #[track_caller]
+    fn buffer_set_label(&mut self, _buffer: BufferId, _label: &str) {}
+    /// Same as `texture_set_label`, for shaders.
+    #[track_caller]
+    fn shader_set_label(&mut self, _shader: ShaderId, _label: &str) {}
+    /// Same as `texture_set_label`, for pipelines.
+    /// GL has no pipeline objects, `apply_pipeline` inserts a debug marker with the label instead.
+    #[track_caller]
+    fn pipeline_set_label(&mut self, _pipeline: Pipeline, _label: &str) {}
+    /// Same as `texture_set_label`, for render passes.
+    /// Every pass is a debug group, named after its label, from `begin_pass` to `end_render_pass`.
+    #[track_caller]
+    fn render_pass_set_label(&mut self, _render_pass: RenderPass, _label: &str) {}
+
     /// Set a new viewport rectangle.
     /// Should be applied after begin_pass.
+    #[track_caller]
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32);
 
     /// Set a new scissor rectangle.
     /// Should be applied after begin_pass.
+    #[track_caller]
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32);
 
+    /// Same as `apply_bindings`, see `Bindings` for the arguments.
+    #[track_caller]
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
         index_buffer: BufferId,
+        index_buffer_offset: usize,
         textures: &[TextureId],
     );
 
+    #[track_caller]
     fn apply_bindings(&mut self, bindings: &Bindings) {
         self.apply_bindings_from_slice(
             &bindings.vertex_buffers,
+            &bindings.vertex_buffer_offsets,
             bindings.index_buffer,
+            bindings.index_buffer_offset,
             &bindings.images,
         );
     }
 
+    #[track_caller]
     fn apply_uniforms(&mut self, uniforms: UniformsSource) {
         self.apply_uniforms_from_bytes(uniforms.0.ptr as _, uniforms.0.size)
     }
+    #[track_caller]
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize);
 
+    /// Bind a range of a `BufferType::UniformBuffer` buffer to
+    /// `ShaderMeta::uniform_blocks[block]` of the current pipeline's shader.
+    ///
+    /// The range starts at `offset` bytes and is `UniformBlockLayout::std140_size` long,
+    /// so one buffer may hold data for many draws. `offset` should be a multiple of
+    /// `features.uniform_buffer_offset_alignment`.
+    /// On Metal the block is bound as `[[buffer(17 + block)]]` in both stages,
+    /// right after the vertex buffers.
+    /// Should be applied after `apply_pipeline`.
+    #[track_caller]
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize);
+
+    #[track_caller]
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
@@ -1290,12 +2281,35 @@ pub trait RenderingBackend {
         stencil: Option<i32>,
     );
     /// start rendering to the default frame buffer
+    #[track_caller]
     fn begin_default_pass(&mut self, action: PassAction);
     /// start rendering to an offscreen framebuffer
+    #[track_caller]
     fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction);
 
+    #[track_caller]
     fn end_render_pass(&mut self);
 
+    /// RGBA8 pixels of the `(x, y, width, height)` `rect` of the default framebuffer,
+    /// top row first, as `png::encode` takes them. `x` and `y` are the bottom-left corner,
+    /// the same as for `apply_scissor_rect`.
+    ///
+    /// Reads what was drawn so far this frame, so should be called at the end of `draw`,
+    /// outside of a render pass. A multisampled default framebuffer is resolved first.
+    /// On Metal this waits for the GPU to finish the frame so far.
+    /// ```no_run
+    /// # use miniquad::*;
+    /// # fn screenshot(ctx: &mut dyn RenderingBackend) {
+    /// let (width, height) = window::screen_size();
+    /// let pixels = ctx.read_default_framebuffer((0, 0, width as i32, height as i32));
+    /// let png = png::encode(width as u32, height as u32, &pixels);
+    /// std::fs::write("screenshot.png", png).unwrap();
+    /// # }
+    /// ```
+    #[track_caller]
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8>;
+
+    #[track_caller]
     fn commit_frame(&mut self);
 
     /// Draw elements using currently applied bindings and pipeline.
@@ -1306,5 +2320,252 @@ pub trait RenderingBackend {
     ///
     /// NOTE: num_instances > 1 might be not supported by the GPU (gl2.1 and gles2).
     /// `features.instancing` check is required.
+    #[track_caller]
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32);
+
+    /// Draw vertices in order, without the index buffer.
+    ///
+    /// + `first_vertex` specifies the first vertex to draw from the vertex buffers.
+    /// + `num_vertices` specifies how many vertices to draw.
+    /// + `num_instances` specifies how many instances should be rendered,
+    ///   `features.instancing` check is required for more than one.
+    #[track_caller]
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32);
+
+    /// Same as `draw`, but `base_vertex` is added to every index before fetching the vertex,
+    /// so many meshes may share one vertex buffer, each indexed from 0.
+    /// `features.draw_base_vertex` check is required.
+    #[track_caller]
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    );
+
+    /// Same as `draw_base_vertex`, with the arguments taken from the `DrawIndirectCommand`
+    /// at `offset` bytes into an `IndirectBuffer`.
+    /// `features.draw_indirect` check is required.
+    #[track_caller]
+    fn draw_indirect(&self, buffer: BufferId, offset: usize);
+
+    /// `draw_count` draws with the `DrawIndirectCommand`s starting at `offset` bytes into an
+    /// `IndirectBuffer`, `stride` bytes apart, 0 for tightly packed.
+    /// `features.multi_draw_indirect` check is required.
+    #[track_caller]
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32);
+
+    /// Create a GPU query object.
+    /// `features.elapsed_query` or `features.occlusion_query` check is required.
+    #[track_caller]
+    fn new_query(&mut self, query_type: QueryType) -> QueryId;
+    /// Start measuring. Only one query of each `QueryType` may be active at once.
+    #[track_caller]
+    fn begin_query(&mut self, query: QueryId);
+    #[track_caller]
+    fn end_query(&mut self, query: QueryId);
+    /// Reports whenever the result of an ended query may be read without stalling.
+    /// Results usually become available a couple frames later.
+    #[track_caller]
+    fn query_available(&mut self, query: QueryId) -> bool;
+    /// Nanoseconds for `QueryType::TimeElapsed`, samples for the occlusion queries.
+    /// Blocks until the result is available.
+    #[track_caller]
+    fn query_result(&mut self, query: QueryId) -> u64;
+    #[track_caller]
+    fn delete_query(&mut self, query: QueryId);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_derive_vertex_uniforms() {
+    #[repr(C)]
+    #[derive(crate::Vertex)]
+    #[vertex(buffer_index = 1, per_instance)]
+    struct Instance {
+        #[vertex(name = "in_pos")]
+        pos: [f32; 3],
+        color: [u8; 4],
+        #[vertex(format = Mat4)]
+        model: [f32; 16],
+    }
+
+    #[repr(C)]
+    #[derive(crate::Uniforms)]
+    struct Uniforms {
+        mvp: [[f32; 4]; 4],
+        offsets: [[f32; 2]; 3],
+        #[uniform(name = "frame")]
+        index: i32,
+    }
+
+    let layout = Instance::buffer_layout();
+    assert_eq!(layout.stride, 12 + 4 + 64);
+    assert_eq!(layout.step_func, VertexStep::PerInstance);
+    let attributes: Vec<_> = Instance::ATTRIBUTES
+        .iter()
+        .map(|attribute| (attribute.name, attribute.format, attribute.buffer_index))
+        .collect();
+    assert_eq!(
+        attributes,
+        [
+            ("in_pos", VertexFormat::Float3, 1),
+            ("color", VertexFormat::Byte4, 1),
+            ("model", VertexFormat::Mat4, 1),
+        ]
+    );
+
+    let uniforms: Vec<_> = Uniforms::uniform_layout()
+        .uniforms
+        .into_iter()
+        .map(|uniform| (uniform.name, uniform.uniform_type, uniform.array_count))
+        .collect();
+    assert_eq!(
+        uniforms,
+        [
+            ("mvp".to_string(), UniformType::Mat4, 1),
+            ("offsets".to_string(), UniformType::Float2, 3),
+            ("frame".to_string(), UniformType::Int1, 1),
+        ]
+    );
+}
+
+#[test]
+fn test_std140_layout() {
+    let layout = UniformBlockLayout {
+        uniforms: vec![
+            UniformDesc::new("a", UniformType::Float3),
+            // a float packs into the vec3 padding
+            UniformDesc::new("b", UniformType::Float1),
+            UniformDesc::new("c", UniformType::Float3),
+            UniformDesc::new("d", UniformType::Float2),
+            // array elements are padded to 16 bytes
+            UniformDesc::new("e", UniformType::Float1).array(3),
+            UniformDesc::new("f", UniformType::Mat4),
+            UniformDesc::new("g", UniformType::Float2).array(2),
+            UniformDesc::new("h", UniformType::Int1),
+        ],
+    };
+    assert_eq!(layout.std140_offsets(), [0, 12, 16, 32, 48, 96, 160, 192]);
+    assert_eq!(layout.std140_size(), 208);
+
+    let layout = UniformBlockLayout {
+        uniforms: vec![
+            UniformDesc::new("a", UniformType::Float3),
+            UniformDesc::new("b", UniformType::Float3),
+        ],
+    };
+    assert_eq!(layout.std140_offsets(), [0, 16]);
+    assert_eq!(layout.std140_size(), 32);
+
+    let layout = UniformBlockLayout {
+        uniforms: vec![
+            UniformDesc::new("a", UniformType::Float1),
+            UniformDesc::new("b", UniformType::Mat4).array(2),
+            UniformDesc::new("c", UniformType::Int3),
+        ],
+    };
+    assert_eq!(layout.std140_offsets(), [0, 16, 144]);
+    assert_eq!(layout.std140_size(), 160);
+
+    let layout = UniformBlockLayout { uniforms: vec![] };
+    assert_eq!(layout.std140_offsets(), []);
+    assert_eq!(layout.std140_size(), 0);
+}
+
+#[test]
+fn test_shader_meta_mismatches() {
+    let block = |uniforms| UniformBlockDesc {
+        name: "Lights".to_string(),
+        layout: UniformBlockLayout { uniforms },
+    };
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("mvp", UniformType::Mat4),
+                UniformDesc::new("offset", UniformType::Float2),
+                UniformDesc::new("typo", UniformType::Float1),
+            ],
+        },
+        images: vec!["tex".to_string(), "unused".to_string()],
+        uniform_blocks: vec![block(vec![
+            UniformDesc::new("position", UniformType::Float3),
+            UniformDesc::new("color", UniformType::Float4).array(4),
+        ])],
+    };
+    let reflected = ShaderMeta {
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("mvp", UniformType::Mat4),
+                UniformDesc::new("offset", UniformType::Float3),
+                UniformDesc::new("time", UniformType::Float1),
+            ],
+        },
+        images: vec!["tex".to_string(), "normals".to_string()],
+        uniform_blocks: vec![block(vec![
+            UniformDesc::new("position", UniformType::Float3),
+            UniformDesc::new("color", UniformType::Float4).array(2),
+        ])],
+    };
+
+    assert_eq!(meta.mismatches(&meta), []);
+    assert_eq!(
+        meta.mismatches(&reflected),
+        [
+            ShaderMetaMismatch::UniformType {
+                name: "offset".to_string(),
+                meta: (UniformType::Float2, 1),
+                shader: (UniformType::Float3, 1),
+            },
+            ShaderMetaMismatch::UnknownUniform {
+                name: "typo".to_string(),
+            },
+            ShaderMetaMismatch::MissingUniform {
+                name: "time".to_string(),
+                uniform_type: UniformType::Float1,
+                array_count: 1,
+            },
+            ShaderMetaMismatch::UnknownImage {
+                name: "unused".to_string(),
+            },
+            ShaderMetaMismatch::MissingImage {
+                name: "normals".to_string(),
+            },
+            ShaderMetaMismatch::UniformBlockMembers {
+                name: "Lights".to_string(),
+                meta: meta.uniform_blocks[0].layout.uniforms.clone(),
+                shader: reflected.uniform_blocks[0].layout.uniforms.clone(),
+            },
+        ]
+    );
+
+    // the order of the members is the layout
+    let mut swapped = meta.clone();
+    swapped.uniform_blocks[0].layout.uniforms.reverse();
+    assert_eq!(
+        meta.mismatches(&swapped)[0],
+        ShaderMetaMismatch::UniformBlockMembers {
+            name: "Lights".to_string(),
+            meta: meta.uniform_blocks[0].layout.uniforms.clone(),
+            shader: swapped.uniform_blocks[0].layout.uniforms.clone(),
+        }
+    );
+
+    let no_blocks = ShaderMeta {
+        uniform_blocks: vec![],
+        ..meta.clone()
+    };
+    assert_eq!(
+        meta.mismatches(&no_blocks),
+        [ShaderMetaMismatch::UnknownUniformBlock {
+            name: "Lights".to_string(),
+        }]
+    );
+    assert_eq!(
+        no_blocks.mismatches(&meta),
+        [ShaderMetaMismatch::MissingUniformBlock {
+            name: "Lights".to_string(),
+        }]
+    );
 }
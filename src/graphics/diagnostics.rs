@@ -0,0 +1,326 @@
+//! Parsing of the shader compile logs, one `ShaderDiagnostic` per message.
+//!
+//! Every vendor formats the log its own way:
+//! ```text
+//! 0:12(5): error: `x' undeclared                     Mesa
+//! 0(12) : error C1008: undefined variable "x"        NVIDIA
+//! ERROR: 0:12: 'x' : undeclared identifier           AMD, ANGLE
+//! ERROR: 0:12: Use of undeclared identifier 'x'      Apple
+//! ```
+
+use super::ShaderType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// One message of a shader compile log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub shader_type: ShaderType,
+    pub severity: DiagnosticSeverity,
+    /// 1-based, `None` when the message is about the whole shader.
+    pub line: Option<usize>,
+    /// 1-based, only some drivers report it.
+    pub column: Option<usize>,
+    pub message: String,
+    /// The source line `line` points at.
+    pub excerpt: Option<String>,
+    /// `None` for the source given to `new_shader`. `shader_preprocessor` sets it
+    /// to the file the line came from, `line` is then the line in that file.
+    pub file: Option<String>,
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}", file)?,
+            None => write!(f, "{:?} shader", self.shader_type)?,
+        }
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Info => "info",
+        };
+        write!(f, ": {}: {}", severity, self.message)
+    }
+}
+
+impl ShaderDiagnostic {
+    /// Split a compile log into diagnostics, `source` is the compiled source,
+    /// to take the excerpts from.
+    ///
+    /// Lines the parser does not recognize are appended to the previous message.
+    pub fn parse_log(shader_type: ShaderType, log: &str, source: &str) -> Vec<ShaderDiagnostic> {
+        let mut diagnostics: Vec<ShaderDiagnostic> = vec![];
+        for line in log.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (prefix, rest) = severity_prefix(line);
+            let (location, rest) = match location(rest) {
+                Some((location, rest)) => (Some(location), rest),
+                None => (None, rest),
+            };
+            let (severity, message) = match prefix {
+                Some(severity) => (Some(severity), rest),
+                None => match severity_word(rest) {
+                    Some((severity, message)) => (Some(severity), message),
+                    None => (None, rest),
+                },
+            };
+
+            match (severity, location) {
+                // AMD and ANGLE summary, "ERROR: 1 compilation errors.  No code generated."
+                (Some(_), None) if message.ends_with("No code generated.") => {}
+                // a continuation of a multi-line message
+                (None, None) if !diagnostics.is_empty() => {
+                    let last = diagnostics.last_mut().unwrap();
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+                (severity, location) => {
+                    let (line, column) = location.unwrap_or((None, None));
+                    diagnostics.push(ShaderDiagnostic {
+                        shader_type,
+                        severity: severity.unwrap_or(DiagnosticSeverity::Error),
+                        line,
+                        column,
+                        message: message.to_string(),
+                        excerpt: line
+                            .and_then(|line| source.lines().nth(line - 1))
+                            .map(|excerpt| excerpt.trim_end().to_string()),
+                        file: None,
+                    });
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+fn severity_from(word: &str) -> Option<DiagnosticSeverity> {
+    match &word.to_ascii_lowercase()[..] {
+        "error" | "fatal error" => Some(DiagnosticSeverity::Error),
+        "warning" => Some(DiagnosticSeverity::Warning),
+        "info" | "note" => Some(DiagnosticSeverity::Info),
+        _ => None,
+    }
+}
+
+/// "ERROR: 0:12: ..." to `(Error, "0:12: ...")`
+fn severity_prefix(line: &str) -> (Option<DiagnosticSeverity>, &str) {
+    if let Some(colon) = line.find(':') {
+        if let Some(severity) = severity_from(&line[..colon]) {
+            return (Some(severity), line[colon + 1..].trim_start());
+        }
+    }
+    (None, line)
+}
+
+/// "error: ..." and "error C1008: ..." after the location, the NVIDIA code stays in the message.
+fn severity_word(rest: &str) -> Option<(DiagnosticSeverity, &str)> {
+    let end = rest
+        .find(|c: char| c == ':' || c.is_whitespace())
+        .unwrap_or(rest.len());
+    let severity = severity_from(&rest[..end])?;
+    let message = rest[end..].trim_start();
+    Some((
+        severity,
+        message.strip_prefix(':').unwrap_or(message).trim_start(),
+    ))
+}
+
+type Location = (Option<usize>, Option<usize>);
+
+/// "0:12(5): ", "0(12) : ", "0:12: " and "0:12:5: " to the line, the column and the rest.
+/// The first number is the source string, always 0 with a single source.
+fn location(line: &str) -> Option<(Location, &str)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let number = |s: &str| -> Option<(usize, usize)> {
+        let end = digits(s);
+        Some((s[..end].parse().ok()?, end))
+    };
+
+    let source_end = digits(line);
+    if source_end == 0 {
+        return None;
+    }
+    let rest = &line[source_end..];
+    let (line_number, column, rest) = if let Some(rest) = rest.strip_prefix('(') {
+        // NVIDIA
+        let (line_number, end) = number(rest)?;
+        (line_number, None, rest[end..].strip_prefix(')')?)
+    } else {
+        let rest = rest.strip_prefix(':')?;
+        let (line_number, end) = number(rest)?;
+        let rest = &rest[end..];
+        if let Some(rest) = rest.strip_prefix('(') {
+            // Mesa
+            let (column, end) = number(rest)?;
+            (line_number, Some(column), rest[end..].strip_prefix(')')?)
+        } else {
+            match rest.strip_prefix(':').map(|rest| (number(rest), rest)) {
+                Some((Some((column, end)), rest)) if rest[end..].starts_with(':') => {
+                    (line_number, Some(column), &rest[end..])
+                }
+                _ => (line_number, None, rest),
+            }
+        }
+    };
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix(':').unwrap_or(rest).trim_start();
+    let line_number = Some(line_number).filter(|line| *line != 0);
+    Some((
+        (line_number, column.filter(|_| line_number.is_some())),
+        rest,
+    ))
+}
+
+#[cfg(test)]
+const TEST_SOURCE: &str = "void main() {\n    vec4 a = x;\n    int b = 1.0;\n}";
+
+/// Severity, line, column and message of each diagnostic.
+#[cfg(test)]
+fn parse_test_log(log: &str) -> Vec<(DiagnosticSeverity, Option<usize>, Option<usize>, String)> {
+    ShaderDiagnostic::parse_log(ShaderType::Fragment, log, TEST_SOURCE)
+        .into_iter()
+        .map(|d| (d.severity, d.line, d.column, d.message))
+        .collect()
+}
+
+#[test]
+fn test_parse_log_mesa() {
+    use DiagnosticSeverity::*;
+
+    let log = "0:2(14): error: `x' undeclared\n0:3(6): warning: implicit conversion\n";
+    let diagnostics = ShaderDiagnostic::parse_log(ShaderType::Fragment, log, TEST_SOURCE);
+    assert_eq!(diagnostics[0].excerpt.as_deref(), Some("    vec4 a = x;"));
+    assert_eq!(diagnostics[1].excerpt.as_deref(), Some("    int b = 1.0;"));
+    assert_eq!(
+        parse_test_log(log),
+        [
+            (Error, Some(2), Some(14), "`x' undeclared".to_string()),
+            (Warning, Some(3), Some(6), "implicit conversion".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_log_nvidia() {
+    use DiagnosticSeverity::*;
+
+    assert_eq!(
+        parse_test_log(
+            "0(2) : error C1008: undefined variable \"x\"\n0(3) : warning C7011: implicit cast"
+        ),
+        [
+            (
+                Error,
+                Some(2),
+                None,
+                "C1008: undefined variable \"x\"".to_string()
+            ),
+            (Warning, Some(3), None, "C7011: implicit cast".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_log_amd_angle() {
+    use DiagnosticSeverity::*;
+
+    // the summary line is dropped
+    assert_eq!(
+        parse_test_log(
+            "ERROR: 0:2: 'x' : undeclared identifier\nWARNING: 0:3: 'b' : narrowing\nERROR: 1 compilation errors.  No code generated.\n\n"
+        ),
+        [
+            (Error, Some(2), None, "'x' : undeclared identifier".to_string()),
+            (Warning, Some(3), None, "'b' : narrowing".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_log_apple() {
+    use DiagnosticSeverity::*;
+
+    // a message spanning two lines
+    assert_eq!(
+        parse_test_log("ERROR: 0:2: Use of undeclared identifier 'x'\n  did you mean 'a'?"),
+        [(
+            Error,
+            Some(2),
+            None,
+            "Use of undeclared identifier 'x'\ndid you mean 'a'?".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_parse_log_column() {
+    use DiagnosticSeverity::*;
+
+    // "0:12:5:", with or without a severity prefix
+    assert_eq!(
+        parse_test_log("ERROR: 0:2:14: 'x' : undeclared identifier\n0:3:6: warning: narrowing"),
+        [
+            (
+                Error,
+                Some(2),
+                Some(14),
+                "'x' : undeclared identifier".to_string()
+            ),
+            (Warning, Some(3), Some(6), "narrowing".to_string()),
+        ]
+    );
+    // a number after the line that is not followed by a colon is part of the message
+    assert_eq!(
+        parse_test_log("ERROR: 0:2: 5 errors"),
+        [(Error, Some(2), None, "5 errors".to_string())]
+    );
+}
+
+#[test]
+fn test_parse_log_without_location() {
+    use DiagnosticSeverity::*;
+
+    assert_eq!(
+        parse_test_log("Compile failed."),
+        [(Error, None, None, "Compile failed.".to_string())]
+    );
+    // line 0 is about the whole shader, the column goes with it
+    assert_eq!(
+        parse_test_log("0:0(1): error: no main function"),
+        [(Error, None, None, "no main function".to_string())]
+    );
+}
+
+#[test]
+fn test_parse_log_line_past_source() {
+    let diagnostics = ShaderDiagnostic::parse_log(
+        ShaderType::Fragment,
+        "0:5(1): error: syntax error, unexpected end of file\nERROR: 0:40: 'y' : undeclared",
+        TEST_SOURCE,
+    );
+    let lines: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.line, d.excerpt.as_deref()))
+        .collect();
+    assert_eq!(lines, [(Some(5), None), (Some(40), None)]);
+    // the last line is still found, without the trailing newline
+    let diagnostics =
+        ShaderDiagnostic::parse_log(ShaderType::Fragment, "0:4(1): error: x", TEST_SOURCE);
+    assert_eq!(diagnostics[0].excerpt.as_deref(), Some("}"));
+}
@@ -3,6 +3,7 @@ use std::ffi::CString;
 use crate::{window, ResourceManager};
 
 mod cache;
+mod debug;
 
 use super::*;
 use cache::*;
@@ -43,6 +44,8 @@ struct ShaderInternal {
     program: GLuint,
     images: Vec<ShaderImage>,
     uniforms: Vec<ShaderUniform>,
+    // std140 size of each of ShaderMeta::uniform_blocks, block N is bound to binding point N
+    uniform_block_sizes: Vec<usize>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -51,6 +54,10 @@ struct Texture {
     params: TextureParams,
 }
 
+fn compressed(internal_format: GLenum) -> (GLenum, GLenum, GLenum) {
+    (internal_format, internal_format, 0)
+}
+
 /// Converts from TextureFormat to (internal_format, format, pixel_type)
 impl From<TextureFormat> for (GLenum, GLenum, GLenum) {
     fn from(format: TextureFormat) -> Self {
@@ -64,6 +71,41 @@ impl From<TextureFormat> for (GLenum, GLenum, GLenum) {
             TextureFormat::Alpha => (GL_ALPHA, GL_ALPHA, GL_UNSIGNED_BYTE),
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => (GL_R8, GL_RED, GL_UNSIGNED_BYTE), // texture updates will swizzle Red -> Alpha to match WASM
+            TextureFormat::R8 => (GL_R8, GL_RED, GL_UNSIGNED_BYTE),
+            TextureFormat::RG8 => (GL_RG8, GL_RG, GL_UNSIGNED_BYTE),
+            TextureFormat::R16F => (GL_R16F, GL_RED, GL_HALF_FLOAT),
+            TextureFormat::RG16F => (GL_RG16F, GL_RG, GL_HALF_FLOAT),
+            TextureFormat::R32F => (GL_R32F, GL_RED, GL_FLOAT),
+            TextureFormat::RGBA32F => (GL_RGBA32F, GL_RGBA, GL_FLOAT),
+            TextureFormat::R32UI => (GL_R32UI, GL_RED_INTEGER, GL_UNSIGNED_INT),
+            TextureFormat::SRGBA8 => (GL_SRGB8_ALPHA8, GL_RGBA, GL_UNSIGNED_BYTE),
+            TextureFormat::Depth24Stencil8 => {
+                (GL_DEPTH24_STENCIL8, GL_DEPTH_STENCIL, GL_UNSIGNED_INT_24_8)
+            }
+            // compressed formats only use the internal format, it is also the glCompressedTexSubImage format
+            TextureFormat::BC1 => compressed(GL_COMPRESSED_RGBA_S3TC_DXT1_EXT),
+            TextureFormat::BC2 => compressed(GL_COMPRESSED_RGBA_S3TC_DXT3_EXT),
+            TextureFormat::BC3 => compressed(GL_COMPRESSED_RGBA_S3TC_DXT5_EXT),
+            TextureFormat::BC4 => compressed(GL_COMPRESSED_RED_RGTC1),
+            TextureFormat::BC5 => compressed(GL_COMPRESSED_RG_RGTC2),
+            TextureFormat::BC6H => compressed(GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT),
+            TextureFormat::BC7 => compressed(GL_COMPRESSED_RGBA_BPTC_UNORM),
+            TextureFormat::ETC2RGB => compressed(GL_COMPRESSED_RGB8_ETC2),
+            TextureFormat::ETC2RGBA => compressed(GL_COMPRESSED_RGBA8_ETC2_EAC),
+            TextureFormat::ASTC4x4 => compressed(GL_COMPRESSED_RGBA_ASTC_4x4_KHR),
+            TextureFormat::ASTC5x5 => compressed(GL_COMPRESSED_RGBA_ASTC_5x5_KHR),
+            TextureFormat::ASTC6x6 => compressed(GL_COMPRESSED_RGBA_ASTC_6x6_KHR),
+            TextureFormat::ASTC8x8 => compressed(GL_COMPRESSED_RGBA_ASTC_8x8_KHR),
+            TextureFormat::BC1SRGB => compressed(GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT),
+            TextureFormat::BC2SRGB => compressed(GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT),
+            TextureFormat::BC3SRGB => compressed(GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT),
+            TextureFormat::BC7SRGB => compressed(GL_COMPRESSED_SRGB_ALPHA_BPTC_UNORM),
+            TextureFormat::ETC2SRGB => compressed(GL_COMPRESSED_SRGB8_ETC2),
+            TextureFormat::ETC2SRGBA => compressed(GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC),
+            TextureFormat::ASTC4x4SRGB => compressed(GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR),
+            TextureFormat::ASTC5x5SRGB => compressed(GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR),
+            TextureFormat::ASTC6x6SRGB => compressed(GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR),
+            TextureFormat::ASTC8x8SRGB => compressed(GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR),
         }
     }
 }
@@ -73,6 +115,8 @@ impl From<TextureKind> for GLuint {
         match kind {
             TextureKind::Texture2D => GL_TEXTURE_2D,
             TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP,
+            TextureKind::Texture2DArray => GL_TEXTURE_2D_ARRAY,
+            TextureKind::Texture3D => GL_TEXTURE_3D,
         }
     }
 }
@@ -134,6 +178,417 @@ impl From<CompareFunc> for GLenum {
     }
 }
 
+fn is_layered(kind: TextureKind) -> bool {
+    matches!(kind, TextureKind::Texture2DArray | TextureKind::Texture3D)
+}
+
+/// `glTexImage2D`/`glFramebufferTexture2D` target of a `Texture2D` or a cubemap face.
+fn face_target(kind: TextureKind, face: u32) -> GLenum {
+    match kind {
+        TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP_POSITIVE_X + face,
+        _ => GL_TEXTURE_2D,
+    }
+}
+
+/// Depth textures with a stencil are attached to both, so the stencil test works.
+fn depth_attachment(format: TextureFormat) -> GLenum {
+    match format {
+        TextureFormat::Depth24Stencil8 => GL_DEPTH_STENCIL_ATTACHMENT,
+        _ => GL_DEPTH_ATTACHMENT,
+    }
+}
+
+/// Attach a texture layer or cubemap face to the bound framebuffer.
+unsafe fn framebuffer_texture(attachment: GLenum, texture: &Texture, layer: u32) {
+    if is_layered(texture.params.kind) {
+        glFramebufferTextureLayer(GL_FRAMEBUFFER, attachment, texture.raw, 0, layer as _);
+    } else {
+        glFramebufferTexture2D(
+            GL_FRAMEBUFFER,
+            attachment,
+            face_target(texture.params.kind, layer),
+            texture.raw,
+            0,
+        );
+    }
+}
+
+/// Sized internal format of a multisampled renderbuffer standing in for a texture of `format`,
+/// has to match the texture's internal format to be resolved into it.
+fn renderbuffer_format(format: TextureFormat) -> GLenum {
+    match format {
+        TextureFormat::RGB8 => GL_RGB8,
+        TextureFormat::RGBA8 => GL_RGBA8,
+        TextureFormat::Depth => GL_DEPTH_COMPONENT16,
+        TextureFormat::Depth32 => GL_DEPTH_COMPONENT32F,
+        TextureFormat::Alpha => GL_R8,
+        _ => {
+            let (internal_format, _, _) = format.into();
+            internal_format
+        }
+    }
+}
+
+/// Internal format `glTexImage` allocates a texture of `format` with. Depth gets the same
+/// sized format as its multisampled renderbuffer on GL 3 and GLES 3 class contexts,
+/// the resolve blit fails when the formats differ. GLES 2 and WebGL 1 take only unsized ones.
+fn texture_internal_format(format: TextureFormat) -> GLenum {
+    match format {
+        TextureFormat::Depth | TextureFormat::Depth32 if texture_arrays_supported() => {
+            renderbuffer_format(format)
+        }
+        _ => {
+            let (internal_format, _, _) = format.into();
+            internal_format
+        }
+    }
+}
+
+/// `glDrawBuffers` of the bound framebuffer with `count` color attachments.
+unsafe fn draw_buffers(count: usize) {
+    if count > 1 {
+        let attachments: Vec<GLenum> = (0..count as u32)
+            .map(|i| GL_COLOR_ATTACHMENT0 + i)
+            .collect();
+        glDrawBuffers(count as _, attachments.as_ptr() as _);
+    }
+}
+
+/// `glBlitFramebuffer` mask of an attachment of `format`.
+fn blit_mask(format: TextureFormat) -> GLenum {
+    match format {
+        TextureFormat::Depth24Stencil8 => GL_DEPTH_BUFFER_BIT | GL_STENCIL_BUFFER_BIT,
+        format if format.is_depth() => GL_DEPTH_BUFFER_BIT,
+        _ => GL_COLOR_BUFFER_BIT,
+    }
+}
+
+/// Blit the first `count` color attachments of the bound read framebuffer into the attachments
+/// with the same index of the bound draw framebuffer. Blits copy the read buffer into every
+/// draw buffer, so the attachments go one by one, each as the only draw buffer.
+/// The draw buffers are left as they were for framebuffers made with `new_render_pass_layer`.
+unsafe fn blit_color_attachments(count: usize, src: (i32, i32), dst: (i32, i32), filter: GLenum) {
+    for i in 0..count {
+        let attachment = GL_COLOR_ATTACHMENT0 + i as u32;
+        let mut buffers = vec![GL_NONE; i + 1];
+        buffers[i] = attachment;
+        glReadBuffer(attachment);
+        glDrawBuffers(buffers.len() as _, buffers.as_ptr() as _);
+        glBlitFramebuffer(
+            0,
+            0,
+            src.0,
+            src.1,
+            0,
+            0,
+            dst.0,
+            dst.1,
+            GL_COLOR_BUFFER_BIT,
+            filter,
+        );
+    }
+    if count > 0 {
+        glReadBuffer(GL_COLOR_ATTACHMENT0);
+        draw_buffers(count);
+    }
+}
+
+/// Format and type `glReadPixels` reads back a framebuffer of `format` with.
+///
+/// GLES and WebGL only have to support RGBA reads, plus the one format/type pair from
+/// GL_IMPLEMENTATION_COLOR_READ_FORMAT/TYPE. Anything else is read as RGBA and repacked.
+#[derive(Clone, Copy)]
+struct ReadFormat {
+    format: TextureFormat,
+    gl_format: GLenum,
+    pixel_type: GLenum,
+    /// Read as RGBA, `repack` has to drop the channels missing in `format`
+    rgba: bool,
+    /// Bytes per read pixel
+    pixel_size: usize,
+}
+
+impl ReadFormat {
+    unsafe fn new(format: TextureFormat) -> ReadFormat {
+        let (_, gl_format, pixel_type) = format.into();
+        let direct = ReadFormat {
+            format,
+            gl_format,
+            pixel_type,
+            rgba: false,
+            pixel_size: format.size(1, 1) as usize,
+        };
+        if !is_gles() {
+            return direct;
+        }
+        assert!(
+            !format.is_depth(),
+            "Depth textures can't be read back on GLES and WebGL"
+        );
+        let mut read_format = 0;
+        let mut read_type = 0;
+        glGetIntegerv(GL_IMPLEMENTATION_COLOR_READ_FORMAT, &mut read_format);
+        glGetIntegerv(GL_IMPLEMENTATION_COLOR_READ_TYPE, &mut read_type);
+        if (read_format as GLenum, read_type as GLenum) == (gl_format, pixel_type) {
+            return direct;
+        }
+
+        let (gl_format, pixel_type, component_size) = match pixel_type {
+            GL_UNSIGNED_BYTE => (GL_RGBA, GL_UNSIGNED_BYTE, 1),
+            GL_UNSIGNED_INT => (GL_RGBA_INTEGER, GL_UNSIGNED_INT, 4),
+            _ => (GL_RGBA, GL_FLOAT, 4),
+        };
+        ReadFormat {
+            format,
+            gl_format,
+            pixel_type,
+            rgba: true,
+            pixel_size: 4 * component_size,
+        }
+    }
+
+    /// `glReadPixels` of the bound framebuffer, `pixels` is an offset with a
+    /// GL_PIXEL_PACK_BUFFER bound.
+    unsafe fn read(&self, width: u32, height: u32, pixels: *mut GLvoid) {
+        glPixelStorei(GL_PACK_ALIGNMENT, 1);
+        glReadPixels(
+            0,
+            0,
+            width as _,
+            height as _,
+            self.gl_format,
+            self.pixel_type,
+            pixels,
+        );
+    }
+
+    /// Copy `read` pixels into `bytes` laid out as `format`.
+    fn repack(&self, read: &[u8], bytes: &mut [u8]) {
+        if !self.rgba {
+            bytes.copy_from_slice(read);
+            return;
+        }
+        let (_, gl_format, pixel_type) = self.format.into();
+        // indices of the format channels in RGBA
+        let channels: &[usize] = match gl_format {
+            GL_RED | GL_RED_INTEGER => &[0],
+            GL_RG => &[0, 1],
+            GL_RGB => &[0, 1, 2],
+            GL_ALPHA => &[3],
+            _ => &[0, 1, 2, 3],
+        };
+        let component_size = self.pixel_size / 4;
+        let mut dst = 0;
+        for pixel in read.chunks_exact(self.pixel_size) {
+            for channel in channels {
+                let component = &pixel[channel * component_size..(channel + 1) * component_size];
+                if pixel_type == GL_HALF_FLOAT {
+                    let value = f32::from_ne_bytes([
+                        component[0],
+                        component[1],
+                        component[2],
+                        component[3],
+                    ]);
+                    bytes[dst..dst + 2]
+                        .copy_from_slice(&super::software::f32_to_f16(value).to_ne_bytes());
+                    dst += 2;
+                } else {
+                    bytes[dst..dst + component_size].copy_from_slice(component);
+                    dst += component_size;
+                }
+            }
+        }
+    }
+}
+
+/// `glReadPixels` of the bound framebuffer into `bytes` laid out as `format`.
+unsafe fn read_framebuffer(width: u32, height: u32, format: TextureFormat, bytes: &mut [u8]) {
+    let read = ReadFormat::new(format);
+    if !read.rgba {
+        read.read(width, height, bytes.as_mut_ptr() as _);
+        return;
+    }
+    let mut rgba = vec![0u8; (width * height) as usize * read.pixel_size];
+    read.read(width, height, rgba.as_mut_ptr() as _);
+    read.repack(&rgba, bytes);
+}
+
+fn mip_size(size: u32, level: usize) -> u32 {
+    (size >> level).max(1)
+}
+
+/// `glTexImage2D` of a single level, `glCompressedTexImage2D` for compressed formats.
+/// `None` only allocates the level.
+unsafe fn tex_image_2d(
+    target: GLenum,
+    level: usize,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    data: Option<&[u8]>,
+) {
+    let (_, gl_format, pixel_type) = format.into();
+    let internal_format = texture_internal_format(format);
+    if format.is_compressed() {
+        // compressed levels can't be allocated without the data
+        let size = format.size(width, height) as usize;
+        let zeroes;
+        let data = match data {
+            Some(data) => data,
+            None => {
+                zeroes = vec![0; size];
+                &zeroes
+            }
+        };
+        glCompressedTexImage2D(
+            target,
+            level as _,
+            internal_format,
+            width as _,
+            height as _,
+            0,
+            size as _,
+            data.as_ptr() as *const _,
+        );
+    } else {
+        glTexImage2D(
+            target,
+            level as _,
+            internal_format as i32,
+            width as _,
+            height as _,
+            0,
+            gl_format,
+            pixel_type,
+            data.map_or(std::ptr::null(), |data| data.as_ptr() as *const _),
+        );
+    }
+}
+
+/// Same as `tex_image_2d`, for all the layers of a `Texture2DArray` or `Texture3D` level.
+unsafe fn tex_image_3d(
+    target: GLenum,
+    level: usize,
+    format: TextureFormat,
+    (width, height, depth): (u32, u32, u32),
+    data: Option<&[u8]>,
+) {
+    let (_, gl_format, pixel_type) = format.into();
+    let internal_format = texture_internal_format(format);
+    if format.is_compressed() {
+        let size = format.size(width, height) as usize * depth as usize;
+        let zeroes;
+        let data = match data {
+            Some(data) => data,
+            None => {
+                zeroes = vec![0; size];
+                &zeroes
+            }
+        };
+        glCompressedTexImage3D(
+            target,
+            level as _,
+            internal_format,
+            width as _,
+            height as _,
+            depth as _,
+            0,
+            size as _,
+            data.as_ptr() as *const _,
+        );
+    } else {
+        glTexImage3D(
+            target,
+            level as _,
+            internal_format as i32,
+            width as _,
+            height as _,
+            depth as _,
+            0,
+            gl_format,
+            pixel_type,
+            data.map_or(std::ptr::null(), |data| data.as_ptr() as *const _),
+        );
+    }
+}
+
+/// `glTexSubImage2D` or `glCompressedTexSubImage2D`.
+unsafe fn tex_sub_image_2d(
+    target: GLenum,
+    level: usize,
+    format: TextureFormat,
+    (x, y, width, height): (i32, i32, i32, i32),
+    data: &[u8],
+) {
+    let (_, gl_format, pixel_type) = format.into();
+    if format.is_compressed() {
+        glCompressedTexSubImage2D(
+            target,
+            level as _,
+            x,
+            y,
+            width,
+            height,
+            gl_format,
+            data.len() as _,
+            data.as_ptr() as *const _,
+        );
+    } else {
+        glTexSubImage2D(
+            target,
+            level as _,
+            x,
+            y,
+            width,
+            height,
+            gl_format,
+            pixel_type,
+            data.as_ptr() as *const _,
+        );
+    }
+}
+
+/// `glTexSubImage3D` or `glCompressedTexSubImage3D` of a single layer.
+unsafe fn tex_sub_image_3d(
+    target: GLenum,
+    level: usize,
+    format: TextureFormat,
+    layer: u32,
+    (x, y, width, height): (i32, i32, i32, i32),
+    data: &[u8],
+) {
+    let (_, gl_format, pixel_type) = format.into();
+    if format.is_compressed() {
+        glCompressedTexSubImage3D(
+            target,
+            level as _,
+            x,
+            y,
+            layer as _,
+            width,
+            height,
+            1,
+            gl_format,
+            data.len() as _,
+            data.as_ptr() as *const _,
+        );
+    } else {
+        glTexSubImage3D(
+            target,
+            level as _,
+            x,
+            y,
+            layer as _,
+            width,
+            height,
+            1,
+            gl_format,
+            pixel_type,
+            data.as_ptr() as *const _,
+        );
+    }
+}
+
 impl Texture {
     pub fn new(
         ctx: &mut GlContext,
@@ -143,13 +598,11 @@ impl Texture {
     ) -> Texture {
         if let TextureSource::Bytes(bytes_data) = source {
             assert_eq!(
-                params.format.size(params.width, params.height) as usize,
+                params.format.size(params.width, params.height) as usize * params.layers() as usize,
                 bytes_data.len()
             );
         }
 
-        let (internal_format, format, pixel_type) = params.format.into();
-
         ctx.cache.store_texture_binding(0);
 
         let mut texture: GLuint = 0;
@@ -172,33 +625,32 @@ impl Texture {
             }
 
             match source {
+                _ if is_layered(params.kind) => {
+                    Self::upload_layers(&params, source);
+                }
                 TextureSource::Empty => {
                     // not quite sure if glTexImage2D(null) is really a requirement
                     // but it was like this for quite a while and apparantly it works?
-                    glTexImage2D(
-                        GL_TEXTURE_2D,
-                        0,
-                        internal_format as i32,
-                        params.width as i32,
-                        params.height as i32,
-                        0,
-                        format,
-                        pixel_type,
-                        std::ptr::null() as _,
-                    );
+                    for face in 0..params.layers() {
+                        tex_image_2d(
+                            face_target(params.kind, face),
+                            0,
+                            params.format,
+                            params.width,
+                            params.height,
+                            None,
+                        );
+                    }
                 }
                 TextureSource::Bytes(source) => {
                     assert!(params.kind == TextureKind::Texture2D, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
-                    glTexImage2D(
+                    tex_image_2d(
                         GL_TEXTURE_2D,
                         0,
-                        internal_format as i32,
-                        params.width as i32,
-                        params.height as i32,
-                        0,
-                        format,
-                        pixel_type,
-                        source.as_ptr() as *const _,
+                        params.format,
+                        params.width,
+                        params.height,
+                        Some(source),
                     );
                 }
                 TextureSource::Array(array) => {
@@ -208,28 +660,27 @@ impl Texture {
                             "Cubemaps require TextureSource::Array of 6 textures."
                         );
                     }
+                    let levels = array.iter().map(|mipmaps| mipmaps.len()).max().unwrap_or(1);
+                    if levels != 1 {
+                        glTexParameteri(params.kind.into(), GL_TEXTURE_BASE_LEVEL, 0);
+                        glTexParameteri(
+                            params.kind.into(),
+                            GL_TEXTURE_MAX_LEVEL,
+                            levels as i32 - 1,
+                        );
+                    }
                     for (cubemap_face, mipmaps) in array.iter().enumerate() {
-                        if mipmaps.len() != 1 {
-                            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
-                            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, array.len() as _);
-                        }
                         for (mipmap_level, bytes) in mipmaps.iter().enumerate() {
-                            let target = match params.kind {
-                                TextureKind::Texture2D => GL_TEXTURE_2D,
-                                TextureKind::CubeMap => {
-                                    GL_TEXTURE_CUBE_MAP_POSITIVE_X + cubemap_face as u32
-                                }
-                            };
-                            glTexImage2D(
-                                target,
-                                mipmap_level as _,
-                                internal_format as i32,
-                                params.width as i32,
-                                params.height as i32,
-                                0,
-                                format,
-                                pixel_type,
-                                bytes.as_ptr() as *const _,
+                            let width = mip_size(params.width, mipmap_level);
+                            let height = mip_size(params.height, mipmap_level);
+                            assert_eq!(params.format.size(width, height) as usize, bytes.len());
+                            tex_image_2d(
+                                face_target(params.kind, cubemap_face as u32),
+                                mipmap_level,
+                                params.format,
+                                width,
+                                height,
+                                Some(bytes),
                             );
                         }
                     }
@@ -250,6 +701,9 @@ impl Texture {
 
             glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_S, wrap as i32);
             glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_T, wrap as i32);
+            if params.kind == TextureKind::Texture3D {
+                glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_R, wrap as i32);
+            }
             glTexParameteri(params.kind.into(), GL_TEXTURE_MIN_FILTER, min_filter as i32);
             glTexParameteri(params.kind.into(), GL_TEXTURE_MAG_FILTER, mag_filter as i32);
         }
@@ -261,38 +715,93 @@ impl Texture {
         }
     }
 
+    /// Allocate every mipmap level of a `Texture2DArray` or `Texture3D` and upload the
+    /// source into it, the texture should be bound.
+    unsafe fn upload_layers(params: &TextureParams, source: TextureSource) {
+        let target = params.kind.into();
+        let levels = match source {
+            TextureSource::Array(array) => array.iter().map(|mipmaps| mipmaps.len()).max(),
+            _ => None,
+        }
+        .unwrap_or(1);
+        if levels != 1 {
+            glTexParameteri(target, GL_TEXTURE_BASE_LEVEL, 0);
+            glTexParameteri(target, GL_TEXTURE_MAX_LEVEL, levels as i32 - 1);
+        }
+
+        let level_size = |level: usize| {
+            let depth = match params.kind {
+                TextureKind::Texture3D => mip_size(params.depth, level),
+                _ => params.depth,
+            };
+            (
+                mip_size(params.width, level),
+                mip_size(params.height, level),
+                depth,
+            )
+        };
+        for level in 0..levels {
+            let data = match source {
+                TextureSource::Bytes(bytes) if level == 0 => Some(bytes),
+                _ => None,
+            };
+            tex_image_3d(target, level, params.format, level_size(level), data);
+        }
+
+        if let TextureSource::Array(array) = source {
+            assert!(
+                array.len() == params.depth as usize,
+                "Texture arrays require TextureSource::Array of TextureParams::depth layers."
+            );
+            for (layer, mipmaps) in array.iter().enumerate() {
+                for (level, bytes) in mipmaps.iter().enumerate() {
+                    let (width, height, depth) = level_size(level);
+                    if layer >= depth as usize {
+                        continue;
+                    }
+                    assert_eq!(params.format.size(width, height) as usize, bytes.len());
+                    tex_sub_image_3d(
+                        target,
+                        level,
+                        params.format,
+                        layer as _,
+                        (0, 0, width as _, height as _),
+                        bytes,
+                    );
+                }
+            }
+        }
+    }
+
     pub fn resize(&mut self, ctx: &mut GlContext, width: u32, height: u32, source: Option<&[u8]>) {
         ctx.cache.store_texture_binding(0);
         ctx.cache.bind_texture(0, self.params.kind.into(), self.raw);
 
-        let (internal_format, format, pixel_type) = self.params.format.into();
-
         self.params.width = width;
         self.params.height = height;
 
         unsafe {
-            glTexImage2D(
-                GL_TEXTURE_2D,
-                0,
-                internal_format as i32,
-                self.params.width as i32,
-                self.params.height as i32,
-                0,
-                format,
-                pixel_type,
-                match source {
-                    Some(source) => source.as_ptr() as *const _,
-                    Option::None => std::ptr::null(),
-                },
-            );
+            if is_layered(self.params.kind) {
+                tex_image_3d(
+                    self.params.kind.into(),
+                    0,
+                    self.params.format,
+                    (width, height, self.params.depth),
+                    source,
+                );
+            } else {
+                tex_image_2d(GL_TEXTURE_2D, 0, self.params.format, width, height, source);
+            }
         }
 
         ctx.cache.restore_texture_binding(0);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update_texture_part(
         &self,
         ctx: &mut GlContext,
+        layer: u32,
         x_offset: i32,
         y_offset: i32,
         width: i32,
@@ -302,12 +811,11 @@ impl Texture {
         assert_eq!(self.size(width as _, height as _), source.len());
         assert!(x_offset + width <= self.params.width as _);
         assert!(y_offset + height <= self.params.height as _);
+        assert!(layer < self.params.layers());
 
         ctx.cache.store_texture_binding(0);
         ctx.cache.bind_texture(0, self.params.kind.into(), self.raw);
 
-        let (_, format, pixel_type) = self.params.format.into();
-
         unsafe {
             glPixelStorei(GL_UNPACK_ALIGNMENT, 1); // miniquad always uses row alignment of 1
 
@@ -316,60 +824,83 @@ impl Texture {
                 if self.params.format == TextureFormat::Alpha {
                     // if alpha miniquad texture, the value on non-WASM is stored in red channel
                     // swizzle red -> alpha
-                    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_SWIZZLE_A, GL_RED as _);
+                    glTexParameteri(self.params.kind.into(), GL_TEXTURE_SWIZZLE_A, GL_RED as _);
                 } else {
                     // keep alpha -> alpha
-                    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_SWIZZLE_A, GL_ALPHA as _);
+                    glTexParameteri(self.params.kind.into(), GL_TEXTURE_SWIZZLE_A, GL_ALPHA as _);
                 }
             }
 
-            glTexSubImage2D(
-                GL_TEXTURE_2D,
-                0,
-                x_offset as _,
-                y_offset as _,
-                width as _,
-                height as _,
-                format,
-                pixel_type,
-                source.as_ptr() as *const _,
-            );
+            let rect = (x_offset, y_offset, width, height);
+            if is_layered(self.params.kind) {
+                tex_sub_image_3d(
+                    self.params.kind.into(),
+                    0,
+                    self.params.format,
+                    layer,
+                    rect,
+                    source,
+                );
+            } else {
+                tex_sub_image_2d(
+                    face_target(self.params.kind, layer),
+                    0,
+                    self.params.format,
+                    rect,
+                    source,
+                );
+            }
         }
 
         ctx.cache.restore_texture_binding(0);
     }
 
-    /// Read texture data into CPU memory
+    /// Read texture data into CPU memory, all the layers of `TextureParams::layers` one after another
     pub fn read_pixels(&self, bytes: &mut [u8]) {
-        let (_, format, pixel_type) = self.params.format.into();
-
-        let mut fbo = 0;
+        let layer_size = self.size(self.params.width, self.params.height);
+        let mut layers = bytes.chunks_exact_mut(layer_size);
         unsafe {
-            let mut binded_fbo: i32 = 0;
-            glGetIntegerv(gl::GL_DRAW_FRAMEBUFFER_BINDING, &mut binded_fbo);
-            glGenFramebuffers(1, &mut fbo);
-            glBindFramebuffer(gl::GL_FRAMEBUFFER, fbo);
-            glFramebufferTexture2D(
-                gl::GL_FRAMEBUFFER,
-                gl::GL_COLOR_ATTACHMENT0,
-                gl::GL_TEXTURE_2D,
-                self.raw,
-                0,
-            );
+            self.read_layers(|_| {
+                if let Some(bytes) = layers.next() {
+                    read_framebuffer(
+                        self.params.width,
+                        self.params.height,
+                        self.params.format,
+                        bytes,
+                    );
+                }
+            });
+        }
+    }
 
-            glReadPixels(
-                0,
-                0,
-                self.params.width as _,
-                self.params.height as _,
-                format,
-                pixel_type,
-                bytes.as_mut_ptr() as _,
-            );
+    /// Bind every cubemap face or layer of the texture for reading, one after another,
+    /// and call `f` with its index.
+    unsafe fn read_layers(&self, mut f: impl FnMut(usize)) {
+        assert!(
+            !self.params.format.is_compressed(),
+            "Compressed textures can't be read back"
+        );
+        let attachment = if self.params.format.is_depth() {
+            depth_attachment(self.params.format)
+        } else {
+            GL_COLOR_ATTACHMENT0
+        };
 
-            glBindFramebuffer(gl::GL_FRAMEBUFFER, binded_fbo as _);
-            glDeleteFramebuffers(1, &fbo);
+        let mut fbo = 0;
+        let mut binded_fbo: i32 = 0;
+        glGetIntegerv(gl::GL_DRAW_FRAMEBUFFER_BINDING, &mut binded_fbo);
+        glGenFramebuffers(1, &mut fbo);
+        glBindFramebuffer(gl::GL_FRAMEBUFFER, fbo);
+        if self.params.format.is_depth() {
+            glReadBuffer(GL_NONE);
+        }
+        for layer in 0..self.params.layers() {
+            framebuffer_texture(attachment, self, layer);
+            f(layer as usize);
         }
+
+        glBindFramebuffer(gl::GL_FRAMEBUFFER, binded_fbo as _);
+        glDeleteFramebuffers(1, &fbo);
     }
 
     #[inline]
@@ -395,8 +926,12 @@ impl Texture {
 
 pub(crate) struct PipelineInternal {
     layout: Vec<Option<VertexAttributeInternal>>,
+    // to find the attributes again when the shader is replaced
+    buffer_layout: Vec<BufferLayout>,
+    attributes: Vec<VertexAttribute>,
     shader: ShaderId,
     params: PipelineParams,
+    label: Option<String>,
 }
 
 type UniformLocation = Option<GLint>;
@@ -416,13 +951,320 @@ fn get_uniform_location(program: GLuint, name: &str) -> Option<i32> {
     Some(location)
 }
 
+pub(crate) fn is_gles() -> bool {
+    #[cfg(target_arch = "wasm32")]
+    {
+        true
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        let version_string = glGetString(GL_VERSION);
+        std::ffi::CStr::from_ptr(version_string as _)
+            .to_string_lossy()
+            .contains("OpenGL ES")
+    }
+}
+
+fn gl_version() -> String {
+    gl_string(GL_VERSION)
+}
+
+fn gl_string(name: GLenum) -> String {
+    unsafe {
+        let string = glGetString(name);
+        if string.is_null() {
+            return String::new();
+        }
+        std::ffi::CStr::from_ptr(string as _)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Substring search over the extension names, so "timer_query" matches
+/// GL_ARB_timer_query and GL_EXT_disjoint_timer_query alike.
+pub(crate) fn has_extension(name: &str) -> bool {
+    any_extension(|extension| extension.contains(name))
+}
+
+fn any_extension<F: Fn(&str) -> bool>(f: F) -> bool {
+    unsafe {
+        // glGetStringi is not a part of gl2 and is not there on WebGL
+        if cfg!(target_arch = "wasm32") || crate::native::gl::is_gl2() {
+            let extensions = glGetString(GL_EXTENSIONS);
+            if extensions.is_null() {
+                return false;
+            }
+            return std::ffi::CStr::from_ptr(extensions as _)
+                .to_string_lossy()
+                .split(' ')
+                .any(f);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut count: GLint = 0;
+            glGetIntegerv(GL_NUM_EXTENSIONS, &mut count);
+            (0..count).any(|i| {
+                let extension = glGetStringi(GL_EXTENSIONS, i as _);
+                !extension.is_null()
+                    && f(&std::ffi::CStr::from_ptr(extension as _).to_string_lossy())
+            })
+        }
+        #[cfg(target_arch = "wasm32")]
+        unreachable!()
+    }
+}
+
+/// (major, minor) of the desktop GL, GLES or WebGL version, whatever `is_gles` says.
+fn gl_version_number() -> (u32, u32) {
+    let version = gl_version();
+    let mut numbers = version
+        .trim_start_matches("OpenGL ES ")
+        .trim_start_matches("WebGL ")
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|number| number.parse().unwrap_or(0));
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+pub(crate) fn elapsed_query_supported() -> bool {
+    if cfg!(target_arch = "wasm32") {
+        // gl.js implements the queries with the WebGL 2 API,
+        // with EXT_disjoint_timer_query_webgl2 for GL_TIME_ELAPSED
+        return gl_version().contains("WebGL 2.0") && has_extension("disjoint_timer_query");
+    }
+    // core since desktop GL 3.3, GLES only has EXT_disjoint_timer_query
+    let supported = if is_gles() {
+        has_extension("GL_EXT_disjoint_timer_query")
+    } else {
+        gl_version_number() >= (3, 3) || has_extension("GL_ARB_timer_query")
+    };
+    supported && query_functions_loaded(QueryType::TimeElapsed)
+}
+
+pub(crate) fn occlusion_query_supported() -> bool {
+    let version = gl_version();
+    if cfg!(target_arch = "wasm32") {
+        return version.contains("WebGL 2.0");
+    }
+    (version.starts_with("3.3")
+        || version.starts_with('4')
+        || version.starts_with("OpenGL ES 3")
+        || has_extension("occlusion_query2")
+        || has_extension("occlusion_query_boolean"))
+        && query_functions_loaded(QueryType::AnySamplesPassed)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn query_functions_loaded(query_type: QueryType) -> bool {
+    crate::native::gl::query_functions_loaded(query_type)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn query_functions_loaded(_query_type: QueryType) -> bool {
+    true
+}
+
+pub(crate) fn uniform_buffers_supported() -> bool {
+    let version = gl_version();
+    if cfg!(target_arch = "wasm32") {
+        return version.contains("WebGL 2.0");
+    }
+    // core since desktop GL 3.1 and GLES 3.0
+    !(version.starts_with('2') || version.starts_with("3.0") || version.starts_with("OpenGL ES 2"))
+        || has_extension("uniform_buffer_object")
+}
+
+pub(crate) fn texture_arrays_supported() -> bool {
+    let version = gl_version();
+    if cfg!(target_arch = "wasm32") {
+        return version.contains("WebGL 2.0");
+    }
+    // core since desktop GL 3.0 and GLES 3.0
+    !(version.starts_with('2') || version.starts_with("OpenGL ES 2"))
+}
+
+pub(crate) fn async_readback_supported() -> bool {
+    // pixel buffer objects and fences are core since desktop GL 3.2, GLES 3.0 and WebGL 2
+    if cfg!(target_arch = "wasm32") || is_gles() {
+        return texture_arrays_supported();
+    }
+    gl_version_number() >= (3, 2)
+}
+
+pub(crate) fn draw_base_vertex_supported() -> bool {
+    // the OES/EXT/ARB extensions have the entry points suffixed, so only core counts
+    !cfg!(target_arch = "wasm32") && gl_version_number() >= (3, 2)
+}
+
+pub(crate) fn program_binary_supported() -> bool {
+    if cfg!(target_arch = "wasm32") {
+        return false;
+    }
+    // core since desktop GL 4.1 and GLES 3.0, ARB_get_program_binary has the same entry points
+    let version = gl_version_number();
+    let api = if is_gles() {
+        version >= (3, 0)
+    } else {
+        version >= (4, 1) || has_extension("GL_ARB_get_program_binary")
+    };
+    // some drivers have the entry points, but not a single binary format
+    let mut formats = 0;
+    if api {
+        unsafe { glGetIntegerv(GL_NUM_PROGRAM_BINARY_FORMATS, &mut formats) };
+    }
+    formats > 0
+}
+
+pub(crate) fn draw_indirect_supported() -> bool {
+    let version = gl_version_number();
+    if cfg!(target_arch = "wasm32") {
+        return false;
+    }
+    // core since desktop GL 4.0 and GLES 3.1, ARB_draw_indirect has the same entry points
+    if is_gles() {
+        version >= (3, 1)
+    } else {
+        version >= (4, 0) || has_extension("GL_ARB_draw_indirect")
+    }
+}
+
+pub(crate) fn multi_draw_indirect_supported() -> bool {
+    // core since desktop GL 4.3, GLES only has it suffixed in EXT_multi_draw_indirect
+    !cfg!(target_arch = "wasm32")
+        && !is_gles()
+        && (gl_version_number() >= (4, 3) || has_extension("GL_ARB_multi_draw_indirect"))
+}
+
+/// Whether the compressed format is supported, always true for uncompressed formats.
+pub(crate) fn texture_compression_supported(format: TextureFormat) -> bool {
+    let version = gl_version_number();
+    let desktop = !is_gles();
+    // extensions are matched by suffix, GL_EXT_texture_compression_s3tc and
+    // WEBGL_compressed_texture_s3tc are the same thing, while GL_EXT_texture_compression_s3tc_srgb
+    // and WEBGL_compressed_texture_etc1 are not
+    match format {
+        TextureFormat::BC1 | TextureFormat::BC2 | TextureFormat::BC3 => {
+            any_extension(|extension| extension.ends_with("_s3tc"))
+        }
+        // desktop GL has them in EXT_texture_sRGB, on top of the S3TC extension
+        TextureFormat::BC1SRGB | TextureFormat::BC2SRGB | TextureFormat::BC3SRGB => {
+            texture_compression_supported(TextureFormat::BC1)
+                && ((desktop && has_extension("GL_EXT_texture_sRGB"))
+                    || any_extension(|extension| extension.ends_with("_s3tc_srgb")))
+        }
+        TextureFormat::BC4 | TextureFormat::BC5 => {
+            (desktop && version >= (3, 0))
+                || any_extension(|extension| extension.ends_with("_rgtc"))
+        }
+        TextureFormat::BC6H | TextureFormat::BC7 | TextureFormat::BC7SRGB => {
+            (desktop && version >= (4, 2))
+                || any_extension(|extension| extension.ends_with("_bptc"))
+        }
+        TextureFormat::ETC2RGB
+        | TextureFormat::ETC2RGBA
+        | TextureFormat::ETC2SRGB
+        | TextureFormat::ETC2SRGBA => {
+            // core in GLES 3.0 and desktop GL 4.3, WebGL 2 still needs the extension
+            let core = if desktop {
+                version >= (4, 3)
+            } else {
+                cfg!(not(target_arch = "wasm32")) && version >= (3, 0)
+            };
+            core || any_extension(|extension| extension.ends_with("compressed_texture_etc"))
+        }
+        TextureFormat::ASTC4x4
+        | TextureFormat::ASTC5x5
+        | TextureFormat::ASTC6x6
+        | TextureFormat::ASTC8x8
+        | TextureFormat::ASTC4x4SRGB
+        | TextureFormat::ASTC5x5SRGB
+        | TextureFormat::ASTC6x6SRGB
+        | TextureFormat::ASTC8x8SRGB => any_extension(|extension| {
+            extension.contains("texture_compression_astc")
+                || extension.ends_with("compressed_texture_astc")
+        }),
+        _ => true,
+    }
+}
+
+pub(crate) fn query_target(query_type: QueryType) -> GLenum {
+    match query_type {
+        QueryType::TimeElapsed => GL_TIME_ELAPSED,
+        QueryType::AnySamplesPassed => GL_ANY_SAMPLES_PASSED,
+        // GLES and WebGL have only boolean occlusion queries
+        QueryType::SamplesPassed if is_gles() => GL_ANY_SAMPLES_PASSED,
+        QueryType::SamplesPassed => GL_SAMPLES_PASSED,
+    }
+}
+
+enum ReadbackInternal {
+    /// Read right away, without `features.async_readback`
+    Done(Vec<u8>),
+    Pending {
+        read: ReadFormat,
+        pbo: GLuint,
+        sync: GLsync,
+        size: usize,
+    },
+}
+
+struct QueryInternal {
+    gl_query: GLuint,
+    target: GLenum,
+}
+
 pub(crate) struct RenderPassInternal {
     gl_fb: GLuint,
     color_textures: Vec<TextureId>,
     depth_texture: Option<TextureId>,
+    msaa: Option<MsaaInternal>,
+    label: Option<String>,
+}
+
+/// Multisampled passes render into renderbuffers attached to `gl_fb`
+/// and blit them into `resolve_fb`, the framebuffer with the textures.
+struct MsaaInternal {
+    resolve_fb: GLuint,
+    /// One per attachment, the color ones and then the depth one.
+    /// Reallocated with the texture in `texture_resize`.
+    renderbuffers: Vec<GLuint>,
+    sample_count: i32,
+}
+
+impl RenderPassInternal {
+    /// Framebuffer with the pass textures attached.
+    fn texture_fb(&self) -> GLuint {
+        self.msaa
+            .as_ref()
+            .map_or(self.gl_fb, |msaa| msaa.resolve_fb)
+    }
+}
+
+/// Pipeline and buffers of the `blit_render_pass` quad for contexts without `glBlitFramebuffer`.
+struct BlitQuad {
+    pipeline: Pipeline,
+    vertex_buffer: BufferId,
+    index_buffer: BufferId,
 }
 
-struct Textures(Vec<Texture>);
+const BLIT_VERTEX: &str = r#"#version 100
+attribute vec2 in_pos;
+varying vec2 uv;
+void main() {
+    uv = in_pos;
+    gl_Position = vec4(in_pos * 2.0 - 1.0, 0.0, 1.0);
+}"#;
+
+const BLIT_FRAGMENT: &str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D tex;
+void main() {
+    gl_FragColor = texture2D(tex, uv);
+}"#;
+
+struct Textures(ResourceManager<Texture>);
 impl Textures {
     fn get(&self, texture: TextureId) -> Texture {
         match texture.0 {
@@ -441,8 +1283,20 @@ pub struct GlContext {
     pipelines: ResourceManager<PipelineInternal>,
     passes: ResourceManager<RenderPassInternal>,
     buffers: ResourceManager<Buffer>,
+    queries: ResourceManager<QueryInternal>,
+    readbacks: ResourceManager<ReadbackInternal>,
     textures: Textures,
     default_framebuffer: GLuint,
+    cur_pass: Option<RenderPass>,
+    // Bindings::index_buffer_offset, glDrawElements takes it together with base_element
+    index_buffer_offset: usize,
+    // glBlitFramebuffer is core with the same GL 3.0/GLES 3.0/WebGL 2 as the texture arrays
+    blit_framebuffer: bool,
+    blit_quad: Option<BlitQuad>,
+    // object labels and debug groups
+    khr_debug: bool,
+    // begin_pass pushed a debug group, end_render_pass pops it
+    debug_group: bool,
     pub(crate) cache: GlCache,
 
     pub(crate) features: Features,
@@ -460,30 +1314,93 @@ impl GlContext {
 
             glGenVertexArrays(1, &mut vao as *mut _);
             glBindVertexArray(vao);
+
+            let uniform_buffers = uniform_buffers_supported();
+            // R8, RG8, float, integer, sRGB and depth-stencil textures
+            // are core with the same GL 3.0/GLES 3.0/WebGL 2 as the texture arrays
+            let gl3_textures = texture_arrays_supported();
+            // rendering to and linear filtering of float textures is also core on desktop GL 3.0
+            let desktop_gl3 = gl3_textures && !is_gles();
+            let mut max_sample_count = 1;
+            if gl3_textures {
+                glGetIntegerv(GL_MAX_SAMPLES, &mut max_sample_count);
+                max_sample_count = max_sample_count.max(1);
+            }
+            let khr_debug = debug::khr_debug_supported();
+            if khr_debug && debug::is_debug_context() {
+                debug::enable_debug_output();
+            }
+            let mut uniform_buffer_offset_alignment =
+                Features::default().uniform_buffer_offset_alignment;
+            if uniform_buffers {
+                let mut alignment: GLint = 0;
+                glGetIntegerv(GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut alignment);
+                if alignment > 0 {
+                    uniform_buffer_offset_alignment = alignment as usize;
+                }
+            }
             GlContext {
                 default_framebuffer,
+                cur_pass: None,
+                index_buffer_offset: 0,
+                blit_framebuffer: gl3_textures,
+                blit_quad: None,
+                khr_debug,
+                debug_group: false,
                 shaders: ResourceManager::default(),
                 pipelines: ResourceManager::default(),
                 passes: ResourceManager::default(),
                 buffers: ResourceManager::default(),
-                textures: Textures(vec![]),
+                queries: ResourceManager::default(),
+                readbacks: ResourceManager::default(),
+                textures: Textures(ResourceManager::default()),
                 features: Features {
                     instancing: !crate::native::gl::is_gl2(),
-                    ..Default::default()
+                    elapsed_query: elapsed_query_supported(),
+                    occlusion_query: occlusion_query_supported(),
+                    uniform_buffers,
+                    uniform_buffer_offset_alignment,
+                    texture_arrays: texture_arrays_supported(),
+                    texture_compression_s3tc: texture_compression_supported(TextureFormat::BC1),
+                    texture_compression_s3tc_srgb: texture_compression_supported(
+                        TextureFormat::BC1SRGB,
+                    ),
+                    texture_compression_rgtc: texture_compression_supported(TextureFormat::BC4),
+                    texture_compression_bptc: texture_compression_supported(TextureFormat::BC7),
+                    texture_compression_etc2: texture_compression_supported(TextureFormat::ETC2RGB),
+                    texture_compression_astc: texture_compression_supported(TextureFormat::ASTC4x4),
+                    texture_rg: gl3_textures,
+                    texture_float: gl3_textures,
+                    texture_float_linear: gl3_textures
+                        && (desktop_gl3 || has_extension("texture_float_linear")),
+                    color_buffer_float: gl3_textures
+                        && (desktop_gl3 || has_extension("color_buffer_float")),
+                    texture_integer: gl3_textures,
+                    texture_srgb: gl3_textures,
+                    depth_stencil_texture: gl3_textures,
+                    draw_base_vertex: draw_base_vertex_supported(),
+                    draw_indirect: draw_indirect_supported(),
+                    multi_draw_indirect: multi_draw_indirect_supported(),
+                    max_sample_count,
+                    async_readback: async_readback_supported(),
+                    program_binary: program_binary_supported(),
                 },
                 cache: GlCache {
                     stored_index_buffer: 0,
                     stored_index_type: None,
                     stored_vertex_buffer: 0,
+                    stored_uniform_buffer: 0,
                     index_buffer: 0,
                     index_type: None,
                     vertex_buffer: 0,
+                    uniform_buffer: 0,
                     cur_pipeline: None,
                     color_blend: None,
                     alpha_blend: None,
                     stencil: None,
                     color_write: (true, true, true, true),
                     cull_face: CullFace::Nothing,
+                    framebuffer_srgb: false,
                     stored_texture: 0,
                     stored_target: 0,
                     textures: [CachedTexture {
@@ -499,72 +1416,682 @@ impl GlContext {
     pub fn features(&self) -> &Features {
         &self.features
     }
+
+    /// Primitive type of the applied pipeline, `None` if the draw call has to be skipped.
+    fn draw_primitive_type(&self, num_instances: i32) -> Option<GLenum> {
+        assert!(
+            self.cache.cur_pipeline.is_some(),
+            "Drawing without any binded pipeline"
+        );
+
+        if !self.features.instancing && num_instances != 1 {
+            eprintln!("Instanced rendering is not supported by the GPU");
+            eprintln!("Ignoring this draw call");
+            return None;
+        }
+
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        Some(pip.params.primitive_type.into())
+    }
+
+    /// GL type and size in bytes of the bound index buffer elements.
+    fn index_type(&self) -> (GLenum, i32) {
+        let index_type = self.cache.index_type.expect("Unset index buffer type");
+        let gl_type = match index_type {
+            1 => GL_UNSIGNED_BYTE,
+            2 => GL_UNSIGNED_SHORT,
+            4 => GL_UNSIGNED_INT,
+            _ => panic!("Unsupported index buffer type!"),
+        };
+        (gl_type, index_type as i32)
+    }
+
+    fn pass_size(&self, pass: &RenderPassInternal) -> (i32, i32) {
+        // new_render_pass will panic with both color and depth components none
+        // so unwrap is safe here
+        let texture = pass
+            .color_textures
+            .first()
+            .copied()
+            .or(pass.depth_texture)
+            .unwrap();
+        let params = self.textures.get(texture).params;
+        (params.width as i32, params.height as i32)
+    }
+
+    /// `blit_render_pass` of the first color attachment by drawing a textured quad.
+    fn blit_quad(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode) {
+        let texture = match self.passes[src.0].color_textures.first() {
+            Some(texture) => *texture,
+            None => return,
+        };
+        if self.blit_quad.is_none() {
+            let shader = self
+                .new_shader(
+                    ShaderSource::Glsl {
+                        vertex: BLIT_VERTEX,
+                        fragment: BLIT_FRAGMENT,
+                    },
+                    ShaderMeta {
+                        uniforms: UniformBlockLayout { uniforms: vec![] },
+                        uniform_blocks: vec![],
+                        images: vec!["tex".to_string()],
+                    },
+                )
+                .expect("blit_render_pass shader");
+            let pipeline = self.new_pipeline(
+                &[BufferLayout::default()],
+                &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+                shader,
+                PipelineParams::default(),
+            );
+            let vertex_buffer = self.new_buffer(
+                BufferType::VertexBuffer,
+                BufferUsage::Immutable,
+                BufferSource::slice(&[0.0f32, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]),
+            );
+            let index_buffer = self.new_buffer(
+                BufferType::IndexBuffer,
+                BufferUsage::Immutable,
+                BufferSource::slice(&[0u16, 1, 2, 0, 2, 3]),
+            );
+            self.blit_quad = Some(BlitQuad {
+                pipeline,
+                vertex_buffer,
+                index_buffer,
+            });
+        }
+        let (pipeline, vertex_buffer, index_buffer) = {
+            let quad = self.blit_quad.as_ref().unwrap();
+            (quad.pipeline, quad.vertex_buffer, quad.index_buffer)
+        };
+
+        let params = self.textures.get(texture).params;
+        self.texture_set_filter(texture, filter, MipmapFilterMode::None);
+        self.begin_pass(dst, PassAction::Nothing);
+        self.apply_pipeline(&pipeline);
+        self.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[texture]);
+        self.draw(0, 6, 1);
+        self.end_render_pass();
+        self.texture_set_min_filter(texture, params.min_filter, params.mipmap_filter);
+        self.texture_set_mag_filter(texture, params.mag_filter);
+    }
+
+    /// Blit the multisampled renderbuffers of a pass into its textures.
+    fn resolve(&self, pass: &RenderPassInternal) {
+        let msaa = match &pass.msaa {
+            Some(msaa) => msaa,
+            None => return,
+        };
+        let (w, h) = self.pass_size(pass);
+        unsafe {
+            glBindFramebuffer(GL_READ_FRAMEBUFFER, pass.gl_fb);
+            glBindFramebuffer(GL_DRAW_FRAMEBUFFER, msaa.resolve_fb);
+            // blits are scissored
+            glScissor(0, 0, w, h);
+            self.unmask_writes();
+            blit_color_attachments(pass.color_textures.len(), (w, h), (w, h), GL_NEAREST);
+            if let Some(depth_texture) = pass.depth_texture {
+                let mask = blit_mask(self.textures.get(depth_texture).params.format);
+                glBlitFramebuffer(0, 0, w, h, 0, 0, w, h, mask, GL_NEAREST);
+            }
+            self.restore_write_masks();
+        }
+    }
+
+    /// Some drivers apply the write masks of the last pipeline to blits too.
+    unsafe fn unmask_writes(&self) {
+        glColorMask(1, 1, 1, 1);
+        glDepthMask(1);
+        glStencilMask(!0);
+    }
+
+    /// Back to what the cache says, the depth mask is never changed otherwise.
+    unsafe fn restore_write_masks(&self) {
+        let (r, g, b, a) = self.cache.color_write;
+        glColorMask(r as _, g as _, b as _, a as _);
+        if let Some(stencil) = &self.cache.stencil {
+            glStencilMaskSeparate(GL_FRONT, stencil.front.write_mask);
+            glStencilMaskSeparate(GL_BACK, stencil.back.write_mask);
+        }
+    }
+}
+
+/// Where each attribute of the pipeline is in the buffers, by the attribute location in `program`.
+fn vertex_layout(
+    program: GLuint,
+    buffer_layout: &[BufferLayout],
+    attributes: &[VertexAttribute],
+) -> Vec<Option<VertexAttributeInternal>> {
+    #[derive(Clone, Copy, Default)]
+    struct BufferCacheData {
+        stride: i32,
+        offset: i64,
+    }
+
+    let mut buffer_cache: Vec<BufferCacheData> =
+        vec![BufferCacheData::default(); buffer_layout.len()];
+
+    for VertexAttribute {
+        format,
+        buffer_index,
+        ..
+    } in attributes
+    {
+        let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
+        let cache = buffer_cache
+            .get_mut(*buffer_index)
+            .unwrap_or_else(|| panic!());
+
+        if layout.stride == 0 {
+            cache.stride += format.size_bytes();
+        } else {
+            cache.stride = layout.stride;
+        }
+        // WebGL 1 limitation
+        assert!(cache.stride <= 255);
+    }
+
+    let attributes_len = attributes
+        .iter()
+        .map(|layout| match layout.format {
+            VertexFormat::Mat4 => 4,
+            _ => 1,
+        })
+        .sum();
+
+    let mut vertex_layout: Vec<Option<VertexAttributeInternal>> = vec![None; attributes_len];
+
+    for VertexAttribute {
+        name,
+        format,
+        buffer_index,
+    } in attributes
+    {
+        let buffer_data = &mut buffer_cache
+            .get_mut(*buffer_index)
+            .unwrap_or_else(|| panic!());
+        let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
+
+        let cname = CString::new(*name).unwrap_or_else(|e| panic!("{}", e));
+        let attr_loc = unsafe { glGetAttribLocation(program, cname.as_ptr() as *const _) };
+        let attr_loc = if attr_loc == -1 { None } else { Some(attr_loc) };
+        let divisor = if layout.step_func == VertexStep::PerVertex {
+            0
+        } else {
+            layout.step_rate
+        };
+
+        let mut attributes_count: usize = 1;
+        let mut format = *format;
+
+        if format == VertexFormat::Mat4 {
+            format = VertexFormat::Float4;
+            attributes_count = 4;
+        }
+        for i in 0..attributes_count {
+            if let Some(attr_loc) = attr_loc {
+                let attr_loc = attr_loc as GLuint + i as GLuint;
+
+                let attr = VertexAttributeInternal {
+                    attr_loc,
+                    size: format.components(),
+                    type_: format.type_(),
+                    offset: buffer_data.offset,
+                    stride: buffer_data.stride,
+                    buffer_index: *buffer_index,
+                    divisor,
+                };
+
+                assert!(
+                    attr_loc < vertex_layout.len() as u32,
+                    "attribute: {} outside of allocated attributes array len: {}",
+                    name,
+                    vertex_layout.len()
+                );
+                vertex_layout[attr_loc as usize] = Some(attr);
+            }
+            buffer_data.offset += format.size_bytes() as i64
+        }
+    }
+
+    vertex_layout
 }
 
 fn load_shader_internal(
     vertex_shader: &str,
     fragment_shader: &str,
     meta: ShaderMeta,
+    features: &Features,
+    validate: bool,
 ) -> Result<ShaderInternal, ShaderError> {
     unsafe {
-        let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
-        let fragment_shader = load_shader(GL_FRAGMENT_SHADER, fragment_shader)?;
-
-        let program = glCreateProgram();
-        glAttachShader(program, vertex_shader);
-        glAttachShader(program, fragment_shader);
-        glLinkProgram(program);
-
-        // delete no longer used shaders
-        glDetachShader(program, vertex_shader);
-        glDeleteShader(vertex_shader);
-        glDeleteShader(fragment_shader);
-
-        let mut link_status = 0;
-        glGetProgramiv(program, GL_LINK_STATUS, &mut link_status as *mut _);
-        if link_status == 0 {
-            let mut max_length: i32 = 0;
-            glGetProgramiv(program, GL_INFO_LOG_LENGTH, &mut max_length as *mut _);
+        let program = link_program(vertex_shader, fragment_shader, features.program_binary)?;
+        program_internal(program, meta, features, validate)
+    }
+}
 
-            let mut error_message = vec![0u8; max_length as usize + 1];
-            glGetProgramInfoLog(
-                program,
-                max_length,
-                &mut max_length as *mut _,
-                error_message.as_mut_ptr() as *mut _,
-            );
-            assert!(max_length >= 1);
-            let error_message =
-                std::string::String::from_utf8_lossy(&error_message[0..max_length as usize - 1]);
-            return Err(ShaderError::LinkError(error_message.to_string()));
+/// Look up the uniforms and images of a linked program, takes the ownership of `program`.
+unsafe fn program_internal(
+    program: GLuint,
+    meta: ShaderMeta,
+    features: &Features,
+    validate: bool,
+) -> Result<ShaderInternal, ShaderError> {
+    // the UBO entry points are not even loaded on GL2, GLES2 and WebGL1
+    if !meta.uniform_blocks.is_empty() && !features.uniform_buffers {
+        glDeleteProgram(program);
+        return Err(ShaderError::UniformBuffersUnsupported);
+    }
+
+    if validate {
+        let reflection = reflect_program(program, features.uniform_buffers);
+        let mismatches = meta.mismatches(&reflection.meta);
+        if !mismatches.is_empty() {
+            glDeleteProgram(program);
+            return Err(ShaderError::MetaMismatch(mismatches));
         }
+    }
 
-        glUseProgram(program);
+    glUseProgram(program);
 
-        #[rustfmt::skip]
-        let images = meta.images.iter().map(|name| ShaderImage {
-            gl_loc: get_uniform_location(program, name),
-        }).collect();
+    #[rustfmt::skip]
+    let images = meta.images.iter().map(|name| ShaderImage {
+        gl_loc: get_uniform_location(program, name),
+    }).collect();
 
-        #[rustfmt::skip]
-        let uniforms = meta.uniforms.uniforms.iter().scan(0, |offset, uniform| {
-            let res = ShaderUniform {
-                gl_loc: get_uniform_location(program, &uniform.name),
-                uniform_type: uniform.uniform_type,
-                array_count: uniform.array_count as _,
-            };
-            *offset += uniform.uniform_type.size() * uniform.array_count;
-            Some(res)
-        }).collect();
+    #[rustfmt::skip]
+    let uniforms = meta.uniforms.uniforms.iter().scan(0, |offset, uniform| {
+        let res = ShaderUniform {
+            gl_loc: get_uniform_location(program, &uniform.name),
+            uniform_type: uniform.uniform_type,
+            array_count: uniform.array_count as _,
+        };
+        *offset += uniform.uniform_type.size() * uniform.array_count;
+        Some(res)
+    }).collect();
+
+    let mut uniform_block_sizes = Vec::with_capacity(meta.uniform_blocks.len());
+    for (binding, block) in meta.uniform_blocks.iter().enumerate() {
+        let name = CString::new(block.name.as_str())?;
+        let block_index = glGetUniformBlockIndex(program, name.as_ptr());
+        // block is not used by the shader and was optimized out
+        if block_index != GL_INVALID_INDEX {
+            if let Err(message) = validate_uniform_block(program, block_index, block) {
+                glDeleteProgram(program);
+                return Err(ShaderError::UniformBlockLayoutMismatch {
+                    block: block.name.clone(),
+                    message,
+                });
+            }
+            glUniformBlockBinding(program, block_index, binding as _);
+        }
+        uniform_block_sizes.push(block.layout.std140_size());
+    }
+
+    Ok(ShaderInternal {
+        program,
+        images,
+        uniforms,
+        uniform_block_sizes,
+    })
+}
 
-        Ok(ShaderInternal {
+/// `retrievable`: the program will be asked for its binary, see `shader_binary`.
+unsafe fn link_program(
+    vertex_shader: &str,
+    fragment_shader: &str,
+    retrievable: bool,
+) -> Result<GLuint, ShaderError> {
+    let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
+    let fragment_shader = load_shader(GL_FRAGMENT_SHADER, fragment_shader)?;
+
+    let program = glCreateProgram();
+    #[cfg(not(target_arch = "wasm32"))]
+    if retrievable {
+        glProgramParameteri(program, GL_PROGRAM_BINARY_RETRIEVABLE_HINT, GL_TRUE as _);
+    }
+    glAttachShader(program, vertex_shader);
+    glAttachShader(program, fragment_shader);
+    glLinkProgram(program);
+
+    // delete no longer used shaders
+    glDetachShader(program, vertex_shader);
+    glDeleteShader(vertex_shader);
+    glDeleteShader(fragment_shader);
+
+    let mut link_status = 0;
+    glGetProgramiv(program, GL_LINK_STATUS, &mut link_status as *mut _);
+    if link_status == 0 {
+        let mut max_length: i32 = 0;
+        glGetProgramiv(program, GL_INFO_LOG_LENGTH, &mut max_length as *mut _);
+
+        let mut error_message = vec![0u8; max_length as usize + 1];
+        glGetProgramInfoLog(
             program,
-            images,
-            uniforms,
+            max_length,
+            &mut max_length as *mut _,
+            error_message.as_mut_ptr() as *mut _,
+        );
+        assert!(max_length >= 1);
+        let error_message =
+            std::string::String::from_utf8_lossy(&error_message[0..max_length as usize - 1]);
+        return Err(ShaderError::LinkError(error_message.to_string()));
+    }
+
+    Ok(program)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn program_binary(program: GLuint) -> Option<ShaderBinary> {
+    let mut length = 0;
+    glGetProgramiv(program, GL_PROGRAM_BINARY_LENGTH, &mut length);
+    if length <= 0 {
+        return None;
+    }
+    let mut data = vec![0u8; length as usize];
+    let mut format = 0;
+    glGetProgramBinary(
+        program,
+        length,
+        &mut length,
+        &mut format,
+        data.as_mut_ptr() as *mut _,
+    );
+    data.truncate(length.max(0) as usize);
+    Some(ShaderBinary { format, data }).filter(|binary| !binary.data.is_empty())
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe fn program_binary(_program: GLuint) -> Option<ShaderBinary> {
+    None
+}
+
+/// `None` if the driver rejects the binary.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn load_program_binary(binary: &ShaderBinary) -> Option<GLuint> {
+    let program = glCreateProgram();
+    glProgramParameteri(program, GL_PROGRAM_BINARY_RETRIEVABLE_HINT, GL_TRUE as _);
+    glProgramBinary(
+        program,
+        binary.format,
+        binary.data.as_ptr() as *const _,
+        binary.data.len() as _,
+    );
+    let mut link_status = 0;
+    glGetProgramiv(program, GL_LINK_STATUS, &mut link_status);
+    if link_status == 0 {
+        glDeleteProgram(program);
+        // an unknown format leaves GL_INVALID_ENUM behind
+        glGetError();
+        return None;
+    }
+    Some(program)
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe fn load_program_binary(_binary: &ShaderBinary) -> Option<GLuint> {
+    None
+}
+
+fn uniform_type_from_gl(gl_type: GLenum) -> Option<UniformType> {
+    match gl_type {
+        GL_FLOAT => Some(UniformType::Float1),
+        GL_FLOAT_VEC2 => Some(UniformType::Float2),
+        GL_FLOAT_VEC3 => Some(UniformType::Float3),
+        GL_FLOAT_VEC4 => Some(UniformType::Float4),
+        GL_INT => Some(UniformType::Int1),
+        GL_INT_VEC2 => Some(UniformType::Int2),
+        GL_INT_VEC3 => Some(UniformType::Int3),
+        GL_INT_VEC4 => Some(UniformType::Int4),
+        GL_FLOAT_MAT4 => Some(UniformType::Mat4),
+        _ => None,
+    }
+}
+
+fn vertex_format_from_gl(gl_type: GLenum) -> Option<VertexFormat> {
+    match gl_type {
+        GL_FLOAT => Some(VertexFormat::Float1),
+        GL_FLOAT_VEC2 => Some(VertexFormat::Float2),
+        GL_FLOAT_VEC3 => Some(VertexFormat::Float3),
+        GL_FLOAT_VEC4 => Some(VertexFormat::Float4),
+        GL_INT => Some(VertexFormat::Int1),
+        GL_INT_VEC2 => Some(VertexFormat::Int2),
+        GL_INT_VEC3 => Some(VertexFormat::Int3),
+        GL_INT_VEC4 => Some(VertexFormat::Int4),
+        GL_FLOAT_MAT4 => Some(VertexFormat::Mat4),
+        _ => None,
+    }
+}
+
+fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        GL_SAMPLER_2D
+            | GL_SAMPLER_3D
+            | GL_SAMPLER_CUBE
+            | GL_SAMPLER_2D_SHADOW
+            | GL_SAMPLER_2D_ARRAY
+            | GL_SAMPLER_2D_ARRAY_SHADOW
+            | GL_SAMPLER_CUBE_SHADOW
+            | GL_INT_SAMPLER_2D
+            | GL_INT_SAMPLER_3D
+            | GL_INT_SAMPLER_CUBE
+            | GL_INT_SAMPLER_2D_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_2D
+            | GL_UNSIGNED_INT_SAMPLER_3D
+            | GL_UNSIGNED_INT_SAMPLER_CUBE
+            | GL_UNSIGNED_INT_SAMPLER_2D_ARRAY
+    )
+}
+
+const MAX_REFLECTED_NAME_LENGTH: usize = 256;
+
+/// Name, array size and type of an active uniform or attribute.
+/// `get` is glGetActiveUniform or glGetActiveAttrib with the program and index applied.
+unsafe fn active_variable(
+    get: impl Fn(GLsizei, *mut GLsizei, *mut GLint, *mut GLenum, *mut GLchar),
+) -> (String, usize, GLenum) {
+    let mut name = [0u8; MAX_REFLECTED_NAME_LENGTH];
+    let mut length = 0;
+    let mut size = 0;
+    let mut gl_type = 0;
+    get(
+        name.len() as _,
+        &mut length,
+        &mut size,
+        &mut gl_type,
+        name.as_mut_ptr() as _,
+    );
+    let name = String::from_utf8_lossy(&name[..length as usize]);
+    // arrays are reported as "name[0]"
+    let name = name.strip_suffix("[0]").unwrap_or(&name).to_string();
+    (name, size as usize, gl_type)
+}
+
+unsafe fn reflect_program(program: GLuint, uniform_buffers: bool) -> ShaderReflection {
+    let mut blocks = vec![];
+    if uniform_buffers {
+        let mut block_count = 0;
+        glGetProgramiv(program, GL_ACTIVE_UNIFORM_BLOCKS, &mut block_count);
+        for block_index in 0..block_count as GLuint {
+            let mut name = [0u8; MAX_REFLECTED_NAME_LENGTH];
+            let mut length = 0;
+            #[rustfmt::skip]
+            glGetActiveUniformBlockName(program, block_index, name.len() as _, &mut length, name.as_mut_ptr() as _);
+            let name = String::from_utf8_lossy(&name[..length as usize]).into_owned();
+            blocks.push((name, vec![]));
+        }
+    }
+
+    let mut uniform_count = 0;
+    glGetProgramiv(program, GL_ACTIVE_UNIFORMS, &mut uniform_count);
+    let indices: Vec<GLuint> = (0..uniform_count as GLuint).collect();
+    let mut block_indices = vec![-1; indices.len()];
+    let mut offsets = vec![-1; indices.len()];
+    if uniform_buffers && !indices.is_empty() {
+        let count = indices.len() as _;
+        #[rustfmt::skip]
+        glGetActiveUniformsiv(program, count, indices.as_ptr(), GL_UNIFORM_BLOCK_INDEX, block_indices.as_mut_ptr());
+        #[rustfmt::skip]
+        glGetActiveUniformsiv(program, count, indices.as_ptr(), GL_UNIFORM_OFFSET, offsets.as_mut_ptr());
+    }
+
+    let mut uniforms = vec![];
+    let mut images = vec![];
+    for index in indices {
+        let (name, size, gl_type) = active_variable(|buf_size, length, size, gl_type, name| {
+            glGetActiveUniform(program, index, buf_size, length, size, gl_type, name)
+        });
+        if name.starts_with("gl_") {
+            continue;
+        }
+
+        let block_index = block_indices[index as usize];
+        if block_index >= 0 {
+            let (block_name, members) = &mut blocks[block_index as usize];
+            // members of blocks with an instance name are prefixed by the block name
+            let prefix = format!("{}.", block_name);
+            let name = name.strip_prefix(&prefix).unwrap_or(&name);
+            if let Some(uniform_type) = uniform_type_from_gl(gl_type) {
+                let uniform = UniformDesc::new(name, uniform_type).array(size);
+                members.push((offsets[index as usize], uniform));
+            }
+        } else if is_sampler(gl_type) {
+            images.push((get_uniform_location(program, &name), name));
+        } else if let Some(uniform_type) = uniform_type_from_gl(gl_type) {
+            let uniform = UniformDesc::new(&name, uniform_type).array(size);
+            uniforms.push((get_uniform_location(program, &name), uniform));
+        }
+    }
+    uniforms.sort_by_key(|(location, _)| *location);
+    images.sort_by_key(|(location, _)| *location);
+
+    let mut attribute_count = 0;
+    glGetProgramiv(program, GL_ACTIVE_ATTRIBUTES, &mut attribute_count);
+    let mut attributes = vec![];
+    for index in 0..attribute_count as GLuint {
+        let (name, _, gl_type) = active_variable(|buf_size, length, size, gl_type, name| {
+            glGetActiveAttrib(program, index, buf_size, length, size, gl_type, name)
+        });
+        if name.starts_with("gl_") {
+            continue;
+        }
+        if let Some(format) = vertex_format_from_gl(gl_type) {
+            let cname = CString::new(name.as_str()).unwrap();
+            let location = glGetAttribLocation(program, cname.as_ptr());
+            attributes.push((location, ShaderAttribute { name, format }));
+        }
+    }
+    attributes.sort_by_key(|(location, _)| *location);
+
+    let uniform_blocks = blocks
+        .into_iter()
+        .map(|(name, mut members)| {
+            members.sort_by_key(|(offset, _)| *offset);
+            let uniforms = members.into_iter().map(|(_, uniform)| uniform).collect();
+            UniformBlockDesc {
+                name,
+                layout: UniformBlockLayout { uniforms },
+            }
         })
+        .collect();
+    ShaderReflection {
+        meta: ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: uniforms.into_iter().map(|(_, uniform)| uniform).collect(),
+            },
+            uniform_blocks,
+            images: images.into_iter().map(|(_, name)| name).collect(),
+        },
+        attributes: attributes.into_iter().map(|(_, attr)| attr).collect(),
     }
 }
 
+/// Compare offsets GL assigned to the block members with the std140 offsets
+/// computed from the `UniformDesc` list.
+unsafe fn validate_uniform_block(
+    program: GLuint,
+    block_index: GLuint,
+    block: &UniformBlockDesc,
+) -> Result<(), String> {
+    let uniforms = &block.layout.uniforms;
+    let mut names = vec![];
+    for uniform in uniforms {
+        // Arrays are reported as "name[0]", members of blocks with an instance name
+        // are prefixed by the block name
+        let suffix = if uniform.array_count > 1 { "[0]" } else { "" };
+        for name in [
+            format!("{}{}", uniform.name, suffix),
+            format!("{}.{}{}", block.name, uniform.name, suffix),
+        ] {
+            names.push(CString::new(name).map_err(|e| e.to_string())?);
+        }
+    }
+    let name_ptrs: Vec<*const GLchar> = names.iter().map(|name| name.as_ptr()).collect();
+    let mut indices = vec![GL_INVALID_INDEX; names.len()];
+    glGetUniformIndices(
+        program,
+        names.len() as _,
+        name_ptrs.as_ptr(),
+        indices.as_mut_ptr(),
+    );
+    let indices: Vec<GLuint> = indices
+        .chunks_exact(2)
+        .zip(uniforms)
+        .map(|(pair, uniform)| {
+            pair.iter()
+                .copied()
+                .find(|index| *index != GL_INVALID_INDEX)
+                .ok_or_else(|| format!("no \"{}\" member in the block", uniform.name))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut gl_offsets = vec![0; indices.len()];
+    let mut gl_strides = vec![0; indices.len()];
+    if !indices.is_empty() {
+        let count = indices.len() as _;
+        #[rustfmt::skip]
+        glGetActiveUniformsiv(program, count, indices.as_ptr(), GL_UNIFORM_OFFSET, gl_offsets.as_mut_ptr());
+        #[rustfmt::skip]
+        glGetActiveUniformsiv(program, count, indices.as_ptr(), GL_UNIFORM_ARRAY_STRIDE, gl_strides.as_mut_ptr());
+    }
+    let offsets = block.layout.std140_offsets();
+    for (i, uniform) in uniforms.iter().enumerate() {
+        if gl_offsets[i] as usize != offsets[i] {
+            return Err(format!(
+                "\"{}\" is at offset {} in the shader, expected {}",
+                uniform.name, gl_offsets[i], offsets[i]
+            ));
+        }
+        if uniform.array_count > 1 {
+            let stride = round_up(uniform.uniform_type.size(), 16);
+            if gl_strides[i] as usize != stride {
+                return Err(format!(
+                    "\"{}\" has array stride {} in the shader, expected {}",
+                    uniform.name, gl_strides[i], stride
+                ));
+            }
+        }
+    }
+
+    let mut data_size = 0;
+    glGetActiveUniformBlockiv(
+        program,
+        block_index,
+        GL_UNIFORM_BLOCK_DATA_SIZE,
+        &mut data_size,
+    );
+    if data_size as usize != block.layout.std140_size() {
+        return Err(format!(
+            "block is {} bytes in the shader, expected {}, is it declared with layout(std140)?",
+            data_size,
+            block.layout.std140_size()
+        ));
+    }
+    Ok(())
+}
+
 pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderError> {
     unsafe {
         let shader = glCreateShader(shader_type);
@@ -599,12 +2126,14 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
                 error_message.pop();
             }
 
+            let shader_type = match shader_type {
+                GL_VERTEX_SHADER => ShaderType::Vertex,
+                GL_FRAGMENT_SHADER => ShaderType::Fragment,
+                _ => unreachable!(),
+            };
             return Err(ShaderError::CompilationError {
-                shader_type: match shader_type {
-                    GL_VERTEX_SHADER => ShaderType::Vertex,
-                    GL_FRAGMENT_SHADER => ShaderType::Fragment,
-                    _ => unreachable!(),
-                },
+                shader_type,
+                diagnostics: ShaderDiagnostic::parse_log(shader_type, &error_message, source),
                 error_message,
             });
         }
@@ -787,6 +2316,8 @@ impl RenderingBackend for GlContext {
         ContextInfo {
             backend: Backend::OpenGl,
             gl_version_string,
+            gl_vendor_string: gl_string(GL_VENDOR),
+            gl_renderer_string: gl_string(GL_RENDERER),
             glsl_support,
             features: self.features.clone(),
         }
@@ -800,7 +2331,33 @@ impl RenderingBackend for GlContext {
             ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
             _ => panic!("Metal source on OpenGl context"),
         };
-        let shader = load_shader_internal(vertex, fragment, meta)?;
+        let shader = load_shader_internal(vertex, fragment, meta, &self.features, false)?;
+        Ok(ShaderId(self.shaders.add(shader)))
+    }
+
+    fn reflect_shader(&mut self, shader: ShaderSource) -> Result<ShaderReflection, ShaderError> {
+        let (fragment, vertex) = match shader {
+            ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
+            _ => panic!("Metal source on OpenGl context"),
+        };
+        unsafe {
+            let program = link_program(vertex, fragment, false)?;
+            let reflection = reflect_program(program, self.features.uniform_buffers);
+            glDeleteProgram(program);
+            Ok(reflection)
+        }
+    }
+
+    fn new_shader_validated(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let (fragment, vertex) = match shader {
+            ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
+            _ => panic!("Metal source on OpenGl context"),
+        };
+        let shader = load_shader_internal(vertex, fragment, meta, &self.features, true)?;
         Ok(ShaderId(self.shaders.add(shader)))
     }
 
@@ -811,8 +2368,7 @@ impl RenderingBackend for GlContext {
         params: TextureParams,
     ) -> TextureId {
         let texture = Texture::new(self, access, source, params);
-        self.textures.0.push(texture);
-        TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+        TextureId(TextureIdInner::Managed(self.textures.0.add(texture)))
     }
 
     fn delete_texture(&mut self, texture: TextureId) {
@@ -822,6 +2378,9 @@ impl RenderingBackend for GlContext {
         unsafe {
             glDeleteTextures(1, &t.raw as *const _);
         }
+        if let TextureIdInner::Managed(texture) = texture.0 {
+            self.textures.0.remove(texture);
+        }
     }
 
     fn delete_shader(&mut self, program: ShaderId) {
@@ -830,6 +2389,53 @@ impl RenderingBackend for GlContext {
         self.cache.cur_pipeline = None;
     }
 
+    fn replace_shader(
+        &mut self,
+        program: ShaderId,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        let (fragment, vertex) = match shader {
+            ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
+            _ => panic!("Metal source on OpenGl context"),
+        };
+        let shader = load_shader_internal(vertex, fragment, meta, &self.features, false)?;
+        let old = std::mem::replace(&mut self.shaders[program.0], shader);
+        unsafe { glDeleteProgram(old.program) };
+
+        // attribute locations are up to the linker and may differ in the new program
+        let new_program = self.shaders[program.0].program;
+        for pipeline in self.pipelines.iter_mut() {
+            if pipeline.shader == program {
+                pipeline.layout =
+                    vertex_layout(new_program, &pipeline.buffer_layout, &pipeline.attributes);
+            }
+        }
+        self.cache.cur_pipeline = None;
+        Ok(())
+    }
+
+    fn shader_binary(&mut self, shader: ShaderId) -> Option<ShaderBinary> {
+        if !self.features.program_binary {
+            return None;
+        }
+        unsafe { program_binary(self.shaders[shader.0].program) }
+    }
+
+    fn new_shader_from_binary(
+        &mut self,
+        binary: &ShaderBinary,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        if !self.features.program_binary {
+            return Err(ShaderError::ProgramBinaryRejected);
+        }
+        let program =
+            unsafe { load_program_binary(binary) }.ok_or(ShaderError::ProgramBinaryRejected)?;
+        let shader = unsafe { program_internal(program, meta, &self.features, false)? };
+        Ok(ShaderId(self.shaders.add(shader)))
+    }
+
     fn delete_pipeline(&mut self, pipeline: Pipeline) {
         self.pipelines.remove(pipeline.0);
     }
@@ -852,8 +2458,11 @@ impl RenderingBackend for GlContext {
         };
 
         unsafe {
-            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, wrap_x as i32);
-            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, wrap_y as i32);
+            glTexParameteri(t.params.kind.into(), GL_TEXTURE_WRAP_S, wrap_x as i32);
+            glTexParameteri(t.params.kind.into(), GL_TEXTURE_WRAP_T, wrap_y as i32);
+            if t.params.kind == TextureKind::Texture3D {
+                glTexParameteri(t.params.kind.into(), GL_TEXTURE_WRAP_R, wrap_x as i32);
+            }
         }
         self.cache.restore_texture_binding(0);
     }
@@ -903,11 +2512,131 @@ impl RenderingBackend for GlContext {
             }
             _ => {}
         };
+
+        // multisampled passes render into renderbuffers of the texture size
+        for pass in self.passes.iter_mut() {
+            let msaa = match &pass.msaa {
+                Some(msaa) => msaa,
+                None => continue,
+            };
+            let attachments = pass
+                .color_textures
+                .iter()
+                .chain(pass.depth_texture.as_ref());
+            for (attachment, renderbuffer) in attachments.zip(&msaa.renderbuffers) {
+                if *attachment != texture {
+                    continue;
+                }
+                unsafe {
+                    glBindRenderbuffer(GL_RENDERBUFFER, *renderbuffer);
+                    glRenderbufferStorageMultisample(
+                        GL_RENDERBUFFER,
+                        msaa.sample_count,
+                        renderbuffer_format(t.params.format),
+                        width as _,
+                        height as _,
+                    );
+                    glBindRenderbuffer(GL_RENDERBUFFER, 0);
+                }
+            }
+        }
     }
     fn texture_read_pixels(&mut self, texture: TextureId, source: &mut [u8]) {
         let t = self.textures.get(texture);
         t.read_pixels(source);
     }
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId {
+        let t = self.textures.get(texture);
+        let (width, height) = (t.params.width, t.params.height);
+        if !self.features.async_readback {
+            let mut pixels = vec![0; t.size(width, height) * t.params.layers() as usize];
+            t.read_pixels(&mut pixels);
+            return ReadbackId(self.readbacks.add(ReadbackInternal::Done(pixels)));
+        }
+        unsafe {
+            let layers = t.params.layers() as usize;
+            let mut pixel_buffer = None;
+            t.read_layers(|layer| {
+                // GL_IMPLEMENTATION_COLOR_READ_FORMAT is of the bound framebuffer
+                let (read, layer_size, _) = *pixel_buffer.get_or_insert_with(|| {
+                    let read = ReadFormat::new(t.params.format);
+                    let layer_size = (width * height) as usize * read.pixel_size;
+                    let mut pbo = 0;
+                    glGenBuffers(1, &mut pbo);
+                    glBindBuffer(GL_PIXEL_PACK_BUFFER, pbo);
+                    glBufferData(
+                        GL_PIXEL_PACK_BUFFER,
+                        (layer_size * layers) as _,
+                        std::ptr::null(),
+                        GL_STREAM_READ,
+                    );
+                    (read, layer_size, pbo)
+                });
+                read.read(width, height, (layer * layer_size) as _);
+            });
+            let (read, layer_size, pbo) = pixel_buffer.unwrap();
+            let size = layer_size * layers;
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+            let sync = glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0);
+            // polling the fence does not flush, so it could otherwise wait in the queue forever
+            glFlush();
+            ReadbackId(self.readbacks.add(ReadbackInternal::Pending {
+                read,
+                pbo,
+                sync,
+                size,
+            }))
+        }
+    }
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool {
+        match self.readbacks[readback.0] {
+            ReadbackInternal::Done(ref pixels) => bytes.copy_from_slice(pixels),
+            ReadbackInternal::Pending {
+                read,
+                pbo,
+                sync,
+                size,
+            } => unsafe {
+                let status = glClientWaitSync(sync, 0, 0);
+                if status != GL_ALREADY_SIGNALED && status != GL_CONDITION_SATISFIED {
+                    return false;
+                }
+                glBindBuffer(GL_PIXEL_PACK_BUFFER, pbo);
+                // WebGL has no buffer mapping
+                #[cfg(target_arch = "wasm32")]
+                {
+                    let mut pixels = vec![0u8; size];
+                    glGetBufferSubData(
+                        GL_PIXEL_PACK_BUFFER,
+                        0,
+                        size as _,
+                        pixels.as_mut_ptr() as _,
+                    );
+                    read.repack(&pixels, bytes);
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let pixels =
+                        glMapBufferRange(GL_PIXEL_PACK_BUFFER, 0, size as _, GL_MAP_READ_BIT);
+                    // out of memory or a lost context, the pixels are gone either way
+                    if pixels.is_null() {
+                        glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+                        glDeleteBuffers(1, &pbo);
+                        glDeleteSync(sync);
+                        self.readbacks.remove(readback.0);
+                        panic!("Readback failed, glMapBufferRange returned null");
+                    }
+                    read.repack(std::slice::from_raw_parts(pixels as *const u8, size), bytes);
+                    glUnmapBuffer(GL_PIXEL_PACK_BUFFER);
+                }
+                glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+                glDeleteBuffers(1, &pbo);
+                glDeleteSync(sync);
+            },
+        }
+        self.readbacks.remove(readback.0);
+        true
+    }
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         let t = self.textures.get(texture);
         self.cache.store_texture_binding(0);
@@ -917,9 +2646,10 @@ impl RenderingBackend for GlContext {
         }
         self.cache.restore_texture_binding(0);
     }
-    fn texture_update_part(
+    fn texture_update_layer_part(
         &mut self,
         texture: TextureId,
+        layer: u32,
         x_offset: i32,
         y_offset: i32,
         width: i32,
@@ -927,7 +2657,62 @@ impl RenderingBackend for GlContext {
         source: &[u8],
     ) {
         let t = self.textures.get(texture);
-        t.update_texture_part(self, x_offset, y_offset, width, height, source);
+        t.update_texture_part(self, layer, x_offset, y_offset, width, height, source);
+    }
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    ) {
+        let src = self.textures.get(src);
+        let dst = self.textures.get(dst);
+        // glCopyTexSubImage2D reads the color buffer only
+        assert!(
+            self.blit_framebuffer || !src.params.format.is_depth(),
+            "copy_texture_region of depth textures needs glBlitFramebuffer, not on GLES2 and WebGL1"
+        );
+        let (x, y, w, h) = src_rect;
+        let (dst_x, dst_y) = dst_pos;
+        unsafe {
+            let mut fbs: [GLuint; 2] = [0; 2];
+            glGenFramebuffers(2, fbs.as_mut_ptr());
+            glBindFramebuffer(GL_FRAMEBUFFER, fbs[0]);
+            if self.blit_framebuffer {
+                let attachment = if src.params.format.is_depth() {
+                    depth_attachment(src.params.format)
+                } else {
+                    GL_COLOR_ATTACHMENT0
+                };
+                framebuffer_texture(attachment, &src, 0);
+                glBindFramebuffer(GL_FRAMEBUFFER, fbs[1]);
+                framebuffer_texture(attachment, &dst, 0);
+                glBindFramebuffer(GL_READ_FRAMEBUFFER, fbs[0]);
+                // blits are scissored
+                glScissor(dst_x, dst_y, w, h);
+                glBlitFramebuffer(
+                    x,
+                    y,
+                    x + w,
+                    y + h,
+                    dst_x,
+                    dst_y,
+                    dst_x + w,
+                    dst_y + h,
+                    blit_mask(src.params.format),
+                    GL_NEAREST,
+                );
+            } else {
+                framebuffer_texture(GL_COLOR_ATTACHMENT0, &src, 0);
+                self.cache.store_texture_binding(0);
+                self.cache.bind_texture(0, dst.params.kind.into(), dst.raw);
+                glCopyTexSubImage2D(face_target(dst.params.kind, 0), 0, dst_x, dst_y, x, y, w, h);
+                self.cache.restore_texture_binding(0);
+            }
+            glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
+            glDeleteFramebuffers(2, fbs.as_ptr());
+        }
     }
     fn texture_params(&self, texture: TextureId) -> TextureParams {
         let texture = self.textures.get(texture);
@@ -938,43 +2723,106 @@ impl RenderingBackend for GlContext {
         RawId::OpenGl(texture.raw)
     }
 
-    fn new_render_pass_mrt(
+    fn texture_set_label(&mut self, texture: TextureId, label: &str) {
+        if self.khr_debug {
+            debug::object_label(GL_TEXTURE, self.textures.get(texture).raw, label);
+        }
+    }
+
+    fn buffer_set_label(&mut self, buffer: BufferId, label: &str) {
+        if self.khr_debug {
+            debug::object_label(GL_BUFFER, self.buffers[buffer.0].gl_buf, label);
+        }
+    }
+
+    fn shader_set_label(&mut self, shader: ShaderId, label: &str) {
+        if self.khr_debug {
+            debug::object_label(GL_PROGRAM, self.shaders[shader.0].program, label);
+        }
+    }
+
+    fn pipeline_set_label(&mut self, pipeline: Pipeline, label: &str) {
+        self.pipelines[pipeline.0].label = Some(label.to_owned());
+    }
+
+    fn render_pass_set_label(&mut self, render_pass: RenderPass, label: &str) {
+        let pass = &mut self.passes[render_pass.0];
+        pass.label = Some(label.to_owned());
+        if self.khr_debug {
+            debug::object_label(GL_FRAMEBUFFER, pass.gl_fb, label);
+            if let Some(msaa) = &pass.msaa {
+                debug::object_label(GL_FRAMEBUFFER, msaa.resolve_fb, label);
+            }
+        }
+    }
+
+    fn new_render_pass_layer(
         &mut self,
         color_img: &[TextureId],
         depth_img: Option<TextureId>,
+        layer: u32,
     ) -> RenderPass {
         if color_img.is_empty() && depth_img.is_none() {
             panic!("Render pass should have at least one non-none target");
         }
         let mut gl_fb = 0;
 
+        let mut attachments: Vec<(GLenum, Texture)> = color_img
+            .iter()
+            .enumerate()
+            .map(|(i, texture)| (GL_COLOR_ATTACHMENT0 + i as u32, self.textures.get(*texture)))
+            .collect();
+        if let Some(depth_img) = depth_img {
+            let texture = self.textures.get(depth_img);
+            attachments.push((depth_attachment(texture.params.format), texture));
+        }
+        let sample_count = attachments
+            .iter()
+            .map(|(_, texture)| texture.params.sample_count)
+            .max()
+            .unwrap_or(1)
+            .min(self.features.max_sample_count);
+        let mut msaa = None;
         unsafe {
             glGenFramebuffers(1, &mut gl_fb as *mut _);
             glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
-            for (i, color_img) in color_img.iter().enumerate() {
-                glFramebufferTexture2D(
-                    GL_FRAMEBUFFER,
-                    GL_COLOR_ATTACHMENT0 + i as u32,
-                    GL_TEXTURE_2D,
-                    self.textures.get(*color_img).raw,
-                    0,
-                );
-            }
-            if let Some(depth_img) = depth_img {
-                glFramebufferTexture2D(
-                    GL_FRAMEBUFFER,
-                    GL_DEPTH_ATTACHMENT,
-                    GL_TEXTURE_2D,
-                    self.textures.get(depth_img).raw,
-                    0,
-                );
+            for (attachment, texture) in &attachments {
+                assert!(layer < texture.params.layers() || texture.params.layers() == 1);
+                framebuffer_texture(*attachment, texture, layer);
             }
-            if color_img.len() > 1 {
-                let mut attachments = vec![];
-                for i in 0..color_img.len() {
-                    attachments.push(GL_COLOR_ATTACHMENT0 + i as u32);
+            draw_buffers(color_img.len());
+
+            if sample_count > 1 {
+                let resolve_fb = gl_fb;
+                glGenFramebuffers(1, &mut gl_fb as *mut _);
+                glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
+                let mut renderbuffers = vec![];
+                for (attachment, texture) in &attachments {
+                    let mut renderbuffer = 0;
+                    glGenRenderbuffers(1, &mut renderbuffer as *mut _);
+                    glBindRenderbuffer(GL_RENDERBUFFER, renderbuffer);
+                    glRenderbufferStorageMultisample(
+                        GL_RENDERBUFFER,
+                        sample_count,
+                        renderbuffer_format(texture.params.format),
+                        texture.params.width as _,
+                        texture.params.height as _,
+                    );
+                    glFramebufferRenderbuffer(
+                        GL_FRAMEBUFFER,
+                        *attachment,
+                        GL_RENDERBUFFER,
+                        renderbuffer,
+                    );
+                    renderbuffers.push(renderbuffer);
                 }
-                glDrawBuffers(2, attachments.as_ptr() as _);
+                draw_buffers(color_img.len());
+                glBindRenderbuffer(GL_RENDERBUFFER, 0);
+                msaa = Some(MsaaInternal {
+                    resolve_fb,
+                    renderbuffers,
+                    sample_count,
+                });
             }
 
             glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
@@ -983,6 +2831,8 @@ impl RenderingBackend for GlContext {
             gl_fb,
             color_textures: color_img.to_vec(),
             depth_texture: depth_img,
+            msaa,
+            label: None,
         };
 
         RenderPass(self.passes.add(pass))
@@ -995,13 +2845,100 @@ impl RenderingBackend for GlContext {
 
         let render_pass = self.passes.remove(pass_id);
 
-        unsafe { glDeleteFramebuffers(1, &render_pass.gl_fb as *const _) }
+        unsafe {
+            glDeleteFramebuffers(1, &render_pass.gl_fb as *const _);
+            if let Some(msaa) = &render_pass.msaa {
+                glDeleteFramebuffers(1, &msaa.resolve_fb as *const _);
+                glDeleteRenderbuffers(
+                    msaa.renderbuffers.len() as _,
+                    msaa.renderbuffers.as_ptr() as _,
+                );
+            }
+        }
 
-        for color_texture in &render_pass.color_textures {
-            self.delete_texture(*color_texture);
+        // a texture attached to several passes goes with the first of them deleted
+        let attachments = render_pass
+            .color_textures
+            .iter()
+            .chain(&render_pass.depth_texture);
+        for texture in attachments {
+            let alive = match texture.0 {
+                TextureIdInner::Managed(texture) => self.textures.0.get(texture).is_some(),
+                TextureIdInner::Raw(_) => true,
+            };
+            if alive {
+                self.delete_texture(*texture);
+            }
+        }
+    }
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode) {
+        if !self.blit_framebuffer {
+            self.blit_quad(src, dst, filter);
+            return;
         }
-        if let Some(depth_texture) = render_pass.depth_texture {
-            self.delete_texture(depth_texture);
+        let filter = match filter {
+            FilterMode::Nearest => GL_NEAREST,
+            FilterMode::Linear => GL_LINEAR,
+        };
+        let src = &self.passes[src.0];
+        let (src_w, src_h) = self.pass_size(src);
+        let src_depth = src
+            .depth_texture
+            .map(|texture| self.textures.get(texture).params.format);
+        let (dst_fb, dst_w, dst_h, dst_colors, dst_depth) = match dst {
+            None => {
+                let (w, h) = window::screen_size();
+                (self.default_framebuffer, w as i32, h as i32, 1, None)
+            }
+            Some(dst) => {
+                let dst = &self.passes[dst.0];
+                let (w, h) = self.pass_size(dst);
+                let depth = dst
+                    .depth_texture
+                    .map(|texture| self.textures.get(texture).params.format);
+                (dst.texture_fb(), w, h, dst.color_textures.len(), depth)
+            }
+        };
+        let colors = src.color_textures.len().min(dst_colors);
+        unsafe {
+            glBindFramebuffer(GL_READ_FRAMEBUFFER, src.texture_fb());
+            glBindFramebuffer(GL_DRAW_FRAMEBUFFER, dst_fb);
+            // blits are scissored
+            glScissor(0, 0, dst_w, dst_h);
+            if dst.is_none() {
+                // the default framebuffer draws into the back buffer, not into attachments
+                if colors > 0 {
+                    glBlitFramebuffer(
+                        0,
+                        0,
+                        src_w,
+                        src_h,
+                        0,
+                        0,
+                        dst_w,
+                        dst_h,
+                        GL_COLOR_BUFFER_BIT,
+                        filter,
+                    );
+                }
+            } else {
+                blit_color_attachments(colors, (src_w, src_h), (dst_w, dst_h), filter);
+            }
+            if let Some(format) = src_depth.filter(|format| Some(*format) == dst_depth) {
+                glBlitFramebuffer(
+                    0,
+                    0,
+                    src_w,
+                    src_h,
+                    0,
+                    0,
+                    dst_w,
+                    dst_h,
+                    blit_mask(format),
+                    GL_NEAREST,
+                );
+            }
+            glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
         }
     }
 
@@ -1012,104 +2949,16 @@ impl RenderingBackend for GlContext {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline {
-        #[derive(Clone, Copy, Default)]
-        struct BufferCacheData {
-            stride: i32,
-            offset: i64,
-        }
-
-        let mut buffer_cache: Vec<BufferCacheData> =
-            vec![BufferCacheData::default(); buffer_layout.len()];
-
-        for VertexAttribute {
-            format,
-            buffer_index,
-            ..
-        } in attributes
-        {
-            let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
-            let cache = buffer_cache
-                .get_mut(*buffer_index)
-                .unwrap_or_else(|| panic!());
-
-            if layout.stride == 0 {
-                cache.stride += format.size_bytes();
-            } else {
-                cache.stride = layout.stride;
-            }
-            // WebGL 1 limitation
-            assert!(cache.stride <= 255);
-        }
-
         let program = self.shaders[shader.0].program;
-
-        let attributes_len = attributes
-            .iter()
-            .map(|layout| match layout.format {
-                VertexFormat::Mat4 => 4,
-                _ => 1,
-            })
-            .sum();
-
-        let mut vertex_layout: Vec<Option<VertexAttributeInternal>> = vec![None; attributes_len];
-
-        for VertexAttribute {
-            name,
-            format,
-            buffer_index,
-        } in attributes
-        {
-            let buffer_data = &mut buffer_cache
-                .get_mut(*buffer_index)
-                .unwrap_or_else(|| panic!());
-            let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
-
-            let cname = CString::new(*name).unwrap_or_else(|e| panic!("{}", e));
-            let attr_loc = unsafe { glGetAttribLocation(program, cname.as_ptr() as *const _) };
-            let attr_loc = if attr_loc == -1 { None } else { Some(attr_loc) };
-            let divisor = if layout.step_func == VertexStep::PerVertex {
-                0
-            } else {
-                layout.step_rate
-            };
-
-            let mut attributes_count: usize = 1;
-            let mut format = *format;
-
-            if format == VertexFormat::Mat4 {
-                format = VertexFormat::Float4;
-                attributes_count = 4;
-            }
-            for i in 0..attributes_count {
-                if let Some(attr_loc) = attr_loc {
-                    let attr_loc = attr_loc as GLuint + i as GLuint;
-
-                    let attr = VertexAttributeInternal {
-                        attr_loc,
-                        size: format.components(),
-                        type_: format.type_(),
-                        offset: buffer_data.offset,
-                        stride: buffer_data.stride,
-                        buffer_index: *buffer_index,
-                        divisor,
-                    };
-
-                    assert!(
-                        attr_loc < vertex_layout.len() as u32,
-                        "attribute: {} outside of allocated attributes array len: {}",
-                        name,
-                        vertex_layout.len()
-                    );
-                    vertex_layout[attr_loc as usize] = Some(attr);
-                }
-                buffer_data.offset += format.size_bytes() as i64
-            }
-        }
+        let vertex_layout = vertex_layout(program, buffer_layout, attributes);
 
         let pipeline = PipelineInternal {
             layout: vertex_layout,
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
             shader,
             params,
+            label: None,
         };
 
         Pipeline(self.pipelines.add(pipeline))
@@ -1120,6 +2969,9 @@ impl RenderingBackend for GlContext {
 
         {
             let pipeline = &self.pipelines[pipeline.0];
+            if let Some(label) = pipeline.label.as_deref().filter(|_| self.khr_debug) {
+                debug::insert_debug_marker(label);
+            }
             let shader = &self.shaders[pipeline.shader.0];
             unsafe {
                 glUseProgram(shader.program);
@@ -1180,6 +3032,20 @@ impl RenderingBackend for GlContext {
             }
             BufferType::IndexBuffer => panic!("unsupported index buffer dimension"),
             BufferType::VertexBuffer => None,
+            BufferType::UniformBuffer => {
+                assert!(
+                    self.features.uniform_buffers,
+                    "Uniform buffers are not supported"
+                );
+                None
+            }
+            BufferType::IndirectBuffer => {
+                assert!(
+                    self.features.draw_indirect,
+                    "Indirect buffers are not supported"
+                );
+                None
+            }
         };
         let mut gl_buf: u32 = 0;
 
@@ -1206,7 +3072,7 @@ impl RenderingBackend for GlContext {
         BufferId(self.buffers.add(buffer))
     }
 
-    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
         let data = match data {
             BufferSource::Slice(data) => data,
             _ => panic!("buffer_update expects BufferSource::slice"),
@@ -1221,13 +3087,13 @@ impl RenderingBackend for GlContext {
 
         let size = data.size;
 
-        assert!(size <= buffer.size);
+        assert!(offset + size <= buffer.size);
 
         let gl_target = gl_buffer_target(&buffer.buffer_type);
         self.cache.store_buffer_binding(gl_target);
         self.cache
             .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
-        unsafe { glBufferSubData(gl_target, 0, size as _, data.ptr as _) };
+        unsafe { glBufferSubData(gl_target, offset as _, size as _, data.ptr as _) };
         self.cache.restore_buffer_binding(gl_target);
     }
 
@@ -1241,8 +3107,7 @@ impl RenderingBackend for GlContext {
     /// More high-level code on top of miniquad probably is going to call this in Drop implementation of some
     /// more RAII buffer object.
     ///
-    /// There is no protection against using deleted textures later. However its not an UB in OpenGl and thats why
-    /// this function is not marked as unsafe
+    /// Using the handle after deletion panics, even once a new buffer took over its slot.
     fn delete_buffer(&mut self, buffer: BufferId) {
         unsafe { glDeleteBuffers(1, &self.buffers[buffer.0].gl_buf as *const _) }
         self.cache.clear_buffer_bindings();
@@ -1269,7 +3134,9 @@ impl RenderingBackend for GlContext {
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
         index_buffer: BufferId,
+        index_buffer_offset: usize,
         textures: &[TextureId],
     ) {
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
@@ -1294,6 +3161,7 @@ impl RenderingBackend for GlContext {
             self.buffers[index_buffer.0].gl_buf,
             self.buffers[index_buffer.0].index_type,
         );
+        self.index_buffer_offset = index_buffer_offset;
 
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
 
@@ -1302,11 +3170,14 @@ impl RenderingBackend for GlContext {
 
             let pip_attribute = pip.layout.get(attr_index).copied();
 
-            if let Some(Some(attribute)) = pip_attribute {
+            if let Some(Some(mut attribute)) = pip_attribute {
                 assert!(
                     attribute.buffer_index < vertex_buffers.len(),
                     "Attribute index outside of vertex_buffers length"
                 );
+                if let Some(offset) = vertex_buffer_offsets.get(attribute.buffer_index) {
+                    attribute.offset += *offset as i64;
+                }
                 let vb = vertex_buffers[attribute.buffer_index];
                 let vb = self.buffers[vb.0];
 
@@ -1402,6 +3273,48 @@ impl RenderingBackend for GlContext {
         }
     }
 
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize) {
+        let pipeline = self
+            .cache
+            .cur_pipeline
+            .expect("apply_pipeline before apply_uniform_block");
+        let shader = self.pipelines[pipeline.0].shader;
+        let size = *self.shaders[shader.0]
+            .uniform_block_sizes
+            .get(block)
+            .unwrap_or_else(|| panic!("No uniform block {} in the shader", block));
+        let buffer = &self.buffers[buffer.0];
+        assert!(
+            buffer.buffer_type == BufferType::UniformBuffer,
+            "apply_uniform_block expects a BufferType::UniformBuffer buffer"
+        );
+        assert!(
+            is_multiple_of(offset, self.features.uniform_buffer_offset_alignment),
+            "Uniform block offset {} is not a multiple of {}",
+            offset,
+            self.features.uniform_buffer_offset_alignment
+        );
+        assert!(
+            offset + size <= buffer.size,
+            "Uniform block range {}..{} is out of buffer bounds, buffer is {} bytes",
+            offset,
+            offset + size,
+            buffer.size
+        );
+
+        unsafe {
+            glBindBufferRange(
+                GL_UNIFORM_BUFFER,
+                block as _,
+                buffer.gl_buf,
+                offset as _,
+                size as _,
+            );
+        }
+        // glBindBufferRange binds the generic GL_UNIFORM_BUFFER binding point as well
+        self.cache.uniform_buffer = buffer.gl_buf;
+    }
+
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
@@ -1454,25 +3367,44 @@ impl RenderingBackend for GlContext {
             }
             Some(pass) => {
                 let pass = &self.passes[pass.0];
-                // new_render_pass will panic with both color and depth components none
-                // so unwrap is safe here
-                let texture = pass
-                    .color_textures
-                    .first()
-                    .copied()
-                    .or(pass.depth_texture)
-                    .unwrap();
-                (
-                    pass.gl_fb,
-                    self.textures.get(texture).params.width as i32,
-                    self.textures.get(texture).params.height as i32,
-                )
+                let (w, h) = self.pass_size(pass);
+                (pass.gl_fb, w, h)
             }
         };
+        self.cur_pass = pass;
+        if self.khr_debug && !self.debug_group {
+            let label = match pass {
+                None => "Default pass",
+                Some(pass) => self.passes[pass.0]
+                    .label
+                    .as_deref()
+                    .unwrap_or("Render pass"),
+            };
+            debug::push_debug_group(label);
+            self.debug_group = true;
+        }
+        // GLES always encodes the color written into sRGB attachments, desktop GL
+        // only with GL_FRAMEBUFFER_SRGB, which would also affect an sRGB default framebuffer
+        let srgb = pass.is_some_and(|pass| {
+            self.passes[pass.0]
+                .color_textures
+                .iter()
+                .any(|texture| self.textures.get(*texture).params.format == TextureFormat::SRGBA8)
+        });
         unsafe {
             glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
             glViewport(0, 0, w, h);
             glScissor(0, 0, w, h);
+            if srgb != self.cache.framebuffer_srgb {
+                self.cache.framebuffer_srgb = srgb;
+                if !is_gles() {
+                    if srgb {
+                        glEnable(GL_FRAMEBUFFER_SRGB);
+                    } else {
+                        glDisable(GL_FRAMEBUFFER_SRGB);
+                    }
+                }
+            }
         }
         match action {
             PassAction::Nothing => {}
@@ -1487,6 +3419,13 @@ impl RenderingBackend for GlContext {
     }
 
     fn end_render_pass(&mut self) {
+        if let Some(pass) = self.cur_pass.take() {
+            self.resolve(&self.passes[pass.0]);
+        }
+        if self.debug_group {
+            debug::pop_debug_group();
+            self.debug_group = false;
+        }
         unsafe {
             glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
             self.cache.bind_buffer(GL_ARRAY_BUFFER, 0, None);
@@ -1494,40 +3433,779 @@ impl RenderingBackend for GlContext {
         }
     }
 
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8> {
+        let (x, y, width, height) = rect;
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            // restored afterwards, GLES2 and WebGL1 have no separate read and draw bindings
+            let (mut read_fb, mut draw_fb) = (0, 0);
+            if self.blit_framebuffer {
+                glGetIntegerv(GL_READ_FRAMEBUFFER_BINDING, &mut read_fb);
+                glGetIntegerv(GL_DRAW_FRAMEBUFFER_BINDING, &mut draw_fb);
+            } else {
+                glGetIntegerv(GL_FRAMEBUFFER_BINDING, &mut read_fb);
+                draw_fb = read_fb;
+            }
+            // GL_SAMPLE_BUFFERS is of the draw framebuffer
+            glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
+
+            // Framebuffer objects can't be read from while multisampled, only the window
+            // system one is resolved implicitly. So resolve the rect into a renderbuffer,
+            // the blit wants the same rect on both sides then.
+            let mut sample_buffers = 0;
+            glGetIntegerv(GL_SAMPLE_BUFFERS, &mut sample_buffers);
+            let mut resolve = None;
+            if sample_buffers > 0 && self.blit_framebuffer {
+                let (mut fb, mut renderbuffer) = (0, 0);
+                glGenRenderbuffers(1, &mut renderbuffer);
+                glBindRenderbuffer(GL_RENDERBUFFER, renderbuffer);
+                glRenderbufferStorage(GL_RENDERBUFFER, GL_RGBA8, x + width, y + height);
+                glBindRenderbuffer(GL_RENDERBUFFER, 0);
+                glGenFramebuffers(1, &mut fb);
+                glBindFramebuffer(GL_DRAW_FRAMEBUFFER, fb);
+                glFramebufferRenderbuffer(
+                    GL_DRAW_FRAMEBUFFER,
+                    GL_COLOR_ATTACHMENT0,
+                    GL_RENDERBUFFER,
+                    renderbuffer,
+                );
+                // blits are scissored
+                glScissor(x, y, width, height);
+                self.unmask_writes();
+                let (x1, y1) = (x + width, y + height);
+                glBlitFramebuffer(x, y, x1, y1, x, y, x1, y1, GL_COLOR_BUFFER_BIT, GL_NEAREST);
+                self.restore_write_masks();
+                glBindFramebuffer(GL_READ_FRAMEBUFFER, fb);
+                resolve = Some((fb, renderbuffer));
+            }
+
+            glPixelStorei(GL_PACK_ALIGNMENT, 1);
+            glReadPixels(
+                x,
+                y,
+                width,
+                height,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as _,
+            );
+
+            if self.blit_framebuffer {
+                glBindFramebuffer(GL_READ_FRAMEBUFFER, read_fb as _);
+                glBindFramebuffer(GL_DRAW_FRAMEBUFFER, draw_fb as _);
+            } else {
+                glBindFramebuffer(GL_FRAMEBUFFER, read_fb as _);
+            }
+            if let Some((fb, renderbuffer)) = resolve {
+                glDeleteFramebuffers(1, &fb);
+                glDeleteRenderbuffers(1, &renderbuffer);
+            }
+        }
+        // glReadPixels rows are bottom-up
+        pixels
+            .chunks_exact(width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+
     fn commit_frame(&mut self) {
         self.cache.clear_buffer_bindings();
         self.cache.clear_texture_bindings();
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
-        assert!(
-            self.cache.cur_pipeline.is_some(),
-            "Drawing without any binded pipeline"
-        );
+        let primitive_type = match self.draw_primitive_type(num_instances) {
+            Some(primitive_type) => primitive_type,
+            None => return,
+        };
+        let (index_type, index_size) = self.index_type();
 
-        if !self.features.instancing && num_instances != 1 {
-            eprintln!("Instanced rendering is not supported by the GPU");
-            eprintln!("Ignoring this draw call");
-            return;
+        unsafe {
+            glDrawElementsInstanced(
+                primitive_type,
+                num_elements,
+                index_type,
+                (self.index_buffer_offset + (index_size * base_element) as usize) as *mut _,
+                num_instances,
+            );
         }
+    }
 
-        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
-        let primitive_type = pip.params.primitive_type.into();
-        let index_type = self.cache.index_type.expect("Unset index buffer type");
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        let primitive_type = match self.draw_primitive_type(num_instances) {
+            Some(primitive_type) => primitive_type,
+            None => return,
+        };
 
         unsafe {
-            glDrawElementsInstanced(
+            glDrawArraysInstanced(primitive_type, first_vertex, num_vertices, num_instances);
+        }
+    }
+
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    ) {
+        assert!(
+            self.features.draw_base_vertex,
+            "draw_base_vertex is not supported"
+        );
+        let primitive_type = match self.draw_primitive_type(num_instances) {
+            Some(primitive_type) => primitive_type,
+            None => return,
+        };
+        let (index_type, index_size) = self.index_type();
+
+        unsafe {
+            glDrawElementsInstancedBaseVertex(
                 primitive_type,
                 num_elements,
-                match index_type {
-                    1 => GL_UNSIGNED_BYTE,
-                    2 => GL_UNSIGNED_SHORT,
-                    4 => GL_UNSIGNED_INT,
-                    _ => panic!("Unsupported index buffer type!"),
-                },
-                (index_type as i32 * base_element) as *mut _,
+                index_type,
+                (self.index_buffer_offset + (index_size * base_element) as usize) as *mut _,
                 num_instances,
+                base_vertex,
+            );
+        }
+    }
+
+    fn draw_indirect(&self, buffer: BufferId, offset: usize) {
+        assert!(
+            self.features.draw_indirect,
+            "draw_indirect is not supported"
+        );
+        let primitive_type = match self.draw_primitive_type(1) {
+            Some(primitive_type) => primitive_type,
+            None => return,
+        };
+        let (index_type, _) = self.index_type();
+        let buffer = &self.buffers[buffer.0];
+        assert_eq!(
+            buffer.buffer_type,
+            BufferType::IndirectBuffer,
+            "draw_indirect expects a BufferType::IndirectBuffer buffer"
+        );
+
+        unsafe {
+            glBindBuffer(GL_DRAW_INDIRECT_BUFFER, buffer.gl_buf);
+            glDrawElementsIndirect(primitive_type, index_type, offset as *mut _);
+        }
+    }
+
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32) {
+        assert!(
+            self.features.multi_draw_indirect,
+            "multi_draw_indirect is not supported"
+        );
+        let primitive_type = match self.draw_primitive_type(1) {
+            Some(primitive_type) => primitive_type,
+            None => return,
+        };
+        let (index_type, _) = self.index_type();
+        let buffer = &self.buffers[buffer.0];
+        assert_eq!(
+            buffer.buffer_type,
+            BufferType::IndirectBuffer,
+            "multi_draw_indirect expects a BufferType::IndirectBuffer buffer"
+        );
+
+        unsafe {
+            glBindBuffer(GL_DRAW_INDIRECT_BUFFER, buffer.gl_buf);
+            glMultiDrawElementsIndirect(
+                primitive_type,
+                index_type,
+                offset as *mut _,
+                draw_count,
+                stride,
             );
         }
     }
+
+    fn new_query(&mut self, query_type: QueryType) -> QueryId {
+        let supported = match query_type {
+            QueryType::TimeElapsed => self.features.elapsed_query,
+            QueryType::AnySamplesPassed | QueryType::SamplesPassed => self.features.occlusion_query,
+        };
+        assert!(supported, "{:?} queries are not supported", query_type);
+
+        let mut gl_query = 0;
+        unsafe { glGenQueries(1, &mut gl_query) };
+        let query = QueryInternal {
+            gl_query,
+            target: query_target(query_type),
+        };
+        QueryId(self.queries.add(query))
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        let query = &self.queries[query.0];
+        unsafe { glBeginQuery(query.target, query.gl_query) };
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        let query = &self.queries[query.0];
+        unsafe { glEndQuery(query.target) };
+    }
+
+    fn query_available(&mut self, query: QueryId) -> bool {
+        let query = &self.queries[query.0];
+        // not glGetQueryObjectiv, EXT_occlusion_query_boolean has only the unsigned one
+        let mut available: GLuint = 0;
+        unsafe { glGetQueryObjectuiv(query.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
+    }
+
+    fn query_result(&mut self, query: QueryId) -> u64 {
+        let query = &self.queries[query.0];
+        if query.target == GL_TIME_ELAPSED {
+            let mut time: GLuint64 = 0;
+            unsafe { glGetQueryObjectui64v(query.gl_query, GL_QUERY_RESULT, &mut time) };
+            time
+        } else {
+            let mut samples: GLuint = 0;
+            unsafe { glGetQueryObjectuiv(query.gl_query, GL_QUERY_RESULT, &mut samples) };
+            samples as u64
+        }
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        let query = self.queries.remove(query.0);
+        unsafe { glDeleteQueries(1, &query.gl_query) };
+    }
+}
+
+#[cfg(test)]
+fn test_quad(x0: f32, x1: f32) -> [[f32; 2]; 6] {
+    [
+        [x0, -1.0],
+        [x1, -1.0],
+        [x1, 1.0],
+        [x0, -1.0],
+        [x1, 1.0],
+        [x0, 1.0],
+    ]
+}
+
+/// Draw the triangles in a solid color, in the current pass.
+#[cfg(test)]
+fn draw_test_triangles(ctx: &mut GlContext, triangles: &[[f32; 2]], color: [f32; 4]) {
+    const VERTEX: &str = "#version 100
+    attribute vec2 in_pos;
+    void main() {
+        gl_Position = vec4(in_pos, 0.0, 1.0);
+    }";
+    const FRAGMENT: &str = "#version 100
+    precision mediump float;
+    uniform vec4 color;
+    void main() {
+        gl_FragColor = color;
+    }";
+
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(triangles),
+    );
+    let indices: Vec<u16> = (0..triangles.len() as u16).collect();
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&indices),
+    );
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: VERTEX,
+                fragment: FRAGMENT,
+            },
+            ShaderMeta {
+                uniforms: UniformBlockLayout {
+                    uniforms: vec![UniformDesc::new("color", UniformType::Float4)],
+                },
+                uniform_blocks: vec![],
+                images: vec![],
+            },
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings(&Bindings::new(vec![vertex_buffer], index_buffer, vec![]));
+    ctx.apply_uniforms(UniformsSource::table(&color));
+    ctx.draw(0, indices.len() as _, 1);
+
+    ctx.delete_pipeline(pipeline);
+    ctx.delete_shader(shader);
+    ctx.delete_buffer(vertex_buffer);
+    ctx.delete_buffer(index_buffer);
+}
+
+/// Draw a quad over the whole pass with a `#version 300 es` fragment shader.
+/// It gets `in vec2 uv` from 0 to 1, `textures` are bound to `tex0`, `tex1` and so on.
+#[cfg(test)]
+fn draw_test_fragment(ctx: &mut GlContext, fragment: &str, textures: &[TextureId]) {
+    const VERTEX: &str = "#version 300 es
+    in vec2 in_pos;
+    out vec2 uv;
+    void main() {
+        uv = in_pos * 0.5 + 0.5;
+        gl_Position = vec4(in_pos, 0.0, 1.0);
+    }";
+
+    let quad = test_quad(-1.0, 1.0);
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&quad),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2, 3, 4, 5]),
+    );
+    let images = (0..textures.len()).map(|i| format!("tex{}", i)).collect();
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: VERTEX,
+                fragment,
+            },
+            ShaderMeta {
+                uniforms: UniformBlockLayout { uniforms: vec![] },
+                uniform_blocks: vec![],
+                images,
+            },
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings(&Bindings::new(
+        vec![vertex_buffer],
+        index_buffer,
+        textures.to_vec(),
+    ));
+    ctx.draw(0, 6, 1);
+
+    ctx.delete_pipeline(pipeline);
+    ctx.delete_shader(shader);
+    ctx.delete_buffer(vertex_buffer);
+    ctx.delete_buffer(index_buffer);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_queries() {
+    let (samples, any_samples, elapsed) =
+        crate::native::linux_headless::with_gl_context(4, 4, |ctx| {
+            let features = ctx.info().features;
+            // llvmpipe has GLES 3.2 and EXT_disjoint_timer_query
+            assert!(features.occlusion_query && features.elapsed_query);
+
+            let samples = ctx.new_query(QueryType::SamplesPassed);
+            let any_samples = ctx.new_query(QueryType::AnySamplesPassed);
+            let elapsed = ctx.new_query(QueryType::TimeElapsed);
+            ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+            ctx.begin_query(elapsed);
+            ctx.begin_query(samples);
+            // the left half of the framebuffer
+            draw_test_triangles(ctx, &test_quad(-1.0, 0.0), [1.0; 4]);
+            ctx.end_query(samples);
+            ctx.begin_query(any_samples);
+            ctx.end_query(any_samples);
+            ctx.end_query(elapsed);
+            ctx.end_render_pass();
+
+            unsafe { glFinish() };
+            let results = [samples, any_samples, elapsed].map(|query| {
+                assert!(ctx.query_available(query));
+                let result = ctx.query_result(query);
+                ctx.delete_query(query);
+                result
+            });
+            (results[0], results[1], results[2])
+        });
+    // GLES has no GL_SAMPLES_PASSED, it falls back to the boolean query
+    assert_eq!(samples, 1);
+    assert_eq!(any_samples, 0);
+    assert!(elapsed > 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_uniform_blocks_unsupported() {
+    const VERTEX: &str = "#version 300 es
+    in vec2 in_pos;
+    layout(std140) uniform Transform {
+        vec2 offset;
+    };
+    void main() {
+        gl_Position = vec4(in_pos + offset, 0.0, 1.0);
+    }";
+    const FRAGMENT: &str = "#version 300 es
+    precision mediump float;
+    out vec4 color;
+    void main() {
+        color = vec4(1.0);
+    }";
+
+    let result = crate::native::linux_headless::with_gl_context(1, 1, |ctx| {
+        // as on GLES2, where glGetUniformBlockIndex is not loaded
+        ctx.features.uniform_buffers = false;
+        let meta = ShaderMeta {
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![UniformBlockDesc::new(
+                "Transform",
+                vec![UniformDesc::new("offset", UniformType::Float2)],
+            )],
+            images: vec![],
+        };
+        let source = ShaderSource::Glsl {
+            vertex: VERTEX,
+            fragment: FRAGMENT,
+        };
+        ctx.new_shader(source, meta).map(|_| ())
+    });
+    assert!(matches!(
+        result,
+        Err(ShaderError::UniformBuffersUnsupported)
+    ));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_reflect_shader() {
+    // explicit locations in the reverse of the declaration order
+    const VERTEX: &str = "#version 310 es
+    layout(location = 1) in vec2 in_uv;
+    layout(location = 0) in vec3 in_pos;
+    layout(location = 4) uniform float weights[3];
+    layout(location = 0) uniform mat4 mvp;
+    out vec2 uv;
+    void main() {
+        uv = in_uv * (weights[0] + weights[1] + weights[2]);
+        gl_Position = mvp * vec4(in_pos, 1.0);
+    }";
+    const FRAGMENT: &str = "#version 310 es
+    precision mediump float;
+    layout(std140) uniform Light {
+        vec4 color;
+        float intensity[2];
+    } light;
+    layout(location = 10) uniform sampler2D albedo;
+    layout(location = 9) uniform sampler2D normals;
+    layout(location = 8) uniform vec4 tint;
+    in vec2 uv;
+    out vec4 color;
+    void main() {
+        vec4 light_color = light.color * (light.intensity[0] + light.intensity[1]);
+        color = texture(albedo, uv) * texture(normals, uv) * tint * light_color;
+    }";
+
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("mvp", UniformType::Mat4),
+                UniformDesc::new("weights", UniformType::Float1).array(3),
+                UniformDesc::new("tint", UniformType::Float4),
+            ],
+        },
+        uniform_blocks: vec![UniformBlockDesc::new(
+            "Light",
+            vec![
+                UniformDesc::new("color", UniformType::Float4),
+                UniformDesc::new("intensity", UniformType::Float1).array(2),
+            ],
+        )],
+        images: vec!["normals".to_string(), "albedo".to_string()],
+    };
+    let source = ShaderSource::Glsl {
+        vertex: VERTEX,
+        fragment: FRAGMENT,
+    };
+
+    let (reflection, validated, mismatches) =
+        crate::native::linux_headless::with_gl_context(1, 1, move |ctx| {
+            let reflection = ctx.reflect_shader(source).unwrap();
+            let validated = ctx.new_shader_validated(source, meta.clone()).is_ok();
+            let mut wrong_meta = meta;
+            wrong_meta.uniforms.uniforms[1].array_count = 2;
+            let mismatches = match ctx.new_shader_validated(source, wrong_meta) {
+                Err(ShaderError::MetaMismatch(mismatches)) => mismatches,
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            };
+            (reflection, validated, mismatches)
+        });
+
+    // "[0]" stripped off the arrays, sorted by location
+    assert_eq!(
+        reflection.meta.uniforms.uniforms,
+        [
+            UniformDesc::new("mvp", UniformType::Mat4),
+            UniformDesc::new("weights", UniformType::Float1).array(3),
+            UniformDesc::new("tint", UniformType::Float4),
+        ]
+    );
+    assert_eq!(reflection.meta.images, ["normals", "albedo"]);
+    // "Light." stripped off the members, sorted by offset
+    assert_eq!(reflection.meta.uniform_blocks.len(), 1);
+    assert_eq!(reflection.meta.uniform_blocks[0].name, "Light");
+    assert_eq!(
+        reflection.meta.uniform_blocks[0].layout.uniforms,
+        [
+            UniformDesc::new("color", UniformType::Float4),
+            UniformDesc::new("intensity", UniformType::Float1).array(2),
+        ]
+    );
+    assert_eq!(
+        reflection.attributes,
+        [
+            ShaderAttribute {
+                name: "in_pos".to_string(),
+                format: VertexFormat::Float3,
+            },
+            ShaderAttribute {
+                name: "in_uv".to_string(),
+                format: VertexFormat::Float2,
+            },
+        ]
+    );
+
+    assert!(validated);
+    assert_eq!(
+        mismatches,
+        [ShaderMetaMismatch::UniformType {
+            name: "weights".to_string(),
+            meta: (UniformType::Float1, 2),
+            shader: (UniformType::Float1, 3),
+        }]
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_texture_set_wrap_3d() {
+    let pixels = crate::native::linux_headless::with_gl_context(1, 1, |ctx| {
+        // a red slice and a green one
+        let texture = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Bytes(&[255, 0, 0, 255, 0, 255, 0, 255]),
+            TextureParams {
+                kind: TextureKind::Texture3D,
+                width: 1,
+                height: 1,
+                depth: 2,
+                min_filter: FilterMode::Nearest,
+                mag_filter: FilterMode::Nearest,
+                ..Default::default()
+            },
+        );
+        ctx.texture_set_wrap(texture, TextureWrap::Repeat, TextureWrap::Repeat);
+
+        ctx.begin_default_pass(PassAction::Nothing);
+        #[rustfmt::skip]
+        draw_test_fragment(ctx, "#version 300 es
+        precision mediump float;
+        uniform mediump sampler3D tex0;
+        out vec4 color;
+        void main() {
+            color = texture(tex0, vec3(0.5, 0.5, 1.25));
+        }", &[texture]);
+        ctx.end_render_pass();
+        ctx.read_default_framebuffer((0, 0, 1, 1))
+    });
+    // 1.25 repeats into the first slice, clamped it would be the second one
+    assert_eq!(pixels, [255, 0, 0, 255]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_texture_layers_read_back() {
+    let (array, volume) = crate::native::linux_headless::with_gl_context(1, 1, |ctx| {
+        let layers: [&[u8]; 3] = [&[1; 8], &[2; 8], &[3; 8]];
+        let array = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Array(&[&[layers[0]], &[layers[1]], &[layers[2]]]),
+            TextureParams {
+                kind: TextureKind::Texture2DArray,
+                width: 2,
+                height: 1,
+                depth: 3,
+                ..Default::default()
+            },
+        );
+        // the second pixel of the last layer
+        ctx.texture_update_layer_part(array, 2, 1, 0, 1, 1, &[9, 9, 9, 9]);
+        let mut array_pixels = vec![0; 24];
+        ctx.texture_read_pixels(array, &mut array_pixels);
+
+        let volume = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Bytes(&[1, 1, 1, 1, 2, 2, 2, 2]),
+            TextureParams {
+                kind: TextureKind::Texture3D,
+                width: 1,
+                height: 1,
+                depth: 2,
+                ..Default::default()
+            },
+        );
+        ctx.texture_update_layer_part(volume, 0, 0, 0, 1, 1, &[5, 5, 5, 5]);
+        let readback = ctx.begin_read_pixels(volume);
+        unsafe { glFinish() };
+        let mut volume_pixels = vec![0; 8];
+        assert!(ctx.try_finish_read_pixels(readback, &mut volume_pixels));
+
+        (array_pixels, volume_pixels)
+    });
+    #[rustfmt::skip]
+    assert_eq!(array, [
+        1, 1, 1, 1, 1, 1, 1, 1,
+        2, 2, 2, 2, 2, 2, 2, 2,
+        3, 3, 3, 3, 9, 9, 9, 9,
+    ]);
+    assert_eq!(volume, [5, 5, 5, 5, 2, 2, 2, 2]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_texture_3d_mipmaps() {
+    let pixels = crate::native::linux_headless::with_gl_context(3, 1, |ctx| {
+        let level0 = [255, 0, 0, 255].repeat(16);
+        let (level1_first, level1_second) =
+            ([0, 0, 255, 255].repeat(4), [0, 255, 0, 255].repeat(4));
+        let level2 = [255; 4];
+        // [slice][level], 4 slices at level 0, 2 at level 1 and 1 at level 2
+        let texture = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Array(&[
+                &[&level0, &level1_first, &level2],
+                &[&level0, &level1_second],
+                &[&level0],
+                &[&level0],
+            ]),
+            TextureParams {
+                kind: TextureKind::Texture3D,
+                width: 4,
+                height: 4,
+                depth: 4,
+                min_filter: FilterMode::Nearest,
+                mag_filter: FilterMode::Nearest,
+                mipmap_filter: MipmapFilterMode::Nearest,
+                ..Default::default()
+            },
+        );
+
+        ctx.begin_default_pass(PassAction::Nothing);
+        // level x of the texture in the pixel x, from the second half of the slices
+        #[rustfmt::skip]
+        draw_test_fragment(ctx, "#version 300 es
+        precision mediump float;
+        uniform mediump sampler3D tex0;
+        out vec4 color;
+        void main() {
+            color = textureLod(tex0, vec3(0.5, 0.5, 0.8), floor(gl_FragCoord.x));
+        }", &[texture]);
+        ctx.end_render_pass();
+        ctx.read_default_framebuffer((0, 0, 3, 1))
+    });
+    #[rustfmt::skip]
+    assert_eq!(pixels, [
+        255, 0, 0, 255,
+        0, 255, 0, 255,
+        255, 255, 255, 255,
+    ]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_render_pass_layer() {
+    let (array, volume) = crate::native::linux_headless::with_gl_context(1, 1, |ctx| {
+        // filled up front, an empty texture has undefined contents
+        let array = ctx.new_texture(
+            TextureAccess::RenderTarget,
+            TextureSource::Bytes(&[7; 16]),
+            TextureParams {
+                kind: TextureKind::Texture2DArray,
+                width: 2,
+                height: 1,
+                depth: 2,
+                ..Default::default()
+            },
+        );
+        let pass = ctx.new_render_pass_layer(&[array], None, 1);
+        ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 1.0, 0.0, 1.0));
+        // the left pixel of the second layer
+        draw_test_triangles(ctx, &test_quad(-1.0, 0.0), [1.0, 0.0, 0.0, 1.0]);
+        ctx.end_render_pass();
+        let mut array_pixels = vec![0; 16];
+        ctx.texture_read_pixels(array, &mut array_pixels);
+
+        let volume = ctx.new_texture(
+            TextureAccess::RenderTarget,
+            TextureSource::Bytes(&[7; 12]),
+            TextureParams {
+                kind: TextureKind::Texture3D,
+                width: 1,
+                height: 1,
+                depth: 3,
+                ..Default::default()
+            },
+        );
+        let pass = ctx.new_render_pass_layer(&[volume], None, 2);
+        ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 0.0, 1.0, 1.0));
+        ctx.end_render_pass();
+        let mut volume_pixels = vec![0; 12];
+        ctx.texture_read_pixels(volume, &mut volume_pixels);
+
+        (array_pixels, volume_pixels)
+    });
+    #[rustfmt::skip]
+    assert_eq!(array, [
+        7, 7, 7, 7, 7, 7, 7, 7,
+        255, 0, 0, 255, 0, 255, 0, 255,
+    ]);
+    assert_eq!(volume, [7, 7, 7, 7, 7, 7, 7, 7, 0, 0, 255, 255]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_multisampled_texture_resize() {
+    let pixels = crate::native::linux_headless::with_gl_context(1, 1, |ctx| {
+        let texture = ctx.new_render_texture(TextureParams {
+            width: 2,
+            height: 1,
+            sample_count: 4,
+            ..Default::default()
+        });
+        let pass = ctx.new_render_pass(texture, None);
+        // e.g. a window sized texture after a resize
+        ctx.texture_resize(texture, 4, 1, None);
+
+        ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 1.0, 0.0, 1.0));
+        draw_test_triangles(ctx, &test_quad(-1.0, 0.0), [1.0, 0.0, 0.0, 1.0]);
+        ctx.end_render_pass();
+        let mut pixels = vec![0; 16];
+        ctx.texture_read_pixels(texture, &mut pixels);
+        pixels
+    });
+    #[rustfmt::skip]
+    assert_eq!(pixels, [
+        255, 0, 0, 255, 255, 0, 0, 255,
+        0, 255, 0, 255, 0, 255, 0, 255,
+    ]);
 }
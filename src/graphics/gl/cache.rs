@@ -28,11 +28,13 @@ pub struct GlCache {
     pub stored_index_buffer: GLuint,
     pub stored_index_type: Option<u32>,
     pub stored_vertex_buffer: GLuint,
+    pub stored_uniform_buffer: GLuint,
     pub stored_target: GLuint,
     pub stored_texture: GLuint,
     pub index_buffer: GLuint,
     pub index_type: Option<u32>,
     pub vertex_buffer: GLuint,
+    pub uniform_buffer: GLuint,
     pub textures: [CachedTexture; MAX_SHADERSTAGE_IMAGES],
     pub cur_pipeline: Option<Pipeline>,
     pub color_blend: Option<BlendState>,
@@ -40,18 +42,31 @@ pub struct GlCache {
     pub stencil: Option<StencilState>,
     pub color_write: ColorMask,
     pub cull_face: CullFace,
+    pub framebuffer_srgb: bool,
     pub attributes: [Option<CachedAttribute>; MAX_VERTEX_ATTRIBUTES],
 }
 
 impl GlCache {
     pub fn bind_buffer(&mut self, target: GLenum, buffer: GLuint, index_type: Option<u32>) {
-        if target == GL_ARRAY_BUFFER {
+        if target == GL_DRAW_INDIRECT_BUFFER {
+            // not cached, draw_indirect takes &self and binds it on every call
+            unsafe {
+                glBindBuffer(target, buffer);
+            }
+        } else if target == GL_ARRAY_BUFFER {
             if self.vertex_buffer != buffer {
                 self.vertex_buffer = buffer;
                 unsafe {
                     glBindBuffer(target, buffer);
                 }
             }
+        } else if target == GL_UNIFORM_BUFFER {
+            if self.uniform_buffer != buffer {
+                self.uniform_buffer = buffer;
+                unsafe {
+                    glBindBuffer(target, buffer);
+                }
+            }
         } else {
             if self.index_buffer != buffer {
                 self.index_buffer = buffer;
@@ -64,8 +79,13 @@ impl GlCache {
     }
 
     pub fn store_buffer_binding(&mut self, target: GLenum) {
+        if target == GL_DRAW_INDIRECT_BUFFER {
+            return;
+        }
         if target == GL_ARRAY_BUFFER {
             self.stored_vertex_buffer = self.vertex_buffer;
+        } else if target == GL_UNIFORM_BUFFER {
+            self.stored_uniform_buffer = self.uniform_buffer;
         } else {
             self.stored_index_buffer = self.index_buffer;
             self.stored_index_type = self.index_type;
@@ -73,11 +93,19 @@ impl GlCache {
     }
 
     pub fn restore_buffer_binding(&mut self, target: GLenum) {
+        if target == GL_DRAW_INDIRECT_BUFFER {
+            return;
+        }
         if target == GL_ARRAY_BUFFER {
             if self.stored_vertex_buffer != 0 {
                 self.bind_buffer(target, self.stored_vertex_buffer, None);
                 self.stored_vertex_buffer = 0;
             }
+        } else if target == GL_UNIFORM_BUFFER {
+            if self.stored_uniform_buffer != 0 {
+                self.bind_buffer(target, self.stored_uniform_buffer, None);
+                self.stored_uniform_buffer = 0;
+            }
         } else {
             if self.stored_index_buffer != 0 {
                 self.bind_buffer(target, self.stored_index_buffer, self.stored_index_type);
@@ -114,6 +142,10 @@ impl GlCache {
 
         self.bind_buffer(GL_ELEMENT_ARRAY_BUFFER, 0, None);
         self.index_buffer = 0;
+
+        if self.uniform_buffer != 0 {
+            self.bind_buffer(GL_UNIFORM_BUFFER, 0, None);
+        }
     }
 
     pub fn clear_texture_bindings(&mut self) {
@@ -0,0 +1,177 @@
+//! KHR_debug: driver messages, object labels and debug groups.
+//! Graphics debuggers like RenderDoc and apitrace show the labels and groups in captures.
+//!
+//! WebGL does not expose KHR_debug, there everything here does nothing.
+
+use crate::native::gl::*;
+
+use super::{gl_version_number, has_extension, is_gles};
+
+/// Labels and debug groups are core since GL 4.3 and GLES 3.2.
+/// GLES 3.1 and lower name the extension entry points with a KHR suffix, those are not loaded.
+pub(crate) fn khr_debug_supported() -> bool {
+    if cfg!(target_arch = "wasm32") {
+        return false;
+    }
+    let version = gl_version_number();
+    if is_gles() {
+        version >= (3, 2)
+    } else {
+        version >= (4, 3) || has_extension("GL_KHR_debug")
+    }
+}
+
+/// Whether the context was created with `conf.platform.gl_debug`.
+pub(crate) fn is_debug_context() -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut flags = 0;
+        unsafe {
+            glGetIntegerv(GL_CONTEXT_FLAGS, &mut flags);
+            // desktop GL 2.1 with GL_KHR_debug does not know GL_CONTEXT_FLAGS, drop the GL_INVALID_ENUM
+            glGetError();
+        }
+        flags as u32 & GL_CONTEXT_FLAG_DEBUG_BIT != 0
+    }
+    #[cfg(target_arch = "wasm32")]
+    false
+}
+
+/// Forward driver messages into the log, on the thread and at the call that caused them.
+pub(crate) fn enable_debug_output() {
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        glEnable(GL_DEBUG_OUTPUT);
+        glEnable(GL_DEBUG_OUTPUT_SYNCHRONOUS);
+        glDebugMessageCallback(Some(debug_message), std::ptr::null());
+    }
+}
+
+pub(crate) fn object_label(identifier: GLenum, name: GLuint, label: &str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        glObjectLabel(identifier, name, label.len() as _, label.as_ptr() as _);
+    }
+}
+
+pub(crate) fn push_debug_group(message: &str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        glPushDebugGroup(
+            GL_DEBUG_SOURCE_APPLICATION,
+            0,
+            message.len() as _,
+            message.as_ptr() as _,
+        );
+    }
+}
+
+pub(crate) fn pop_debug_group() {
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        glPopDebugGroup();
+    }
+}
+
+pub(crate) fn insert_debug_marker(message: &str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        glDebugMessageInsert(
+            GL_DEBUG_SOURCE_APPLICATION,
+            GL_DEBUG_TYPE_MARKER,
+            0,
+            GL_DEBUG_SEVERITY_NOTIFICATION,
+            message.len() as _,
+            message.as_ptr() as _,
+        );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+extern "system" fn debug_message(
+    source: GLenum,
+    _type: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut GLvoid,
+) {
+    // our own groups and markers
+    if source == GL_DEBUG_SOURCE_APPLICATION {
+        return;
+    }
+    let message = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+    let message = format_message(id, message);
+
+    #[cfg(feature = "log-impl")]
+    match level(severity) {
+        Level::Error => {
+            crate::error!("{}", message);
+        }
+        Level::Warn => {
+            crate::warn!("{}", message);
+        }
+        Level::Info => {
+            crate::info!("{}", message);
+        }
+        Level::Debug => {
+            crate::debug!("{}", message);
+        }
+    }
+    // notifications are too chatty for stderr
+    #[cfg(not(feature = "log-impl"))]
+    if level(severity) != Level::Debug {
+        eprintln!("{}", message);
+    }
+}
+
+/// Log level of a driver message with the GL_DEBUG_SEVERITY `severity`.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn level(severity: GLenum) -> Level {
+    match severity {
+        GL_DEBUG_SEVERITY_HIGH => Level::Error,
+        GL_DEBUG_SEVERITY_MEDIUM => Level::Warn,
+        GL_DEBUG_SEVERITY_LOW => Level::Info,
+        _ => Level::Debug,
+    }
+}
+
+/// Some drivers count the terminating null, or end messages with a newline.
+#[cfg(not(target_arch = "wasm32"))]
+fn format_message(id: GLuint, message: &[u8]) -> String {
+    let message = String::from_utf8_lossy(message);
+    format!("GL {}: {}", id, message.trim_end_matches(&['\0', '\n'][..]))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_debug_message_format() {
+    assert_eq!(level(GL_DEBUG_SEVERITY_HIGH), Level::Error);
+    assert_eq!(level(GL_DEBUG_SEVERITY_MEDIUM), Level::Warn);
+    assert_eq!(level(GL_DEBUG_SEVERITY_LOW), Level::Info);
+    assert_eq!(level(GL_DEBUG_SEVERITY_NOTIFICATION), Level::Debug);
+
+    assert_eq!(
+        format_message(131218, b"Program/shader state performance warning"),
+        "GL 131218: Program/shader state performance warning"
+    );
+    assert_eq!(
+        format_message(1, b"buffer too small\n\0"),
+        "GL 1: buffer too small"
+    );
+    assert_eq!(
+        format_message(2, b"bad \xff byte"),
+        "GL 2: bad \u{fffd} byte"
+    );
+    assert_eq!(format_message(3, b""), "GL 3: ");
+}
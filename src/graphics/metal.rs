@@ -6,6 +6,7 @@ use crate::native::apple::{
 };
 
 use super::*;
+use crate::ResourceManager;
 
 // https://developer.apple.com/metal/Metal-Feature-Set-Tables.pdf
 const MAX_UNIFORM_BUFFER_SIZE: u64 = 4 * 1024 * 1024;
@@ -14,6 +15,8 @@ const NUM_INFLIGHT_FRAMES: usize = 3;
 const UNIFORM_BUFFER_ALIGN: u64 = 256;
 #[cfg(all(target_os = "ios", not(target_arch = "x86_64")))]
 const UNIFORM_BUFFER_ALIGN: u64 = 16;
+// Buffer 0 is for apply_uniforms, vertex buffers take the next MAX_VERTEX_ATTRIBUTES
+const FIRST_UNIFORM_BLOCK_INDEX: usize = MAX_VERTEX_ATTRIBUTES + 1;
 
 impl From<VertexFormat> for MTLVertexFormat {
     fn from(vf: VertexFormat) -> Self {
@@ -162,6 +165,39 @@ impl From<TextureFormat> for MTLPixelFormat {
             //TODO: Depth16Unorm ?
             TextureFormat::Depth => MTLPixelFormat::Depth32Float_Stencil8,
             TextureFormat::RGBA16F => MTLPixelFormat::RGBA16Float,
+            TextureFormat::R8 => MTLPixelFormat::R8Unorm,
+            TextureFormat::RG8 => MTLPixelFormat::RG8Unorm,
+            TextureFormat::R16F => MTLPixelFormat::R16Float,
+            TextureFormat::RG16F => MTLPixelFormat::RG16Float,
+            TextureFormat::R32F => MTLPixelFormat::R32Float,
+            TextureFormat::RGBA32F => MTLPixelFormat::RGBA32Float,
+            TextureFormat::R32UI => MTLPixelFormat::R32Uint,
+            TextureFormat::SRGBA8 => MTLPixelFormat::RGBA8Unorm_sRGB,
+            // Depth24Unorm_Stencil8 is not there on Apple GPUs
+            TextureFormat::Depth24Stencil8 => MTLPixelFormat::Depth32Float_Stencil8,
+            TextureFormat::BC1 => MTLPixelFormat::BC1_RGBA,
+            TextureFormat::BC2 => MTLPixelFormat::BC2_RGBA,
+            TextureFormat::BC3 => MTLPixelFormat::BC3_RGBA,
+            TextureFormat::BC4 => MTLPixelFormat::BC4_RUnorm,
+            TextureFormat::BC5 => MTLPixelFormat::BC5_RGUnorm,
+            TextureFormat::BC6H => MTLPixelFormat::BC6H_RGBUfloat,
+            TextureFormat::BC7 => MTLPixelFormat::BC7_RGBAUnorm,
+            TextureFormat::ETC2RGB => MTLPixelFormat::ETC2_RGB8,
+            TextureFormat::ETC2RGBA => MTLPixelFormat::EAC_RGBA8,
+            TextureFormat::ASTC4x4 => MTLPixelFormat::ASTC_4x4_LDR,
+            TextureFormat::ASTC5x5 => MTLPixelFormat::ASTC_5x5_LDR,
+            TextureFormat::ASTC6x6 => MTLPixelFormat::ASTC_6x6_LDR,
+            TextureFormat::ASTC8x8 => MTLPixelFormat::ASTC_8x8_LDR,
+            TextureFormat::BC1SRGB => MTLPixelFormat::BC1_RGBA_sRGB,
+            TextureFormat::BC2SRGB => MTLPixelFormat::BC2_RGBA_sRGB,
+            TextureFormat::BC3SRGB => MTLPixelFormat::BC3_RGBA_sRGB,
+            TextureFormat::BC7SRGB => MTLPixelFormat::BC7_RGBAUnorm_sRGB,
+            TextureFormat::ETC2SRGB => MTLPixelFormat::ETC2_RGB8_sRGB,
+            TextureFormat::ETC2SRGBA => MTLPixelFormat::EAC_RGBA8_sRGB,
+            TextureFormat::ASTC4x4SRGB => MTLPixelFormat::ASTC_4x4_sRGB,
+            TextureFormat::ASTC5x5SRGB => MTLPixelFormat::ASTC_5x5_sRGB,
+            TextureFormat::ASTC6x6SRGB => MTLPixelFormat::ASTC_6x6_sRGB,
+            TextureFormat::ASTC8x8SRGB => MTLPixelFormat::ASTC_8x8_sRGB,
             _ => todo!(),
         }
     }
@@ -226,10 +262,18 @@ struct ShaderInternal {
     //stride: u64,
 }
 
+// Shared buffer the texture is blitted into and the command buffer doing it
+#[derive(Clone, Copy)]
+struct ReadbackInternal {
+    buffer: ObjcId,
+    command_buffer: ObjcId,
+    size: usize,
+}
+
 struct RenderPassInternal {
     render_pass_desc: ObjcId,
     texture: Vec<TextureId>,
-    _depth_texture: Option<TextureId>,
+    depth_texture: Option<TextureId>,
 }
 
 #[derive(Clone, Debug)]
@@ -249,7 +293,7 @@ struct Texture {
     sampler_descriptor: ObjcId,
     params: TextureParams,
 }
-struct Textures(Vec<Texture>);
+struct Textures(ResourceManager<Texture>);
 
 impl Textures {
     fn get(&self, texture: TextureId) -> Texture {
@@ -269,11 +313,15 @@ impl Textures {
     }
 }
 pub struct MetalContext {
-    buffers: Vec<Buffer>,
-    shaders: Vec<ShaderInternal>,
-    pipelines: Vec<PipelineInternal>,
+    buffers: ResourceManager<Buffer>,
+    shaders: ResourceManager<ShaderInternal>,
+    pipelines: ResourceManager<PipelineInternal>,
     textures: Textures,
-    passes: Vec<RenderPassInternal>,
+    passes: ResourceManager<RenderPassInternal>,
+    // There are no queries on Metal yet, `features` says so. These are handles
+    // that never become available, so the code checking for them keeps working.
+    queries: ResourceManager<QueryType>,
+    readbacks: ResourceManager<ReadbackInternal>,
     command_queue: ObjcId,
     command_buffer: Option<ObjcId>,
     render_encoder: Option<ObjcId>,
@@ -283,12 +331,52 @@ pub struct MetalContext {
     uniform_buffers: [ObjcId; 3],
     // cached index_buffer from apply_bindings
     index_buffer: Option<ObjcId>,
+    index_buffer_offset: u64,
     // cached pipeline from apply_pipeline
     current_pipeline: Option<Pipeline>,
     current_ub_offset: u64,
 }
 
 impl MetalContext {
+    /// Blit every layer of `texture` into a new shared buffer, one after another,
+    /// in the command buffer of the frame.
+    unsafe fn encode_readback(&mut self, texture: TextureId) -> (ObjcId, usize) {
+        let texture = self.textures.get(texture);
+        let params = texture.params;
+        assert!(
+            !params.format.is_compressed() && !params.format.is_depth(),
+            "Compressed and depth textures can't be read back on Metal"
+        );
+        let row_size = params.format.size(params.width, 1) as u64;
+        let layer_size = params.format.size(params.width, params.height) as u64;
+        let size = layer_size * params.layers() as u64;
+        let buffer = msg_send_![self.device, newBufferWithLength:size
+                                options:MTLResourceOptions::StorageModeShared];
+
+        if self.command_buffer.is_none() {
+            self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+        }
+        let encoder = msg_send_![self.command_buffer.unwrap(), blitCommandEncoder];
+        for layer in 0..params.layers() as u64 {
+            // 3D textures have a single slice with the layers along z
+            let (slice, z) = match params.kind {
+                TextureKind::Texture3D => (0, layer),
+                _ => (layer, 0),
+            };
+            msg_send_![encoder, copyFromTexture:texture.texture
+                       sourceSlice:slice
+                       sourceLevel:0u64
+                       sourceOrigin:MTLOrigin { x: 0, y: 0, z }
+                       sourceSize:MTLSize { width: params.width as u64, height: params.height as u64, depth: 1 }
+                       toBuffer:buffer
+                       destinationOffset:layer * layer_size
+                       destinationBytesPerRow:row_size
+                       destinationBytesPerImage:layer_size];
+        }
+        msg_send_![encoder, endEncoding];
+        (buffer, size as usize)
+    }
+
     pub fn new() -> MetalContext {
         unsafe {
             let view = crate::window::apple_view();
@@ -364,12 +452,15 @@ impl MetalContext {
                 render_encoder: None,
                 view,
                 device,
-                buffers: vec![],
-                shaders: vec![],
-                pipelines: vec![],
-                textures: Textures(Vec::new()),
-                passes: vec![],
+                buffers: ResourceManager::default(),
+                shaders: ResourceManager::default(),
+                pipelines: ResourceManager::default(),
+                textures: Textures(ResourceManager::default()),
+                passes: ResourceManager::default(),
+                queries: ResourceManager::default(),
+                readbacks: ResourceManager::default(),
                 index_buffer: None,
+                index_buffer_offset: 0,
                 current_pipeline: None,
                 uniform_buffers,
                 current_frame_index: 1,
@@ -384,8 +475,35 @@ impl RenderingBackend for MetalContext {
         ContextInfo {
             backend: Backend::Metal,
             gl_version_string: Default::default(),
+            gl_vendor_string: Default::default(),
+            gl_renderer_string: Default::default(),
             glsl_support: Default::default(),
-            features: Features { instancing: true },
+            features: Features {
+                instancing: true,
+                texture_arrays: true,
+                // BC is desktop only, ETC2 and ASTC are there on Apple GPUs
+                texture_compression_s3tc: cfg!(target_os = "macos"),
+                texture_compression_s3tc_srgb: cfg!(target_os = "macos"),
+                texture_compression_rgtc: cfg!(target_os = "macos"),
+                texture_compression_bptc: cfg!(target_os = "macos"),
+                texture_compression_etc2: cfg!(target_os = "ios"),
+                texture_compression_astc: cfg!(target_os = "ios"),
+                texture_rg: true,
+                texture_float: true,
+                // filtering 32 bit floats is optional on iOS
+                texture_float_linear: cfg!(target_os = "macos"),
+                color_buffer_float: true,
+                texture_integer: true,
+                texture_srgb: true,
+                depth_stencil_texture: true,
+                draw_base_vertex: true,
+                draw_indirect: true,
+                multi_draw_indirect: true,
+                async_readback: true,
+                uniform_buffers: true,
+                uniform_buffer_offset_alignment: UNIFORM_BUFFER_ALIGN as usize,
+                ..Default::default()
+            },
         }
     }
     fn buffer_size(&mut self, buffer: BufferId) -> usize {
@@ -393,7 +511,7 @@ impl RenderingBackend for MetalContext {
         buffer.size
     }
     fn delete_buffer(&mut self, buffer: BufferId) {
-        let buffer = &self.buffers[buffer.0];
+        let buffer = self.buffers.remove(buffer.0);
         unsafe {
             for buffer in &buffer.raw {
                 msg_send_![*buffer, release];
@@ -401,9 +519,12 @@ impl RenderingBackend for MetalContext {
         }
     }
     fn delete_texture(&mut self, texture: TextureId) {
-        let texture = self.textures.get(texture);
+        let t = self.textures.get(texture);
         unsafe {
-            msg_send_![texture.texture, release];
+            msg_send_![t.texture, release];
+        }
+        if let TextureIdInner::Managed(texture) = texture.0 {
+            self.textures.0.remove(texture);
         }
     }
     fn apply_viewport(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
@@ -492,8 +613,54 @@ impl RenderingBackend for MetalContext {
     ) {
         unimplemented!()
     }
-    fn texture_read_pixels(&mut self, _texture: TextureId, _bytes: &mut [u8]) {
-        unimplemented!()
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        unsafe {
+            let (buffer, size) = self.encode_readback(texture);
+            let command_buffer = self.command_buffer.unwrap();
+            msg_send_![command_buffer, commit];
+            msg_send_![command_buffer, waitUntilCompleted];
+            self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+
+            let contents: *const u8 = msg_send![buffer, contents];
+            bytes.copy_from_slice(std::slice::from_raw_parts(contents, size));
+            msg_send_![buffer, release];
+        }
+    }
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId {
+        unsafe {
+            let (buffer, size) = self.encode_readback(texture);
+            // done once the frame is committed and the GPU gets to it
+            let command_buffer = self.command_buffer.unwrap();
+            msg_send_![command_buffer, retain];
+            ReadbackId(self.readbacks.add(ReadbackInternal {
+                buffer,
+                command_buffer,
+                size,
+            }))
+        }
+    }
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool {
+        let ReadbackInternal {
+            buffer,
+            command_buffer,
+            size,
+        } = self.readbacks[readback.0];
+        unsafe {
+            let status: u64 = msg_send![command_buffer, status];
+            if status < MTLCommandBufferStatus::Completed as u64 {
+                return false;
+            }
+            assert!(
+                status == MTLCommandBufferStatus::Completed as u64,
+                "Command buffer of the readback failed"
+            );
+            let contents: *const u8 = msg_send![buffer, contents];
+            bytes.copy_from_slice(std::slice::from_raw_parts(contents, size));
+            msg_send_![buffer, release];
+            msg_send_![command_buffer, release];
+        }
+        self.readbacks.remove(readback.0);
+        true
     }
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         unsafe {
@@ -531,11 +698,23 @@ impl RenderingBackend for MetalContext {
         self.end_render_pass();
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_layer(
         &mut self,
         color_img: &[TextureId],
         depth_img: Option<TextureId>,
+        layer: u32,
     ) -> RenderPass {
+        let set_layer = |attachment: ObjcId, texture: TextureId| unsafe {
+            match self.textures.get(texture).params.kind {
+                TextureKind::Texture2D => {}
+                TextureKind::CubeMap | TextureKind::Texture2DArray => {
+                    msg_send_![attachment, setSlice: layer as u64];
+                }
+                TextureKind::Texture3D => {
+                    msg_send_![attachment, setDepthPlane: layer as u64];
+                }
+            }
+        };
         unsafe {
             let render_pass_desc =
                 msg_send_![class!(MTLRenderPassDescriptor), renderPassDescriptor];
@@ -545,6 +724,7 @@ impl RenderingBackend for MetalContext {
                 let color_texture = self.textures.get(*color_img).texture;
                 let color_attachment = msg_send_![msg_send_![render_pass_desc, colorAttachments], objectAtIndexedSubscript:i];
                 msg_send_![color_attachment, setTexture: color_texture];
+                set_layer(color_attachment, *color_img);
                 msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
             }
@@ -553,27 +733,27 @@ impl RenderingBackend for MetalContext {
 
                 let depth_attachment = msg_send_![render_pass_desc, depthAttachment];
                 msg_send_![depth_attachment, setTexture: depth_texture];
+                set_layer(depth_attachment, depth_img);
                 msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
                 msg_send_![depth_attachment, setClearDepth:1.];
 
                 let stencil_attachment = msg_send_![render_pass_desc, stencilAttachment];
                 msg_send_![stencil_attachment, setTexture: depth_texture];
+                set_layer(stencil_attachment, depth_img);
             }
             let pass = RenderPassInternal {
                 render_pass_desc,
                 texture: color_img.to_vec(),
-                _depth_texture: depth_img,
+                depth_texture: depth_img,
             };
 
-            self.passes.push(pass);
-
-            RenderPass(self.passes.len() - 1)
+            RenderPass(self.passes.add(pass))
         }
     }
 
     fn delete_render_pass(&mut self, render_pass: RenderPass) {
-        let render_pass = &self.passes[render_pass.0];
+        let render_pass = self.passes.remove(render_pass.0);
         unsafe {
             msg_send_![render_pass.render_pass_desc, release];
         }
@@ -583,6 +763,49 @@ impl RenderingBackend for MetalContext {
         &self.passes[render_pass.0].texture
     }
 
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, _filter: FilterMode) {
+        // A blit encoder copies texels as they are, there is nothing to filter with. The
+        // drawable is framebufferOnly and can't be a blit destination.
+        let dst = dst.expect("blit_render_pass into the default framebuffer is not supported on Metal, draw a textured quad instead");
+        let src = &self.passes[src.0];
+        let dst = &self.passes[dst.0];
+        let mut pairs: Vec<(Texture, Texture)> = src
+            .texture
+            .iter()
+            .zip(&dst.texture)
+            .map(|(src, dst)| (self.textures.get(*src), self.textures.get(*dst)))
+            .collect();
+        if let (Some(src), Some(dst)) = (src.depth_texture, dst.depth_texture) {
+            let (src, dst) = (self.textures.get(src), self.textures.get(dst));
+            if src.params.format == dst.params.format {
+                pairs.push((src, dst));
+            }
+        }
+        unsafe {
+            if self.command_buffer.is_none() {
+                self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+            }
+            let command_buffer = self.command_buffer.unwrap();
+            let encoder = msg_send_![command_buffer, blitCommandEncoder];
+            for (src, dst) in pairs {
+                assert!(
+                    src.params.width == dst.params.width && src.params.height == dst.params.height,
+                    "blit_render_pass can't scale on Metal, the passes should have the same size"
+                );
+                msg_send_![encoder, copyFromTexture:src.texture
+                           sourceSlice:0u64
+                           sourceLevel:0u64
+                           sourceOrigin:MTLOrigin { x: 0, y: 0, z: 0 }
+                           sourceSize:MTLSize { width: src.params.width as u64, height: src.params.height as u64, depth: 1 }
+                           toTexture:dst.texture
+                           destinationSlice:0u64
+                           destinationLevel:0u64
+                           destinationOrigin:MTLOrigin { x: 0, y: 0, z: 0 }];
+            }
+            msg_send_![encoder, endEncoding];
+        }
+    }
+
     fn new_buffer(&mut self, _: BufferType, _usage: BufferUsage, data: BufferSource) -> BufferId {
         let mut raw = [nil; BUFFERS_IN_ROTATION];
         let size = match &data {
@@ -623,24 +846,36 @@ impl RenderingBackend for MetalContext {
             value: 0,
             next_value: 0,
         };
-        self.buffers.push(buffer);
-        BufferId(self.buffers.len() - 1)
+        BufferId(self.buffers.add(buffer))
     }
 
-    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
         let data = match data {
             BufferSource::Slice(data) => data,
             _ => panic!("buffer_update expects BufferSource::slice"),
         };
         let buffer = &mut self.buffers[buffer.0];
-        assert!(data.size <= buffer.size);
+        assert!(offset + data.size <= buffer.size);
 
         unsafe {
-            let dest: *mut std::ffi::c_void = msg_send![buffer.raw[buffer.next_value], contents];
-            std::ptr::copy(data.ptr, dest, data.size);
+            let dest: *mut u8 = msg_send![buffer.raw[buffer.next_value], contents];
+            // the next buffer in rotation may hold stale data, carry over the part kept
+            let carry_over = buffer.next_value != buffer.value && data.size != buffer.size;
+            if carry_over {
+                let src: *const u8 = msg_send![buffer.raw[buffer.value], contents];
+                std::ptr::copy_nonoverlapping(src, dest, buffer.size);
+            }
+            std::ptr::copy(data.ptr as *const u8, dest.add(offset), data.size);
 
             #[cfg(target_os = "macos")]
-            msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, data.size as u64)];
+            {
+                let range = if carry_over {
+                    NSRange::new(0, buffer.size as u64)
+                } else {
+                    NSRange::new(offset as u64, data.size as u64)
+                };
+                msg_send_![buffer.raw[buffer.next_value], didModifyRange: range];
+            }
         }
         buffer.value = buffer.next_value;
     }
@@ -677,11 +912,14 @@ impl RenderingBackend for MetalContext {
                 vertex_function,
                 fragment_function,
             };
-            self.shaders.push(shader);
-            Ok(ShaderId(self.shaders.len() - 1))
+            Ok(ShaderId(self.shaders.add(shader)))
         }
     }
 
+    fn reflect_shader(&mut self, _shader: ShaderSource) -> Result<ShaderReflection, ShaderError> {
+        Err(ShaderError::ReflectionUnsupported)
+    }
+
     fn new_texture(
         &mut self,
         access: TextureAccess,
@@ -703,7 +941,7 @@ impl RenderingBackend for MetalContext {
             msg_send_![descriptor, setCpuCacheMode: MTLCPUCacheMode::DefaultCache];
 
             if access == TextureAccess::RenderTarget {
-                if params.format != TextureFormat::Depth {
+                if !params.format.is_depth() {
                     let pixel_format: MTLPixelFormat = params.format.into();
                     msg_send_![descriptor, setPixelFormat: pixel_format];
                 }
@@ -742,6 +980,14 @@ impl RenderingBackend for MetalContext {
             TextureKind::CubeMap => unsafe {
                 msg_send_![descriptor, setTextureType: MTLTextureType::CubeArray];
             },
+            TextureKind::Texture2DArray => unsafe {
+                msg_send_![descriptor, setTextureType: MTLTextureType::D2Array];
+                msg_send_![descriptor, setArrayLength: params.depth as u64];
+            },
+            TextureKind::Texture3D => unsafe {
+                msg_send_![descriptor, setTextureType: MTLTextureType::D3];
+                msg_send_![descriptor, setDepth: params.depth as u64];
+            },
         }
 
         let texture = unsafe {
@@ -762,56 +1008,63 @@ impl RenderingBackend for MetalContext {
             ];
             let raw_texture = msg_send_![self.device, newTextureWithDescriptor: descriptor];
             msg_send_![raw_texture, retain];
-            self.textures.0.push(Texture {
+            TextureId(TextureIdInner::Managed(self.textures.0.add(Texture {
                 sampler: sampler_state,
                 texture: raw_texture,
                 sampler_descriptor,
                 params,
-            });
-            TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+            })))
         };
 
         match bytes {
             TextureSource::Empty => {}
             TextureSource::Bytes(bytes) => {
-                assert_eq!(
-                    params.format.size(params.width, params.height) as usize,
-                    bytes.len()
-                );
-
-                self.texture_update_part(
-                    texture,
-                    0,
-                    0,
-                    params.width as _,
-                    params.height as _,
-                    bytes,
-                );
+                let layer_size = params.format.size(params.width, params.height) as usize;
+                assert_eq!(layer_size * params.layers() as usize, bytes.len());
+
+                for (layer, bytes) in bytes.chunks_exact(layer_size).enumerate() {
+                    self.texture_update_layer_part(
+                        texture,
+                        layer as _,
+                        0,
+                        0,
+                        params.width as _,
+                        params.height as _,
+                        bytes,
+                    );
+                }
             }
             TextureSource::Array(array) => {
                 for (n, face) in array.iter().enumerate() {
+                    // 3D textures have a single slice with the layers along z
+                    let (slice, z) = match params.kind {
+                        TextureKind::Texture3D => (0, n as u64),
+                        _ => (n, 0),
+                    };
                     for (mipmap_level, bytes) in face.iter().enumerate() {
                         let raw_texture = self.textures.get(texture).texture;
+                        let width = (params.width >> mipmap_level).max(1);
+                        let height = (params.height >> mipmap_level).max(1);
                         let region = MTLRegion {
                             origin: MTLOrigin {
                                 x: 0 as u64,
                                 y: 0 as u64,
-                                z: 0,
+                                z,
                             },
                             size: MTLSize {
-                                width: params.width as u64,
-                                height: params.height as u64,
+                                width: width as u64,
+                                height: height as u64,
                                 depth: 1,
                             },
                         };
-                        assert!(bytes.len() as u32 == params.width * params.height * 4);
+                        assert!(bytes.len() as u32 == params.format.size(width, height));
                         unsafe {
                             msg_send_![raw_texture, replaceRegion:region
                                   mipmapLevel:mipmap_level
-                                  slice: n
+                                  slice: slice
                                   withBytes:bytes.as_ptr()
-                                  bytesPerRow:(params.width * 4) as u64
-                                  bytesPerImage:0
+                                  bytesPerRow:params.format.size(width, 1) as u64
+                                  bytesPerImage:bytes.len() as u64
                             ];
                         }
                     }
@@ -821,21 +1074,27 @@ impl RenderingBackend for MetalContext {
         texture
     }
 
-    fn texture_update_part(
+    fn texture_update_layer_part(
         &mut self,
         texture: TextureId,
+        layer: u32,
         x_offset: i32,
         y_offset: i32,
         width: i32,
         height: i32,
         bytes: &[u8],
     ) {
-        let raw_texture = self.textures.get(texture).texture;
+        let texture = self.textures.get(texture);
+        let raw_texture = texture.texture;
+        let (slice, z) = match texture.params.kind {
+            TextureKind::Texture3D => (0, layer as u64),
+            _ => (layer as u64, 0),
+        };
         let region = MTLRegion {
             origin: MTLOrigin {
                 x: x_offset as u64,
                 y: y_offset as u64,
-                z: 0,
+                z,
             },
             size: MTLSize {
                 width: width as u64,
@@ -846,8 +1105,39 @@ impl RenderingBackend for MetalContext {
         unsafe {
             msg_send_![raw_texture, replaceRegion:region
                        mipmapLevel:0
+                       slice:slice
                        withBytes:bytes.as_ptr()
-                       bytesPerRow:(width * 4) as u64];
+                       bytesPerRow:texture.params.format.size(width as _, 1) as u64
+                       bytesPerImage:bytes.len() as u64];
+        }
+    }
+
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    ) {
+        let (x, y, width, height) = src_rect;
+        let src = self.textures.get(src).texture;
+        let dst = self.textures.get(dst).texture;
+        unsafe {
+            if self.command_buffer.is_none() {
+                self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+            }
+            let command_buffer = self.command_buffer.unwrap();
+            let encoder = msg_send_![command_buffer, blitCommandEncoder];
+            msg_send_![encoder, copyFromTexture:src
+                       sourceSlice:0u64
+                       sourceLevel:0u64
+                       sourceOrigin:MTLOrigin { x: x as u64, y: y as u64, z: 0 }
+                       sourceSize:MTLSize { width: width as u64, height: height as u64, depth: 1 }
+                       toTexture:dst
+                       destinationSlice:0u64
+                       destinationLevel:0u64
+                       destinationOrigin:MTLOrigin { x: dst_pos.0 as u64, y: dst_pos.1 as u64, z: 0 }];
+            msg_send_![encoder, endEncoding];
         }
     }
 
@@ -1023,9 +1313,7 @@ impl RenderingBackend for MetalContext {
                 //params,
             };
 
-            self.pipelines.push(pipeline);
-
-            Pipeline(self.pipelines.len() - 1)
+            Pipeline(self.pipelines.add(pipeline))
         }
     }
 
@@ -1050,7 +1338,9 @@ impl RenderingBackend for MetalContext {
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
         index_buffer: BufferId,
+        index_buffer_offset: usize,
         textures: &[TextureId],
     ) {
         assert!(
@@ -1062,14 +1352,16 @@ impl RenderingBackend for MetalContext {
             let render_encoder = self.render_encoder.unwrap();
             for (index, vertex_buffer) in vertex_buffers.iter().enumerate() {
                 let buffer = &mut self.buffers[vertex_buffer.0];
+                let offset = vertex_buffer_offsets.get(index).copied().unwrap_or(0);
                 let () = msg_send![render_encoder,
                                    setVertexBuffer:buffer.raw[buffer.value]
-                                   offset:0
+                                   offset:offset as u64
                                    atIndex:(index + 1) as u64];
                 buffer.next_value = buffer.value + 1;
             }
             let index_buffer = &mut self.buffers[index_buffer.0];
             self.index_buffer = Some(index_buffer.raw[index_buffer.value]);
+            self.index_buffer_offset = index_buffer_offset as u64;
             index_buffer.next_value = index_buffer.value + 1;
 
             let img_count = textures.len();
@@ -1241,7 +1533,7 @@ impl RenderingBackend for MetalContext {
                        indexCount:num_elements as u64
                        indexType:MTLIndexType::UInt16
                        indexBuffer:index_buffer
-                       indexBufferOffset:0
+                       indexBufferOffset:self.index_buffer_offset
                        instanceCount:num_instances as u64
                        baseVertex:0
                        baseInstance:0
@@ -1249,11 +1541,123 @@ impl RenderingBackend for MetalContext {
         }
     }
 
-    fn delete_shader(&mut self, _shader: ShaderId) {
-        // TODO: place holder
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+
+        unsafe {
+            msg_send_![render_encoder, drawPrimitives:MTLPrimitiveType::Triangle
+                       vertexStart:first_vertex as u64
+                       vertexCount:num_vertices as u64
+                       instanceCount:num_instances as u64
+            ];
+        }
+    }
+
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    ) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+        assert!(self.index_buffer.is_some());
+        let index_buffer = self.index_buffer.unwrap();
+
+        unsafe {
+            msg_send_![render_encoder, drawIndexedPrimitives:MTLPrimitiveType::Triangle
+                       indexCount:num_elements as u64
+                       indexType:MTLIndexType::UInt16
+                       indexBuffer:index_buffer
+                       indexBufferOffset:self.index_buffer_offset + (base_element * 2) as u64
+                       instanceCount:num_instances as u64
+                       baseVertex:base_vertex as i64
+                       baseInstance:0
+            ];
+        }
+    }
+
+    fn draw_indirect(&self, buffer: BufferId, offset: usize) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+        assert!(self.index_buffer.is_some());
+        let index_buffer = self.index_buffer.unwrap();
+        let buffer = &self.buffers[buffer.0];
+
+        unsafe {
+            msg_send_![render_encoder, drawIndexedPrimitives:MTLPrimitiveType::Triangle
+                       indexType:MTLIndexType::UInt16
+                       indexBuffer:index_buffer
+                       indexBufferOffset:0
+                       indirectBuffer:buffer.raw[buffer.value]
+                       indirectBufferOffset:offset as u64
+            ];
+        }
+    }
+
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32) {
+        let stride = match stride {
+            0 => std::mem::size_of::<DrawIndirectCommand>(),
+            stride => stride as usize,
+        };
+        for draw in 0..draw_count as usize {
+            self.draw_indirect(buffer, offset + draw * stride);
+        }
     }
-    fn delete_pipeline(&mut self, _pipeline: Pipeline) {
-        // TODO: place holder
+
+    fn delete_shader(&mut self, shader: ShaderId) {
+        // TODO: release the functions
+        self.shaders.remove(shader.0);
+    }
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        // TODO: release the states
+        self.pipelines.remove(pipeline.0);
+    }
+
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8> {
+        let (x, y, width, height) = rect;
+        let (row_size, size) = (width as u64 * 4, width as u64 * height as u64 * 4);
+        let mut pixels = vec![0u8; size as usize];
+        unsafe {
+            // the view is created with framebufferOnly off for this
+            let drawable: ObjcId = msg_send!(self.view, currentDrawable);
+            let texture: ObjcId = msg_send!(drawable, texture);
+            let drawable_height: u64 = msg_send![texture, height];
+            let buffer = msg_send_![self.device, newBufferWithLength:size
+                                    options:MTLResourceOptions::StorageModeShared];
+
+            if self.command_buffer.is_none() {
+                self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+            }
+            let command_buffer = self.command_buffer.unwrap();
+            let encoder = msg_send_![command_buffer, blitCommandEncoder];
+            // rect is bottom-up, as in GL, Metal textures are top-down
+            msg_send_![encoder, copyFromTexture:texture
+                       sourceSlice:0u64
+                       sourceLevel:0u64
+                       sourceOrigin:MTLOrigin { x: x as u64, y: drawable_height - (y + height) as u64, z: 0 }
+                       sourceSize:MTLSize { width: width as u64, height: height as u64, depth: 1 }
+                       toBuffer:buffer
+                       destinationOffset:0u64
+                       destinationBytesPerRow:row_size
+                       destinationBytesPerImage:size];
+            msg_send_![encoder, endEncoding];
+            msg_send_![command_buffer, commit];
+            msg_send_![command_buffer, waitUntilCompleted];
+            // the rest of the frame, presentDrawable included, goes into a new one
+            self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+
+            let contents: *const u8 = msg_send![buffer, contents];
+            std::ptr::copy_nonoverlapping(contents, pixels.as_mut_ptr(), size as usize);
+            msg_send_![buffer, release];
+        }
+        // the drawable is BGRA8Unorm
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        pixels
     }
 
     fn commit_frame(&mut self) {
@@ -1265,7 +1669,7 @@ impl RenderingBackend for MetalContext {
             msg_send_![self.command_buffer.unwrap(), commit];
             msg_send_![self.command_buffer.unwrap(), waitUntilCompleted];
         }
-        for buffer in &mut self.buffers {
+        for buffer in self.buffers.iter_mut() {
             buffer.next_value = 0;
         }
         self.current_ub_offset = 0;
@@ -1275,4 +1679,44 @@ impl RenderingBackend for MetalContext {
             self.current_frame_index = 0;
         }
     }
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize) {
+        assert!(
+            self.current_pipeline.is_some(),
+            "apply_uniform_block before apply_pipeline"
+        );
+        assert!(
+            self.render_encoder.is_some(),
+            "apply_uniform_block before begin_pass"
+        );
+
+        let render_encoder = self.render_encoder.unwrap();
+        let buffer = &mut self.buffers[buffer.0];
+        assert!(offset < buffer.size);
+        let index = (FIRST_UNIFORM_BLOCK_INDEX + block) as u64;
+        unsafe {
+            msg_send_![render_encoder,
+                       setVertexBuffer:buffer.raw[buffer.value]
+                       offset:offset as u64
+                       atIndex:index];
+            msg_send_![render_encoder,
+                       setFragmentBuffer:buffer.raw[buffer.value]
+                       offset:offset as u64
+                       atIndex:index];
+        }
+        buffer.next_value = buffer.value + 1;
+    }
+    fn new_query(&mut self, query_type: QueryType) -> QueryId {
+        QueryId(self.queries.add(query_type))
+    }
+    fn begin_query(&mut self, _query: QueryId) {}
+    fn end_query(&mut self, _query: QueryId) {}
+    fn query_available(&mut self, _query: QueryId) -> bool {
+        false
+    }
+    fn query_result(&mut self, _query: QueryId) -> u64 {
+        0
+    }
+    fn delete_query(&mut self, query: QueryId) {
+        self.queries.remove(query.0);
+    }
 }
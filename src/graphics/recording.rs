@@ -0,0 +1,858 @@
+//! Headless `RenderingBackend` that does not talk to any GPU.
+//!
+//! All the resources are kept in CPU memory and every state-changing call is recorded
+//! into a command list, so rendering code may be unit-tested without a window or a GL context.
+
+use std::cell::{Ref, RefCell};
+
+use crate::ResourceManager;
+
+use super::*;
+
+/// A single recorded `RenderingBackend` call.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    BeginPass {
+        pass: Option<RenderPass>,
+        action: PassAction,
+    },
+    EndPass,
+    ApplyPipeline(Pipeline),
+    ApplyBindings {
+        vertex_buffers: Vec<BufferId>,
+        vertex_buffer_offsets: Vec<usize>,
+        index_buffer: BufferId,
+        index_buffer_offset: usize,
+        images: Vec<TextureId>,
+    },
+    /// Raw bytes of the uniforms struct
+    ApplyUniforms(Vec<u8>),
+    ApplyUniformBlock {
+        block: usize,
+        buffer: BufferId,
+        offset: usize,
+    },
+    ApplyViewport {
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+    },
+    ApplyScissorRect {
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+    },
+    Clear {
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    },
+    Draw {
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+    },
+    DrawArrays {
+        first_vertex: i32,
+        num_vertices: i32,
+        num_instances: i32,
+    },
+    DrawBaseVertex {
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    },
+    DrawIndirect {
+        buffer: BufferId,
+        offset: usize,
+    },
+    MultiDrawIndirect {
+        buffer: BufferId,
+        offset: usize,
+        draw_count: i32,
+        stride: i32,
+    },
+    CopyTextureRegion {
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    },
+    BlitRenderPass {
+        src: RenderPass,
+        dst: Option<RenderPass>,
+        filter: FilterMode,
+    },
+    CommitFrame,
+    BeginQuery(QueryId),
+    EndQuery(QueryId),
+}
+
+#[derive(Clone)]
+pub struct RecordedShader {
+    pub meta: ShaderMeta,
+    /// GLSL or MSL sources, exactly as they were given to `new_shader`
+    pub vertex: String,
+    pub fragment: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedTexture {
+    /// `params.wrap` is the horizontal wrap mode, see `wrap_y` for the vertical one.
+    pub params: TextureParams,
+    /// Vertical wrap mode set with `texture_set_wrap`, `params.wrap` until then.
+    pub wrap_y: TextureWrap,
+    /// Level 0 of the texture, in `params.format`.
+    /// Cubemap faces and array layers are stored one after another.
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedBuffer {
+    pub buffer_type: BufferType,
+    pub usage: BufferUsage,
+    pub element_size: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedPipeline {
+    pub buffer_layout: Vec<BufferLayout>,
+    pub attributes: Vec<VertexAttribute>,
+    pub shader: ShaderId,
+    pub params: PipelineParams,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedPass {
+    pub color_textures: Vec<TextureId>,
+    pub depth_texture: Option<TextureId>,
+    /// Attached cubemap face or array layer, 0 for passes made with `new_render_pass_mrt`
+    pub layer: u32,
+}
+
+/// `RenderingBackend` that allocates fake resource handles, keeps texture and buffer
+/// contents in memory and records all the rendering calls.
+///
+/// Does not require `miniquad::start`, may be created anywhere:
+/// ```
+/// # use miniquad::*;
+/// let mut ctx: Box<dyn RenderingBackend> = Box::new(RecordingContext::new());
+/// ctx.begin_default_pass(PassAction::Nothing);
+/// ctx.end_render_pass();
+/// ```
+///
+/// `info()` reports `Backend::OpenGl`, so code selecting shader sources by backend
+/// would pass GLSL sources in.
+pub struct RecordingContext {
+    shaders: ResourceManager<RecordedShader>,
+    pipelines: ResourceManager<RecordedPipeline>,
+    passes: ResourceManager<RecordedPass>,
+    buffers: ResourceManager<RecordedBuffer>,
+    queries: ResourceManager<QueryType>,
+    // pixels of the textures at begin_read_pixels
+    readbacks: ResourceManager<Vec<u8>>,
+    textures: ResourceManager<RecordedTexture>,
+    // RenderingBackend::draw takes &self
+    commands: RefCell<Vec<Command>>,
+    features: Features,
+}
+
+impl Default for RecordingContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordingContext {
+    pub fn new() -> RecordingContext {
+        RecordingContext {
+            shaders: ResourceManager::default(),
+            pipelines: ResourceManager::default(),
+            passes: ResourceManager::default(),
+            buffers: ResourceManager::default(),
+            queries: ResourceManager::default(),
+            readbacks: ResourceManager::default(),
+            textures: ResourceManager::default(),
+            commands: RefCell::new(vec![]),
+            // queries are only recorded, their results are always available and 0
+            features: Features {
+                elapsed_query: true,
+                occlusion_query: true,
+                uniform_buffers: true,
+                texture_arrays: true,
+                texture_compression_s3tc: true,
+                texture_compression_s3tc_srgb: true,
+                texture_compression_rgtc: true,
+                texture_compression_bptc: true,
+                texture_compression_etc2: true,
+                texture_compression_astc: true,
+                texture_rg: true,
+                texture_float: true,
+                texture_float_linear: true,
+                color_buffer_float: true,
+                texture_integer: true,
+                texture_srgb: true,
+                depth_stencil_texture: true,
+                draw_base_vertex: true,
+                draw_indirect: true,
+                multi_draw_indirect: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// All the commands recorded so far.
+    pub fn commands(&self) -> Ref<'_, Vec<Command>> {
+        self.commands.borrow()
+    }
+
+    /// Take all the commands recorded so far, leaving the command list empty.
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(self.commands.get_mut())
+    }
+
+    pub fn shader(&self, shader: ShaderId) -> &RecordedShader {
+        &self.shaders[shader.0]
+    }
+
+    pub fn pipeline(&self, pipeline: Pipeline) -> &RecordedPipeline {
+        &self.pipelines[pipeline.0]
+    }
+
+    pub fn render_pass(&self, render_pass: RenderPass) -> &RecordedPass {
+        &self.passes[render_pass.0]
+    }
+
+    pub fn buffer(&self, buffer: BufferId) -> &RecordedBuffer {
+        &self.buffers[buffer.0]
+    }
+
+    pub fn query_type(&self, query: QueryId) -> QueryType {
+        self.queries[query.0]
+    }
+
+    pub fn texture(&self, texture: TextureId) -> &RecordedTexture {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => &self.textures[texture],
+            TextureIdInner::Raw(_) => panic!("Raw texture in RecordingContext!"),
+        }
+    }
+
+    fn texture_mut(&mut self, texture: TextureId) -> &mut RecordedTexture {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => &mut self.textures[texture],
+            TextureIdInner::Raw(_) => panic!("Raw texture in RecordingContext!"),
+        }
+    }
+
+    fn record(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+}
+
+impl RenderingBackend for RecordingContext {
+    fn info(&self) -> ContextInfo {
+        ContextInfo {
+            backend: Backend::OpenGl,
+            gl_version_string: String::new(),
+            gl_vendor_string: String::new(),
+            gl_renderer_string: String::new(),
+            glsl_support: GlslSupport {
+                v100: true,
+                ..Default::default()
+            },
+            features: self.features.clone(),
+        }
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let (vertex, fragment) = match shader {
+            ShaderSource::Glsl { vertex, fragment } => (vertex.to_string(), fragment.to_string()),
+            ShaderSource::Msl { program } => (program.to_string(), program.to_string()),
+        };
+        let shader = RecordedShader {
+            meta,
+            vertex,
+            fragment,
+        };
+        Ok(ShaderId(self.shaders.add(shader)))
+    }
+
+    fn reflect_shader(&mut self, _shader: ShaderSource) -> Result<ShaderReflection, ShaderError> {
+        // nothing is compiled
+        Err(ShaderError::ReflectionUnsupported)
+    }
+
+    fn new_texture(
+        &mut self,
+        _access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        let size =
+            params.format.size(params.width, params.height) as usize * params.layers() as usize;
+        let data = match source {
+            TextureSource::Empty => vec![0; size],
+            TextureSource::Bytes(bytes) => {
+                assert_eq!(size, bytes.len());
+                bytes.to_vec()
+            }
+            TextureSource::Array(array) => array
+                .iter()
+                .flat_map(|mipmaps| mipmaps[0].iter().copied())
+                .collect(),
+        };
+        TextureId(TextureIdInner::Managed(self.textures.add(
+            RecordedTexture {
+                params,
+                wrap_y: params.wrap,
+                data,
+            },
+        )))
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.texture(texture).params
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => RawId::OpenGl(texture.index() as _),
+            TextureIdInner::Raw(raw) => raw,
+        }
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        let params = &mut self.texture_mut(texture).params;
+        params.min_filter = filter;
+        params.mipmap_filter = mipmap_filter;
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.texture_mut(texture).params.mag_filter = filter;
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        let texture = self.texture_mut(texture);
+        texture.params.wrap = wrap_x;
+        texture.wrap_y = wrap_y;
+    }
+
+    fn texture_generate_mipmaps(&mut self, _texture: TextureId) {}
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let texture = self.texture_mut(texture);
+        texture.params.width = width;
+        texture.params.height = height;
+        let size =
+            texture.params.format.size(width, height) as usize * texture.params.layers() as usize;
+        texture.data = match bytes {
+            Some(bytes) => {
+                assert_eq!(size, bytes.len());
+                bytes.to_vec()
+            }
+            None => vec![0; size],
+        };
+    }
+
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        let data = &self.texture(texture).data;
+        let len = bytes.len().min(data.len());
+        bytes[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId {
+        let pixels = self.texture(texture).data.clone();
+        ReadbackId(self.readbacks.add(pixels))
+    }
+
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool {
+        let pixels = self.readbacks.remove(readback.0);
+        let len = bytes.len().min(pixels.len());
+        bytes[..len].copy_from_slice(&pixels[..len]);
+        true
+    }
+
+    fn texture_update_layer_part(
+        &mut self,
+        texture: TextureId,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        let texture = self.texture_mut(texture);
+        let params = texture.params;
+        assert_eq!(
+            params.format.size(width as _, height as _) as usize,
+            bytes.len()
+        );
+        assert!(x_offset + width <= params.width as _);
+        assert!(y_offset + height <= params.height as _);
+        assert!(layer < params.layers());
+
+        // rows of pixels, or rows of blocks for compressed formats
+        let block_height = params.format.block().map_or(1, |(_, height, _)| height) as usize;
+        let row_size = params.format.size(width as _, 1) as usize;
+        let pitch = params.format.size(params.width, 1) as usize;
+        let offset = params.format.size(params.width, params.height) as usize * layer as usize
+            + y_offset as usize / block_height * pitch
+            + params.format.size(x_offset as _, 1) as usize;
+        for row in 0..(height as usize).div_ceil(block_height) {
+            let dst = offset + row * pitch;
+            texture.data[dst..dst + row_size]
+                .copy_from_slice(&bytes[row * row_size..(row + 1) * row_size]);
+        }
+    }
+
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    ) {
+        let (x, y, width, height) = src_rect;
+        let params = self.texture(src).params;
+        assert!(x + width <= params.width as _ && y + height <= params.height as _);
+        assert_eq!(
+            self.texture(dst).params.format,
+            params.format,
+            "Copy between textures of different formats"
+        );
+
+        // copied out first, src and dst may be the same texture
+        let block_height = params.format.block().map_or(1, |(_, height, _)| height) as usize;
+        let row_size = params.format.size(width as _, 1) as usize;
+        let pitch = params.format.size(params.width, 1) as usize;
+        let offset = y as usize / block_height * pitch + params.format.size(x as _, 1) as usize;
+        let data = &self.texture(src).data;
+        let bytes: Vec<u8> = (0..(height as usize).div_ceil(block_height))
+            .flat_map(|row| &data[offset + row * pitch..offset + row * pitch + row_size])
+            .copied()
+            .collect();
+        self.texture_update_part(dst, dst_pos.0, dst_pos.1, width, height, &bytes);
+
+        self.record(Command::CopyTextureRegion {
+            src,
+            src_rect,
+            dst,
+            dst_pos,
+        });
+    }
+
+    fn new_render_pass_layer(
+        &mut self,
+        color_img: &[TextureId],
+        depth_img: Option<TextureId>,
+        layer: u32,
+    ) -> RenderPass {
+        if color_img.is_empty() && depth_img.is_none() {
+            panic!("Render pass should have at least one non-none target");
+        }
+        let pass = RecordedPass {
+            color_textures: color_img.to_vec(),
+            depth_texture: depth_img,
+            layer,
+        };
+        RenderPass(self.passes.add(pass))
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        &self.passes[render_pass.0].color_textures
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        let render_pass = self.passes.remove(render_pass.0);
+        // a texture attached to several passes goes with the first of them deleted
+        let attachments = render_pass
+            .color_textures
+            .iter()
+            .chain(&render_pass.depth_texture);
+        for texture in attachments {
+            if let TextureIdInner::Managed(texture) = texture.0 {
+                if self.textures.get(texture).is_some() {
+                    self.textures.remove(texture);
+                }
+            }
+        }
+    }
+
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode) {
+        self.record(Command::BlitRenderPass { src, dst, filter });
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        let pipeline = RecordedPipeline {
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
+            shader,
+            params,
+        };
+        Pipeline(self.pipelines.add(pipeline))
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.record(Command::ApplyPipeline(*pipeline));
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.pipelines.remove(pipeline.0);
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let (data, element_size) = match data {
+            BufferSource::Slice(data) => {
+                let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+                (bytes.to_vec(), data.element_size)
+            }
+            BufferSource::Empty { size, element_size } => (vec![0; size], element_size),
+        };
+        let buffer = RecordedBuffer {
+            buffer_type: type_,
+            usage,
+            element_size,
+            data,
+        };
+        BufferId(self.buffers.add(buffer))
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        let data = match data {
+            BufferSource::Slice(data) => data,
+            _ => panic!("buffer_update expects BufferSource::slice"),
+        };
+        let buffer = &mut self.buffers[buffer.0];
+        assert!(offset + data.size <= buffer.data.len());
+
+        let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+        buffer.data[offset..offset + data.size].copy_from_slice(bytes);
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.buffers[buffer.0].data.len()
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        self.buffers.remove(buffer.0);
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => {
+                self.textures.remove(texture);
+            }
+            TextureIdInner::Raw(_) => panic!("Raw texture in RecordingContext!"),
+        }
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        self.shaders.remove(program.0);
+    }
+
+    fn replace_shader(
+        &mut self,
+        program: ShaderId,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        let (vertex, fragment) = match shader {
+            ShaderSource::Glsl { vertex, fragment } => (vertex.to_string(), fragment.to_string()),
+            ShaderSource::Msl { program } => (program.to_string(), program.to_string()),
+        };
+        self.shaders[program.0] = RecordedShader {
+            meta,
+            vertex,
+            fragment,
+        };
+        Ok(())
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record(Command::ApplyViewport { x, y, w, h });
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record(Command::ApplyScissorRect { x, y, w, h });
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
+        index_buffer: BufferId,
+        index_buffer_offset: usize,
+        textures: &[TextureId],
+    ) {
+        self.record(Command::ApplyBindings {
+            vertex_buffers: vertex_buffers.to_vec(),
+            vertex_buffer_offsets: vertex_buffer_offsets.to_vec(),
+            index_buffer,
+            index_buffer_offset,
+            images: textures.to_vec(),
+        });
+    }
+
+    // the signature comes from the trait, `apply_uniforms` is the safe way in
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        self.record(Command::ApplyUniforms(bytes.to_vec()));
+    }
+
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize) {
+        self.record(Command::ApplyUniformBlock {
+            block,
+            buffer,
+            offset,
+        });
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.record(Command::Clear {
+            color,
+            depth,
+            stencil,
+        });
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.begin_pass(None, action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.record(Command::BeginPass { pass, action });
+    }
+
+    fn end_render_pass(&mut self) {
+        self.record(Command::EndPass);
+    }
+
+    /// There is no default framebuffer, all the pixels are zero.
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8> {
+        vec![0; rect.2 as usize * rect.3 as usize * 4]
+    }
+
+    fn commit_frame(&mut self) {
+        self.record(Command::CommitFrame);
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.record(Command::Draw {
+            base_element,
+            num_elements,
+            num_instances,
+        });
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        self.record(Command::DrawArrays {
+            first_vertex,
+            num_vertices,
+            num_instances,
+        });
+    }
+
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    ) {
+        self.record(Command::DrawBaseVertex {
+            base_element,
+            num_elements,
+            base_vertex,
+            num_instances,
+        });
+    }
+
+    fn draw_indirect(&self, buffer: BufferId, offset: usize) {
+        self.record(Command::DrawIndirect { buffer, offset });
+    }
+
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32) {
+        self.record(Command::MultiDrawIndirect {
+            buffer,
+            offset,
+            draw_count,
+            stride,
+        });
+    }
+
+    fn new_query(&mut self, query_type: QueryType) -> QueryId {
+        QueryId(self.queries.add(query_type))
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        self.record(Command::BeginQuery(query));
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        self.record(Command::EndQuery(query));
+    }
+
+    fn query_available(&mut self, _query: QueryId) -> bool {
+        true
+    }
+
+    fn query_result(&mut self, _query: QueryId) -> u64 {
+        0
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        self.queries.remove(query.0);
+    }
+}
+
+#[test]
+fn test_recording() {
+    let mut ctx = RecordingContext::new();
+
+    let indices: [u16; 3] = [0, 1, 2];
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&indices),
+    );
+    assert_eq!(ctx.buffer(index_buffer).data, [0, 0, 1, 0, 2, 0]);
+
+    let texture = ctx.new_render_texture(TextureParams {
+        width: 2,
+        height: 2,
+        ..Default::default()
+    });
+    ctx.texture_update_part(texture, 1, 1, 1, 1, &[1, 2, 3, 4]);
+    let mut pixels = [0; 16];
+    ctx.texture_read_pixels(texture, &mut pixels);
+    assert_eq!(&pixels[12..], &[1, 2, 3, 4]);
+    ctx.texture_set_wrap(texture, TextureWrap::Repeat, TextureWrap::Mirror);
+    assert_eq!(ctx.texture(texture).params.wrap, TextureWrap::Repeat);
+    assert_eq!(ctx.texture(texture).wrap_y, TextureWrap::Mirror);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_uniforms(UniformsSource::table(&1.0f32));
+    ctx.draw(0, 3, 1);
+    ctx.end_render_pass();
+
+    assert_eq!(
+        *ctx.commands(),
+        [
+            Command::BeginPass {
+                pass: None,
+                action: PassAction::Nothing
+            },
+            Command::ApplyUniforms(1.0f32.to_ne_bytes().to_vec()),
+            Command::Draw {
+                base_element: 0,
+                num_elements: 3,
+                num_instances: 1
+            },
+            Command::EndPass,
+        ]
+    );
+}
+
+#[test]
+fn test_recording_readback() {
+    let mut ctx = RecordingContext::new();
+    let texture = ctx.new_render_texture(TextureParams {
+        kind: TextureKind::Texture2DArray,
+        width: 1,
+        height: 1,
+        depth: 2,
+        ..Default::default()
+    });
+    ctx.texture_update_layer_part(texture, 0, 0, 0, 1, 1, &[1, 2, 3, 4]);
+    ctx.texture_update_layer_part(texture, 1, 0, 0, 1, 1, &[5, 6, 7, 8]);
+    let first = ctx.begin_read_pixels(texture);
+    ctx.texture_update_layer_part(texture, 1, 0, 0, 1, 1, &[9, 9, 9, 9]);
+    let second = ctx.begin_read_pixels(texture);
+
+    // the pixels as they were at begin_read_pixels, layers one after another
+    let mut pixels = [0; 8];
+    assert!(ctx.try_finish_read_pixels(second, &mut pixels));
+    assert_eq!(pixels, [1, 2, 3, 4, 9, 9, 9, 9]);
+    assert!(ctx.try_finish_read_pixels(first, &mut pixels));
+    assert_eq!(pixels, [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(ctx.readbacks.get(first.0).is_none());
+}
+
+#[test]
+fn test_recording_copy_texture_region() {
+    let mut ctx = RecordingContext::new();
+    let params = TextureParams {
+        width: 2,
+        height: 2,
+        ..Default::default()
+    };
+    #[rustfmt::skip]
+    let pixels = [
+        1, 1, 1, 1,  2, 2, 2, 2,
+        3, 3, 3, 3,  4, 4, 4, 4,
+    ];
+    let src = ctx.new_texture_from_data_and_format(&pixels, params);
+    let dst = ctx.new_render_texture(params);
+    ctx.copy_texture_region(src, (1, 0, 1, 2), dst, (0, 0));
+
+    let mut copied = [0; 16];
+    ctx.texture_read_pixels(dst, &mut copied);
+    #[rustfmt::skip]
+    assert_eq!(copied, [
+        2, 2, 2, 2,  0, 0, 0, 0,
+        4, 4, 4, 4,  0, 0, 0, 0,
+    ]);
+    assert_eq!(
+        ctx.commands().last(),
+        Some(&Command::CopyTextureRegion {
+            src,
+            src_rect: (1, 0, 1, 2),
+            dst,
+            dst_pos: (0, 0),
+        })
+    );
+
+    // a short buffer gets the first pixels, the same as with texture_read_pixels
+    let mut first_row = [0; 8];
+    let readback = ctx.begin_read_pixels(dst);
+    assert!(ctx.try_finish_read_pixels(readback, &mut first_row));
+    assert_eq!(first_row, copied[..8]);
+}
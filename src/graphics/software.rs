@@ -0,0 +1,3016 @@
+//! CPU-only `RenderingBackend`.
+//!
+//! Triangles, lines and points are rasterized in plain Rust, no GPU or system GL
+//! library is required. Shaders are Rust closures, see [`SoftwareShader`].
+//!
+//! Rendering follows OpenGL conventions: framebuffer row 0 is the bottom row, window
+//! space depth is in [0, 1], texture coordinate (0, 0) is the first uploaded texel.
+
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::rc::Rc;
+
+use crate::ResourceManager;
+
+use super::*;
+
+/// Per-vertex shader input.
+pub struct VertexInput<'a> {
+    /// Vertex attributes, in the order of `attributes` given to `new_pipeline`.
+    /// Each attribute is extended to 4 components with (0, 0, 0, 1).
+    /// `VertexFormat::Mat4` occupies 4 consecutive entries, one per column.
+    pub attributes: &'a [[f32; 4]],
+    pub vertex_id: i32,
+    pub instance_id: i32,
+    /// Raw bytes of the last `apply_uniforms` call.
+    pub uniforms: &'a [u8],
+    /// Buffer ranges bound with `apply_uniform_block`, empty for unbound blocks.
+    pub uniform_blocks: &'a [&'a [u8]],
+}
+
+impl<'a> VertexInput<'a> {
+    /// Read the uniforms as the same `#[repr(C)]` struct given to `apply_uniforms`.
+    pub fn uniforms<T: Copy>(&self) -> T {
+        read_uniforms(self.uniforms)
+    }
+
+    /// Read a uniform block as a `#[repr(C)]` struct laid out by std140 rules.
+    pub fn uniform_block<T: Copy>(&self, block: usize) -> T {
+        read_uniforms(self.uniform_blocks[block])
+    }
+}
+
+/// Per-vertex shader output.
+pub struct VertexOutput {
+    /// Clip-space position, the same thing as `gl_Position`.
+    pub position: [f32; 4],
+    /// Values interpolated across the primitive and handed to the fragment shader.
+    pub varyings: Vec<f32>,
+}
+
+/// Per-fragment shader input.
+pub struct FragmentInput<'a> {
+    /// Perspective-correct interpolated varyings from `VertexOutput`.
+    pub varyings: &'a [f32],
+    /// Window-space pixel center, depth and 1/w, the same thing as `gl_FragCoord`.
+    pub frag_coord: [f32; 4],
+    pub front_facing: bool,
+    /// Raw bytes of the last `apply_uniforms` call.
+    pub uniforms: &'a [u8],
+    /// Buffer ranges bound with `apply_uniform_block`, empty for unbound blocks.
+    pub uniform_blocks: &'a [&'a [u8]],
+    images: &'a [SampledImage<'a>],
+}
+
+impl<'a> FragmentInput<'a> {
+    /// Read the uniforms as the same `#[repr(C)]` struct given to `apply_uniforms`.
+    pub fn uniforms<T: Copy>(&self) -> T {
+        read_uniforms(self.uniforms)
+    }
+
+    /// Read a uniform block as a `#[repr(C)]` struct laid out by std140 rules.
+    pub fn uniform_block<T: Copy>(&self, block: usize) -> T {
+        read_uniforms(self.uniform_blocks[block])
+    }
+
+    /// Sample an image from `Bindings::images`, honoring its filter and wrap modes.
+    /// Only mipmap level 0 is sampled.
+    pub fn sample(&self, image: usize, uv: [f32; 2]) -> [f32; 4] {
+        self.sample_layer(image, uv, 0)
+    }
+
+    /// Same as `sample`, but reads the given cubemap face, array layer or 3D slice.
+    /// There is no filtering between layers.
+    pub fn sample_layer(&self, image: usize, uv: [f32; 2], layer: u32) -> [f32; 4] {
+        let image = &self.images[image];
+        let params = &image.params;
+        let (w, h) = (params.width as i32, params.height as i32);
+        let layer_offset = (layer.min(params.layers() - 1) * params.width * params.height) as i32;
+        let texel = |x: i32, y: i32| {
+            let x = wrap_coord(x, w, params.wrap);
+            let y = wrap_coord(y, h, image.wrap_y);
+            read_color(
+                params.format,
+                &image.data,
+                (layer_offset + y * w + x) as usize,
+            )
+        };
+        // GL picks the magnification filter when the texture is not minified
+        filter_texels(texel, uv[0] * w as f32, uv[1] * h as f32, params.mag_filter)
+    }
+}
+
+/// Filtered color at `(x, y)`, in texels.
+fn filter_texels(
+    texel: impl Fn(i32, i32) -> [f32; 4],
+    x: f32,
+    y: f32,
+    filter: FilterMode,
+) -> [f32; 4] {
+    match filter {
+        FilterMode::Nearest => texel(x.floor() as i32, y.floor() as i32),
+        FilterMode::Linear => {
+            let (x, y) = (x - 0.5, y - 0.5);
+            let (x0, y0) = (x.floor(), y.floor());
+            let (fx, fy) = (x - x0, y - y0);
+            let (x0, y0) = (x0 as i32, y0 as i32);
+            let c00 = texel(x0, y0);
+            let c10 = texel(x0 + 1, y0);
+            let c01 = texel(x0, y0 + 1);
+            let c11 = texel(x0 + 1, y0 + 1);
+            let mut res = [0.0; 4];
+            for i in 0..4 {
+                let top = c00[i] + (c10[i] - c00[i]) * fx;
+                let bottom = c01[i] + (c11[i] - c01[i]) * fx;
+                res[i] = top + (bottom - top) * fy;
+            }
+            res
+        }
+    }
+}
+
+struct SampledImage<'a> {
+    params: TextureParams,
+    wrap_y: TextureWrap,
+    data: Ref<'a, Vec<u8>>,
+}
+
+// Everything bound for a draw call besides vertex data
+struct DrawResources<'a> {
+    images: &'a [SampledImage<'a>],
+    uniform_blocks: &'a [&'a [u8]],
+}
+
+fn read_uniforms<T: Copy>(uniforms: &[u8]) -> T {
+    assert!(
+        std::mem::size_of::<T>() <= uniforms.len(),
+        "Uniforms struct does not match applied uniforms"
+    );
+    unsafe { std::ptr::read_unaligned(uniforms.as_ptr() as *const T) }
+}
+
+fn borrow_slice(data: &RefCell<Vec<u8>>) -> RefMut<'_, [u8]> {
+    RefMut::map(data.borrow_mut(), |data| &mut data[..])
+}
+
+fn wrap_coord(x: i32, size: i32, wrap: TextureWrap) -> i32 {
+    match wrap {
+        TextureWrap::Clamp => x.clamp(0, size - 1),
+        TextureWrap::Repeat => x.rem_euclid(size),
+        TextureWrap::Mirror => {
+            let x = x.rem_euclid(size * 2);
+            if x < size {
+                x
+            } else {
+                size * 2 - 1 - x
+            }
+        }
+    }
+}
+
+type VertexFn = dyn Fn(&VertexInput) -> VertexOutput;
+type FragmentFn = dyn Fn(&FragmentInput) -> Option<[f32; 4]>;
+
+/// Shader program for `SoftwareContext`.
+///
+/// The fragment function returns `None` to discard the fragment.
+/// Only the first color attachment of a render pass is written.
+/// ```
+/// # use miniquad::graphics::software::*;
+/// let shader = SoftwareShader::new(
+///     |input| VertexOutput {
+///         position: input.attributes[0],
+///         varyings: vec![],
+///     },
+///     |_| Some([1.0, 0.0, 0.0, 1.0]),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct SoftwareShader {
+    vertex: Rc<VertexFn>,
+    fragment: Rc<FragmentFn>,
+}
+
+impl SoftwareShader {
+    pub fn new<V, F>(vertex: V, fragment: F) -> SoftwareShader
+    where
+        V: Fn(&VertexInput) -> VertexOutput + 'static,
+        F: Fn(&FragmentInput) -> Option<[f32; 4]> + 'static,
+    {
+        SoftwareShader {
+            vertex: Rc::new(vertex),
+            fragment: Rc::new(fragment),
+        }
+    }
+}
+
+struct Texture {
+    // `params.wrap` is the horizontal wrap mode
+    params: TextureParams,
+    wrap_y: TextureWrap,
+    data: RefCell<Vec<u8>>,
+}
+
+struct Buffer {
+    buffer_type: BufferType,
+    element_size: usize,
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AttributeInternal {
+    buffer_index: usize,
+    format: VertexFormat,
+    offset: usize,
+    stride: usize,
+    divisor: i32,
+}
+
+struct ShaderInternal {
+    shader: SoftwareShader,
+    // std140 size of each of ShaderMeta::uniform_blocks
+    uniform_block_sizes: Vec<usize>,
+}
+
+struct PipelineInternal {
+    attributes: Vec<AttributeInternal>,
+    shader: ShaderId,
+    params: PipelineParams,
+}
+
+struct Query {
+    query_type: QueryType,
+    started: Option<std::time::Instant>,
+    result: u64,
+}
+
+struct RenderPassInternal {
+    color_textures: Vec<TextureId>,
+    depth_texture: Option<TextureId>,
+    layer: u32,
+    // rendered into instead of the textures when `sample_count` > 1
+    samples: Option<Samples>,
+}
+
+/// Multisampled color and depth of a pass, `count` samples per pixel one after another.
+/// Resolved into the textures at `end_render_pass`, like the renderbuffers of the GL backend.
+struct Samples {
+    count: usize,
+    color: Option<(TextureFormat, RefCell<Vec<u8>>)>,
+    depth: Option<(TextureFormat, RefCell<Vec<u8>>)>,
+}
+
+const MAX_SAMPLE_COUNT: i32 = 4;
+
+/// Sample positions within a pixel, the standard Vulkan and D3D patterns.
+fn sample_positions(count: usize) -> &'static [(f32, f32)] {
+    match count {
+        1 => &[(0.5, 0.5)],
+        2 => &[(0.75, 0.75), (0.25, 0.25)],
+        _ => &[
+            (0.375, 0.125),
+            (0.875, 0.375),
+            (0.125, 0.625),
+            (0.625, 0.875),
+        ],
+    }
+}
+
+struct DefaultFramebuffer {
+    width: u32,
+    height: u32,
+    // RGBA8
+    color: RefCell<Vec<u8>>,
+    // Depth32
+    depth: RefCell<Vec<u8>>,
+    stencil: RefCell<Vec<u8>>,
+}
+
+impl DefaultFramebuffer {
+    fn new(width: u32, height: u32) -> DefaultFramebuffer {
+        let pixels = (width * height) as usize;
+        DefaultFramebuffer {
+            width,
+            height,
+            color: RefCell::new(vec![0; pixels * 4]),
+            depth: RefCell::new(vec![0; pixels * 4]),
+            stencil: RefCell::new(vec![0; pixels]),
+        }
+    }
+}
+
+// Borrowed render target for the duration of a single draw or clear
+struct Target<'a> {
+    width: i32,
+    height: i32,
+    // samples per pixel, pixel `i` is at `i * samples` in the buffers
+    samples: usize,
+    color: Option<(TextureFormat, RefMut<'a, [u8]>)>,
+    depth: Option<(TextureFormat, RefMut<'a, [u8]>)>,
+    stencil: Option<RefMut<'a, [u8]>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+// Post-vertex-shader vertex
+#[derive(Clone)]
+struct ClipVertex {
+    position: [f32; 4],
+    varyings: Vec<f32>,
+}
+
+// Vertex after perspective division and viewport transform
+struct WindowVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    varyings: Vec<f32>,
+}
+
+/// `RenderingBackend` rasterizing on the CPU.
+///
+/// Shaders are [`SoftwareShader`] closures. They may be created with
+/// [`SoftwareContext::new_software_shader`], or registered for a GLSL source with
+/// [`SoftwareContext::register_shader`]; then unmodified `new_shader` calls with the
+/// same vertex source get the closures instead.
+///
+/// `info()` reports `Backend::OpenGl`, so code selecting shader sources by backend
+/// would pass GLSL sources in.
+pub struct SoftwareContext {
+    shaders: ResourceManager<ShaderInternal>,
+    registered_shaders: HashMap<String, SoftwareShader>,
+    pipelines: ResourceManager<PipelineInternal>,
+    passes: ResourceManager<RenderPassInternal>,
+    buffers: ResourceManager<Buffer>,
+    queries: ResourceManager<Query>,
+    // pixels of the textures at begin_read_pixels
+    readbacks: ResourceManager<Vec<u8>>,
+    textures: ResourceManager<Texture>,
+    default_framebuffer: DefaultFramebuffer,
+    // Samples passed depth and stencil tests since the last occlusion begin_query
+    samples_passed: Cell<u64>,
+
+    cur_pass: Option<Option<RenderPass>>,
+    cur_pipeline: Option<Pipeline>,
+    vertex_buffers: Vec<BufferId>,
+    vertex_buffer_offsets: Vec<usize>,
+    index_buffer: Option<BufferId>,
+    index_buffer_offset: usize,
+    images: Vec<TextureId>,
+    uniforms: Vec<u8>,
+    // buffer, offset and size of the bound ranges
+    uniform_blocks: Vec<Option<(BufferId, usize, usize)>>,
+    viewport: Rect,
+    scissor: Rect,
+    color_write: ColorMask,
+}
+
+impl SoftwareContext {
+    /// Create a context with a `width` x `height` RGBA8 default framebuffer,
+    /// with depth and stencil buffers.
+    pub fn new(width: u32, height: u32) -> SoftwareContext {
+        let full = Rect {
+            x: 0,
+            y: 0,
+            w: width as _,
+            h: height as _,
+        };
+        SoftwareContext {
+            shaders: ResourceManager::default(),
+            registered_shaders: HashMap::new(),
+            pipelines: ResourceManager::default(),
+            passes: ResourceManager::default(),
+            buffers: ResourceManager::default(),
+            queries: ResourceManager::default(),
+            readbacks: ResourceManager::default(),
+            textures: ResourceManager::default(),
+            default_framebuffer: DefaultFramebuffer::new(width, height),
+            samples_passed: Cell::new(0),
+            cur_pass: None,
+            cur_pipeline: None,
+            vertex_buffers: vec![],
+            vertex_buffer_offsets: vec![],
+            index_buffer: None,
+            index_buffer_offset: 0,
+            images: vec![],
+            uniforms: vec![],
+            uniform_blocks: vec![],
+            viewport: full,
+            scissor: full,
+            color_write: (true, true, true, true),
+        }
+    }
+
+    /// Make `new_shader` calls with this exact vertex source use `shader`.
+    pub fn register_shader(&mut self, vertex_source: &str, shader: SoftwareShader) {
+        self.registered_shaders
+            .insert(vertex_source.to_string(), shader);
+    }
+
+    pub fn new_software_shader(&mut self, shader: SoftwareShader, meta: ShaderMeta) -> ShaderId {
+        let uniform_block_sizes = meta
+            .uniform_blocks
+            .iter()
+            .map(|block| block.layout.std140_size())
+            .collect();
+        ShaderId(self.shaders.add(ShaderInternal {
+            shader,
+            uniform_block_sizes,
+        }))
+    }
+
+    /// Size of the default framebuffer.
+    pub fn screen_size(&self) -> (u32, u32) {
+        (
+            self.default_framebuffer.width,
+            self.default_framebuffer.height,
+        )
+    }
+
+    /// Reallocate the default framebuffer, dropping its content.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.default_framebuffer = DefaultFramebuffer::new(width, height);
+    }
+
+    /// RGBA8 content of the default framebuffer, bottom row first.
+    pub fn default_framebuffer_pixels(&self) -> Vec<u8> {
+        self.default_framebuffer.color.borrow().clone()
+    }
+
+    fn texture(&self, texture: TextureId) -> &Texture {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => &self.textures[texture],
+            TextureIdInner::Raw(_) => panic!("Raw texture in SoftwareContext!"),
+        }
+    }
+
+    fn texture_mut(&mut self, texture: TextureId) -> &mut Texture {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => &mut self.textures[texture],
+            TextureIdInner::Raw(_) => panic!("Raw texture in SoftwareContext!"),
+        }
+    }
+
+    fn pass_size(&self, pass: Option<RenderPass>) -> (i32, i32) {
+        match pass {
+            None => (
+                self.default_framebuffer.width as _,
+                self.default_framebuffer.height as _,
+            ),
+            Some(pass) => {
+                let pass = &self.passes[pass.0];
+                // new_render_pass will panic with both color and depth components none
+                // so unwrap is safe here
+                let texture = pass
+                    .color_textures
+                    .first()
+                    .copied()
+                    .or(pass.depth_texture)
+                    .unwrap();
+                let params = self.texture(texture).params;
+                (params.width as _, params.height as _)
+            }
+        }
+    }
+
+    fn target(&self) -> Target<'_> {
+        let pass = self
+            .cur_pass
+            .expect("Rendering outside of begin_pass/end_render_pass");
+        if let Some(samples) = pass.and_then(|pass| self.passes[pass.0].samples.as_ref()) {
+            let (width, height) = self.pass_size(pass);
+            return Target {
+                width,
+                height,
+                samples: samples.count,
+                color: samples
+                    .color
+                    .as_ref()
+                    .map(|(f, data)| (*f, borrow_slice(data))),
+                depth: samples
+                    .depth
+                    .as_ref()
+                    .map(|(f, data)| (*f, borrow_slice(data))),
+                stencil: None,
+            };
+        }
+        // the textures themselves, also what blits and copies see of a multisampled pass
+        self.pass_target(pass)
+    }
+
+    /// Average the color samples of a multisampled pass into its texture, depth takes
+    /// the first sample, as a GL_NEAREST blit does.
+    fn resolve(&self, pass: RenderPass) {
+        let samples = match &self.passes[pass.0].samples {
+            Some(samples) => samples,
+            None => return,
+        };
+        let mut target = self.pass_target(Some(pass));
+        let pixels = (target.width * target.height) as usize;
+        let count = samples.count;
+        if let (Some((format, data)), Some((_, color))) = (&mut target.color, &samples.color) {
+            let color = color.borrow();
+            for pixel in 0..pixels {
+                let mut sum = [0.0; 4];
+                for sample in 0..count {
+                    let c = read_color(*format, &color, pixel * count + sample);
+                    for i in 0..4 {
+                        sum[i] += c[i] / count as f32;
+                    }
+                }
+                write_color(*format, data, pixel, sum, (true, true, true, true));
+            }
+        }
+        if let (Some((format, data)), Some((_, depth))) = (&mut target.depth, &samples.depth) {
+            let depth = depth.borrow();
+            for pixel in 0..pixels {
+                write_depth(
+                    *format,
+                    data,
+                    pixel,
+                    read_depth(*format, &depth, pixel * count),
+                );
+            }
+        }
+    }
+
+    fn pass_target(&self, pass: Option<RenderPass>) -> Target<'_> {
+        let (width, height) = self.pass_size(pass);
+        match pass {
+            None => {
+                let fb = &self.default_framebuffer;
+                Target {
+                    width,
+                    height,
+                    samples: 1,
+                    color: Some((TextureFormat::RGBA8, borrow_slice(&fb.color))),
+                    depth: Some((TextureFormat::Depth32, borrow_slice(&fb.depth))),
+                    stencil: Some(borrow_slice(&fb.stencil)),
+                }
+            }
+            Some(pass) => {
+                let pass = &self.passes[pass.0];
+                let borrow = |texture: TextureId| {
+                    let texture = self.texture(texture);
+                    let data = texture
+                        .data
+                        .try_borrow_mut()
+                        .expect("Texture is both sampled and rendered to");
+                    // Texture2D attachments ignore the layer, as in GL
+                    let layer = if texture.params.layers() == 1 {
+                        0
+                    } else {
+                        pass.layer as usize
+                    };
+                    let layer_size = texture.params.format.size(width as _, height as _) as usize;
+                    let data = RefMut::map(data, |data| {
+                        &mut data[layer * layer_size..(layer + 1) * layer_size]
+                    });
+                    (texture.params.format, data)
+                };
+                Target {
+                    width,
+                    height,
+                    samples: 1,
+                    color: pass.color_textures.first().map(|t| borrow(*t)),
+                    depth: pass.depth_texture.map(borrow),
+                    stencil: None,
+                }
+            }
+        }
+    }
+
+    fn fetch_vertex(
+        &self,
+        pipeline: &PipelineInternal,
+        vertex_id: i32,
+        instance_id: i32,
+        base_instance: i32,
+    ) -> Vec<[f32; 4]> {
+        pipeline
+            .attributes
+            .iter()
+            .map(|attr| {
+                let buffer = self
+                    .vertex_buffers
+                    .get(attr.buffer_index)
+                    .unwrap_or_else(|| panic!("Attribute index outside of vertex_buffers length"));
+                let buffer_offset = self
+                    .vertex_buffer_offsets
+                    .get(attr.buffer_index)
+                    .copied()
+                    .unwrap_or(0);
+                let buffer = &self.buffers[buffer.0];
+                let element = if attr.divisor == 0 {
+                    vertex_id
+                } else {
+                    base_instance + instance_id / attr.divisor
+                };
+                let offset = buffer_offset + attr.offset + element as usize * attr.stride;
+                read_attribute(attr.format, &buffer.data[offset..])
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_instance(
+        &self,
+        pipeline: &PipelineInternal,
+        shader: &SoftwareShader,
+        resources: &DrawResources,
+        target: &mut Target,
+        indices: &[i32],
+        instance_id: i32,
+        base_instance: i32,
+    ) {
+        let vertices: Vec<ClipVertex> = indices
+            .iter()
+            .map(|&vertex_id| {
+                let attributes = self.fetch_vertex(pipeline, vertex_id, instance_id, base_instance);
+                let output = (shader.vertex)(&VertexInput {
+                    attributes: &attributes,
+                    vertex_id,
+                    instance_id,
+                    uniforms: &self.uniforms,
+                    uniform_blocks: resources.uniform_blocks,
+                });
+                ClipVertex {
+                    position: output.position,
+                    varyings: output.varyings,
+                }
+            })
+            .collect();
+
+        let mut raster = Rasterizer {
+            params: &pipeline.params,
+            shader,
+            images: resources.images,
+            uniforms: &self.uniforms,
+            uniform_blocks: resources.uniform_blocks,
+            viewport: self.viewport,
+            clip: self.clip_rect(target),
+            color_write: self.color_write,
+            target,
+            samples_passed: &self.samples_passed,
+        };
+
+        match pipeline.params.primitive_type {
+            PrimitiveType::Triangles => {
+                for triangle in vertices.chunks_exact(3) {
+                    raster.triangle(triangle);
+                }
+            }
+            PrimitiveType::Lines => {
+                for line in vertices.chunks_exact(2) {
+                    raster.line(&line[0], &line[1]);
+                }
+            }
+            PrimitiveType::Points => {
+                for point in &vertices {
+                    raster.point(point);
+                }
+            }
+        }
+    }
+
+    /// Vertex ids from the bound index buffer, starting `offset` bytes into it.
+    fn indices(
+        &self,
+        offset: usize,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+    ) -> Vec<i32> {
+        let index_buffer = &self.buffers[self.index_buffer.expect("Unset index buffer type").0];
+        index_buffer.data[offset..]
+            .chunks_exact(index_buffer.element_size)
+            .skip(base_element as usize)
+            .take(num_elements as usize)
+            .map(|index| match index {
+                [a] => *a as i32,
+                [a, b] => u16::from_ne_bytes([*a, *b]) as i32,
+                [a, b, c, d] => u32::from_ne_bytes([*a, *b, *c, *d]) as i32,
+                _ => unreachable!(),
+            })
+            .map(|index| index + base_vertex)
+            .collect()
+    }
+
+    fn draw_vertices(&self, vertex_ids: &[i32], num_instances: i32, base_instance: i32) {
+        let pipeline = self
+            .cur_pipeline
+            .expect("Drawing without any binded pipeline");
+        let pipeline = &self.pipelines[pipeline.0];
+        let shader = &self.shaders[pipeline.shader.0].shader;
+
+        let images: Vec<SampledImage> = self
+            .images
+            .iter()
+            .map(|image| {
+                let texture = self.texture(*image);
+                SampledImage {
+                    params: texture.params,
+                    wrap_y: texture.wrap_y,
+                    data: texture.data.borrow(),
+                }
+            })
+            .collect();
+        let uniform_blocks: Vec<&[u8]> = self
+            .uniform_blocks
+            .iter()
+            .map(|block| match block {
+                Some((buffer, offset, size)) => {
+                    &self.buffers[buffer.0].data[*offset..*offset + *size]
+                }
+                None => &[],
+            })
+            .collect();
+        let resources = DrawResources {
+            images: &images,
+            uniform_blocks: &uniform_blocks,
+        };
+        let mut target = self.target();
+
+        for instance_id in 0..num_instances {
+            self.draw_instance(
+                pipeline,
+                shader,
+                &resources,
+                &mut target,
+                vertex_ids,
+                instance_id,
+                base_instance,
+            );
+        }
+    }
+
+    fn clip_rect(&self, target: &Target) -> Rect {
+        let x0 = self.scissor.x.max(0);
+        let y0 = self.scissor.y.max(0);
+        let x1 = (self.scissor.x + self.scissor.w).min(target.width);
+        let y1 = (self.scissor.y + self.scissor.h).min(target.height);
+        Rect {
+            x: x0,
+            y: y0,
+            w: (x1 - x0).max(0),
+            h: (y1 - y0).max(0),
+        }
+    }
+}
+
+fn read_attribute(format: VertexFormat, bytes: &[u8]) -> [f32; 4] {
+    let mut res = [0.0, 0.0, 0.0, 1.0];
+    let components = format.components() as usize;
+    for (i, component) in res.iter_mut().enumerate().take(components) {
+        *component = match format {
+            VertexFormat::Float1
+            | VertexFormat::Float2
+            | VertexFormat::Float3
+            | VertexFormat::Float4
+            | VertexFormat::Mat4 => f32::from_ne_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()),
+            VertexFormat::Byte1
+            | VertexFormat::Byte2
+            | VertexFormat::Byte3
+            | VertexFormat::Byte4 => bytes[i] as f32,
+            VertexFormat::Short1
+            | VertexFormat::Short2
+            | VertexFormat::Short3
+            | VertexFormat::Short4 => {
+                u16::from_ne_bytes(bytes[i * 2..i * 2 + 2].try_into().unwrap()) as f32
+            }
+            VertexFormat::Int1 | VertexFormat::Int2 | VertexFormat::Int3 | VertexFormat::Int4 => {
+                u32::from_ne_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as f32
+            }
+        };
+    }
+    res
+}
+
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if (bits & 0x7fff_ffff) > 0x7f80_0000 {
+        // NaN
+        sign | 0x7e00
+    } else if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            sign
+        } else {
+            let mantissa = (mantissa | 0x80_0000) >> (1 - exponent);
+            // rounding up may carry into the exponent, making the smallest normal number
+            sign | ((mantissa + 0x1000) >> 13) as u16
+        }
+    } else {
+        // the rounding carry out of the mantissa bumps the exponent,
+        // past the largest exponent that is infinity
+        let half = ((exponent as u32) << 10) + ((mantissa + 0x1000) >> 13);
+        sign | half.min(0x7c00) as u16
+    }
+}
+
+fn f16_to_f32(value: u16) -> f32 {
+    let sign = if value & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((value >> 10) & 0x1f) as i32;
+    let mantissa = (value & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Amount of channels, all of the same size, of an uncompressed color format.
+fn channels(format: TextureFormat) -> usize {
+    match format {
+        TextureFormat::Alpha | TextureFormat::R8 | TextureFormat::R16F => 1,
+        TextureFormat::R32F | TextureFormat::R32UI => 1,
+        TextureFormat::RG8 | TextureFormat::RG16F => 2,
+        TextureFormat::RGB8 => 3,
+        _ => 4,
+    }
+}
+
+fn read_color(format: TextureFormat, data: &[u8], index: usize) -> [f32; 4] {
+    if format.is_depth() {
+        let depth = read_depth(format, data, index);
+        return [depth, depth, depth, 1.0];
+    }
+    let channels = channels(format);
+    let component_size = format.size(1, 1) as usize / channels;
+    let mut res = [0.0, 0.0, 0.0, 1.0];
+    for (i, c) in res.iter_mut().enumerate().take(channels) {
+        let o = (index * channels + i) * component_size;
+        let p = &data[o..o + component_size];
+        *c = match format {
+            TextureFormat::RGBA16F | TextureFormat::R16F | TextureFormat::RG16F => {
+                f16_to_f32(u16::from_ne_bytes([p[0], p[1]]))
+            }
+            TextureFormat::R32F | TextureFormat::RGBA32F => {
+                f32::from_ne_bytes(p.try_into().unwrap())
+            }
+            TextureFormat::R32UI => u32::from_ne_bytes(p.try_into().unwrap()) as f32,
+            TextureFormat::SRGBA8 if i < 3 => srgb_to_linear(p[0] as f32 / 255.0),
+            _ => p[0] as f32 / 255.0,
+        };
+    }
+    if format == TextureFormat::Alpha {
+        res = [0.0, 0.0, 0.0, res[0]];
+    }
+    res
+}
+
+fn write_color(
+    format: TextureFormat,
+    data: &mut [u8],
+    index: usize,
+    color: [f32; 4],
+    mask: ColorMask,
+) {
+    if format.is_depth() {
+        panic!("Color write into a depth texture")
+    }
+    let mut mask = [mask.0, mask.1, mask.2, mask.3];
+    let mut color = color;
+    if format == TextureFormat::Alpha {
+        color[0] = color[3];
+        mask[0] = mask[3];
+    }
+    let unorm = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let channels = channels(format);
+    let component_size = format.size(1, 1) as usize / channels;
+    for i in (0..channels).filter(|i| mask[*i]) {
+        let o = (index * channels + i) * component_size;
+        let p = &mut data[o..o + component_size];
+        match format {
+            TextureFormat::RGBA16F | TextureFormat::R16F | TextureFormat::RG16F => {
+                p.copy_from_slice(&f32_to_f16(color[i]).to_ne_bytes())
+            }
+            TextureFormat::R32F | TextureFormat::RGBA32F => {
+                p.copy_from_slice(&color[i].to_ne_bytes())
+            }
+            TextureFormat::R32UI => p.copy_from_slice(&(color[i].max(0.0) as u32).to_ne_bytes()),
+            TextureFormat::SRGBA8 if i < 3 => {
+                p[0] = unorm(linear_to_srgb(color[i].clamp(0.0, 1.0)))
+            }
+            _ => p[0] = unorm(color[i]),
+        }
+    }
+}
+
+fn read_depth(format: TextureFormat, data: &[u8], index: usize) -> f32 {
+    match format {
+        TextureFormat::Depth => {
+            u16::from_ne_bytes([data[index * 2], data[index * 2 + 1]]) as f32 / 65535.0
+        }
+        TextureFormat::Depth32 => {
+            f32::from_ne_bytes(data[index * 4..index * 4 + 4].try_into().unwrap())
+        }
+        // GL_UNSIGNED_INT_24_8, depth in the high 24 bits, stencil in the low 8
+        TextureFormat::Depth24Stencil8 => {
+            let packed = u32::from_ne_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+            (packed >> 8) as f32 / 0xff_ffff as f32
+        }
+        _ => panic!("Depth attachment is not a depth texture"),
+    }
+}
+
+fn write_depth(format: TextureFormat, data: &mut [u8], index: usize, depth: f32) {
+    let depth = depth.clamp(0.0, 1.0);
+    match format {
+        TextureFormat::Depth => {
+            let depth = (depth * 65535.0).round() as u16;
+            data[index * 2..index * 2 + 2].copy_from_slice(&depth.to_ne_bytes());
+        }
+        TextureFormat::Depth32 => {
+            data[index * 4..index * 4 + 4].copy_from_slice(&depth.to_ne_bytes());
+        }
+        TextureFormat::Depth24Stencil8 => {
+            let p = &mut data[index * 4..index * 4 + 4];
+            let stencil = u32::from_ne_bytes(p.try_into().unwrap()) & 0xff;
+            let depth = (depth * 0xff_ffff as f32).round() as u32;
+            p.copy_from_slice(&(depth << 8 | stencil).to_ne_bytes());
+        }
+        _ => panic!("Depth attachment is not a depth texture"),
+    }
+}
+
+fn compare(func: Comparison, a: f32, b: f32) -> bool {
+    match func {
+        Comparison::Never => false,
+        Comparison::Less => a < b,
+        Comparison::LessOrEqual => a <= b,
+        Comparison::Greater => a > b,
+        Comparison::GreaterOrEqual => a >= b,
+        Comparison::Equal => a == b,
+        Comparison::NotEqual => a != b,
+        Comparison::Always => true,
+    }
+}
+
+fn stencil_compare(func: CompareFunc, a: u32, b: u32) -> bool {
+    match func {
+        CompareFunc::Always => true,
+        CompareFunc::Never => false,
+        CompareFunc::Less => a < b,
+        CompareFunc::Equal => a == b,
+        CompareFunc::LessOrEqual => a <= b,
+        CompareFunc::Greater => a > b,
+        CompareFunc::NotEqual => a != b,
+        CompareFunc::GreaterOrEqual => a >= b,
+    }
+}
+
+fn stencil_op(op: StencilOp, value: u8, reference: i32) -> u8 {
+    match op {
+        StencilOp::Keep => value,
+        StencilOp::Zero => 0,
+        StencilOp::Replace => reference as u8,
+        StencilOp::IncrementClamp => value.saturating_add(1),
+        StencilOp::DecrementClamp => value.saturating_sub(1),
+        StencilOp::Invert => !value,
+        StencilOp::IncrementWrap => value.wrapping_add(1),
+        StencilOp::DecrementWrap => value.wrapping_sub(1),
+    }
+}
+
+fn blend_factor(factor: BlendFactor, src: [f32; 4], dst: [f32; 4], alpha: bool) -> [f32; 4] {
+    let value = |value: BlendValue| match value {
+        BlendValue::SourceColor => src,
+        BlendValue::SourceAlpha => [src[3]; 4],
+        BlendValue::DestinationColor => dst,
+        BlendValue::DestinationAlpha => [dst[3]; 4],
+    };
+    match factor {
+        BlendFactor::Zero => [0.0; 4],
+        BlendFactor::One => [1.0; 4],
+        BlendFactor::Value(v) => value(v),
+        BlendFactor::OneMinusValue(v) => value(v).map(|x| 1.0 - x),
+        BlendFactor::SourceAlphaSaturate if alpha => [1.0; 4],
+        BlendFactor::SourceAlphaSaturate => [src[3].min(1.0 - dst[3]); 4],
+    }
+}
+
+fn blend_channel(state: BlendState, src: [f32; 4], dst: [f32; 4], channel: usize) -> f32 {
+    let alpha = channel == 3;
+    let s = src[channel] * blend_factor(state.sfactor, src, dst, alpha)[channel];
+    let d = dst[channel] * blend_factor(state.dfactor, src, dst, alpha)[channel];
+    match state.equation {
+        Equation::Add => s + d,
+        Equation::Subtract => s - d,
+        Equation::ReverseSubtract => d - s,
+    }
+}
+
+struct Rasterizer<'a, 'b> {
+    params: &'a PipelineParams,
+    shader: &'a SoftwareShader,
+    images: &'a [SampledImage<'a>],
+    uniforms: &'a [u8],
+    uniform_blocks: &'a [&'a [u8]],
+    viewport: Rect,
+    clip: Rect,
+    color_write: ColorMask,
+    target: &'a mut Target<'b>,
+    samples_passed: &'a Cell<u64>,
+}
+
+impl<'a, 'b> Rasterizer<'a, 'b> {
+    fn to_window(&self, v: &ClipVertex) -> WindowVertex {
+        let [x, y, z, w] = v.position;
+        let inv_w = 1.0 / w;
+        let vp = self.viewport;
+        WindowVertex {
+            x: vp.x as f32 + (x * inv_w + 1.0) * 0.5 * vp.w as f32,
+            y: vp.y as f32 + (y * inv_w + 1.0) * 0.5 * vp.h as f32,
+            z: (z * inv_w) * 0.5 + 0.5,
+            inv_w,
+            varyings: v.varyings.clone(),
+        }
+    }
+
+    fn triangle(&mut self, triangle: &[ClipVertex]) {
+        // Only the near plane is clipped, everything else is handled by the
+        // scissor-clamped bounding box.
+        let polygon = clip_near(triangle);
+        if polygon.len() < 3 {
+            return;
+        }
+        let polygon: Vec<WindowVertex> = polygon.iter().map(|v| self.to_window(v)).collect();
+        for i in 1..polygon.len() - 1 {
+            self.window_triangle([&polygon[0], &polygon[i], &polygon[i + 1]]);
+        }
+    }
+
+    fn window_triangle(&mut self, mut v: [&WindowVertex; 3]) {
+        let edge = |a: &WindowVertex, b: &WindowVertex, x: f32, y: f32| {
+            (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+        };
+        let area = edge(v[0], v[1], v[2].x, v[2].y);
+        if area == 0.0 {
+            return;
+        }
+        let counter_clockwise = area > 0.0;
+        let front_facing =
+            counter_clockwise == (self.params.front_face_order == FrontFaceOrder::CounterClockwise);
+        match self.params.cull_face {
+            CullFace::Front if front_facing => return,
+            CullFace::Back if !front_facing => return,
+            _ => {}
+        }
+        if !counter_clockwise {
+            v.swap(1, 2);
+        }
+        let area = area.abs();
+
+        // top-left fill rule: a pixel center exactly on a shared edge belongs to only one triangle
+        let top_left = |a: &WindowVertex, b: &WindowVertex| b.y > a.y || (b.y == a.y && b.x < a.x);
+        let bias = [
+            top_left(v[1], v[2]),
+            top_left(v[2], v[0]),
+            top_left(v[0], v[1]),
+        ];
+
+        let min_x = v.iter().map(|v| v.x).fold(f32::MAX, f32::min).floor() as i32;
+        let max_x = v.iter().map(|v| v.x).fold(f32::MIN, f32::max).ceil() as i32;
+        let min_y = v.iter().map(|v| v.y).fold(f32::MAX, f32::min).floor() as i32;
+        let max_y = v.iter().map(|v| v.y).fold(f32::MIN, f32::max).ceil() as i32;
+        let clip = self.clip;
+        let (x0, x1) = (min_x.max(clip.x), max_x.min(clip.x + clip.w));
+        let (y0, y1) = (min_y.max(clip.y), max_y.min(clip.y + clip.h));
+
+        let barycentric = |px: f32, py: f32| {
+            let w = [
+                edge(v[1], v[2], px, py),
+                edge(v[2], v[0], px, py),
+                edge(v[0], v[1], px, py),
+            ];
+            let inside = (0..3).all(|i| w[i] > 0.0 || (w[i] == 0.0 && bias[i]));
+            (inside, [w[0] / area, w[1] / area, w[2] / area])
+        };
+        let positions = sample_positions(self.target.samples);
+        let mut covered = [(0, 0.0); MAX_SAMPLE_COUNT as usize];
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let mut count = 0;
+                for (sample, (sx, sy)) in positions.iter().enumerate() {
+                    let (inside, b) = barycentric(x as f32 + sx, y as f32 + sy);
+                    if inside {
+                        let z = b[0] * v[0].z + b[1] * v[1].z + b[2] * v[2].z;
+                        covered[count] = (sample, z);
+                        count += 1;
+                    }
+                }
+                if count == 0 {
+                    continue;
+                }
+                // shaded once, at the pixel center even if only some samples are covered
+                let (_, b) = barycentric(x as f32 + 0.5, y as f32 + 0.5);
+                let z = b[0] * v[0].z + b[1] * v[1].z + b[2] * v[2].z;
+                let inv_w = b[0] * v[0].inv_w + b[1] * v[1].inv_w + b[2] * v[2].inv_w;
+                let varyings = interpolate(&v, &b, inv_w);
+                let covered = &covered[..count];
+                self.fragment(x, y, z, inv_w, &varyings, front_facing, covered);
+            }
+        }
+    }
+
+    fn line(&mut self, a: &ClipVertex, b: &ClipVertex) {
+        let segment = clip_near(&[a.clone(), b.clone()]);
+        if segment.len() != 2 {
+            return;
+        }
+        let a = self.to_window(&segment[0]);
+        let b = self.to_window(&segment[1]);
+        let steps = (b.x - a.x).abs().max((b.y - a.y).abs()).ceil().max(1.0) as i32;
+        for step in 0..steps {
+            let t = (step as f32 + 0.5) / steps as f32;
+            let weights = [1.0 - t, t];
+            let x = a.x + (b.x - a.x) * t;
+            let y = a.y + (b.y - a.y) * t;
+            let z = a.z + (b.z - a.z) * t;
+            let inv_w = a.inv_w + (b.inv_w - a.inv_w) * t;
+            let varyings = interpolate(&[&a, &b], &weights, inv_w);
+            let (x, y) = (x.floor() as i32, y.floor() as i32);
+            if self.inside_clip(x, y) {
+                let covered: Vec<_> = (0..self.target.samples).map(|s| (s, z)).collect();
+                self.fragment(x, y, z, inv_w, &varyings, true, &covered);
+            }
+        }
+    }
+
+    fn point(&mut self, v: &ClipVertex) {
+        if v.position[3] <= 0.0 {
+            return;
+        }
+        let v = self.to_window(v);
+        let (x, y) = (v.x.floor() as i32, v.y.floor() as i32);
+        if self.inside_clip(x, y) {
+            let covered: Vec<_> = (0..self.target.samples).map(|s| (s, v.z)).collect();
+            self.fragment(x, y, v.z, v.inv_w, &v.varyings, true, &covered);
+        }
+    }
+
+    fn inside_clip(&self, x: i32, y: i32) -> bool {
+        let clip = self.clip;
+        x >= clip.x && y >= clip.y && x < clip.x + clip.w && y < clip.y + clip.h
+    }
+
+    /// Shade the pixel and write the result to the `covered` samples, given with their depth.
+    #[allow(clippy::too_many_arguments)]
+    fn fragment(
+        &mut self,
+        x: i32,
+        y: i32,
+        z: f32,
+        inv_w: f32,
+        varyings: &[f32],
+        front_facing: bool,
+        covered: &[(usize, f32)],
+    ) {
+        let pixel = (y * self.target.width + x) as usize;
+        let params = self.params;
+
+        let color = (self.shader.fragment)(&FragmentInput {
+            varyings,
+            frag_coord: [x as f32 + 0.5, y as f32 + 0.5, z, inv_w],
+            front_facing,
+            uniforms: self.uniforms,
+            uniform_blocks: self.uniform_blocks,
+            images: self.images,
+        });
+        let color = match color {
+            Some(color) => color,
+            None => return,
+        };
+
+        for &(sample, z) in covered {
+            let index = pixel * self.target.samples + sample;
+            // The same as GL backend: depth test is only enabled together with depth write
+            let depth_pass = match &self.target.depth {
+                Some((format, depth)) if params.depth_write => {
+                    compare(params.depth_test, z, read_depth(*format, depth, index))
+                }
+                _ => true,
+            };
+
+            if let (Some(stencil), Some(state)) = (&mut self.target.stencil, params.stencil_test) {
+                let face = if front_facing {
+                    state.front
+                } else {
+                    state.back
+                };
+                let value = stencil[index];
+                let stencil_pass = stencil_compare(
+                    face.test_func,
+                    face.test_ref as u32 & face.test_mask,
+                    value as u32 & face.test_mask,
+                );
+                let op = if !stencil_pass {
+                    face.fail_op
+                } else if !depth_pass {
+                    face.depth_fail_op
+                } else {
+                    face.pass_op
+                };
+                let new = stencil_op(op, value, face.test_ref);
+                let mask = face.write_mask as u8;
+                stencil[index] = (value & !mask) | (new & mask);
+                if !stencil_pass {
+                    continue;
+                }
+            }
+            if !depth_pass {
+                continue;
+            }
+            self.samples_passed.set(self.samples_passed.get() + 1);
+            if params.depth_write {
+                if let Some((format, depth)) = &mut self.target.depth {
+                    write_depth(*format, depth, index, z);
+                }
+            }
+
+            if let Some((format, data)) = &mut self.target.color {
+                let color = match params.color_blend {
+                    Some(color_blend) => {
+                        let dst = read_color(*format, data, index);
+                        let alpha_blend = params.alpha_blend.unwrap_or(color_blend);
+                        [
+                            blend_channel(color_blend, color, dst, 0),
+                            blend_channel(color_blend, color, dst, 1),
+                            blend_channel(color_blend, color, dst, 2),
+                            blend_channel(alpha_blend, color, dst, 3),
+                        ]
+                    }
+                    None => color,
+                };
+                write_color(*format, data, index, color, self.color_write);
+            }
+        }
+    }
+}
+
+fn interpolate(v: &[&WindowVertex], weights: &[f32], inv_w: f32) -> Vec<f32> {
+    let count = v.iter().map(|v| v.varyings.len()).min().unwrap_or(0);
+    (0..count)
+        .map(|i| {
+            v.iter()
+                .zip(weights)
+                .map(|(v, weight)| v.varyings[i] * v.inv_w * weight)
+                .sum::<f32>()
+                / inv_w
+        })
+        .collect()
+}
+
+// Sutherland-Hodgman against the z >= -w plane
+fn clip_near(polygon: &[ClipVertex]) -> Vec<ClipVertex> {
+    let distance = |v: &ClipVertex| v.position[2] + v.position[3];
+    let closed = polygon.len() > 2;
+    let mut res = vec![];
+    let edges = if closed {
+        polygon.len()
+    } else {
+        polygon.len() - 1
+    };
+    for i in 0..polygon.len() {
+        let a = &polygon[i];
+        let da = distance(a);
+        if da >= 0.0 {
+            res.push(a.clone());
+        }
+        if i >= edges {
+            continue;
+        }
+        let b = &polygon[(i + 1) % polygon.len()];
+        let db = distance(b);
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            let lerp = |a: f32, b: f32| a + (b - a) * t;
+            let mut position = [0.0; 4];
+            for (c, p) in position.iter_mut().enumerate() {
+                *p = lerp(a.position[c], b.position[c]);
+            }
+            let varyings = a
+                .varyings
+                .iter()
+                .zip(&b.varyings)
+                .map(|(a, b)| lerp(*a, *b))
+                .collect();
+            res.push(ClipVertex { position, varyings });
+        }
+    }
+    res
+}
+
+impl RenderingBackend for SoftwareContext {
+    fn info(&self) -> ContextInfo {
+        ContextInfo {
+            backend: Backend::OpenGl,
+            gl_version_string: String::new(),
+            gl_vendor_string: String::new(),
+            gl_renderer_string: String::new(),
+            glsl_support: GlslSupport {
+                v100: true,
+                ..Default::default()
+            },
+            // TimeElapsed queries measure CPU time spent between begin and end
+            features: Features {
+                elapsed_query: true,
+                occlusion_query: true,
+                uniform_buffers: true,
+                texture_arrays: true,
+                texture_rg: true,
+                texture_float: true,
+                texture_float_linear: true,
+                color_buffer_float: true,
+                texture_integer: true,
+                texture_srgb: true,
+                depth_stencil_texture: true,
+                draw_base_vertex: true,
+                draw_indirect: true,
+                multi_draw_indirect: true,
+                max_sample_count: MAX_SAMPLE_COUNT,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let vertex = match shader {
+            ShaderSource::Glsl { vertex, .. } => vertex,
+            ShaderSource::Msl { program } => program,
+        };
+        let shader = self
+            .registered_shaders
+            .get(vertex)
+            .cloned()
+            .ok_or_else(|| {
+                let error_message = "No SoftwareShader registered for this source";
+                ShaderError::CompilationError {
+                    shader_type: ShaderType::Vertex,
+                    error_message: error_message.to_string(),
+                    diagnostics: ShaderDiagnostic::parse_log(
+                        ShaderType::Vertex,
+                        error_message,
+                        vertex,
+                    ),
+                }
+            })?;
+        Ok(self.new_software_shader(shader, meta))
+    }
+
+    fn reflect_shader(&mut self, _shader: ShaderSource) -> Result<ShaderReflection, ShaderError> {
+        // shaders are Rust closures, there is no program to inspect
+        Err(ShaderError::ReflectionUnsupported)
+    }
+
+    fn new_texture(
+        &mut self,
+        _access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        assert!(
+            !params.format.is_compressed(),
+            "SoftwareContext does not support compressed textures"
+        );
+        let size =
+            params.format.size(params.width, params.height) as usize * params.layers() as usize;
+        let data = match source {
+            TextureSource::Empty => vec![0; size],
+            TextureSource::Bytes(bytes) => {
+                assert_eq!(size, bytes.len());
+                bytes.to_vec()
+            }
+            // only the first mipmap level is used
+            TextureSource::Array(array) => array
+                .iter()
+                .flat_map(|mipmaps| mipmaps[0].iter().copied())
+                .collect(),
+        };
+        TextureId(TextureIdInner::Managed(self.textures.add(Texture {
+            params,
+            wrap_y: params.wrap,
+            data: RefCell::new(data),
+        })))
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.texture(texture).params
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => RawId::OpenGl(texture.index() as _),
+            TextureIdInner::Raw(raw) => raw,
+        }
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        let params = &mut self.texture_mut(texture).params;
+        params.min_filter = filter;
+        params.mipmap_filter = mipmap_filter;
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.texture_mut(texture).params.mag_filter = filter;
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        let texture = self.texture_mut(texture);
+        texture.params.wrap = wrap_x;
+        texture.wrap_y = wrap_y;
+    }
+
+    fn texture_generate_mipmaps(&mut self, _texture: TextureId) {}
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let id = texture;
+        let texture = self.texture_mut(id);
+        texture.params.width = width;
+        texture.params.height = height;
+        let size =
+            texture.params.format.size(width, height) as usize * texture.params.layers() as usize;
+        *texture.data.get_mut() = match bytes {
+            Some(bytes) => {
+                assert_eq!(size, bytes.len());
+                bytes.to_vec()
+            }
+            None => vec![0; size],
+        };
+
+        // the samples of multisampled passes are of the texture size too
+        let size = texture.params.format.size(width, height) as usize;
+        for pass in self.passes.iter_mut() {
+            let samples = match &mut pass.samples {
+                Some(samples) => samples,
+                None => continue,
+            };
+            let color = pass.color_textures.first() == Some(&id);
+            let depth = pass.depth_texture == Some(id);
+            for (attached, storage) in [(color, &mut samples.color), (depth, &mut samples.depth)] {
+                if let (true, Some((_, data))) = (attached, storage) {
+                    *data.get_mut() = vec![0; size * samples.count];
+                }
+            }
+        }
+    }
+
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        let data = self.texture(texture).data.borrow();
+        let len = bytes.len().min(data.len());
+        bytes[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId {
+        let pixels = self.texture(texture).data.borrow().clone();
+        ReadbackId(self.readbacks.add(pixels))
+    }
+
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool {
+        bytes.copy_from_slice(&self.readbacks.remove(readback.0));
+        true
+    }
+
+    fn texture_update_layer_part(
+        &mut self,
+        texture: TextureId,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        let texture = self.texture_mut(texture);
+        let params = texture.params;
+        assert_eq!(
+            params.format.size(width as _, height as _) as usize,
+            bytes.len()
+        );
+        assert!(x_offset + width <= params.width as _);
+        assert!(y_offset + height <= params.height as _);
+        assert!(layer < params.layers());
+
+        let data = texture.data.get_mut();
+        let pixel_size = params.format.size(1, 1) as usize;
+        let row_size = width as usize * pixel_size;
+        let layer_offset =
+            params.format.size(params.width, params.height) as usize * layer as usize;
+        for row in 0..height as usize {
+            let dst = layer_offset
+                + ((y_offset as usize + row) * params.width as usize + x_offset as usize)
+                    * pixel_size;
+            data[dst..dst + row_size].copy_from_slice(&bytes[row * row_size..(row + 1) * row_size]);
+        }
+    }
+
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    ) {
+        let (x, y, width, height) = src_rect;
+        let src = self.texture(src);
+        let params = src.params;
+        let pixel_size = params.format.size(1, 1) as usize;
+        let row_size = width as usize * pixel_size;
+        assert!(x + width <= params.width as _ && y + height <= params.height as _);
+        // copied out first, src and dst may be the same texture
+        let mut bytes = Vec::with_capacity(row_size * height as usize);
+        {
+            let data = src.data.borrow();
+            for row in y..y + height {
+                let offset = (row as usize * params.width as usize + x as usize) * pixel_size;
+                bytes.extend_from_slice(&data[offset..offset + row_size]);
+            }
+        }
+        assert_eq!(
+            self.texture(dst).params.format,
+            params.format,
+            "Copy between textures of different formats"
+        );
+        self.texture_update_part(dst, dst_pos.0, dst_pos.1, width, height, &bytes);
+    }
+
+    fn new_render_pass_layer(
+        &mut self,
+        color_img: &[TextureId],
+        depth_img: Option<TextureId>,
+        layer: u32,
+    ) -> RenderPass {
+        if color_img.is_empty() && depth_img.is_none() {
+            panic!("Render pass should have at least one non-none target");
+        }
+        let attachments = color_img.iter().chain(depth_img.as_ref());
+        let params: Vec<TextureParams> = attachments.map(|t| self.texture(*t).params).collect();
+        let sample_count = params
+            .iter()
+            .map(|params| params.sample_count)
+            .max()
+            .unwrap_or(1)
+            .clamp(1, MAX_SAMPLE_COUNT);
+        let samples = if sample_count > 1 {
+            // only the standard 2 and 4 sample patterns
+            let count = if sample_count == 2 { 2 } else { 4 };
+            let storage = |params: &TextureParams| {
+                let size = params.format.size(params.width, params.height) as usize;
+                (params.format, RefCell::new(vec![0; size * count]))
+            };
+            Some(Samples {
+                count,
+                color: color_img.first().map(|_| storage(&params[0])),
+                depth: depth_img.map(|_| storage(params.last().unwrap())),
+            })
+        } else {
+            None
+        };
+        let pass = RenderPassInternal {
+            color_textures: color_img.to_vec(),
+            depth_texture: depth_img,
+            layer,
+            samples,
+        };
+        RenderPass(self.passes.add(pass))
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        &self.passes[render_pass.0].color_textures
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        let render_pass = self.passes.remove(render_pass.0);
+        // a texture attached to several passes goes with the first of them deleted
+        let attachments = render_pass
+            .color_textures
+            .iter()
+            .chain(&render_pass.depth_texture);
+        for texture in attachments {
+            if let TextureIdInner::Managed(texture) = texture.0 {
+                if self.textures.get(texture).is_some() {
+                    self.textures.remove(texture);
+                }
+            }
+        }
+    }
+
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode) {
+        let src = self.pass_target(Some(src));
+        let mut target = self.pass_target(dst);
+        let (src_w, src_h) = (src.width, src.height);
+        let (dst_w, dst_h) = (target.width, target.height);
+        // texel of the src pixel the dst pixel center falls into
+        let src_coord = |x: i32, y: i32| {
+            (
+                (x as f32 + 0.5) * src_w as f32 / dst_w as f32,
+                (y as f32 + 0.5) * src_h as f32 / dst_h as f32,
+            )
+        };
+        if let (Some((src_format, src_color)), Some((format, color))) =
+            (&src.color, &mut target.color)
+        {
+            let texel = |x: i32, y: i32| {
+                let index = y.clamp(0, src_h - 1) * src_w + x.clamp(0, src_w - 1);
+                read_color(*src_format, src_color, index as usize)
+            };
+            for y in 0..dst_h {
+                for x in 0..dst_w {
+                    let (src_x, src_y) = src_coord(x, y);
+                    let c = filter_texels(texel, src_x, src_y, filter);
+                    let mask = (true, true, true, true);
+                    write_color(*format, color, (y * dst_w + x) as usize, c, mask);
+                }
+            }
+        }
+        // the default framebuffer depth is never blitted into, as in GL
+        if let (Some((src_format, src_depth)), Some((format, depth)), Some(_)) =
+            (&src.depth, &mut target.depth, dst)
+        {
+            if src_format == format {
+                let size = format.size(1, 1) as usize;
+                for y in 0..dst_h {
+                    for x in 0..dst_w {
+                        let (src_x, src_y) = src_coord(x, y);
+                        let src_index = src_y as usize * src_w as usize + src_x as usize;
+                        let index = (y * dst_w + x) as usize * size;
+                        depth[index..index + size]
+                            .copy_from_slice(&src_depth[src_index * size..(src_index + 1) * size]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        let mut strides = vec![0; buffer_layout.len()];
+        let mut offsets = vec![0; buffer_layout.len()];
+        for attribute in attributes {
+            let layout = &buffer_layout[attribute.buffer_index];
+            if layout.stride == 0 {
+                strides[attribute.buffer_index] += attribute.format.size_bytes() as usize;
+            } else {
+                strides[attribute.buffer_index] = layout.stride as usize;
+            }
+        }
+
+        let mut internal = vec![];
+        for attribute in attributes {
+            let layout = &buffer_layout[attribute.buffer_index];
+            let divisor = match layout.step_func {
+                VertexStep::PerVertex => 0,
+                VertexStep::PerInstance => layout.step_rate.max(1),
+            };
+            let (format, count) = match attribute.format {
+                VertexFormat::Mat4 => (VertexFormat::Float4, 4),
+                format => (format, 1),
+            };
+            for _ in 0..count {
+                internal.push(AttributeInternal {
+                    buffer_index: attribute.buffer_index,
+                    format,
+                    offset: offsets[attribute.buffer_index],
+                    stride: strides[attribute.buffer_index],
+                    divisor,
+                });
+                offsets[attribute.buffer_index] += format.size_bytes() as usize;
+            }
+        }
+
+        let pipeline = PipelineInternal {
+            attributes: internal,
+            shader,
+            params,
+        };
+        Pipeline(self.pipelines.add(pipeline))
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.cur_pipeline = Some(*pipeline);
+        self.color_write = self.pipelines[pipeline.0].params.color_write;
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.pipelines.remove(pipeline.0);
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        _usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let (data, element_size) = match data {
+            BufferSource::Slice(data) => {
+                let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+                (bytes.to_vec(), data.element_size)
+            }
+            BufferSource::Empty { size, element_size } => (vec![0; size], element_size),
+        };
+        if type_ == BufferType::IndexBuffer {
+            assert!(
+                element_size == 1 || element_size == 2 || element_size == 4,
+                "unsupported index buffer dimension"
+            );
+        }
+        let buffer = Buffer {
+            buffer_type: type_,
+            element_size,
+            data,
+        };
+        BufferId(self.buffers.add(buffer))
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        let data = match data {
+            BufferSource::Slice(data) => data,
+            _ => panic!("buffer_update expects BufferSource::slice"),
+        };
+        let buffer = &mut self.buffers[buffer.0];
+        if buffer.buffer_type == BufferType::IndexBuffer {
+            assert!(data.element_size == buffer.element_size);
+        }
+        assert!(offset + data.size <= buffer.data.len());
+
+        let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+        buffer.data[offset..offset + data.size].copy_from_slice(bytes);
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.buffers[buffer.0].data.len()
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        self.buffers.remove(buffer.0);
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        match texture.0 {
+            TextureIdInner::Managed(texture) => {
+                self.textures.remove(texture);
+            }
+            TextureIdInner::Raw(_) => panic!("Raw texture in SoftwareContext!"),
+        }
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        self.shaders.remove(program.0);
+        self.cur_pipeline = None;
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.viewport = Rect { x, y, w, h };
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.scissor = Rect { x, y, w, h };
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
+        index_buffer: BufferId,
+        index_buffer_offset: usize,
+        textures: &[TextureId],
+    ) {
+        self.vertex_buffers = vertex_buffers.to_vec();
+        self.vertex_buffer_offsets = vertex_buffer_offsets.to_vec();
+        self.index_buffer = Some(index_buffer);
+        self.index_buffer_offset = index_buffer_offset;
+        self.images = textures.to_vec();
+    }
+
+    // the signature comes from the trait, `apply_uniforms` is the safe way in
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        self.uniforms = unsafe { std::slice::from_raw_parts(uniform_ptr, size) }.to_vec();
+    }
+
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize) {
+        let pipeline = self
+            .cur_pipeline
+            .expect("apply_pipeline before apply_uniform_block");
+        let shader = self.pipelines[pipeline.0].shader;
+        let size = *self.shaders[shader.0]
+            .uniform_block_sizes
+            .get(block)
+            .unwrap_or_else(|| panic!("No uniform block {} in the shader", block));
+        let data = &self.buffers[buffer.0];
+        assert!(
+            data.buffer_type == BufferType::UniformBuffer,
+            "apply_uniform_block expects a BufferType::UniformBuffer buffer"
+        );
+        let alignment = self.info().features.uniform_buffer_offset_alignment;
+        assert!(
+            is_multiple_of(offset, alignment),
+            "Uniform block offset {} is not a multiple of {}",
+            offset,
+            alignment
+        );
+        assert!(
+            offset + size <= data.data.len(),
+            "Uniform block range {}..{} is out of buffer bounds, buffer is {} bytes",
+            offset,
+            offset + size,
+            data.data.len()
+        );
+
+        if self.uniform_blocks.len() <= block {
+            self.uniform_blocks.resize(block + 1, None);
+        }
+        self.uniform_blocks[block] = Some((buffer, offset, size));
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        let mut target = self.target();
+        let clip = self.clip_rect(&target);
+        for y in clip.y..clip.y + clip.h {
+            for x in clip.x..clip.x + clip.w {
+                let pixel = (y * target.width + x) as usize * target.samples;
+                for index in pixel..pixel + target.samples {
+                    if let (Some((r, g, b, a)), Some((format, data))) = (color, &mut target.color) {
+                        write_color(*format, data, index, [r, g, b, a], self.color_write);
+                    }
+                    if let (Some(v), Some((format, data))) = (depth, &mut target.depth) {
+                        write_depth(*format, data, index, v);
+                    }
+                    if let (Some(v), Some(data)) = (stencil, &mut target.stencil) {
+                        data[index] = v as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.begin_pass(None, action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        let (w, h) = self.pass_size(pass);
+        self.cur_pass = Some(pass);
+        self.viewport = Rect { x: 0, y: 0, w, h };
+        self.scissor = self.viewport;
+        match action {
+            PassAction::Nothing => {}
+            PassAction::Clear {
+                color,
+                depth,
+                stencil,
+            } => {
+                self.clear(color, depth, stencil);
+            }
+        }
+    }
+
+    fn end_render_pass(&mut self) {
+        if let Some(Some(pass)) = self.cur_pass.take() {
+            self.resolve(pass);
+        }
+    }
+
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8> {
+        let (x, y, width, height) = rect;
+        let fb = &self.default_framebuffer;
+        assert!(x + width <= fb.width as i32 && y + height <= fb.height as i32);
+        let color = fb.color.borrow();
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        // the framebuffer is bottom row first
+        for row in (y..y + height).rev() {
+            let offset = (row as usize * fb.width as usize + x as usize) * 4;
+            pixels.extend_from_slice(&color[offset..offset + width as usize * 4]);
+        }
+        pixels
+    }
+
+    fn commit_frame(&mut self) {
+        self.cur_pipeline = None;
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.draw_base_vertex(base_element, num_elements, 0, num_instances);
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        let vertex_ids: Vec<i32> = (first_vertex..first_vertex + num_vertices).collect();
+        self.draw_vertices(&vertex_ids, num_instances, 0);
+    }
+
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    ) {
+        let indices = self.indices(
+            self.index_buffer_offset,
+            base_element,
+            num_elements,
+            base_vertex,
+        );
+        self.draw_vertices(&indices, num_instances, 0);
+    }
+
+    fn draw_indirect(&self, buffer: BufferId, offset: usize) {
+        self.multi_draw_indirect(buffer, offset, 1, 0);
+    }
+
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32) {
+        let buffer = &self.buffers[buffer.0];
+        assert!(
+            buffer.buffer_type == BufferType::IndirectBuffer,
+            "draw_indirect expects a BufferType::IndirectBuffer buffer"
+        );
+        let stride = match stride {
+            0 => std::mem::size_of::<DrawIndirectCommand>(),
+            stride => stride as usize,
+        };
+        for draw in 0..draw_count as usize {
+            let offset = offset + draw * stride;
+            let field = |i: usize| {
+                let bytes = &buffer.data[offset + i * 4..offset + i * 4 + 4];
+                u32::from_ne_bytes(bytes.try_into().unwrap())
+            };
+            let indices = self.indices(0, field(2) as i32, field(0) as i32, field(3) as i32);
+            self.draw_vertices(&indices, field(1) as i32, field(4) as i32);
+        }
+    }
+
+    fn new_query(&mut self, query_type: QueryType) -> QueryId {
+        let query = Query {
+            query_type,
+            started: None,
+            result: 0,
+        };
+        QueryId(self.queries.add(query))
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        let query = &mut self.queries[query.0];
+        query.result = 0;
+        match query.query_type {
+            QueryType::TimeElapsed => query.started = Some(std::time::Instant::now()),
+            QueryType::AnySamplesPassed | QueryType::SamplesPassed => self.samples_passed.set(0),
+        }
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        let query = &mut self.queries[query.0];
+        query.result = match query.query_type {
+            QueryType::TimeElapsed => {
+                let started = query.started.take().expect("end_query without begin_query");
+                started.elapsed().as_nanos() as u64
+            }
+            QueryType::AnySamplesPassed => self.samples_passed.get().min(1),
+            QueryType::SamplesPassed => self.samples_passed.get(),
+        };
+    }
+
+    fn query_available(&mut self, _query: QueryId) -> bool {
+        // rendering is synchronous
+        true
+    }
+
+    fn query_result(&mut self, query: QueryId) -> u64 {
+        self.queries[query.0].result
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        self.queries.remove(query.0);
+    }
+}
+
+#[test]
+fn test_software_triangle() {
+    let mut ctx = SoftwareContext::new(4, 4);
+
+    #[rustfmt::skip]
+    let vertices: [f32; 6] = [
+        -1.0, -1.0,
+         1.0, -1.0,
+        -1.0,  1.0,
+    ];
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&vertices),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2]),
+    );
+    let shader = ctx.new_software_shader(
+        SoftwareShader::new(
+            |input| VertexOutput {
+                position: input.attributes[0],
+                varyings: vec![],
+            },
+            |input| Some(input.uniforms::<[f32; 4]>()),
+        ),
+        ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("color", UniformType::Float4)],
+            },
+            uniform_blocks: vec![],
+            images: vec![],
+        },
+    );
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[]);
+    ctx.apply_uniforms(UniformsSource::table(&[1.0f32, 0.0, 0.0, 1.0]));
+    ctx.draw(0, 3, 1);
+    ctx.end_render_pass();
+
+    let pixels = ctx.default_framebuffer_pixels();
+    let pixel = |x: usize, y: usize| &pixels[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+    // bottom-left corner is inside the triangle, top-right is outside
+    assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(3, 3), [0, 0, 0, 255]);
+
+    // same corners, top row first
+    let top_down = ctx.read_default_framebuffer((0, 0, 4, 4));
+    assert_eq!(top_down[12..16], [0, 0, 0, 255]);
+    assert_eq!(top_down[48..52], [255, 0, 0, 255]);
+}
+
+#[test]
+fn test_software_copy_and_blit() {
+    let mut ctx = SoftwareContext::new(2, 2);
+
+    let bytes: Vec<u8> = (0..16u8).flat_map(|i| [i, 0, 0, 255]).collect();
+    let src = ctx.new_texture_from_rgba8(4, 4, &bytes);
+    let dst = ctx.new_texture_from_rgba8(4, 4, &[0; 64]);
+    ctx.copy_texture_region(src, (1, 1, 2, 2), dst, (0, 2));
+    let mut pixels = [0; 64];
+    ctx.texture_read_pixels(dst, &mut pixels);
+    let red: Vec<u8> = pixels.chunks(4).map(|pixel| pixel[0]).collect();
+    assert_eq!(red, [0, 0, 0, 0, 0, 0, 0, 0, 5, 6, 0, 0, 9, 10, 0, 0]);
+
+    // 4x4 into the 2x2 default framebuffer, nearest picks every other pixel
+    let pass = ctx.new_render_pass(src, None);
+    ctx.blit_render_pass(pass, None, FilterMode::Nearest);
+    let red: Vec<u8> = ctx
+        .default_framebuffer_pixels()
+        .chunks(4)
+        .map(|pixel| pixel[0])
+        .collect();
+    assert_eq!(red, [5, 7, 13, 15]);
+}
+
+#[test]
+fn test_software_buffer_offsets() {
+    let mut ctx = SoftwareContext::new(4, 4);
+
+    // two meshes sharing the buffers, only the second one is drawn
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Stream,
+        BufferSource::empty::<f32>(12),
+    );
+    #[rustfmt::skip]
+    let top_right: [f32; 6] = [
+        1.0, 1.0,
+       -1.0, 1.0,
+        1.0, -1.0,
+    ];
+    ctx.buffer_update_range(vertex_buffer, 24, BufferSource::slice(&top_right));
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 0, 0, 0, 1, 2]),
+    );
+    let shader = ctx.new_software_shader(
+        SoftwareShader::new(
+            |input| VertexOutput {
+                position: input.attributes[0],
+                varyings: vec![],
+            },
+            |_| Some([1.0, 0.0, 0.0, 1.0]),
+        ),
+        ShaderMeta {
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
+            images: vec![],
+        },
+    );
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[24], index_buffer, 6, &[]);
+    ctx.draw(0, 3, 1);
+    ctx.end_render_pass();
+
+    let pixels = ctx.default_framebuffer_pixels();
+    let pixel = |x: usize, y: usize| &pixels[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+    assert_eq!(pixel(3, 3), [255, 0, 0, 255]);
+    assert_eq!(pixel(0, 0), [0, 0, 0, 255]);
+}
+
+#[test]
+#[should_panic(expected = "used after it was deleted")]
+fn test_software_deleted_buffer() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    let source = [0u16; 4];
+    let buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&source),
+    );
+    ctx.delete_buffer(buffer);
+    // takes over the slot of the deleted buffer
+    let reused = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&source),
+    );
+    assert_eq!(ctx.buffer_size(reused), 8);
+    ctx.buffer_size(buffer);
+}
+
+/// Full-height quad from `x0` to `x1` in clip space, counter-clockwise.
+#[cfg(test)]
+fn test_quad(x0: f32, x1: f32, z: f32) -> [[f32; 3]; 6] {
+    [
+        [x0, -1.0, z],
+        [x1, -1.0, z],
+        [x1, 1.0, z],
+        [x0, -1.0, z],
+        [x1, 1.0, z],
+        [x0, 1.0, z],
+    ]
+}
+
+/// Draw the triangles in a solid color, in the current pass.
+#[cfg(test)]
+fn draw_test_triangles(
+    ctx: &mut SoftwareContext,
+    params: PipelineParams,
+    triangles: &[[f32; 3]],
+    color: [f32; 4],
+) {
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(triangles),
+    );
+    let indices: Vec<u16> = (0..triangles.len() as u16).collect();
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&indices),
+    );
+    let shader = ctx.new_software_shader(
+        SoftwareShader::new(
+            |input| VertexOutput {
+                position: input.attributes[0],
+                varyings: vec![],
+            },
+            |input| Some(input.uniforms::<[f32; 4]>()),
+        ),
+        ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("color", UniformType::Float4)],
+            },
+            uniform_blocks: vec![],
+            images: vec![],
+        },
+    );
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float3)],
+        shader,
+        params,
+    );
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[]);
+    ctx.apply_uniforms(UniformsSource::table(&color));
+    ctx.draw(0, triangles.len() as i32, 1);
+}
+
+#[test]
+fn test_f32_to_f16() {
+    #[rustfmt::skip]
+    let cases = [
+        (1.0, 0x3c00), (-2.0, 0xc000), (0.5, 0x3800),
+        // rounding carries into the exponent
+        (1.9999, 0x4000), (0.49999, 0x3800), (-1.9999, 0xc000),
+        // the largest half, rounding down to it and up to infinity
+        (65504.0, 0x7bff), (65519.0, 0x7bff), (65520.0, 0x7c00),
+        (1e6, 0x7c00), (f32::INFINITY, 0x7c00), (f32::NEG_INFINITY, 0xfc00),
+        // subnormals, rounding up to the smallest normal and flushing to zero
+        (5.9604645e-8, 0x0001), (6.1035e-5, 0x0400), (1e-9, 0x0000),
+    ];
+    for (value, half) in cases.iter() {
+        assert_eq!(f32_to_f16(*value), *half, "{}", value);
+    }
+    assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+}
+
+#[test]
+fn test_software_depth_test() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    let params = PipelineParams {
+        depth_test: Comparison::Less,
+        depth_write: true,
+        ..Default::default()
+    };
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    // behind the red quad
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, 0.5),
+        [0.0, 1.0, 0.0, 1.0],
+    );
+    assert_eq!(ctx.default_framebuffer_pixels(), [255, 0, 0, 255]);
+    // in front of it
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, -0.5),
+        [0.0, 0.0, 1.0, 1.0],
+    );
+    ctx.end_render_pass();
+    assert_eq!(ctx.default_framebuffer_pixels(), [0, 0, 255, 255]);
+}
+
+#[test]
+fn test_software_occlusion_query() {
+    let mut ctx = SoftwareContext::new(4, 4);
+    let params = PipelineParams {
+        depth_test: Comparison::Less,
+        depth_write: true,
+        ..Default::default()
+    };
+    let samples = ctx.new_query(QueryType::SamplesPassed);
+    let any = ctx.new_query(QueryType::AnySamplesPassed);
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    // covers the left half in front
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 0.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    // full screen behind it, only the right half passes
+    ctx.begin_query(samples);
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, 0.5),
+        [0.0, 1.0, 0.0, 1.0],
+    );
+    ctx.end_query(samples);
+    // entirely hidden now
+    ctx.begin_query(any);
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, 0.9),
+        [0.0, 0.0, 1.0, 1.0],
+    );
+    ctx.end_query(any);
+    ctx.end_render_pass();
+
+    assert!(ctx.query_available(samples));
+    assert_eq!(ctx.query_result(samples), 8);
+    assert!(ctx.query_available(any));
+    assert_eq!(ctx.query_result(any), 0);
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    ctx.begin_query(any);
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, 0.0),
+        [0.0, 0.0, 1.0, 1.0],
+    );
+    ctx.end_query(any);
+    ctx.end_render_pass();
+    assert_eq!(ctx.query_result(any), 1);
+
+    ctx.delete_query(samples);
+    ctx.delete_query(any);
+}
+
+#[test]
+fn test_software_elapsed_query() {
+    let mut ctx = SoftwareContext::new(16, 16);
+    let query = ctx.new_query(QueryType::TimeElapsed);
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    ctx.begin_query(query);
+    draw_test_triangles(
+        &mut ctx,
+        Default::default(),
+        &test_quad(-1.0, 1.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    ctx.end_query(query);
+    ctx.end_render_pass();
+
+    assert!(ctx.query_available(query));
+    assert!(ctx.query_result(query) > 0);
+    ctx.delete_query(query);
+}
+
+#[test]
+fn test_software_blending() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    let alpha_blending = BlendState::new(
+        Equation::Add,
+        BlendFactor::Value(BlendValue::SourceAlpha),
+        BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+    );
+    let quad = test_quad(-1.0, 1.0, 0.0);
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 1.0, 1.0));
+    let params = PipelineParams {
+        color_blend: Some(alpha_blending),
+        ..Default::default()
+    };
+    draw_test_triangles(&mut ctx, params, &quad, [1.0, 0.0, 0.0, 0.5]);
+    ctx.end_render_pass();
+    // alpha is blended the same way: 0.5 * 0.5 + 1.0 * 0.5
+    assert_eq!(ctx.default_framebuffer_pixels(), [128, 0, 128, 191]);
+
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 1.0, 1.0));
+    let params = PipelineParams {
+        color_blend: Some(alpha_blending),
+        alpha_blend: Some(BlendState::new(
+            Equation::Add,
+            BlendFactor::Zero,
+            BlendFactor::One,
+        )),
+        ..Default::default()
+    };
+    draw_test_triangles(&mut ctx, params, &quad, [1.0, 0.0, 0.0, 0.5]);
+    ctx.end_render_pass();
+    assert_eq!(ctx.default_framebuffer_pixels(), [128, 0, 128, 255]);
+}
+
+#[test]
+fn test_software_culling() {
+    let mut ctx = SoftwareContext::new(2, 1);
+    let counter_clockwise = test_quad(-1.0, 0.0, 0.0);
+    let mut clockwise = test_quad(0.0, 1.0, 0.0);
+    clockwise.reverse();
+    let red = |pixels: Vec<u8>| [pixels[0], pixels[4]];
+
+    for (front_face_order, expected) in [
+        (FrontFaceOrder::CounterClockwise, [255, 0]),
+        (FrontFaceOrder::Clockwise, [0, 255]),
+    ]
+    .iter()
+    {
+        let params = PipelineParams {
+            cull_face: CullFace::Back,
+            front_face_order: *front_face_order,
+            ..Default::default()
+        };
+        ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+        draw_test_triangles(&mut ctx, params, &counter_clockwise, [1.0, 0.0, 0.0, 1.0]);
+        draw_test_triangles(&mut ctx, params, &clockwise, [1.0, 0.0, 0.0, 1.0]);
+        ctx.end_render_pass();
+        assert_eq!(red(ctx.default_framebuffer_pixels()), *expected);
+    }
+}
+
+#[test]
+fn test_software_stencil() {
+    let mut ctx = SoftwareContext::new(2, 1);
+    let face = |test_func, pass_op| StencilFaceState {
+        fail_op: StencilOp::Keep,
+        depth_fail_op: StencilOp::Keep,
+        pass_op,
+        test_func,
+        test_ref: 1,
+        test_mask: !0,
+        write_mask: !0,
+    };
+    let stencil = |test_func, pass_op| {
+        Some(StencilState {
+            front: face(test_func, pass_op),
+            back: face(test_func, pass_op),
+        })
+    };
+
+    ctx.begin_default_pass(PassAction::Clear {
+        color: Some((0.0, 0.0, 0.0, 1.0)),
+        depth: Some(1.0),
+        stencil: Some(0),
+    });
+    // marks the left pixel without drawing anything
+    let params = PipelineParams {
+        stencil_test: stencil(CompareFunc::Always, StencilOp::Replace),
+        color_write: (false, false, false, false),
+        ..Default::default()
+    };
+    draw_test_triangles(&mut ctx, params, &test_quad(-1.0, 0.0, 0.0), [1.0; 4]);
+    let params = PipelineParams {
+        stencil_test: stencil(CompareFunc::Equal, StencilOp::Keep),
+        ..Default::default()
+    };
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 1.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    ctx.end_render_pass();
+
+    assert_eq!(
+        ctx.default_framebuffer_pixels(),
+        [255, 0, 0, 255, 0, 0, 0, 255]
+    );
+}
+
+#[test]
+fn test_software_color_mask() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    let params = PipelineParams {
+        color_write: (true, false, true, false),
+        ..Default::default()
+    };
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 0.0));
+    draw_test_triangles(&mut ctx, params, &test_quad(-1.0, 1.0, 0.0), [1.0; 4]);
+    ctx.end_render_pass();
+    assert_eq!(ctx.default_framebuffer_pixels(), [255, 0, 255, 0]);
+}
+
+#[test]
+fn test_software_texture_wrap() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    // red bottom row, green top row
+    let texture = ctx.new_texture_from_rgba8(1, 2, &[255, 0, 0, 255, 0, 255, 0, 255]);
+    ctx.texture_set_mag_filter(texture, FilterMode::Nearest);
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&test_quad(-1.0, 1.0, 0.0)),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2, 3, 4, 5]),
+    );
+    let shader = ctx.new_software_shader(
+        SoftwareShader::new(
+            |input| VertexOutput {
+                position: input.attributes[0],
+                varyings: vec![],
+            },
+            // 1.25 is the bottom row repeated and the top row clamped
+            |input| Some(input.sample(0, [1.25, 1.25])),
+        ),
+        ShaderMeta {
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
+            images: vec!["tex".to_string()],
+        },
+    );
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float3)],
+        shader,
+        PipelineParams::default(),
+    );
+
+    for (wrap_x, wrap_y, expected) in [
+        (TextureWrap::Repeat, TextureWrap::Clamp, [0, 255, 0, 255]),
+        (TextureWrap::Clamp, TextureWrap::Repeat, [255, 0, 0, 255]),
+    ]
+    .iter()
+    {
+        ctx.texture_set_wrap(texture, *wrap_x, *wrap_y);
+        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[texture]);
+        ctx.draw(0, 6, 1);
+        ctx.end_render_pass();
+        assert_eq!(ctx.default_framebuffer_pixels(), *expected);
+    }
+}
+
+#[test]
+fn test_software_texture_formats() {
+    fn bytes<T: Copy>(values: &[T]) -> Vec<u8> {
+        let size = std::mem::size_of_val(values);
+        unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, size) }.to_vec()
+    }
+    let half = |values: &[f32]| bytes(&values.iter().map(|v| f32_to_f16(*v)).collect::<Vec<_>>());
+    let r8 = 64.0 / 255.0;
+    let srgb = |x: u8| srgb_to_linear(x as f32 / 255.0);
+    // a texel of each format and what sampling it gives
+    let formats = [
+        (TextureFormat::R8, vec![64], [r8, 0.0, 0.0, 1.0]),
+        (TextureFormat::RG8, vec![64, 255], [r8, 1.0, 0.0, 1.0]),
+        (TextureFormat::R16F, half(&[0.25]), [0.25, 0.0, 0.0, 1.0]),
+        (
+            TextureFormat::RG16F,
+            half(&[0.25, -2.0]),
+            [0.25, -2.0, 0.0, 1.0],
+        ),
+        (TextureFormat::R32F, bytes(&[0.1f32]), [0.1, 0.0, 0.0, 1.0]),
+        (
+            TextureFormat::RGBA16F,
+            half(&[0.25, 0.5, 0.75, 1.5]),
+            [0.25, 0.5, 0.75, 1.5],
+        ),
+        (
+            TextureFormat::RGBA32F,
+            bytes(&[0.1f32, -0.2, 100.0, 0.5]),
+            [0.1, -0.2, 100.0, 0.5],
+        ),
+        (TextureFormat::R32UI, bytes(&[7u32]), [7.0, 0.0, 0.0, 1.0]),
+        (
+            TextureFormat::SRGBA8,
+            vec![188, 128, 64, 100],
+            [srgb(188), srgb(128), srgb(64), 100.0 / 255.0],
+        ),
+    ];
+
+    let mut ctx = SoftwareContext::new(1, 1);
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&test_quad(-1.0, 1.0, 0.0)),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2, 3, 4, 5]),
+    );
+    let shader = ctx.new_software_shader(
+        SoftwareShader::new(
+            |input| VertexOutput {
+                position: input.attributes[0],
+                varyings: vec![],
+            },
+            |input| Some(input.sample(0, [0.5, 0.5])),
+        ),
+        ShaderMeta {
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
+            images: vec!["tex".to_string()],
+        },
+    );
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float3)],
+        shader,
+        PipelineParams::default(),
+    );
+    let params = |format, width| TextureParams {
+        format,
+        width,
+        height: 1,
+        min_filter: FilterMode::Nearest,
+        mag_filter: FilterMode::Nearest,
+        ..Default::default()
+    };
+    // sample `source` into a new 1x1 texture of `format` and read it back
+    let copy = |ctx: &mut SoftwareContext, source, format| {
+        let target = ctx.new_render_texture(params(format, 1));
+        let pass = ctx.new_render_pass(target, None);
+        ctx.begin_pass(Some(pass), PassAction::Nothing);
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[source]);
+        ctx.draw(0, 6, 1);
+        ctx.end_render_pass();
+        let mut bytes = vec![0; format.size(1, 1) as usize];
+        ctx.texture_read_pixels(target, &mut bytes);
+        ctx.delete_render_pass(pass);
+        bytes
+    };
+
+    for (format, texel, color) in formats.iter() {
+        // two texels, the second one replaced by texture_update_part
+        let mut data = vec![0xab; texel.len()];
+        data.extend_from_slice(texel);
+        let texture = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Bytes(&data),
+            params(*format, 2),
+        );
+        let mut read = vec![0; data.len()];
+        ctx.texture_read_pixels(texture, &mut read);
+        assert_eq!(read, data, "{:?}", format);
+
+        ctx.texture_update_part(texture, 0, 0, 1, 1, texel);
+        ctx.texture_update_part(texture, 1, 0, 1, 1, &[0xcd; 16][..texel.len()]);
+        ctx.texture_read_pixels(texture, &mut read);
+        assert_eq!(read[..texel.len()], texel[..], "{:?}", format);
+
+        let texture = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Bytes(texel),
+            params(*format, 1),
+        );
+        // decoded when sampled and encoded again when rendered to
+        assert_eq!(copy(&mut ctx, texture, *format), *texel, "{:?}", format);
+        let decoded = copy(&mut ctx, texture, TextureFormat::RGBA32F);
+        assert_eq!(decoded, bytes(color), "{:?}", format);
+    }
+
+    // depth is only read by the depth test, but the bytes are kept as they are
+    let data = bytes(&[0x1234_5678u32]);
+    let texture = ctx.new_texture(
+        TextureAccess::Static,
+        TextureSource::Bytes(&data),
+        params(TextureFormat::Depth24Stencil8, 1),
+    );
+    let mut read = vec![0; 4];
+    ctx.texture_read_pixels(texture, &mut read);
+    assert_eq!(read, data);
+}
+
+#[test]
+fn test_software_draw_calls() {
+    // one quad per pixel column, each in its own color
+    let colors = [
+        [1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [1.0, 1.0, 1.0, 1.0],
+    ];
+    let mut vertices: Vec<[f32; 7]> = vec![];
+    for (column, color) in colors.iter().enumerate() {
+        let x = -1.0 + column as f32 * 0.5;
+        for [x, y, z] in test_quad(x, x + 0.5, 0.0).iter() {
+            vertices.push([*x, *y, *z, color[0], color[1], color[2], color[3]]);
+        }
+    }
+    let columns = |drawn: &[usize]| -> Vec<u8> {
+        (0..4)
+            .flat_map(|column| {
+                let color = if drawn.contains(&column) {
+                    colors[column]
+                } else {
+                    [0.0, 0.0, 0.0, 1.0]
+                };
+                color.iter().map(|c| (c * 255.0) as u8).collect::<Vec<_>>()
+            })
+            .collect()
+    };
+
+    let mut ctx = SoftwareContext::new(4, 1);
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&vertices),
+    );
+    // a single quad, moved to the others with base_vertex
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2, 3, 4, 5]),
+    );
+    let shader = ctx.new_software_shader(
+        SoftwareShader::new(
+            |input| {
+                let mut position = input.attributes[0];
+                // instances go one column to the right each
+                position[0] += input.instance_id as f32 * 0.5;
+                VertexOutput {
+                    position,
+                    varyings: input.attributes[1].to_vec(),
+                }
+            },
+            |input| {
+                let c = input.varyings;
+                Some([c[0], c[1], c[2], c[3]])
+            },
+        ),
+        ShaderMeta {
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
+            images: vec![],
+        },
+    );
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[
+            VertexAttribute::new("in_pos", VertexFormat::Float3),
+            VertexAttribute::new("in_color", VertexFormat::Float4),
+        ],
+        shader,
+        PipelineParams::default(),
+    );
+    let command = |column: u32| DrawIndirectCommand {
+        num_elements: 6,
+        num_instances: 1,
+        base_element: 0,
+        base_vertex: column as i32 * 6,
+        base_instance: 0,
+    };
+    let indirect_buffer = ctx.new_buffer(
+        BufferType::IndirectBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[command(0), command(1), command(2)]),
+    );
+    let command_size = std::mem::size_of::<DrawIndirectCommand>();
+
+    let mut draw = |draw: &dyn Fn(&mut SoftwareContext)| {
+        ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[]);
+        draw(&mut ctx);
+        ctx.end_render_pass();
+        ctx.default_framebuffer_pixels()
+    };
+
+    assert_eq!(draw(&|ctx| ctx.draw(0, 6, 1)), columns(&[0]));
+    assert_eq!(draw(&|ctx| ctx.draw_arrays(6, 12, 1)), columns(&[1, 2]));
+    let mut instanced = columns(&[0]);
+    instanced.copy_within(0..4, 4);
+    assert_eq!(draw(&|ctx| ctx.draw_arrays(0, 6, 2)), instanced);
+    assert_eq!(
+        draw(&|ctx| ctx.draw_base_vertex(0, 6, 18, 1)),
+        columns(&[3])
+    );
+    assert_eq!(
+        draw(&|ctx| ctx.draw_indirect(indirect_buffer, command_size)),
+        columns(&[1])
+    );
+    assert_eq!(
+        draw(&|ctx| ctx.multi_draw_indirect(indirect_buffer, 0, 3, 0)),
+        columns(&[0, 1, 2])
+    );
+    // every other command
+    let stride = 2 * command_size as i32;
+    assert_eq!(
+        draw(&|ctx| ctx.multi_draw_indirect(indirect_buffer, 0, 2, stride)),
+        columns(&[0, 2])
+    );
+}
+
+#[test]
+fn test_software_multisample() {
+    let mut ctx = SoftwareContext::new(4, 4);
+    assert_eq!(ctx.info().features.max_sample_count, 4);
+    let texture = |ctx: &mut SoftwareContext, format, sample_count| {
+        ctx.new_render_texture(TextureParams {
+            width: 4,
+            height: 4,
+            format,
+            sample_count,
+            ..Default::default()
+        })
+    };
+    let color = texture(&mut ctx, TextureFormat::RGBA8, 4);
+    let depth = texture(&mut ctx, TextureFormat::Depth32, 4);
+    let pass = ctx.new_render_pass(color, Some(depth));
+    assert_eq!(ctx.passes[pass.0].samples.as_ref().unwrap().count, 4);
+
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+    let params = PipelineParams {
+        depth_test: Comparison::Less,
+        depth_write: true,
+        ..Default::default()
+    };
+    // lower right half, the diagonal runs through the pixel corners
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &[[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [1.0, 1.0, 0.0]],
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    ctx.end_render_pass();
+
+    let mut pixels = [0u8; 4 * 4 * 4];
+    ctx.texture_read_pixels(color, &mut pixels);
+    for y in 0..4 {
+        for x in 0..4 {
+            let red = pixels[(y * 4 + x) * 4];
+            match x.cmp(&y) {
+                std::cmp::Ordering::Greater => assert_eq!(red, 255, "{} {}", x, y),
+                std::cmp::Ordering::Less => assert_eq!(red, 0, "{} {}", x, y),
+                // two of the four samples
+                std::cmp::Ordering::Equal => assert!(red == 127 || red == 128, "{}", red),
+            }
+        }
+    }
+    // depth takes the first sample, covered on the diagonal too
+    let mut depths = [0u8; 4 * 4 * 4];
+    ctx.texture_read_pixels(depth, &mut depths);
+    let depth_at = |x: usize, y: usize| {
+        let i = (y * 4 + x) * 4;
+        f32::from_ne_bytes([depths[i], depths[i + 1], depths[i + 2], depths[i + 3]])
+    };
+    assert_eq!(depth_at(1, 1), 0.5);
+    assert_eq!(depth_at(2, 1), 0.5);
+    assert_eq!(depth_at(1, 2), 1.0);
+
+    // the sample count is clamped to the supported ones
+    for (requested, count) in [(16, Some(4)), (3, Some(4)), (2, Some(2)), (1, None)] {
+        let color = texture(&mut ctx, TextureFormat::RGBA8, requested);
+        let pass = ctx.new_render_pass(color, None);
+        let samples = ctx.passes[pass.0].samples.as_ref();
+        assert_eq!(samples.map(|samples| samples.count), count);
+        ctx.delete_render_pass(pass);
+    }
+}
+
+#[test]
+fn test_software_read_default_framebuffer() {
+    let mut ctx = SoftwareContext::new(4, 2);
+    ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 1.0, 1.0));
+    // red left half, green top right quarter
+    let params = PipelineParams::default();
+    draw_test_triangles(
+        &mut ctx,
+        params,
+        &test_quad(-1.0, 0.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    let top_right = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    draw_test_triangles(&mut ctx, params, &top_right, [0.0, 1.0, 0.0, 1.0]);
+    ctx.end_render_pass();
+
+    let (red, green, blue) = ([255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]);
+    // the rect is bottom-up, the rows come top row first
+    let pixels = ctx.read_default_framebuffer((1, 0, 3, 2));
+    assert_eq!(pixels, [red, green, green, red, blue, blue].concat());
+    let pixels = ctx.read_default_framebuffer((2, 1, 2, 1));
+    assert_eq!(pixels, [green, green].concat());
+    let pixels = ctx.read_default_framebuffer((0, 0, 1, 1));
+    assert_eq!(pixels, red);
+}
+
+#[test]
+fn test_software_readback() {
+    let mut ctx = SoftwareContext::new(2, 1);
+    let color = ctx.new_render_texture(TextureParams {
+        width: 2,
+        height: 1,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(color, None);
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 0.0, 1.0, 1.0));
+    draw_test_triangles(
+        &mut ctx,
+        Default::default(),
+        &test_quad(-1.0, 0.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    ctx.end_render_pass();
+    let readback = ctx.begin_read_pixels(color);
+
+    // drawn over before the readback is picked up
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 1.0, 0.0, 1.0));
+    ctx.end_render_pass();
+
+    let mut pixels = [0; 8];
+    assert!(ctx.try_finish_read_pixels(readback, &mut pixels));
+    assert_eq!(pixels, [255, 0, 0, 255, 0, 0, 255, 255]);
+    ctx.texture_read_pixels(color, &mut pixels);
+    assert_eq!(pixels, [0, 255, 0, 255, 0, 255, 0, 255]);
+    assert!(ctx.readbacks.get(readback.0).is_none());
+}
+
+#[test]
+fn test_software_shared_attachments() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    let texture = |ctx: &mut SoftwareContext, format| {
+        ctx.new_render_texture(TextureParams {
+            width: 1,
+            height: 1,
+            format,
+            ..Default::default()
+        })
+    };
+    let (a, b) = (
+        texture(&mut ctx, TextureFormat::RGBA8),
+        texture(&mut ctx, TextureFormat::RGBA8),
+    );
+    let depth = texture(&mut ctx, TextureFormat::Depth);
+    let first = ctx.new_render_pass_mrt(&[a, b], Some(depth));
+    let second = ctx.new_render_pass(b, Some(depth));
+
+    ctx.delete_render_pass(first);
+    let dead = |ctx: &SoftwareContext, texture: TextureId| match texture.0 {
+        TextureIdInner::Managed(texture) => ctx.textures.get(texture).is_none(),
+        TextureIdInner::Raw(_) => unreachable!(),
+    };
+    assert!(dead(&ctx, a) && dead(&ctx, b) && dead(&ctx, depth));
+    // the attachments are gone already
+    ctx.delete_render_pass(second);
+}
+
+#[test]
+fn test_software_multisampled_texture_resize() {
+    let mut ctx = SoftwareContext::new(1, 1);
+    let texture = ctx.new_render_texture(TextureParams {
+        width: 2,
+        height: 1,
+        sample_count: 4,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(texture, None);
+    ctx.texture_resize(texture, 4, 1, None);
+
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0.0, 1.0, 0.0, 1.0));
+    draw_test_triangles(
+        &mut ctx,
+        PipelineParams::default(),
+        &test_quad(-1.0, 0.0, 0.0),
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    ctx.end_render_pass();
+    let mut pixels = [0; 16];
+    ctx.texture_read_pixels(texture, &mut pixels);
+    #[rustfmt::skip]
+    assert_eq!(pixels, [
+        255, 0, 0, 255, 255, 0, 0, 255,
+        0, 255, 0, 255, 0, 255, 0, 255,
+    ]);
+}
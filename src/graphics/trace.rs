@@ -0,0 +1,1755 @@
+//! `.mqtrace` command traces.
+//!
+//! `TracingBackend` serializes every state-changing `RenderingBackend` call, with the
+//! shader sources, texture and buffer contents and uniforms it was given, into a compact
+//! binary trace. `Replay` runs such a trace against any backend, frame by frame,
+//! so a rendering bug may be reproduced away from the machine it happened on:
+//! ```no_run
+//! # use miniquad::*;
+//! # fn f(ctx: &mut dyn RenderingBackend, tracer: &TracingBackend<dyn RenderingBackend>) {
+//! std::fs::write("bug.mqtrace", tracer.trace()).unwrap();
+//!
+//! let mut replay = Replay::new(std::fs::read("bug.mqtrace").unwrap()).unwrap();
+//! while replay.replay_frame(ctx).unwrap() {}
+//! # }
+//! ```
+//! `examples/replay.rs` replays a trace file in a window or with the headless backend.
+//!
+//! The format is little-endian: the header, then one record per call, an `Op` byte followed
+//! by the arguments. Resources are referred to by the order they were created in,
+//! the handles of the recording backend are meaningless for the replaying one.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::*;
+
+const MAGIC: &[u8; 8] = b"MQTRACE\0";
+const VERSION: u32 = 1;
+
+/// Id of a resource the tracer did not see created.
+const UNKNOWN: u32 = u32::MAX;
+
+#[derive(Debug)]
+pub enum TraceError {
+    /// No `.mqtrace` header
+    NotATrace,
+    /// Written by a newer miniquad
+    UnsupportedVersion(u32),
+    /// The trace is cut in the middle of a call
+    UnexpectedEnd,
+    /// Unknown call or enum value, or a string that is not UTF-8
+    InvalidData,
+    /// A resource that was created before the backend was wrapped, or already deleted
+    UnknownResource(u32),
+    /// The replaying backend did not accept a shader, e.g. GLSL on Metal
+    Shader(ShaderError),
+}
+
+impl std::fmt::Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error: {:?}", self)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TraceError> {
+        if self.bytes.len() < len {
+            return Err(TraceError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+}
+
+/// Encoding of a call argument.
+trait Wire<'a>: Sized {
+    fn write(&self, out: &mut Vec<u8>);
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError>;
+}
+
+fn read<'a, T: Wire<'a>>(input: &mut Reader<'a>) -> Result<T, TraceError> {
+    T::read(input)
+}
+
+macro_rules! wire_number {
+    ($($ty:ty),*) => {$(
+        impl<'a> Wire<'a> for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+            fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+                let mut bytes = [0; std::mem::size_of::<$ty>()];
+                bytes.copy_from_slice(input.take(std::mem::size_of::<$ty>())?);
+                Ok(<$ty>::from_le_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+wire_number!(u8, u32, i32, u64, f32);
+
+impl<'a> Wire<'a> for usize {
+    fn write(&self, out: &mut Vec<u8>) {
+        (*self as u64).write(out)
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        Ok(u64::read(input)? as usize)
+    }
+}
+
+impl<'a> Wire<'a> for bool {
+    fn write(&self, out: &mut Vec<u8>) {
+        (*self as u8).write(out)
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        match u8::read(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(TraceError::InvalidData),
+        }
+    }
+}
+
+impl<'a> Wire<'a> for &'a [u8] {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.len().write(out);
+        out.extend_from_slice(self);
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        let len = usize::read(input)?;
+        input.take(len)
+    }
+}
+
+impl<'a> Wire<'a> for &'a str {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.as_bytes().write(out)
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        std::str::from_utf8(read(input)?).map_err(|_| TraceError::InvalidData)
+    }
+}
+
+impl<'a> Wire<'a> for String {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.as_str().write(out)
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        <&str>::read(input).map(str::to_owned)
+    }
+}
+
+impl<'a, T: Wire<'a>> Wire<'a> for Vec<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.len().write(out);
+        for item in self {
+            item.write(out);
+        }
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        let len = usize::read(input)?;
+        // every item takes at least a byte, a broken length fails before allocating
+        if len > input.bytes.len() {
+            return Err(TraceError::UnexpectedEnd);
+        }
+        (0..len).map(|_| T::read(input)).collect()
+    }
+}
+
+impl<'a, T: Wire<'a>> Wire<'a> for Option<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.is_some().write(out);
+        if let Some(value) = self {
+            value.write(out);
+        }
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        Ok(match bool::read(input)? {
+            true => Some(T::read(input)?),
+            false => None,
+        })
+    }
+}
+
+impl<'a, A: Wire<'a>, B: Wire<'a>> Wire<'a> for (A, B) {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.0.write(out);
+        self.1.write(out);
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        Ok((read(input)?, read(input)?))
+    }
+}
+
+impl<'a, A: Wire<'a>, B: Wire<'a>, C: Wire<'a>, D: Wire<'a>> Wire<'a> for (A, B, C, D) {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.0.write(out);
+        self.1.write(out);
+        self.2.write(out);
+        self.3.write(out);
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        Ok((read(input)?, read(input)?, read(input)?, read(input)?))
+    }
+}
+
+/// Fieldless enums, as the index of the variant in the list.
+/// `write` matches exhaustively, so a new variant does not compile until it is listed.
+macro_rules! wire_enum {
+    ($($ty:ident { $($variant:ident),* $(,)? })*) => {$(
+        impl<'a> Wire<'a> for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                #[allow(non_camel_case_types, clippy::enum_variant_names)]
+                enum Index { $($variant),* }
+                let index = match self { $($ty::$variant => Index::$variant),* };
+                (index as u8).write(out)
+            }
+            fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+                const VARIANTS: &[$ty] = &[$($ty::$variant),*];
+                VARIANTS
+                    .get(u8::read(input)? as usize)
+                    .copied()
+                    .ok_or(TraceError::InvalidData)
+            }
+        }
+    )*};
+}
+
+macro_rules! wire_struct {
+    ($($ty:ident { $($field:ident),* $(,)? })*) => {$(
+        impl<'a> Wire<'a> for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                $(self.$field.write(out);)*
+            }
+            fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+                Ok($ty { $($field: read(input)?),* })
+            }
+        }
+    )*};
+}
+
+/// Recorded calls. Getters, like `texture_params` or `buffer_size`, are not recorded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    NewShader,
+    NewTexture,
+    TextureSetMinFilter,
+    TextureSetMagFilter,
+    TextureSetWrap,
+    TextureGenerateMipmaps,
+    TextureResize,
+    TextureReadPixels,
+    BeginReadPixels,
+    TryFinishReadPixels,
+    TextureUpdateLayerPart,
+    CopyTextureRegion,
+    NewRenderPassLayer,
+    DeleteRenderPass,
+    BlitRenderPass,
+    NewPipeline,
+    ApplyPipeline,
+    DeletePipeline,
+    NewBuffer,
+    BufferUpdateRange,
+    DeleteBuffer,
+    DeleteTexture,
+    DeleteShader,
+    TextureSetLabel,
+    BufferSetLabel,
+    ShaderSetLabel,
+    PipelineSetLabel,
+    RenderPassSetLabel,
+    ApplyViewport,
+    ApplyScissorRect,
+    ApplyBindings,
+    ApplyUniforms,
+    ApplyUniformBlock,
+    Clear,
+    BeginPass,
+    EndRenderPass,
+    ReadDefaultFramebuffer,
+    CommitFrame,
+    Draw,
+    DrawArrays,
+    DrawBaseVertex,
+    DrawIndirect,
+    MultiDrawIndirect,
+    NewQuery,
+    BeginQuery,
+    EndQuery,
+    QueryAvailable,
+    QueryResult,
+    DeleteQuery,
+    ReplaceShader,
+}
+
+wire_enum! {
+    Op {
+        NewShader,
+        NewTexture,
+        TextureSetMinFilter,
+        TextureSetMagFilter,
+        TextureSetWrap,
+        TextureGenerateMipmaps,
+        TextureResize,
+        TextureReadPixels,
+        BeginReadPixels,
+        TryFinishReadPixels,
+        TextureUpdateLayerPart,
+        CopyTextureRegion,
+        NewRenderPassLayer,
+        DeleteRenderPass,
+        BlitRenderPass,
+        NewPipeline,
+        ApplyPipeline,
+        DeletePipeline,
+        NewBuffer,
+        BufferUpdateRange,
+        DeleteBuffer,
+        DeleteTexture,
+        DeleteShader,
+        TextureSetLabel,
+        BufferSetLabel,
+        ShaderSetLabel,
+        PipelineSetLabel,
+        RenderPassSetLabel,
+        ApplyViewport,
+        ApplyScissorRect,
+        ApplyBindings,
+        ApplyUniforms,
+        ApplyUniformBlock,
+        Clear,
+        BeginPass,
+        EndRenderPass,
+        ReadDefaultFramebuffer,
+        CommitFrame,
+        Draw,
+        DrawArrays,
+        DrawBaseVertex,
+        DrawIndirect,
+        MultiDrawIndirect,
+        NewQuery,
+        BeginQuery,
+        EndQuery,
+        QueryAvailable,
+        QueryResult,
+        DeleteQuery,
+        ReplaceShader,
+    }
+    UniformType { Float1, Float2, Float3, Float4, Int1, Int2, Int3, Int4, Mat4 }
+    VertexFormat {
+        Float1, Float2, Float3, Float4,
+        Byte1, Byte2, Byte3, Byte4,
+        Short1, Short2, Short3, Short4,
+        Int1, Int2, Int3, Int4,
+        Mat4,
+    }
+    VertexStep { PerVertex, PerInstance }
+    TextureFormat {
+        RGB8, RGBA8, RGBA16F, Depth, Depth32, Alpha, R8, RG8, R16F, RG16F, R32F, RGBA32F,
+        R32UI, SRGBA8, Depth24Stencil8, BC1, BC2, BC3, BC4, BC5, BC6H, BC7, ETC2RGB, ETC2RGBA,
+        ASTC4x4, ASTC5x5, ASTC6x6, ASTC8x8, BC1SRGB, BC2SRGB, BC3SRGB, BC7SRGB, ETC2SRGB,
+        ETC2SRGBA, ASTC4x4SRGB, ASTC5x5SRGB, ASTC6x6SRGB, ASTC8x8SRGB,
+    }
+    TextureWrap { Repeat, Mirror, Clamp }
+    FilterMode { Linear, Nearest }
+    MipmapFilterMode { None, Linear, Nearest }
+    TextureAccess { Static, RenderTarget }
+    TextureKind { Texture2D, CubeMap, Texture2DArray, Texture3D }
+    StencilOp {
+        Keep, Zero, Replace, IncrementClamp, DecrementClamp, Invert, IncrementWrap, DecrementWrap,
+    }
+    CompareFunc { Always, Never, Less, Equal, LessOrEqual, Greater, NotEqual, GreaterOrEqual }
+    CullFace { Nothing, Front, Back }
+    FrontFaceOrder { Clockwise, CounterClockwise }
+    Comparison { Never, Less, LessOrEqual, Greater, GreaterOrEqual, Equal, NotEqual, Always }
+    Equation { Add, Subtract, ReverseSubtract }
+    BlendValue { SourceColor, SourceAlpha, DestinationColor, DestinationAlpha }
+    PrimitiveType { Triangles, Lines, Points }
+    BufferType { VertexBuffer, IndexBuffer, UniformBuffer, IndirectBuffer }
+    BufferUsage { Immutable, Dynamic, Stream }
+    QueryType { TimeElapsed, AnySamplesPassed, SamplesPassed }
+}
+
+wire_struct! {
+    UniformDesc { name, uniform_type, array_count }
+    UniformBlockLayout { uniforms }
+    UniformBlockDesc { name, layout }
+    ShaderMeta { uniforms, uniform_blocks, images }
+    BufferLayout { stride, step_func, step_rate }
+    TextureParams {
+        kind, format, wrap, min_filter, mag_filter, mipmap_filter,
+        width, height, depth, allocate_mipmaps, sample_count,
+    }
+    BlendState { equation, sfactor, dfactor }
+    StencilState { front, back }
+    StencilFaceState { fail_op, depth_fail_op, pass_op, test_func, test_ref, test_mask, write_mask }
+    PipelineParams {
+        cull_face, front_face_order, depth_test, depth_write, depth_write_offset,
+        color_blend, alpha_blend, stencil_test, color_write, primitive_type,
+    }
+}
+
+impl<'a> Wire<'a> for BlendFactor {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            BlendFactor::Zero => 0u8.write(out),
+            BlendFactor::One => 1u8.write(out),
+            BlendFactor::Value(value) => {
+                2u8.write(out);
+                value.write(out);
+            }
+            BlendFactor::OneMinusValue(value) => {
+                3u8.write(out);
+                value.write(out);
+            }
+            BlendFactor::SourceAlphaSaturate => 4u8.write(out),
+        }
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        match u8::read(input)? {
+            0 => Ok(BlendFactor::Zero),
+            1 => Ok(BlendFactor::One),
+            2 => Ok(BlendFactor::Value(read(input)?)),
+            3 => Ok(BlendFactor::OneMinusValue(read(input)?)),
+            4 => Ok(BlendFactor::SourceAlphaSaturate),
+            _ => Err(TraceError::InvalidData),
+        }
+    }
+}
+
+impl<'a> Wire<'a> for PassAction {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            PassAction::Nothing => 0u8.write(out),
+            PassAction::Clear {
+                color,
+                depth,
+                stencil,
+            } => {
+                1u8.write(out);
+                color.write(out);
+                depth.write(out);
+                stencil.write(out);
+            }
+        }
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        match u8::read(input)? {
+            0 => Ok(PassAction::Nothing),
+            1 => Ok(PassAction::Clear {
+                color: read(input)?,
+                depth: read(input)?,
+                stencil: read(input)?,
+            }),
+            _ => Err(TraceError::InvalidData),
+        }
+    }
+}
+
+impl<'a> Wire<'a> for ShaderSource<'a> {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            ShaderSource::Glsl { vertex, fragment } => {
+                0u8.write(out);
+                vertex.write(out);
+                fragment.write(out);
+            }
+            ShaderSource::Msl { program } => {
+                1u8.write(out);
+                program.write(out);
+            }
+        }
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        match u8::read(input)? {
+            0 => Ok(ShaderSource::Glsl {
+                vertex: read(input)?,
+                fragment: read(input)?,
+            }),
+            1 => Ok(ShaderSource::Msl {
+                program: read(input)?,
+            }),
+            _ => Err(TraceError::InvalidData),
+        }
+    }
+}
+
+impl<'a> Wire<'a> for BufferSource<'a> {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            BufferSource::Slice(data) => {
+                let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+                0u8.write(out);
+                data.element_size.write(out);
+                bytes.write(out);
+            }
+            BufferSource::Empty { size, element_size } => {
+                1u8.write(out);
+                size.write(out);
+                element_size.write(out);
+            }
+        }
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        match u8::read(input)? {
+            0 => {
+                let element_size = read(input)?;
+                let bytes: &'a [u8] = read(input)?;
+                Ok(unsafe { BufferSource::pointer(bytes.as_ptr(), bytes.len(), element_size) })
+            }
+            1 => Ok(BufferSource::Empty {
+                size: read(input)?,
+                element_size: read(input)?,
+            }),
+            _ => Err(TraceError::InvalidData),
+        }
+    }
+}
+
+impl<'a> Wire<'a> for VertexAttribute {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.name.write(out);
+        self.format.write(out);
+        self.buffer_index.write(out);
+    }
+    fn read(input: &mut Reader<'a>) -> Result<Self, TraceError> {
+        // the pipeline API wants &'static names, replaying leaks a few bytes per pipeline
+        let name: String = read(input)?;
+        Ok(VertexAttribute::with_buffer(
+            Box::leak(name.into_boxed_str()),
+            read(input)?,
+            read(input)?,
+        ))
+    }
+}
+
+/// `TextureSource::Array` is written as `Vec<Vec<&[u8]>>` and can't be read back
+/// without somewhere to keep the inner slices, see `Replay::new_texture`.
+fn write_texture_source(source: &TextureSource, out: &mut Vec<u8>) {
+    match source {
+        TextureSource::Empty => 0u8.write(out),
+        TextureSource::Bytes(bytes) => {
+            1u8.write(out);
+            bytes.write(out);
+        }
+        TextureSource::Array(array) => {
+            2u8.write(out);
+            let array: Vec<Vec<&[u8]>> = array.iter().map(|levels| levels.to_vec()).collect();
+            array.write(out);
+        }
+    }
+}
+
+macro_rules! record {
+    ($self:ident, $op:expr $(, $arg:expr)* $(,)?) => {{
+        let mut trace = $self.trace.borrow_mut();
+        $op.write(&mut trace);
+        $($arg.write(&mut trace);)*
+    }};
+}
+
+fn id<H: Eq + Hash>(ids: &HashMap<H, u32>, handle: H) -> u32 {
+    ids.get(&handle).copied().unwrap_or(UNKNOWN)
+}
+
+/// Wraps a backend, records every call into a `.mqtrace` and forwards it.
+///
+/// Wrap the backend right after creating it: the trace keeps every resource from its creation,
+/// so it replays from the start even if only the last frame shows the bug.
+/// Textures the wrapper did not see created, like the ones made with
+/// `TextureId::from_raw_id`, are replayed as empty textures with the same params.
+/// ```no_run
+/// # use miniquad::*;
+/// let ctx: Box<dyn RenderingBackend> =
+///     Box::new(TracingBackend::new(window::new_rendering_backend()));
+/// ```
+pub struct TracingBackend<B: RenderingBackend + ?Sized> {
+    // RenderingBackend::draw takes &self
+    trace: RefCell<Vec<u8>>,
+    next_id: u32,
+    shaders: HashMap<ShaderId, u32>,
+    textures: HashMap<TextureId, u32>,
+    passes: HashMap<RenderPass, u32>,
+    pipelines: HashMap<Pipeline, u32>,
+    buffers: HashMap<BufferId, u32>,
+    queries: HashMap<QueryId, u32>,
+    readbacks: HashMap<ReadbackId, u32>,
+    inner: Box<B>,
+}
+
+impl<B: RenderingBackend + ?Sized> TracingBackend<B> {
+    pub fn new(inner: Box<B>) -> TracingBackend<B> {
+        let mut trace = MAGIC.to_vec();
+        VERSION.write(&mut trace);
+        TracingBackend {
+            trace: RefCell::new(trace),
+            next_id: 0,
+            shaders: HashMap::new(),
+            textures: HashMap::new(),
+            passes: HashMap::new(),
+            pipelines: HashMap::new(),
+            buffers: HashMap::new(),
+            queries: HashMap::new(),
+            readbacks: HashMap::new(),
+            inner,
+        }
+    }
+
+    /// Contents of a `.mqtrace` file with all the calls recorded so far.
+    pub fn trace(&self) -> Vec<u8> {
+        self.trace.borrow().clone()
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> Box<B> {
+        self.inner
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    #[track_caller]
+    fn texture(&mut self, texture: TextureId) -> u32 {
+        if let Some(id) = self.textures.get(&texture) {
+            return *id;
+        }
+        let params = self.inner.texture_params(texture);
+        let id = self.next_id();
+        self.textures.insert(texture, id);
+        record!(self, Op::NewTexture, id, TextureAccess::Static, params);
+        write_texture_source(&TextureSource::Empty, &mut self.trace.borrow_mut());
+        id
+    }
+
+    #[track_caller]
+    fn textures(&mut self, textures: &[TextureId]) -> Vec<u32> {
+        textures
+            .iter()
+            .map(|texture| self.texture(*texture))
+            .collect()
+    }
+
+    fn record_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        result: &Result<ShaderId, ShaderError>,
+    ) {
+        if let Ok(shader_id) = result {
+            let id = self.next_id();
+            self.shaders.insert(*shader_id, id);
+            record!(self, Op::NewShader, id, shader, meta);
+        }
+    }
+}
+
+impl<B: RenderingBackend + ?Sized> RenderingBackend for TracingBackend<B> {
+    fn info(&self) -> ContextInfo {
+        let mut info = self.inner.info();
+        // see new_shader_from_binary
+        info.features.program_binary = false;
+        info
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let result = self.inner.new_shader(shader, meta.clone());
+        self.record_shader(shader, meta, &result);
+        result
+    }
+
+    fn reflect_shader(&mut self, shader: ShaderSource) -> Result<ShaderReflection, ShaderError> {
+        self.inner.reflect_shader(shader)
+    }
+
+    fn new_shader_validated(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let result = self.inner.new_shader_validated(shader, meta.clone());
+        self.record_shader(shader, meta, &result);
+        result
+    }
+
+    fn new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        let id = self.next_id();
+        record!(self, Op::NewTexture, id, access, params);
+        write_texture_source(&data, &mut self.trace.borrow_mut());
+        let texture = self.inner.new_texture(access, data, params);
+        self.textures.insert(texture, id);
+        texture
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.inner.texture_params(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.inner.texture_raw_id(texture)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureSetMinFilter, id, filter, mipmap_filter);
+        self.inner
+            .texture_set_min_filter(texture, filter, mipmap_filter)
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureSetMagFilter, id, filter);
+        self.inner.texture_set_mag_filter(texture, filter)
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureSetWrap, id, wrap_x, wrap_y);
+        self.inner.texture_set_wrap(texture, wrap_x, wrap_y)
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureGenerateMipmaps, id);
+        self.inner.texture_generate_mipmaps(texture)
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureResize, id, width, height, bytes);
+        self.inner.texture_resize(texture, width, height, bytes)
+    }
+
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureReadPixels, id, bytes.len());
+        self.inner.texture_read_pixels(texture, bytes)
+    }
+
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId {
+        let texture_id = self.texture(texture);
+        let id = self.next_id();
+        record!(self, Op::BeginReadPixels, id, texture_id);
+        let readback = self.inner.begin_read_pixels(texture);
+        self.readbacks.insert(readback, id);
+        readback
+    }
+
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool {
+        record!(
+            self,
+            Op::TryFinishReadPixels,
+            id(&self.readbacks, readback),
+            bytes.len()
+        );
+        self.inner.try_finish_read_pixels(readback, bytes)
+    }
+
+    fn texture_update_layer_part(
+        &mut self,
+        texture: TextureId,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        let id = self.texture(texture);
+        record!(
+            self,
+            Op::TextureUpdateLayerPart,
+            id,
+            layer,
+            (x_offset, y_offset, width, height),
+            bytes
+        );
+        self.inner
+            .texture_update_layer_part(texture, layer, x_offset, y_offset, width, height, bytes)
+    }
+
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    ) {
+        let (src_id, dst_id) = (self.texture(src), self.texture(dst));
+        record!(
+            self,
+            Op::CopyTextureRegion,
+            src_id,
+            src_rect,
+            dst_id,
+            dst_pos
+        );
+        self.inner.copy_texture_region(src, src_rect, dst, dst_pos)
+    }
+
+    fn new_render_pass_layer(
+        &mut self,
+        color_img: &[TextureId],
+        depth_img: Option<TextureId>,
+        layer: u32,
+    ) -> RenderPass {
+        let color_ids = self.textures(color_img);
+        let depth_id = depth_img.map(|texture| self.texture(texture));
+        let id = self.next_id();
+        record!(self, Op::NewRenderPassLayer, id, color_ids, depth_id, layer);
+        let pass = self
+            .inner
+            .new_render_pass_layer(color_img, depth_img, layer);
+        self.passes.insert(pass, id);
+        pass
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        self.inner.render_pass_color_attachments(render_pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        record!(self, Op::DeleteRenderPass, id(&self.passes, render_pass));
+        self.passes.remove(&render_pass);
+        self.inner.delete_render_pass(render_pass)
+    }
+
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode) {
+        record!(
+            self,
+            Op::BlitRenderPass,
+            id(&self.passes, src),
+            dst.map(|dst| id(&self.passes, dst)),
+            filter
+        );
+        self.inner.blit_render_pass(src, dst, filter)
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        let id = self.next_id();
+        record!(
+            self,
+            Op::NewPipeline,
+            id,
+            buffer_layout.to_vec(),
+            attributes.to_vec(),
+            self::id(&self.shaders, shader),
+            params
+        );
+        let pipeline = self
+            .inner
+            .new_pipeline(buffer_layout, attributes, shader, params);
+        self.pipelines.insert(pipeline, id);
+        pipeline
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        record!(self, Op::ApplyPipeline, id(&self.pipelines, *pipeline));
+        self.inner.apply_pipeline(pipeline)
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        record!(self, Op::DeletePipeline, id(&self.pipelines, pipeline));
+        self.pipelines.remove(&pipeline);
+        self.inner.delete_pipeline(pipeline)
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let id = self.next_id();
+        record!(self, Op::NewBuffer, id, type_, usage, data);
+        let buffer = self.inner.new_buffer(type_, usage, data);
+        self.buffers.insert(buffer, id);
+        buffer
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        record!(
+            self,
+            Op::BufferUpdateRange,
+            id(&self.buffers, buffer),
+            offset,
+            data
+        );
+        self.inner.buffer_update_range(buffer, offset, data)
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.inner.buffer_size(buffer)
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        record!(self, Op::DeleteBuffer, id(&self.buffers, buffer));
+        self.buffers.remove(&buffer);
+        self.inner.delete_buffer(buffer)
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        let id = self.texture(texture);
+        record!(self, Op::DeleteTexture, id);
+        self.textures.remove(&texture);
+        self.inner.delete_texture(texture)
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        record!(self, Op::DeleteShader, id(&self.shaders, program));
+        self.shaders.remove(&program);
+        self.inner.delete_shader(program)
+    }
+
+    fn replace_shader(
+        &mut self,
+        program: ShaderId,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        let result = self.inner.replace_shader(program, shader, meta.clone());
+        if result.is_ok() {
+            let id = id(&self.shaders, program);
+            record!(self, Op::ReplaceShader, id, shader, meta);
+        }
+        result
+    }
+
+    fn shader_binary(&mut self, shader: ShaderId) -> Option<ShaderBinary> {
+        self.inner.shader_binary(shader)
+    }
+
+    fn new_shader_from_binary(
+        &mut self,
+        _binary: &ShaderBinary,
+        _meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        // a binary only loads on the driver it came from, shaders compiled from source
+        // keep the trace replayable anywhere
+        Err(ShaderError::ProgramBinaryRejected)
+    }
+
+    fn texture_set_label(&mut self, texture: TextureId, label: &str) {
+        let id = self.texture(texture);
+        record!(self, Op::TextureSetLabel, id, label);
+        self.inner.texture_set_label(texture, label)
+    }
+
+    fn buffer_set_label(&mut self, buffer: BufferId, label: &str) {
+        record!(self, Op::BufferSetLabel, id(&self.buffers, buffer), label);
+        self.inner.buffer_set_label(buffer, label)
+    }
+
+    fn shader_set_label(&mut self, shader: ShaderId, label: &str) {
+        record!(self, Op::ShaderSetLabel, id(&self.shaders, shader), label);
+        self.inner.shader_set_label(shader, label)
+    }
+
+    fn pipeline_set_label(&mut self, pipeline: Pipeline, label: &str) {
+        record!(
+            self,
+            Op::PipelineSetLabel,
+            id(&self.pipelines, pipeline),
+            label
+        );
+        self.inner.pipeline_set_label(pipeline, label)
+    }
+
+    fn render_pass_set_label(&mut self, render_pass: RenderPass, label: &str) {
+        record!(
+            self,
+            Op::RenderPassSetLabel,
+            id(&self.passes, render_pass),
+            label
+        );
+        self.inner.render_pass_set_label(render_pass, label)
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        record!(self, Op::ApplyViewport, (x, y, w, h));
+        self.inner.apply_viewport(x, y, w, h)
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        record!(self, Op::ApplyScissorRect, (x, y, w, h));
+        self.inner.apply_scissor_rect(x, y, w, h)
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
+        index_buffer: BufferId,
+        index_buffer_offset: usize,
+        textures: &[TextureId],
+    ) {
+        let vertex_buffer_ids: Vec<u32> = vertex_buffers
+            .iter()
+            .map(|buffer| id(&self.buffers, *buffer))
+            .collect();
+        let texture_ids = self.textures(textures);
+        record!(
+            self,
+            Op::ApplyBindings,
+            vertex_buffer_ids,
+            vertex_buffer_offsets.to_vec(),
+            id(&self.buffers, index_buffer),
+            index_buffer_offset,
+            texture_ids
+        );
+        self.inner.apply_bindings_from_slice(
+            vertex_buffers,
+            vertex_buffer_offsets,
+            index_buffer,
+            index_buffer_offset,
+            textures,
+        )
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        record!(self, Op::ApplyUniforms, bytes);
+        self.inner.apply_uniforms_from_bytes(uniform_ptr, size)
+    }
+
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize) {
+        record!(
+            self,
+            Op::ApplyUniformBlock,
+            block,
+            id(&self.buffers, buffer),
+            offset
+        );
+        self.inner.apply_uniform_block(block, buffer, offset)
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        record!(self, Op::Clear, color, depth, stencil);
+        self.inner.clear(color, depth, stencil)
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        record!(self, Op::BeginPass, None::<u32>, action);
+        self.inner.begin_default_pass(action)
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        record!(
+            self,
+            Op::BeginPass,
+            pass.map(|pass| id(&self.passes, pass)),
+            action
+        );
+        self.inner.begin_pass(pass, action)
+    }
+
+    fn end_render_pass(&mut self) {
+        record!(self, Op::EndRenderPass);
+        self.inner.end_render_pass()
+    }
+
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8> {
+        record!(self, Op::ReadDefaultFramebuffer, rect);
+        self.inner.read_default_framebuffer(rect)
+    }
+
+    fn commit_frame(&mut self) {
+        record!(self, Op::CommitFrame);
+        self.inner.commit_frame()
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        record!(self, Op::Draw, base_element, num_elements, num_instances);
+        self.inner.draw(base_element, num_elements, num_instances)
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        record!(
+            self,
+            Op::DrawArrays,
+            first_vertex,
+            num_vertices,
+            num_instances
+        );
+        self.inner
+            .draw_arrays(first_vertex, num_vertices, num_instances)
+    }
+
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    ) {
+        record!(
+            self,
+            Op::DrawBaseVertex,
+            (base_element, num_elements, base_vertex, num_instances)
+        );
+        self.inner
+            .draw_base_vertex(base_element, num_elements, base_vertex, num_instances)
+    }
+
+    fn draw_indirect(&self, buffer: BufferId, offset: usize) {
+        record!(self, Op::DrawIndirect, id(&self.buffers, buffer), offset);
+        self.inner.draw_indirect(buffer, offset)
+    }
+
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32) {
+        record!(
+            self,
+            Op::MultiDrawIndirect,
+            id(&self.buffers, buffer),
+            offset,
+            draw_count,
+            stride
+        );
+        self.inner
+            .multi_draw_indirect(buffer, offset, draw_count, stride)
+    }
+
+    fn new_query(&mut self, query_type: QueryType) -> QueryId {
+        let id = self.next_id();
+        record!(self, Op::NewQuery, id, query_type);
+        let query = self.inner.new_query(query_type);
+        self.queries.insert(query, id);
+        query
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        record!(self, Op::BeginQuery, id(&self.queries, query));
+        self.inner.begin_query(query)
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        record!(self, Op::EndQuery, id(&self.queries, query));
+        self.inner.end_query(query)
+    }
+
+    fn query_available(&mut self, query: QueryId) -> bool {
+        record!(self, Op::QueryAvailable, id(&self.queries, query));
+        self.inner.query_available(query)
+    }
+
+    fn query_result(&mut self, query: QueryId) -> u64 {
+        record!(self, Op::QueryResult, id(&self.queries, query));
+        self.inner.query_result(query)
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        record!(self, Op::DeleteQuery, id(&self.queries, query));
+        self.queries.remove(&query);
+        self.inner.delete_query(query)
+    }
+}
+
+/// Resources created by the replay, by their trace id.
+#[derive(Default)]
+struct Resources {
+    shaders: HashMap<u32, ShaderId>,
+    textures: HashMap<u32, TextureId>,
+    passes: HashMap<u32, RenderPass>,
+    pipelines: HashMap<u32, Pipeline>,
+    buffers: HashMap<u32, BufferId>,
+    queries: HashMap<u32, QueryId>,
+    readbacks: HashMap<u32, ReadbackId>,
+}
+
+fn get<H: Copy>(resources: &HashMap<u32, H>, input: &mut Reader) -> Result<H, TraceError> {
+    let id = u32::read(input)?;
+    resources
+        .get(&id)
+        .copied()
+        .ok_or(TraceError::UnknownResource(id))
+}
+
+fn get_all<H: Copy>(resources: &HashMap<u32, H>, input: &mut Reader) -> Result<Vec<H>, TraceError> {
+    let ids: Vec<u32> = read(input)?;
+    ids.into_iter()
+        .map(|id| {
+            resources
+                .get(&id)
+                .copied()
+                .ok_or(TraceError::UnknownResource(id))
+        })
+        .collect()
+}
+
+/// Runs a `.mqtrace` against a backend.
+///
+/// The pixels read back and query results are dropped, the calls are only there
+/// to reproduce the stalls and the state changes they made on the recording backend.
+pub struct Replay {
+    trace: Vec<u8>,
+    position: usize,
+    resources: Resources,
+}
+
+impl Replay {
+    pub fn new(trace: Vec<u8>) -> Result<Replay, TraceError> {
+        let mut input = Reader { bytes: &trace };
+        if input.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(TraceError::NotATrace);
+        }
+        let version = u32::read(&mut input)?;
+        if version > VERSION {
+            return Err(TraceError::UnsupportedVersion(version));
+        }
+        Ok(Replay {
+            position: trace.len() - input.bytes.len(),
+            trace,
+            resources: Resources::default(),
+        })
+    }
+
+    /// Replay the calls up to and including the next `commit_frame`.
+    /// Returns false once the whole trace is replayed.
+    pub fn replay_frame(&mut self, ctx: &mut dyn RenderingBackend) -> Result<bool, TraceError> {
+        let mut input = Reader {
+            bytes: &self.trace[self.position..],
+        };
+        while !input.bytes.is_empty() {
+            let op = Op::read(&mut input)?;
+            self.resources.call(op, &mut input, ctx)?;
+            self.position = self.trace.len() - input.bytes.len();
+            if op == Op::CommitFrame {
+                break;
+            }
+        }
+        Ok(!input.bytes.is_empty())
+    }
+}
+
+impl Resources {
+    fn call(
+        &mut self,
+        op: Op,
+        input: &mut Reader,
+        ctx: &mut dyn RenderingBackend,
+    ) -> Result<(), TraceError> {
+        match op {
+            Op::NewShader => {
+                let id = read(input)?;
+                let shader = ctx
+                    .new_shader(read(input)?, read(input)?)
+                    .map_err(TraceError::Shader)?;
+                self.shaders.insert(id, shader);
+            }
+            Op::NewTexture => {
+                let id = read(input)?;
+                let access = read(input)?;
+                let params = read(input)?;
+                let texture = match u8::read(input)? {
+                    0 => ctx.new_texture(access, TextureSource::Empty, params),
+                    1 => ctx.new_texture(access, TextureSource::Bytes(read(input)?), params),
+                    2 => {
+                        let array: Vec<Vec<&[u8]>> = read(input)?;
+                        let array: Vec<&[&[u8]]> = array.iter().map(Vec::as_slice).collect();
+                        ctx.new_texture(access, TextureSource::Array(&array), params)
+                    }
+                    _ => return Err(TraceError::InvalidData),
+                };
+                self.textures.insert(id, texture);
+            }
+            Op::TextureSetMinFilter => {
+                let texture = get(&self.textures, input)?;
+                ctx.texture_set_min_filter(texture, read(input)?, read(input)?);
+            }
+            Op::TextureSetMagFilter => {
+                let texture = get(&self.textures, input)?;
+                ctx.texture_set_mag_filter(texture, read(input)?);
+            }
+            Op::TextureSetWrap => {
+                let texture = get(&self.textures, input)?;
+                ctx.texture_set_wrap(texture, read(input)?, read(input)?);
+            }
+            Op::TextureGenerateMipmaps => {
+                ctx.texture_generate_mipmaps(get(&self.textures, input)?);
+            }
+            Op::TextureResize => {
+                let texture = get(&self.textures, input)?;
+                ctx.texture_resize(texture, read(input)?, read(input)?, read(input)?);
+            }
+            Op::TextureReadPixels => {
+                let texture = get(&self.textures, input)?;
+                let mut bytes = vec![0; read(input)?];
+                ctx.texture_read_pixels(texture, &mut bytes);
+            }
+            Op::BeginReadPixels => {
+                let id = read(input)?;
+                let readback = ctx.begin_read_pixels(get(&self.textures, input)?);
+                self.readbacks.insert(id, readback);
+            }
+            Op::TryFinishReadPixels => {
+                let readback = get(&self.readbacks, input)?;
+                let mut bytes = vec![0; read(input)?];
+                ctx.try_finish_read_pixels(readback, &mut bytes);
+            }
+            Op::TextureUpdateLayerPart => {
+                let texture = get(&self.textures, input)?;
+                let layer = read(input)?;
+                let (x, y, w, h) = read(input)?;
+                ctx.texture_update_layer_part(texture, layer, x, y, w, h, read(input)?);
+            }
+            Op::CopyTextureRegion => {
+                let src = get(&self.textures, input)?;
+                let src_rect = read(input)?;
+                let dst = get(&self.textures, input)?;
+                ctx.copy_texture_region(src, src_rect, dst, read(input)?);
+            }
+            Op::NewRenderPassLayer => {
+                let id = read(input)?;
+                let color_img = get_all(&self.textures, input)?;
+                let depth_img = match bool::read(input)? {
+                    true => Some(get(&self.textures, input)?),
+                    false => None,
+                };
+                let pass = ctx.new_render_pass_layer(&color_img, depth_img, read(input)?);
+                self.passes.insert(id, pass);
+            }
+            Op::DeleteRenderPass => {
+                ctx.delete_render_pass(get(&self.passes, input)?);
+            }
+            Op::BlitRenderPass => {
+                let src = get(&self.passes, input)?;
+                let dst = match bool::read(input)? {
+                    true => Some(get(&self.passes, input)?),
+                    false => None,
+                };
+                ctx.blit_render_pass(src, dst, read(input)?);
+            }
+            Op::NewPipeline => {
+                let id = read(input)?;
+                let buffer_layout: Vec<BufferLayout> = read(input)?;
+                let attributes: Vec<VertexAttribute> = read(input)?;
+                let shader = get(&self.shaders, input)?;
+                let pipeline = ctx.new_pipeline(&buffer_layout, &attributes, shader, read(input)?);
+                self.pipelines.insert(id, pipeline);
+            }
+            Op::ApplyPipeline => {
+                ctx.apply_pipeline(&get(&self.pipelines, input)?);
+            }
+            Op::DeletePipeline => {
+                ctx.delete_pipeline(get(&self.pipelines, input)?);
+            }
+            Op::NewBuffer => {
+                let id = read(input)?;
+                let buffer = ctx.new_buffer(read(input)?, read(input)?, read(input)?);
+                self.buffers.insert(id, buffer);
+            }
+            Op::BufferUpdateRange => {
+                let buffer = get(&self.buffers, input)?;
+                ctx.buffer_update_range(buffer, read(input)?, read(input)?);
+            }
+            Op::DeleteBuffer => {
+                ctx.delete_buffer(get(&self.buffers, input)?);
+            }
+            Op::DeleteTexture => {
+                ctx.delete_texture(get(&self.textures, input)?);
+            }
+            Op::DeleteShader => {
+                ctx.delete_shader(get(&self.shaders, input)?);
+            }
+            Op::ReplaceShader => {
+                let shader = get(&self.shaders, input)?;
+                ctx.replace_shader(shader, read(input)?, read(input)?)
+                    .map_err(TraceError::Shader)?;
+            }
+            Op::TextureSetLabel => {
+                ctx.texture_set_label(get(&self.textures, input)?, read(input)?);
+            }
+            Op::BufferSetLabel => {
+                ctx.buffer_set_label(get(&self.buffers, input)?, read(input)?);
+            }
+            Op::ShaderSetLabel => {
+                ctx.shader_set_label(get(&self.shaders, input)?, read(input)?);
+            }
+            Op::PipelineSetLabel => {
+                ctx.pipeline_set_label(get(&self.pipelines, input)?, read(input)?);
+            }
+            Op::RenderPassSetLabel => {
+                ctx.render_pass_set_label(get(&self.passes, input)?, read(input)?);
+            }
+            Op::ApplyViewport => {
+                let (x, y, w, h) = read(input)?;
+                ctx.apply_viewport(x, y, w, h);
+            }
+            Op::ApplyScissorRect => {
+                let (x, y, w, h) = read(input)?;
+                ctx.apply_scissor_rect(x, y, w, h);
+            }
+            Op::ApplyBindings => {
+                let vertex_buffers = get_all(&self.buffers, input)?;
+                let vertex_buffer_offsets: Vec<usize> = read(input)?;
+                let index_buffer = get(&self.buffers, input)?;
+                let index_buffer_offset = read(input)?;
+                let textures = get_all(&self.textures, input)?;
+                ctx.apply_bindings_from_slice(
+                    &vertex_buffers,
+                    &vertex_buffer_offsets,
+                    index_buffer,
+                    index_buffer_offset,
+                    &textures,
+                );
+            }
+            Op::ApplyUniforms => {
+                let bytes: &[u8] = read(input)?;
+                // backends read the uniforms as f32 and i32
+                let mut words = vec![0u32; bytes.len().div_ceil(4)];
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        bytes.as_ptr(),
+                        words.as_mut_ptr() as *mut u8,
+                        bytes.len(),
+                    );
+                }
+                ctx.apply_uniforms_from_bytes(words.as_ptr() as _, bytes.len());
+            }
+            Op::ApplyUniformBlock => {
+                let block = read(input)?;
+                let buffer = get(&self.buffers, input)?;
+                ctx.apply_uniform_block(block, buffer, read(input)?);
+            }
+            Op::Clear => {
+                ctx.clear(read(input)?, read(input)?, read(input)?);
+            }
+            Op::BeginPass => {
+                let pass = match bool::read(input)? {
+                    true => Some(get(&self.passes, input)?),
+                    false => None,
+                };
+                ctx.begin_pass(pass, read(input)?);
+            }
+            Op::EndRenderPass => ctx.end_render_pass(),
+            Op::ReadDefaultFramebuffer => {
+                ctx.read_default_framebuffer(read(input)?);
+            }
+            Op::CommitFrame => ctx.commit_frame(),
+            Op::Draw => ctx.draw(read(input)?, read(input)?, read(input)?),
+            Op::DrawArrays => ctx.draw_arrays(read(input)?, read(input)?, read(input)?),
+            Op::DrawBaseVertex => {
+                let (base_element, num_elements, base_vertex, num_instances) = read(input)?;
+                ctx.draw_base_vertex(base_element, num_elements, base_vertex, num_instances);
+            }
+            Op::DrawIndirect => {
+                ctx.draw_indirect(get(&self.buffers, input)?, read(input)?);
+            }
+            Op::MultiDrawIndirect => {
+                let buffer = get(&self.buffers, input)?;
+                ctx.multi_draw_indirect(buffer, read(input)?, read(input)?, read(input)?);
+            }
+            Op::NewQuery => {
+                let id = read(input)?;
+                let query = ctx.new_query(read(input)?);
+                self.queries.insert(id, query);
+            }
+            Op::BeginQuery => ctx.begin_query(get(&self.queries, input)?),
+            Op::EndQuery => ctx.end_query(get(&self.queries, input)?),
+            Op::QueryAvailable => {
+                ctx.query_available(get(&self.queries, input)?);
+            }
+            Op::QueryResult => {
+                ctx.query_result(get(&self.queries, input)?);
+            }
+            Op::DeleteQuery => ctx.delete_query(get(&self.queries, input)?),
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_trace_replay() {
+    use super::recording::Command;
+
+    let mut ctx = TracingBackend::new(Box::new(RecordingContext::new()));
+    let texture = ctx.new_texture_from_rgba8(1, 1, &[1, 2, 3, 4]);
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0.0f32, 1.0, 2.0]),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2]),
+    );
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: "vertex",
+                fragment: "fragment",
+            },
+            ShaderMeta {
+                uniforms: UniformBlockLayout {
+                    uniforms: vec![UniformDesc::new("offset", UniformType::Float2)],
+                },
+                uniform_blocks: vec![],
+                images: vec!["tex".to_string()],
+            },
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float1)],
+        shader,
+        PipelineParams {
+            color_blend: Some(BlendState::new(
+                Equation::Add,
+                BlendFactor::Value(BlendValue::SourceAlpha),
+                BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+            )),
+            ..Default::default()
+        },
+    );
+    for frame in 0..2 {
+        ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings_from_slice(&[vertex_buffer], &[4], index_buffer, 2, &[texture]);
+        ctx.apply_uniforms(UniformsSource::table(&[frame as f32, 0.5]));
+        ctx.draw(0, 2, 1);
+        ctx.end_render_pass();
+        ctx.commit_frame();
+    }
+
+    let mut replayed = RecordingContext::new();
+    let mut replay = Replay::new(ctx.trace()).unwrap();
+    assert!(replay.replay_frame(&mut replayed).unwrap());
+    assert_eq!(replayed.commands().last(), Some(&Command::CommitFrame));
+    assert!(!replay.replay_frame(&mut replayed).unwrap());
+
+    let recorded = ctx.into_inner();
+    assert_eq!(*replayed.commands(), *recorded.commands());
+    assert_eq!(replayed.texture(texture).data, [1, 2, 3, 4]);
+    assert_eq!(
+        replayed.buffer(vertex_buffer).data,
+        recorded.buffer(vertex_buffer).data
+    );
+    let replayed_shader = replayed.shader(shader);
+    assert_eq!(replayed_shader.fragment, "fragment");
+    assert_eq!(replayed_shader.meta.images, ["tex"]);
+    assert_eq!(
+        replayed.pipeline(pipeline).params,
+        recorded.pipeline(pipeline).params
+    );
+}
+
+#[cfg(test)]
+fn replay_all(trace: Vec<u8>, ctx: &mut dyn RenderingBackend) -> Result<(), TraceError> {
+    let mut replay = Replay::new(trace)?;
+    while replay.replay_frame(ctx)? {}
+    Ok(())
+}
+
+#[test]
+fn test_trace_truncated_or_corrupt() {
+    let mut ctx = TracingBackend::new(Box::new(RecordingContext::new()));
+    ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[1.0f32; 4]),
+    );
+    ctx.commit_frame();
+    let trace = ctx.trace();
+    let header = MAGIC.len() + 4;
+
+    assert!(matches!(
+        Replay::new(b"\x89PNG\r\n\x1a\n".to_vec()),
+        Err(TraceError::NotATrace)
+    ));
+    assert!(matches!(
+        Replay::new(trace[..4].to_vec()),
+        Err(TraceError::NotATrace)
+    ));
+    assert!(matches!(
+        Replay::new(trace[..header - 1].to_vec()),
+        Err(TraceError::UnexpectedEnd)
+    ));
+
+    // cut anywhere inside the new_buffer call, the commit_frame after it is a single byte
+    for len in header + 1..trace.len() - 1 {
+        let result = replay_all(trace[..len].to_vec(), &mut RecordingContext::new());
+        assert!(
+            matches!(result, Err(TraceError::UnexpectedEnd)),
+            "{}: {:?}",
+            len,
+            result
+        );
+    }
+    // cut between the calls
+    for len in [header, trace.len() - 1, trace.len()] {
+        replay_all(trace[..len].to_vec(), &mut RecordingContext::new()).unwrap();
+    }
+
+    let mut unknown_op = trace.clone();
+    unknown_op.push(u8::MAX);
+    let result = replay_all(unknown_op, &mut RecordingContext::new());
+    assert!(
+        matches!(result, Err(TraceError::InvalidData)),
+        "{:?}",
+        result
+    );
+
+    // BufferType, after the op and the id
+    let mut unknown_enum_value = trace;
+    unknown_enum_value[header + 1 + 4] = u8::MAX;
+    let result = replay_all(unknown_enum_value, &mut RecordingContext::new());
+    assert!(
+        matches!(result, Err(TraceError::InvalidData)),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn test_trace_replay_remaps_resources() {
+    use super::recording::Command;
+
+    let mut ctx = TracingBackend::new(Box::new(RecordingContext::new()));
+    // the slot of the deleted buffer is reused by the index buffer
+    let deleted = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[9u16; 3]),
+    );
+    ctx.delete_buffer(deleted);
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2]),
+    );
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0.0f32, 1.0, 2.0]),
+    );
+    let texture = ctx.new_texture_from_rgba8(1, 1, &[1, 2, 3, 4]);
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[0], index_buffer, 0, &[texture]);
+    ctx.end_render_pass();
+    ctx.commit_frame();
+
+    // the replaying backend has resources of its own, so its handles are different
+    let mut replayed = RecordingContext::new();
+    replayed.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[5u16; 3]),
+    );
+    replayed.new_render_texture(TextureParams {
+        width: 1,
+        height: 1,
+        ..Default::default()
+    });
+    replay_all(ctx.trace(), &mut replayed).unwrap();
+
+    let commands = replayed.commands();
+    let (vertex_buffers, replayed_index_buffer, images) = commands
+        .iter()
+        .find_map(|command| match command {
+            Command::ApplyBindings {
+                vertex_buffers,
+                index_buffer,
+                images,
+                ..
+            } => Some((vertex_buffers.clone(), *index_buffer, images.clone())),
+            _ => None,
+        })
+        .unwrap();
+    assert_ne!(replayed_index_buffer, index_buffer);
+    assert_eq!(
+        replayed.buffer(replayed_index_buffer).data,
+        [0, 0, 1, 0, 2, 0]
+    );
+    assert_ne!(vertex_buffers, [vertex_buffer]);
+    assert_eq!(
+        replayed.buffer(vertex_buffers[0]).data,
+        ctx.inner().buffer(vertex_buffer).data
+    );
+    assert_ne!(images, [texture]);
+    assert_eq!(replayed.texture(images[0]).data, [1, 2, 3, 4]);
+}
+
+#[test]
+fn test_trace_unknown_resource() {
+    // created before the backend was wrapped, the trace has no record of it
+    let mut inner = RecordingContext::new();
+    let buffer = inner.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Stream,
+        BufferSource::empty::<f32>(4),
+    );
+    let mut ctx = TracingBackend::new(Box::new(inner));
+    ctx.buffer_update(buffer, BufferSource::slice(&[1.0f32; 4]));
+
+    let result = replay_all(ctx.trace(), &mut RecordingContext::new());
+    assert!(
+        matches!(result, Err(TraceError::UnknownResource(UNKNOWN))),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn test_trace_version() {
+    let mut trace = TracingBackend::new(Box::new(RecordingContext::new())).trace();
+    assert_eq!(trace.len(), MAGIC.len() + 4);
+    Replay::new(trace.clone()).unwrap();
+
+    // written by a newer miniquad
+    trace[MAGIC.len()..].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        Replay::new(trace),
+        Err(TraceError::UnsupportedVersion(v)) if v == VERSION + 1
+    ));
+}
@@ -0,0 +1,1075 @@
+//! Debug layer checking `RenderingBackend` calls before they reach the real backend.
+//!
+//! Misuse that the GPU would turn into a black screen, a driver crash or silently
+//! read garbage, panics instead, with a message pointing at the offending call.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Arguments;
+use std::panic::Location;
+
+use super::*;
+
+struct BufferInfo {
+    type_: BufferType,
+    size: usize,
+    element_size: usize,
+}
+
+struct PipelineInfo {
+    shader: ShaderId,
+    buffer_layouts: usize,
+}
+
+struct BindingsInfo {
+    index_buffer: BufferId,
+    index_buffer_offset: usize,
+}
+
+/// Wraps a backend, checks every call and forwards it.
+///
+/// Catches:
+/// + use of deleted buffers, textures, shaders, pipelines, passes, queries and readbacks,
+/// + bindings that do not match the pipeline's `BufferLayout`s or miss `ShaderMeta::images`,
+/// + uniforms which size differs from `ShaderMeta::uniforms`,
+/// + draws reading past the end of the index or indirect buffer,
+/// + `apply_*` and draw calls outside of a render pass, and passes that are not ended.
+///
+/// Violations panic with the location of the call, so the checks are meant for debug
+/// builds. Wrap the backend right after creating it, the resources created before are
+/// not known to the wrapper and are reported as deleted.
+/// ```no_run
+/// # use miniquad::*;
+/// let ctx: Box<dyn RenderingBackend> =
+///     Box::new(ValidatingBackend::new(window::new_rendering_backend()));
+/// ```
+pub struct ValidatingBackend<B: RenderingBackend + ?Sized> {
+    shaders: HashMap<ShaderId, ShaderMeta>,
+    pipelines: HashMap<Pipeline, PipelineInfo>,
+    // color and depth attachments, deleted together with the pass
+    passes: HashMap<RenderPass, Vec<TextureId>>,
+    buffers: HashMap<BufferId, BufferInfo>,
+    textures: HashSet<TextureId>,
+    queries: HashSet<QueryId>,
+    readbacks: HashSet<ReadbackId>,
+    in_pass: bool,
+    cur_pipeline: Option<Pipeline>,
+    cur_bindings: Option<BindingsInfo>,
+    features: Features,
+    inner: Box<B>,
+}
+
+#[track_caller]
+fn violation(message: Arguments) -> ! {
+    panic!(
+        "Invalid RenderingBackend call at {}: {}",
+        Location::caller(),
+        message
+    )
+}
+
+macro_rules! check {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            violation(format_args!($($arg)+));
+        }
+    };
+}
+
+impl<B: RenderingBackend + ?Sized> ValidatingBackend<B> {
+    pub fn new(inner: Box<B>) -> ValidatingBackend<B> {
+        let features = inner.info().features;
+        ValidatingBackend {
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+            passes: HashMap::new(),
+            buffers: HashMap::new(),
+            textures: HashSet::new(),
+            queries: HashSet::new(),
+            readbacks: HashSet::new(),
+            in_pass: false,
+            cur_pipeline: None,
+            cur_bindings: None,
+            features,
+            inner,
+        }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> Box<B> {
+        self.inner
+    }
+
+    #[track_caller]
+    fn buffer(&self, buffer: BufferId) -> &BufferInfo {
+        self.buffers
+            .get(&buffer)
+            .unwrap_or_else(|| violation(format_args!("{:?} was deleted", buffer)))
+    }
+
+    #[track_caller]
+    fn buffer_of_type(&self, buffer: BufferId, type_: BufferType) -> &BufferInfo {
+        let info = self.buffer(buffer);
+        check!(
+            info.type_ == type_,
+            "{:?} is a {:?}, {:?} expected",
+            buffer,
+            info.type_,
+            type_
+        );
+        info
+    }
+
+    #[track_caller]
+    fn check_texture(&self, texture: TextureId) {
+        // raw textures are owned by the user
+        if let TextureIdInner::Managed(_) = texture.0 {
+            check!(
+                self.textures.contains(&texture),
+                "{:?} was deleted",
+                texture
+            );
+        }
+    }
+
+    #[track_caller]
+    fn check_shader(&self, shader: ShaderId) -> &ShaderMeta {
+        self.shaders
+            .get(&shader)
+            .unwrap_or_else(|| violation(format_args!("{:?} was deleted", shader)))
+    }
+
+    #[track_caller]
+    fn check_pass(&self, pass: RenderPass) {
+        check!(self.passes.contains_key(&pass), "{:?} was deleted", pass);
+    }
+
+    #[track_caller]
+    fn check_query(&self, query: QueryId) {
+        check!(self.queries.contains(&query), "{:?} was deleted", query);
+    }
+
+    #[track_caller]
+    fn check_in_pass(&self, call: &str) {
+        check!(self.in_pass, "{} outside of a render pass", call);
+    }
+
+    #[track_caller]
+    fn check_outside_pass(&self, call: &str) {
+        check!(
+            !self.in_pass,
+            "{} inside of a render pass, end_render_pass first",
+            call
+        );
+    }
+
+    /// Pipeline applied in the current pass and the meta of its shader.
+    #[track_caller]
+    fn cur_pipeline(&self, call: &str) -> (&PipelineInfo, &ShaderMeta) {
+        self.check_in_pass(call);
+        let pipeline = self
+            .cur_pipeline
+            .unwrap_or_else(|| violation(format_args!("{} before apply_pipeline", call)));
+        let info = self
+            .pipelines
+            .get(&pipeline)
+            .unwrap_or_else(|| violation(format_args!("{:?} was deleted while applied", pipeline)));
+        (info, self.check_shader(info.shader))
+    }
+
+    #[track_caller]
+    fn cur_bindings(&self, call: &str) -> &BindingsInfo {
+        self.cur_pipeline(call);
+        self.cur_bindings
+            .as_ref()
+            .unwrap_or_else(|| violation(format_args!("{} before apply_bindings", call)))
+    }
+
+    #[track_caller]
+    fn check_indexed_draw(&self, call: &str, base_element: i32, num_elements: i32) {
+        let bindings = self.cur_bindings(call);
+        check!(
+            base_element >= 0 && num_elements >= 0,
+            "{} with negative base_element {} or num_elements {}",
+            call,
+            base_element,
+            num_elements
+        );
+        let buffer = self.buffer(bindings.index_buffer);
+        let end = bindings.index_buffer_offset
+            + (base_element + num_elements) as usize * buffer.element_size;
+        check!(
+            end <= buffer.size,
+            "{} of elements {}..{} reads past the end of {:?}, {} indices long",
+            call,
+            base_element,
+            base_element + num_elements,
+            bindings.index_buffer,
+            (buffer.size - bindings.index_buffer_offset) / buffer.element_size
+        );
+    }
+
+    #[track_caller]
+    fn check_instances(&self, call: &str, num_instances: i32) {
+        check!(
+            num_instances <= 1 || self.features.instancing,
+            "{} of {} instances, instancing is not supported",
+            call,
+            num_instances
+        );
+    }
+
+    #[track_caller]
+    fn check_indirect_draws(&self, call: &str, buffer: BufferId, offset: usize, end: usize) {
+        check!(
+            self.features.draw_indirect,
+            "{}, draw_indirect is not supported",
+            call
+        );
+        self.cur_bindings(call);
+        let info = self.buffer_of_type(buffer, BufferType::IndirectBuffer);
+        check!(
+            end <= info.size,
+            "{} at offset {} reads past the end of {:?}, {} bytes long",
+            call,
+            offset,
+            buffer,
+            info.size
+        );
+    }
+}
+
+impl<B: RenderingBackend + ?Sized> RenderingBackend for ValidatingBackend<B> {
+    fn info(&self) -> ContextInfo {
+        self.inner.info()
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let id = self.inner.new_shader(shader, meta.clone())?;
+        self.shaders.insert(id, meta);
+        Ok(id)
+    }
+
+    fn reflect_shader(&mut self, shader: ShaderSource) -> Result<ShaderReflection, ShaderError> {
+        self.inner.reflect_shader(shader)
+    }
+
+    fn new_shader_validated(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let id = self.inner.new_shader_validated(shader, meta.clone())?;
+        self.shaders.insert(id, meta);
+        Ok(id)
+    }
+
+    fn new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        let texture = self.inner.new_texture(access, data, params);
+        self.textures.insert(texture);
+        texture
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.check_texture(texture);
+        self.inner.texture_params(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.check_texture(texture);
+        self.inner.texture_raw_id(texture)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        self.check_texture(texture);
+        self.inner
+            .texture_set_min_filter(texture, filter, mipmap_filter)
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.check_texture(texture);
+        self.inner.texture_set_mag_filter(texture, filter)
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        self.check_texture(texture);
+        self.inner.texture_set_wrap(texture, wrap_x, wrap_y)
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        self.check_texture(texture);
+        self.inner.texture_generate_mipmaps(texture)
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        self.check_texture(texture);
+        self.inner.texture_resize(texture, width, height, bytes)
+    }
+
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        self.check_texture(texture);
+        self.inner.texture_read_pixels(texture, bytes)
+    }
+
+    fn begin_read_pixels(&mut self, texture: TextureId) -> ReadbackId {
+        self.check_texture(texture);
+        let readback = self.inner.begin_read_pixels(texture);
+        self.readbacks.insert(readback);
+        readback
+    }
+
+    fn try_finish_read_pixels(&mut self, readback: ReadbackId, bytes: &mut [u8]) -> bool {
+        check!(
+            self.readbacks.contains(&readback),
+            "{:?} was already finished",
+            readback
+        );
+        let finished = self.inner.try_finish_read_pixels(readback, bytes);
+        if finished {
+            self.readbacks.remove(&readback);
+        }
+        finished
+    }
+
+    fn texture_update_layer_part(
+        &mut self,
+        texture: TextureId,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        self.check_texture(texture);
+        self.inner
+            .texture_update_layer_part(texture, layer, x_offset, y_offset, width, height, bytes)
+    }
+
+    fn copy_texture_region(
+        &mut self,
+        src: TextureId,
+        src_rect: (i32, i32, i32, i32),
+        dst: TextureId,
+        dst_pos: (i32, i32),
+    ) {
+        self.check_outside_pass("copy_texture_region");
+        self.check_texture(src);
+        self.check_texture(dst);
+        self.inner.copy_texture_region(src, src_rect, dst, dst_pos)
+    }
+
+    fn new_render_pass_layer(
+        &mut self,
+        color_img: &[TextureId],
+        depth_img: Option<TextureId>,
+        layer: u32,
+    ) -> RenderPass {
+        for texture in color_img.iter().chain(&depth_img) {
+            self.check_texture(*texture);
+        }
+        let pass = self
+            .inner
+            .new_render_pass_layer(color_img, depth_img, layer);
+        let attachments = color_img.iter().chain(&depth_img).copied().collect();
+        self.passes.insert(pass, attachments);
+        pass
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        self.check_pass(render_pass);
+        self.inner.render_pass_color_attachments(render_pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        self.check_pass(render_pass);
+        self.inner.delete_render_pass(render_pass);
+        for texture in self.passes.remove(&render_pass).unwrap() {
+            self.textures.remove(&texture);
+        }
+    }
+
+    fn blit_render_pass(&mut self, src: RenderPass, dst: Option<RenderPass>, filter: FilterMode) {
+        self.check_outside_pass("blit_render_pass");
+        self.check_pass(src);
+        if let Some(dst) = dst {
+            self.check_pass(dst);
+        }
+        self.inner.blit_render_pass(src, dst, filter)
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        self.check_shader(shader);
+        for attribute in attributes {
+            check!(
+                attribute.buffer_index < buffer_layout.len(),
+                "attribute \"{}\" reads buffer {}, but there are {} BufferLayouts",
+                attribute.name,
+                attribute.buffer_index,
+                buffer_layout.len()
+            );
+        }
+        let pipeline = self
+            .inner
+            .new_pipeline(buffer_layout, attributes, shader, params);
+        self.pipelines.insert(
+            pipeline,
+            PipelineInfo {
+                shader,
+                buffer_layouts: buffer_layout.len(),
+            },
+        );
+        pipeline
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.check_in_pass("apply_pipeline");
+        check!(
+            self.pipelines.contains_key(pipeline),
+            "{:?} was deleted",
+            pipeline
+        );
+        self.cur_pipeline = Some(*pipeline);
+        // vertex attributes are set up from the pipeline in apply_bindings
+        self.cur_bindings = None;
+        self.inner.apply_pipeline(pipeline)
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        check!(
+            self.pipelines.remove(&pipeline).is_some(),
+            "{:?} was deleted",
+            pipeline
+        );
+        if self.cur_pipeline == Some(pipeline) {
+            self.cur_pipeline = None;
+        }
+        self.inner.delete_pipeline(pipeline)
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let (size, element_size) = match &data {
+            BufferSource::Slice(data) => (data.size, data.element_size),
+            BufferSource::Empty { size, element_size } => (*size, *element_size),
+        };
+        let buffer = self.inner.new_buffer(type_, usage, data);
+        self.buffers.insert(
+            buffer,
+            BufferInfo {
+                type_,
+                size,
+                element_size,
+            },
+        );
+        buffer
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        let info = self.buffer(buffer);
+        let (size, element_size) = match &data {
+            BufferSource::Slice(data) => (data.size, data.element_size),
+            BufferSource::Empty { .. } => violation(format_args!(
+                "buffer_update of {:?} with an empty BufferSource",
+                buffer
+            )),
+        };
+        check!(
+            offset + size <= info.size,
+            "buffer_update of bytes {}..{} past the end of {:?}, {} bytes long",
+            offset,
+            offset + size,
+            buffer,
+            info.size
+        );
+        check!(
+            info.type_ != BufferType::IndexBuffer || element_size == info.element_size,
+            "buffer_update of {:?} with {} byte indices, the buffer has {} byte indices",
+            buffer,
+            element_size,
+            info.element_size
+        );
+        self.inner.buffer_update_range(buffer, offset, data)
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.buffer(buffer);
+        self.inner.buffer_size(buffer)
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        check!(
+            self.buffers.remove(&buffer).is_some(),
+            "{:?} was deleted",
+            buffer
+        );
+        self.inner.delete_buffer(buffer)
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        self.check_texture(texture);
+        self.textures.remove(&texture);
+        self.inner.delete_texture(texture)
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        check!(
+            self.shaders.remove(&program).is_some(),
+            "{:?} was deleted",
+            program
+        );
+        self.inner.delete_shader(program)
+    }
+
+    fn replace_shader(
+        &mut self,
+        program: ShaderId,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        self.check_shader(program);
+        self.inner.replace_shader(program, shader, meta.clone())?;
+        self.shaders.insert(program, meta);
+        Ok(())
+    }
+
+    fn shader_binary(&mut self, shader: ShaderId) -> Option<ShaderBinary> {
+        self.check_shader(shader);
+        self.inner.shader_binary(shader)
+    }
+
+    fn new_shader_from_binary(
+        &mut self,
+        binary: &ShaderBinary,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let id = self.inner.new_shader_from_binary(binary, meta.clone())?;
+        self.shaders.insert(id, meta);
+        Ok(id)
+    }
+
+    fn texture_set_label(&mut self, texture: TextureId, label: &str) {
+        self.check_texture(texture);
+        self.inner.texture_set_label(texture, label)
+    }
+
+    fn buffer_set_label(&mut self, buffer: BufferId, label: &str) {
+        self.buffer(buffer);
+        self.inner.buffer_set_label(buffer, label)
+    }
+
+    fn shader_set_label(&mut self, shader: ShaderId, label: &str) {
+        self.check_shader(shader);
+        self.inner.shader_set_label(shader, label)
+    }
+
+    fn pipeline_set_label(&mut self, pipeline: Pipeline, label: &str) {
+        check!(
+            self.pipelines.contains_key(&pipeline),
+            "{:?} was deleted",
+            pipeline
+        );
+        self.inner.pipeline_set_label(pipeline, label)
+    }
+
+    fn render_pass_set_label(&mut self, render_pass: RenderPass, label: &str) {
+        self.check_pass(render_pass);
+        self.inner.render_pass_set_label(render_pass, label)
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.check_in_pass("apply_viewport");
+        self.inner.apply_viewport(x, y, w, h)
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.check_in_pass("apply_scissor_rect");
+        self.inner.apply_scissor_rect(x, y, w, h)
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        vertex_buffer_offsets: &[usize],
+        index_buffer: BufferId,
+        index_buffer_offset: usize,
+        textures: &[TextureId],
+    ) {
+        let (pipeline, meta) = self.cur_pipeline("apply_bindings");
+        check!(
+            vertex_buffers.len() == pipeline.buffer_layouts,
+            "{} vertex buffers bound, the pipeline has {} BufferLayouts",
+            vertex_buffers.len(),
+            pipeline.buffer_layouts
+        );
+        check!(
+            vertex_buffer_offsets.len() <= vertex_buffers.len(),
+            "{} vertex buffer offsets for {} vertex buffers",
+            vertex_buffer_offsets.len(),
+            vertex_buffers.len()
+        );
+        check!(
+            textures.len() >= meta.images.len(),
+            "no textures bound for images {:?} of the shader",
+            &meta.images[textures.len()..]
+        );
+        for (n, buffer) in vertex_buffers.iter().enumerate() {
+            let info = self.buffer_of_type(*buffer, BufferType::VertexBuffer);
+            let offset = vertex_buffer_offsets.get(n).copied().unwrap_or(0);
+            check!(
+                offset <= info.size,
+                "offset {} is past the end of {:?}, {} bytes long",
+                offset,
+                buffer,
+                info.size
+            );
+        }
+        let info = self.buffer_of_type(index_buffer, BufferType::IndexBuffer);
+        check!(
+            index_buffer_offset <= info.size
+                && is_multiple_of(index_buffer_offset, info.element_size),
+            "index buffer offset {} is not a multiple of the index size {} within {:?}, {} bytes long",
+            index_buffer_offset,
+            info.element_size,
+            index_buffer,
+            info.size
+        );
+        for texture in textures {
+            self.check_texture(*texture);
+        }
+
+        self.cur_bindings = Some(BindingsInfo {
+            index_buffer,
+            index_buffer_offset,
+        });
+        self.inner.apply_bindings_from_slice(
+            vertex_buffers,
+            vertex_buffer_offsets,
+            index_buffer,
+            index_buffer_offset,
+            textures,
+        )
+    }
+
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        let (_, meta) = self.cur_pipeline("apply_uniforms");
+        // uniforms are tightly packed, unlike std140 blocks
+        let expected: usize = meta
+            .uniforms
+            .uniforms
+            .iter()
+            .map(|uniform| uniform.uniform_type.size() * uniform.array_count)
+            .sum();
+        check!(
+            size == expected,
+            "uniforms of {} bytes, the shader's uniforms take {} bytes",
+            size,
+            expected
+        );
+        self.inner.apply_uniforms_from_bytes(uniform_ptr, size)
+    }
+
+    fn apply_uniform_block(&mut self, block: usize, buffer: BufferId, offset: usize) {
+        let (_, meta) = self.cur_pipeline("apply_uniform_block");
+        let desc = meta.uniform_blocks.get(block).unwrap_or_else(|| {
+            violation(format_args!(
+                "uniform block {}, the shader has {} blocks",
+                block,
+                meta.uniform_blocks.len()
+            ))
+        });
+        let size = desc.layout.std140_size();
+        let info = self.buffer_of_type(buffer, BufferType::UniformBuffer);
+        check!(
+            offset + size <= info.size,
+            "block \"{}\" of {} bytes at offset {} is past the end of {:?}, {} bytes long",
+            desc.name,
+            size,
+            offset,
+            buffer,
+            info.size
+        );
+        let alignment = self.features.uniform_buffer_offset_alignment;
+        check!(
+            is_multiple_of(offset, alignment),
+            "uniform block offset {} is not a multiple of {}",
+            offset,
+            alignment
+        );
+        self.inner.apply_uniform_block(block, buffer, offset)
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.check_in_pass("clear");
+        self.inner.clear(color, depth, stencil)
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.check_outside_pass("begin_default_pass");
+        self.in_pass = true;
+        self.inner.begin_default_pass(action)
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.check_outside_pass("begin_pass");
+        if let Some(pass) = pass {
+            self.check_pass(pass);
+        }
+        self.in_pass = true;
+        self.inner.begin_pass(pass, action)
+    }
+
+    fn end_render_pass(&mut self) {
+        self.check_in_pass("end_render_pass");
+        self.in_pass = false;
+        self.cur_pipeline = None;
+        self.cur_bindings = None;
+        self.inner.end_render_pass()
+    }
+
+    fn read_default_framebuffer(&mut self, rect: (i32, i32, i32, i32)) -> Vec<u8> {
+        self.check_outside_pass("read_default_framebuffer");
+        self.inner.read_default_framebuffer(rect)
+    }
+
+    fn commit_frame(&mut self) {
+        self.check_outside_pass("commit_frame");
+        self.inner.commit_frame()
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.check_indexed_draw("draw", base_element, num_elements);
+        self.check_instances("draw", num_instances);
+        self.inner.draw(base_element, num_elements, num_instances)
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        let (pipeline, _) = self.cur_pipeline("draw_arrays");
+        if pipeline.buffer_layouts != 0 {
+            self.cur_bindings("draw_arrays");
+        }
+        self.check_instances("draw_arrays", num_instances);
+        self.inner
+            .draw_arrays(first_vertex, num_vertices, num_instances)
+    }
+
+    fn draw_base_vertex(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        base_vertex: i32,
+        num_instances: i32,
+    ) {
+        check!(
+            self.features.draw_base_vertex,
+            "draw_base_vertex is not supported"
+        );
+        self.check_indexed_draw("draw_base_vertex", base_element, num_elements);
+        self.check_instances("draw_base_vertex", num_instances);
+        self.inner
+            .draw_base_vertex(base_element, num_elements, base_vertex, num_instances)
+    }
+
+    fn draw_indirect(&self, buffer: BufferId, offset: usize) {
+        let end = offset + std::mem::size_of::<DrawIndirectCommand>();
+        self.check_indirect_draws("draw_indirect", buffer, offset, end);
+        self.inner.draw_indirect(buffer, offset)
+    }
+
+    fn multi_draw_indirect(&self, buffer: BufferId, offset: usize, draw_count: i32, stride: i32) {
+        check!(
+            self.features.multi_draw_indirect,
+            "multi_draw_indirect is not supported"
+        );
+        let size = std::mem::size_of::<DrawIndirectCommand>();
+        let step = match stride {
+            0 => size,
+            stride => stride as usize,
+        };
+        let end = match draw_count {
+            0 => offset,
+            draw_count => offset + (draw_count as usize - 1) * step + size,
+        };
+        self.check_indirect_draws("multi_draw_indirect", buffer, offset, end);
+        self.inner
+            .multi_draw_indirect(buffer, offset, draw_count, stride)
+    }
+
+    fn new_query(&mut self, query_type: QueryType) -> QueryId {
+        let query = self.inner.new_query(query_type);
+        self.queries.insert(query);
+        query
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        self.check_query(query);
+        self.inner.begin_query(query)
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        self.check_query(query);
+        self.inner.end_query(query)
+    }
+
+    fn query_available(&mut self, query: QueryId) -> bool {
+        self.check_query(query);
+        self.inner.query_available(query)
+    }
+
+    fn query_result(&mut self, query: QueryId) -> u64 {
+        self.check_query(query);
+        self.inner.query_result(query)
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        check!(self.queries.remove(&query), "{:?} was deleted", query);
+        self.inner.delete_query(query)
+    }
+}
+
+#[test]
+#[should_panic(expected = "draw of elements 3..6 reads past the end")]
+fn test_validation_index_overrun() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0.0f32; 6]),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2]),
+    );
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: "",
+                fragment: "",
+            },
+            ShaderMeta {
+                uniforms: UniformBlockLayout { uniforms: vec![] },
+                uniform_blocks: vec![],
+                images: vec![],
+            },
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[]);
+    ctx.draw(0, 3, 1);
+    ctx.draw(3, 3, 1);
+}
+
+#[test]
+#[should_panic(expected = "apply_viewport outside of a render pass")]
+fn test_validation_outside_pass() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.end_render_pass();
+    ctx.apply_viewport(0, 0, 1, 1);
+}
+
+#[test]
+fn test_validation_shared_attachments() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let params = TextureParams {
+        format: TextureFormat::Depth,
+        ..Default::default()
+    };
+    let depth = ctx.new_render_texture(params);
+    let color = ctx.new_render_texture(Default::default());
+    let first = ctx.new_render_pass(color, Some(depth));
+    let second = ctx.new_render_pass(color, Some(depth));
+    ctx.delete_render_pass(first);
+    ctx.delete_render_pass(second);
+    assert!(ctx.textures.is_empty());
+}
+
+/// Pipeline with a single `Float2` attribute and the given images and uniforms,
+/// with a vertex and an index buffer for it.
+#[cfg(test)]
+fn test_pipeline(
+    ctx: &mut ValidatingBackend<RecordingContext>,
+    images: &[&str],
+    uniforms: Vec<UniformDesc>,
+) -> (Pipeline, BufferId, BufferId) {
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0.0f32; 6]),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2]),
+    );
+    let shader = ctx
+        .new_shader(
+            ShaderSource::Glsl {
+                vertex: "",
+                fragment: "",
+            },
+            ShaderMeta {
+                uniforms: UniformBlockLayout { uniforms },
+                uniform_blocks: vec![],
+                images: images.iter().map(|image| image.to_string()).collect(),
+            },
+        )
+        .unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+    (pipeline, vertex_buffer, index_buffer)
+}
+
+#[test]
+fn test_validation_valid_calls() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let uniforms = vec![UniformDesc::new("color", UniformType::Float4)];
+    let (pipeline, vertex_buffer, index_buffer) = test_pipeline(&mut ctx, &["tex"], uniforms);
+    let texture = ctx.new_texture_from_rgba8(1, 1, &[0; 4]);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[texture]);
+    ctx.apply_uniforms(UniformsSource::table(&[1.0f32; 4]));
+    ctx.draw(0, 3, 1);
+    ctx.end_render_pass();
+}
+
+#[test]
+#[should_panic(expected = "2 vertex buffers bound, the pipeline has 1 BufferLayouts")]
+fn test_validation_bindings_count() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let (pipeline, vertex_buffer, index_buffer) = test_pipeline(&mut ctx, &[], vec![]);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    let vertex_buffers = [vertex_buffer, vertex_buffer];
+    ctx.apply_bindings_from_slice(&vertex_buffers, &[], index_buffer, 0, &[]);
+}
+
+#[test]
+#[should_panic(expected = "no textures bound for images [\"normals\"] of the shader")]
+fn test_validation_missing_images() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let (pipeline, vertex_buffer, index_buffer) =
+        test_pipeline(&mut ctx, &["albedo", "normals"], vec![]);
+    let texture = ctx.new_texture_from_rgba8(1, 1, &[0; 4]);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[texture]);
+}
+
+#[test]
+#[should_panic(expected = "uniforms of 4 bytes, the shader's uniforms take 16 bytes")]
+fn test_validation_uniforms_size() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let uniforms = vec![UniformDesc::new("color", UniformType::Float4)];
+    let (pipeline, _, _) = test_pipeline(&mut ctx, &[], uniforms);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_uniforms(UniformsSource::table(&1.0f32));
+}
+
+#[test]
+#[should_panic(expected = "was deleted")]
+fn test_validation_deleted_buffer() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let (pipeline, vertex_buffer, index_buffer) = test_pipeline(&mut ctx, &[], vec![]);
+    ctx.delete_buffer(vertex_buffer);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[]);
+}
+
+#[test]
+#[should_panic(expected = "was deleted")]
+fn test_validation_deleted_texture() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let (pipeline, vertex_buffer, index_buffer) = test_pipeline(&mut ctx, &["tex"], vec![]);
+    let texture = ctx.new_texture_from_rgba8(1, 1, &[0; 4]);
+    ctx.delete_texture(texture);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], &[], index_buffer, 0, &[texture]);
+}
+
+#[test]
+#[should_panic(expected = "was deleted")]
+fn test_validation_deleted_pipeline() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let (pipeline, _, _) = test_pipeline(&mut ctx, &[], vec![]);
+    ctx.delete_pipeline(pipeline);
+
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+}
+
+#[test]
+#[should_panic(expected = "was deleted")]
+fn test_validation_deleted_pass() {
+    let mut ctx = ValidatingBackend::new(Box::new(RecordingContext::new()));
+    let texture = ctx.new_render_texture(Default::default());
+    let pass = ctx.new_render_pass(texture, None);
+    ctx.delete_render_pass(pass);
+
+    // the attachments went with the pass
+    ctx.begin_pass(Some(pass), PassAction::Nothing);
+}
@@ -4,58 +4,154 @@ mod event;
 pub mod fs;
 pub mod graphics;
 pub mod native;
-use std::collections::HashMap;
+pub mod png;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod program_cache;
+pub mod shader_preprocessor;
+#[cfg(all(
+    feature = "shader-hot-reload",
+    not(any(target_arch = "wasm32", target_os = "android", target_os = "ios"))
+))]
+pub mod shader_watcher;
+pub mod texture_loader;
 use std::ops::{Index, IndexMut};
 
 #[cfg(feature = "log-impl")]
 pub mod log;
 
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
 pub use event::*;
 
 pub use graphics::*;
 
+#[cfg(feature = "derive")]
+pub use miniquad_derive::{Uniforms, Vertex};
+
+// lets the derives, which name `::miniquad`, be tested inside the crate
+#[cfg(feature = "derive")]
+extern crate self as miniquad;
+
 mod default_icon;
 
 pub use native::gl;
 
+/// Handle of a resource in a `ResourceManager`: the slot index and the generation
+/// the slot had when the resource was added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct ResourceId {
+    index: u32,
+    generation: u32,
+}
+
+impl ResourceId {
+    /// Slot index, unique among the live resources of one manager.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+#[derive(Clone)]
+struct Slot<T> {
+    generation: u32,
+    resource: Option<T>,
+}
+
+/// Slot map: resources live in a `Vec` and removed slots are reused.
+/// Each reuse bumps the slot generation, so a handle to a removed resource
+/// never silently resolves to the new one.
 #[derive(Clone)]
 pub(crate) struct ResourceManager<T> {
-    id: usize,
-    resources: HashMap<usize, T>,
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
 }
 
 impl<T> Default for ResourceManager<T> {
     fn default() -> Self {
         Self {
-            id: 0,
-            resources: HashMap::new(),
+            slots: vec![],
+            free: vec![],
         }
     }
 }
 
 impl<T> ResourceManager<T> {
-    pub fn add(&mut self, resource: T) -> usize {
-        self.resources.insert(self.id, resource);
-        self.id += 1;
-        self.id - 1
+    pub fn add(&mut self, resource: T) -> ResourceId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.resource = Some(resource);
+                ResourceId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    resource: Some(resource),
+                });
+                ResourceId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: ResourceId) -> T {
+        if self.get(id).is_none() {
+            stale_resource::<T>(id);
+        }
+        let slot = &mut self.slots[id.index()];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        slot.resource.take().unwrap()
     }
 
-    pub fn remove(&mut self, id: usize) -> T {
-        // Let it crash if the resource is not found
-        self.resources.remove(&id).unwrap()
+    /// `None` if the resource was removed.
+    pub fn get(&self, id: ResourceId) -> Option<&T> {
+        self.slots
+            .get(id.index())
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.resource.as_ref())
     }
+
+    pub fn get_mut(&mut self, id: ResourceId) -> Option<&mut T> {
+        self.slots
+            .get_mut(id.index())
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.resource.as_mut())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.resource.as_mut())
+    }
+}
+
+#[cold]
+fn stale_resource<T>(id: ResourceId) -> ! {
+    let name = std::any::type_name::<T>();
+    let name = name.rsplit("::").next().unwrap_or(name);
+    panic!(
+        "{} {:?} used after it was deleted, or with another context",
+        name, id
+    )
 }
 
-impl<T> Index<usize> for ResourceManager<T> {
+impl<T> Index<ResourceId> for ResourceManager<T> {
     type Output = T;
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.resources[&index]
+    fn index(&self, id: ResourceId) -> &Self::Output {
+        self.get(id).unwrap_or_else(|| stale_resource::<T>(id))
     }
 }
 
-impl<T> IndexMut<usize> for ResourceManager<T> {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.resources.get_mut(&index).unwrap()
+impl<T> IndexMut<ResourceId> for ResourceManager<T> {
+    fn index_mut(&mut self, id: ResourceId) -> &mut Self::Output {
+        self.get_mut(id).unwrap_or_else(|| stale_resource::<T>(id))
     }
 }
 
@@ -85,9 +181,14 @@ use std::sync::{Mutex, OnceLock};
 static NATIVE_DISPLAY: OnceLock<Mutex<native::NativeDisplayData>> = OnceLock::new();
 
 fn set_display(display: native::NativeDisplayData) {
-    NATIVE_DISPLAY
-        .set(Mutex::new(display))
-        .unwrap_or_else(|_| panic!("NATIVE_DISPLAY already set"));
+    // The event loop may run more than once in a process, e.g. the headless backend
+    // with `test_support::capture_frames`. The display of the previous run is replaced.
+    if let Err(display) = NATIVE_DISPLAY.set(Mutex::new(display)) {
+        let display = display.into_inner().unwrap_or_else(|err| err.into_inner());
+        *native_display()
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = display;
+    }
 }
 fn native_display() -> &'static Mutex<native::NativeDisplayData> {
     NATIVE_DISPLAY
@@ -360,6 +461,9 @@ where
                     native::linux_x11::run(&conf, f);
                 }
             }
+            conf::LinuxBackend::Headless => {
+                native::linux_headless::run(&conf, f).expect("Headless backend failed")
+            }
         }
     }
 
@@ -388,3 +492,48 @@ where
         native::ios::run(conf, f);
     }
 }
+
+#[test]
+fn test_resource_manager_reuse() {
+    let mut resources = ResourceManager::default();
+    let a = resources.add("a");
+    assert_eq!(resources.remove(a), "a");
+    assert!(resources.get(a).is_none());
+
+    // the slot is reused with the next generation
+    let b = resources.add("b");
+    assert_eq!(b.index(), a.index());
+    assert_eq!(b.generation, a.generation + 1);
+    assert_eq!(resources[b], "b");
+    assert!(resources.get(a).is_none());
+    assert!(resources.get_mut(a).is_none());
+    let c = resources.add("c");
+    assert_ne!(c.index(), b.index());
+    assert_eq!(resources.iter_mut().count(), 2);
+}
+
+#[test]
+#[should_panic(expected = "used after it was deleted")]
+fn test_resource_manager_stale_index() {
+    let mut resources = ResourceManager::default();
+    let a = resources.add(1);
+    resources.remove(a);
+    resources.add(2);
+    let _ = resources[a];
+}
+
+#[test]
+fn test_resource_manager_generation_wraps() {
+    let mut resources = ResourceManager::default();
+    let a = resources.add(1);
+    resources.slots[a.index()].generation = u32::MAX;
+    let a = ResourceId {
+        generation: u32::MAX,
+        ..a
+    };
+    resources.remove(a);
+    let b = resources.add(2);
+    assert_eq!(b.generation, 0);
+    assert!(resources.get(a).is_none());
+    assert_eq!(resources[b], 2);
+}
@@ -86,6 +86,9 @@ pub mod linux_x11;
 #[cfg(target_os = "linux")]
 pub mod linux_wayland;
 
+#[cfg(target_os = "linux")]
+pub mod linux_headless;
+
 #[cfg(target_os = "android")]
 pub mod android;
 
@@ -394,6 +394,7 @@ where
             std::ptr::null_mut(), /* EGL_DEFAULT_DISPLAY */
             conf.platform.framebuffer_alpha,
             conf.sample_count,
+            conf.platform.gl_debug,
         )
         .expect("Cant create EGL context");
 
@@ -568,8 +568,39 @@ pub enum MTLPixelFormat {
     Stencil8 = 253,
     Depth24Unorm_Stencil8 = 255,
     Depth32Float_Stencil8 = 260,
+    R8Unorm = 10,
+    R16Float = 25,
+    RG8Unorm = 30,
+    R32Uint = 53,
+    R32Float = 55,
+    RG16Float = 65,
     RGBA8Unorm = 70,
+    RGBA8Unorm_sRGB = 71,
     RGBA16Float = 115,
+    RGBA32Float = 125,
+    BC1_RGBA = 130,
+    BC1_RGBA_sRGB = 131,
+    BC2_RGBA = 132,
+    BC2_RGBA_sRGB = 133,
+    BC3_RGBA = 134,
+    BC3_RGBA_sRGB = 135,
+    BC4_RUnorm = 140,
+    BC5_RGUnorm = 142,
+    BC6H_RGBUfloat = 151,
+    BC7_RGBAUnorm = 152,
+    BC7_RGBAUnorm_sRGB = 153,
+    EAC_RGBA8 = 178,
+    EAC_RGBA8_sRGB = 179,
+    ETC2_RGB8 = 180,
+    ETC2_RGB8_sRGB = 181,
+    ASTC_4x4_sRGB = 186,
+    ASTC_5x5_sRGB = 188,
+    ASTC_6x6_sRGB = 190,
+    ASTC_8x8_sRGB = 194,
+    ASTC_4x4_LDR = 204,
+    ASTC_5x5_LDR = 206,
+    ASTC_6x6_LDR = 208,
+    ASTC_8x8_LDR = 212,
 }
 
 /// See <https://developer.apple.com/documentation/metal/mtlsamplerminmagfilter>
@@ -767,6 +798,18 @@ pub enum MTLStorageMode {
     Memoryless = 3,
 }
 
+#[repr(u64)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MTLCommandBufferStatus {
+    NotEnqueued = 0,
+    Enqueued = 1,
+    Committed = 2,
+    Scheduled = 3,
+    Completed = 4,
+    Error = 5,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct MTLOrigin {
@@ -20,7 +20,9 @@ pub use core::ptr::null_mut;
 
 pub const EGL_SUCCESS: u32 = 12288;
 
+pub const EGL_PBUFFER_BIT: u32 = 1;
 pub const EGL_WINDOW_BIT: u32 = 4;
+pub const EGL_OPENGL_ES2_BIT: u32 = 4;
 
 pub const EGL_ALPHA_SIZE: u32 = 12321;
 pub const EGL_BLUE_SIZE: u32 = 12322;
@@ -33,8 +35,13 @@ pub const EGL_NATIVE_VISUAL_ID: u32 = 12334;
 pub const EGL_WIDTH: u32 = 12375;
 pub const EGL_HEIGHT: u32 = 12374;
 pub const EGL_SURFACE_TYPE: u32 = 12339;
+pub const EGL_RENDERABLE_TYPE: u32 = 12352;
+pub const EGL_EXTENSIONS: u32 = 12373;
+pub const EGL_PLATFORM_SURFACELESS_MESA: u32 = 12765;
 pub const EGL_NONE: u32 = 12344;
 pub const EGL_CONTEXT_CLIENT_VERSION: u32 = 12440;
+pub const EGL_CONTEXT_OPENGL_DEBUG: u32 = 12720;
+pub const EGL_TRUE: u32 = 1;
 
 pub type NativeDisplayType = EGLNativeDisplayType;
 pub type NativePixmapType = EGLNativePixmapType;
@@ -118,6 +125,13 @@ pub type PFNEGLGETCURRENTSURFACEPROC =
     ::std::option::Option<unsafe extern "C" fn(readdraw: EGLint) -> EGLSurface>;
 pub type PFNEGLGETDISPLAYPROC =
     ::std::option::Option<unsafe extern "C" fn(display_id: EGLNativeDisplayType) -> EGLDisplay>;
+pub type PFNEGLGETPLATFORMDISPLAYEXTPROC = ::std::option::Option<
+    unsafe extern "C" fn(
+        platform: EGLint,
+        native_display: *mut ::std::os::raw::c_void,
+        attrib_list: *const EGLint,
+    ) -> EGLDisplay,
+>;
 pub type PFNEGLGETERRORPROC = ::std::option::Option<unsafe extern "C" fn() -> EGLint>;
 pub type PFNEGLGETPROCADDRESSPROC = ::std::option::Option<
     unsafe extern "C" fn(
@@ -263,6 +277,7 @@ pub unsafe fn create_egl_context(
     display: *mut std::ffi::c_void,
     alpha: bool,
     sample_count: i32,
+    debug: bool,
 ) -> Result<(EGLContext, EGLConfig, EGLDisplay), EglError> {
     let display = (egl.eglGetDisplay.unwrap())(display as _);
     if display == /* EGL_NO_DISPLAY */ null_mut() {
@@ -327,16 +342,47 @@ pub unsafe fn create_egl_context(
     if !exact_cfg_found {
         config = available_cfgs[0];
     }
-    let ctx_attributes = vec![EGL_CONTEXT_CLIENT_VERSION, 2, EGL_NONE];
-    let context = (egl.eglCreateContext.unwrap())(
-        display,
-        config,
-        /* EGL_NO_CONTEXT */ null_mut(),
-        ctx_attributes.as_ptr() as _,
-    );
+    let context = create_context(egl, display, config, debug);
     if context.is_null() {
         return Err(EglError::CreateContextFailed);
     }
 
     return Ok((context, config, display));
 }
+
+/// GLES2+ context, a debug one if asked for and supported.
+/// EGL_CONTEXT_OPENGL_DEBUG needs EGL 1.5 or EGL_KHR_create_context,
+/// without them context creation fails and it is retried without.
+pub(crate) unsafe fn create_context(
+    egl: &LibEgl,
+    display: EGLDisplay,
+    config: EGLConfig,
+    debug: bool,
+) -> EGLContext {
+    if debug {
+        let ctx_attributes = [
+            EGL_CONTEXT_CLIENT_VERSION,
+            2,
+            EGL_CONTEXT_OPENGL_DEBUG,
+            EGL_TRUE,
+            EGL_NONE,
+        ];
+        let context = (egl.eglCreateContext.unwrap())(
+            display,
+            config,
+            /* EGL_NO_CONTEXT */ null_mut(),
+            ctx_attributes.as_ptr() as _,
+        );
+        if !context.is_null() {
+            return context;
+        }
+        eprintln!("EGL: failed to create a debug context, trying without");
+    }
+    let ctx_attributes = [EGL_CONTEXT_CLIENT_VERSION, 2, EGL_NONE];
+    (egl.eglCreateContext.unwrap())(
+        display,
+        config,
+        /* EGL_NO_CONTEXT */ null_mut(),
+        ctx_attributes.as_ptr() as _,
+    )
+}
@@ -26,10 +26,29 @@ pub type GLclampf = f32;
 pub type GLdouble = f64;
 pub type GLclampd = f64;
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct __GLsync {
+    _unused: [u8; 0],
+}
+pub type GLsync = *mut __GLsync;
+pub type GLDEBUGPROC = Option<
+    extern "system" fn(
+        source: GLenum,
+        type_: GLenum,
+        id: GLuint,
+        severity: GLenum,
+        length: GLsizei,
+        message: *const GLchar,
+        user_param: *mut GLvoid,
+    ),
+>;
+
 pub const GL_INT_2_10_10_10_REV: u32 = 0x8D9F;
 pub const GL_PROGRAM_POINT_SIZE: u32 = 0x8642;
 pub const GL_STENCIL_ATTACHMENT: u32 = 0x8D20;
 pub const GL_DEPTH_ATTACHMENT: u32 = 0x8D00;
+pub const GL_DEPTH_STENCIL_ATTACHMENT: u32 = 0x821A;
 pub const GL_COLOR_ATTACHMENT2: u32 = 0x8CE2;
 pub const GL_COLOR_ATTACHMENT0: u32 = 0x8CE0;
 pub const GL_COLOR_ATTACHMENT22: u32 = 0x8CF6;
@@ -136,6 +155,7 @@ pub const GL_LEQUAL: u32 = 0x0203;
 pub const GL_STENCIL_TEST: u32 = 0x0B90;
 pub const GL_DITHER: u32 = 0x0BD0;
 pub const GL_DEPTH_COMPONENT16: u32 = 0x81A5;
+pub const GL_DEPTH24_STENCIL8: u32 = 0x88F0;
 pub const GL_EQUAL: u32 = 0x0202;
 pub const GL_FRAMEBUFFER: u32 = 0x8D40;
 pub const GL_RGB5: u32 = 0x8050;
@@ -235,15 +255,108 @@ pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
+pub const GL_PACK_ALIGNMENT: u32 = 3333;
 pub const GL_TEXTURE_SWIZZLE_R: u32 = 36418;
 pub const GL_TEXTURE_SWIZZLE_G: u32 = 36419;
 pub const GL_TEXTURE_SWIZZLE_B: u32 = 36420;
 pub const GL_TEXTURE_SWIZZLE_A: u32 = 36421;
 pub const GL_TEXTURE_SWIZZLE_RGBA: u32 = 36422;
 pub const GL_DRAW_FRAMEBUFFER_BINDING: u32 = 36006;
+pub const GL_READ_FRAMEBUFFER_BINDING: u32 = 0x8CAA;
+pub const GL_SAMPLE_BUFFERS: u32 = 0x80A8;
 pub const GL_TIME_ELAPSED: u32 = 35007;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
+pub const GL_SAMPLES_PASSED: u32 = 0x8914;
+pub const GL_ANY_SAMPLES_PASSED: u32 = 0x8C2F;
+pub const GL_UNIFORM_BUFFER: u32 = 0x8A11;
+pub const GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT: u32 = 0x8A34;
+pub const GL_UNIFORM_OFFSET: u32 = 0x8A3B;
+pub const GL_UNIFORM_ARRAY_STRIDE: u32 = 0x8A3C;
+pub const GL_UNIFORM_BLOCK_DATA_SIZE: u32 = 0x8A40;
+pub const GL_INVALID_INDEX: u32 = 0xFFFFFFFF;
+pub const GL_ACTIVE_UNIFORMS: u32 = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: u32 = 0x8B89;
+pub const GL_ACTIVE_UNIFORM_BLOCKS: u32 = 0x8A36;
+pub const GL_UNIFORM_BLOCK_INDEX: u32 = 0x8A3A;
+pub const GL_FLOAT_VEC2: u32 = 0x8B50;
+pub const GL_FLOAT_VEC3: u32 = 0x8B51;
+pub const GL_FLOAT_VEC4: u32 = 0x8B52;
+pub const GL_INT_VEC2: u32 = 0x8B53;
+pub const GL_INT_VEC3: u32 = 0x8B54;
+pub const GL_INT_VEC4: u32 = 0x8B55;
+pub const GL_FLOAT_MAT4: u32 = 0x8B5C;
+pub const GL_SAMPLER_2D: u32 = 0x8B5E;
+pub const GL_SAMPLER_3D: u32 = 0x8B5F;
+pub const GL_SAMPLER_CUBE: u32 = 0x8B60;
+pub const GL_SAMPLER_2D_SHADOW: u32 = 0x8B62;
+pub const GL_SAMPLER_2D_ARRAY: u32 = 0x8DC1;
+pub const GL_SAMPLER_2D_ARRAY_SHADOW: u32 = 0x8DC4;
+pub const GL_SAMPLER_CUBE_SHADOW: u32 = 0x8DC5;
+pub const GL_INT_SAMPLER_2D: u32 = 0x8DCA;
+pub const GL_INT_SAMPLER_3D: u32 = 0x8DCB;
+pub const GL_INT_SAMPLER_CUBE: u32 = 0x8DCC;
+pub const GL_INT_SAMPLER_2D_ARRAY: u32 = 0x8DCF;
+pub const GL_UNSIGNED_INT_SAMPLER_2D: u32 = 0x8DD2;
+pub const GL_UNSIGNED_INT_SAMPLER_3D: u32 = 0x8DD3;
+pub const GL_UNSIGNED_INT_SAMPLER_CUBE: u32 = 0x8DD4;
+pub const GL_UNSIGNED_INT_SAMPLER_2D_ARRAY: u32 = 0x8DD7;
+pub const GL_SRGB8_ALPHA8: u32 = 0x8C43;
+pub const GL_DEPTH_STENCIL: u32 = 0x84F9;
+pub const GL_UNSIGNED_INT_24_8: u32 = 0x84FA;
+pub const GL_HALF_FLOAT: u32 = 0x140B;
+pub const GL_IMPLEMENTATION_COLOR_READ_TYPE: u32 = 0x8B9A;
+pub const GL_IMPLEMENTATION_COLOR_READ_FORMAT: u32 = 0x8B9B;
+pub const GL_FRAMEBUFFER_SRGB: u32 = 0x8DB9;
+pub const GL_DRAW_INDIRECT_BUFFER: u32 = 0x8F3F;
+pub const GL_MAX_SAMPLES: u32 = 0x8D57;
+pub const GL_DEPTH_COMPONENT32F: u32 = 0x8CAC;
+pub const GL_PIXEL_PACK_BUFFER: u32 = 0x88EB;
+pub const GL_STREAM_READ: u32 = 0x88E1;
+pub const GL_MAP_READ_BIT: u32 = 0x0001;
+pub const GL_SYNC_GPU_COMMANDS_COMPLETE: u32 = 0x9117;
+pub const GL_ALREADY_SIGNALED: u32 = 0x911A;
+pub const GL_CONDITION_SATISFIED: u32 = 0x911C;
+pub const GL_CONTEXT_FLAGS: u32 = 0x821E;
+pub const GL_CONTEXT_FLAG_DEBUG_BIT: u32 = 0x00000002;
+pub const GL_DEBUG_OUTPUT: u32 = 0x92E0;
+pub const GL_DEBUG_OUTPUT_SYNCHRONOUS: u32 = 0x8242;
+pub const GL_DEBUG_SOURCE_APPLICATION: u32 = 0x824A;
+pub const GL_DEBUG_TYPE_MARKER: u32 = 0x8268;
+pub const GL_DEBUG_SEVERITY_HIGH: u32 = 0x9146;
+pub const GL_DEBUG_SEVERITY_MEDIUM: u32 = 0x9147;
+pub const GL_DEBUG_SEVERITY_LOW: u32 = 0x9148;
+pub const GL_DEBUG_SEVERITY_NOTIFICATION: u32 = 0x826B;
+pub const GL_TEXTURE: u32 = 0x1702;
+pub const GL_BUFFER: u32 = 0x82E0;
+pub const GL_PROGRAM: u32 = 0x82E2;
+pub const GL_PROGRAM_BINARY_RETRIEVABLE_HINT: u32 = 0x8257;
+pub const GL_PROGRAM_BINARY_LENGTH: u32 = 0x8741;
+pub const GL_NUM_PROGRAM_BINARY_FORMATS: u32 = 0x87FE;
+pub const GL_RENDERER: u32 = 0x1F01;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT3_EXT: u32 = 0x83F2;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+pub const GL_COMPRESSED_RED_RGTC1: u32 = 0x8DBB;
+pub const GL_COMPRESSED_RG_RGTC2: u32 = 0x8DBD;
+pub const GL_COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+pub const GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT: u32 = 0x8E8F;
+pub const GL_COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+pub const GL_COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+pub const GL_COMPRESSED_RGBA_ASTC_4x4_KHR: u32 = 0x93B0;
+pub const GL_COMPRESSED_RGBA_ASTC_5x5_KHR: u32 = 0x93B2;
+pub const GL_COMPRESSED_RGBA_ASTC_6x6_KHR: u32 = 0x93B4;
+pub const GL_COMPRESSED_RGBA_ASTC_8x8_KHR: u32 = 0x93B7;
+pub const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: u32 = 0x8C4D;
+pub const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: u32 = 0x8C4E;
+pub const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
+pub const GL_COMPRESSED_SRGB_ALPHA_BPTC_UNORM: u32 = 0x8E8D;
+pub const GL_COMPRESSED_SRGB8_ETC2: u32 = 0x9275;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC: u32 = 0x9279;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR: u32 = 0x93D0;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR: u32 = 0x93D2;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR: u32 = 0x93D4;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR: u32 = 0x93D7;
 pub const GL_VENDOR: u32 = 0x1F00;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
@@ -302,6 +415,19 @@ pub const ERROR_INVALID_VERSION_ARB: u32 = 0x2095;
 pub const ERROR_INVALID_PROFILE_ARB: u32 = 0x2096;
 pub const ERROR_INCOMPATIBLE_DEVICE_CONTEXTS_ARB: u32 = 0x2054;
 
+/// GLES 2 has the queries only as extensions, e.g. glGenQueriesEXT of
+/// EXT_occlusion_query_boolean, so these are looked up by their EXT names too.
+/// Nothing else is, EXT variants don't always behave the same as the core functions.
+const QUERY_FUNCTIONS: &[&str] = &[
+    "glGenQueries",
+    "glDeleteQueries",
+    "glBeginQuery",
+    "glEndQuery",
+    "glGetQueryObjectiv",
+    "glGetQueryObjectuiv",
+    "glGetQueryObjectui64v",
+];
+
 macro_rules! gl_loader {
     (
         $(
@@ -326,7 +452,11 @@ macro_rules! gl_loader {
             $(
                 unsafe {
                     let fn_name = stringify!($fn);
-                    __pfns::$fn = ::std::mem::transmute_copy(&getprocaddr(fn_name));
+                    let mut proc = getprocaddr(fn_name);
+                    if proc.is_none() && QUERY_FUNCTIONS.contains(&fn_name) {
+                        proc = getprocaddr(&format!("{}EXT", fn_name));
+                    }
+                    __pfns::$fn = ::std::mem::transmute_copy(&proc);
                 }
             )*
         }
@@ -635,7 +765,155 @@ gl_loader!(
     fn glEndQuery(target: GLenum) -> (),
     fn glGenQueries(n: GLsizei, ids: *mut GLuint) -> (),
     fn glGetQueryObjectiv(id: GLuint, pname: GLenum, params: *mut GLint) -> (),
+    fn glGetQueryObjectuiv(id: GLuint, pname: GLenum, params: *mut GLuint) -> (),
     fn glGetQueryObjectui64v(id: GLuint, pname: GLenum, params: *mut GLuint64) -> (),
+    fn glBindBufferRange(
+        target: GLenum,
+        index: GLuint,
+        buffer: GLuint,
+        offset: GLintptr,
+        size: GLsizeiptr
+    ) -> (),
+    fn glGetUniformBlockIndex(program: GLuint, uniformBlockName: *const GLchar) -> GLuint,
+    fn glUniformBlockBinding(
+        program: GLuint,
+        uniformBlockIndex: GLuint,
+        uniformBlockBinding: GLuint
+    ) -> (),
+    fn glGetActiveUniformBlockiv(
+        program: GLuint,
+        uniformBlockIndex: GLuint,
+        pname: GLenum,
+        params: *mut GLint
+    ) -> (),
+    fn glGetUniformIndices(
+        program: GLuint,
+        uniformCount: GLsizei,
+        uniformNames: *const *const GLchar,
+        uniformIndices: *mut GLuint
+    ) -> (),
+    fn glGetActiveUniformsiv(
+        program: GLuint,
+        uniformCount: GLsizei,
+        uniformIndices: *const GLuint,
+        pname: GLenum,
+        params: *mut GLint
+    ) -> (),
+    fn glGetActiveUniformBlockName(
+        program: GLuint,
+        uniformBlockIndex: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        uniformBlockName: *mut GLchar
+    ) -> (),
+    fn glGetActiveUniform(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
+    fn glGetActiveAttrib(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
+    fn glCompressedTexSubImage2D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        imageSize: GLsizei,
+        data: *const GLvoid
+    ) -> (),
+    fn glCompressedTexSubImage3D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        zoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        depth: GLsizei,
+        format: GLenum,
+        imageSize: GLsizei,
+        data: *const GLvoid
+    ) -> (),
+    fn glDrawElementsInstancedBaseVertex(
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const ::std::os::raw::c_void,
+        instancecount: GLsizei,
+        basevertex: GLint
+    ) -> (),
+    fn glDrawElementsIndirect(
+        mode: GLenum,
+        type_: GLenum,
+        indirect: *const ::std::os::raw::c_void
+    ) -> (),
+    fn glMultiDrawElementsIndirect(
+        mode: GLenum,
+        type_: GLenum,
+        indirect: *const ::std::os::raw::c_void,
+        drawcount: GLsizei,
+        stride: GLsizei
+    ) -> (),
+    fn glCopyTexSubImage2D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei
+    ) -> (),
+    fn glMapBufferRange(
+        target: GLenum,
+        offset: GLintptr,
+        length: GLsizeiptr,
+        access: GLbitfield
+    ) -> *mut ::std::os::raw::c_void,
+    fn glUnmapBuffer(target: GLenum) -> GLboolean,
+    fn glFenceSync(condition: GLenum, flags: GLbitfield) -> GLsync,
+    fn glClientWaitSync(sync: GLsync, flags: GLbitfield, timeout: GLuint64) -> GLenum,
+    fn glDeleteSync(sync: GLsync) -> (),
+    fn glDebugMessageCallback(callback: GLDEBUGPROC, user_param: *const GLvoid) -> (),
+    fn glObjectLabel(identifier: GLenum, name: GLuint, length: GLsizei, label: *const GLchar) -> (),
+    fn glPushDebugGroup(source: GLenum, id: GLuint, length: GLsizei, message: *const GLchar) -> (),
+    fn glPopDebugGroup() -> (),
+    fn glDebugMessageInsert(
+        source: GLenum,
+        type_: GLenum,
+        id: GLuint,
+        severity: GLenum,
+        length: GLsizei,
+        buf: *const GLchar
+    ) -> (),
+    fn glGetProgramBinary(
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        binary_format: *mut GLenum,
+        binary: *mut ::std::os::raw::c_void
+    ) -> (),
+    fn glProgramBinary(
+        program: GLuint,
+        binary_format: GLenum,
+        binary: *const ::std::os::raw::c_void,
+        length: GLsizei
+    ) -> (),
+    fn glProgramParameteri(program: GLuint, pname: GLenum, value: GLint) -> (),
     fn glFlush() -> (),
     fn glFinish() -> (),
     fn glPolygonMode(face: GLenum, mode: GLenum) -> ()
@@ -653,3 +931,46 @@ pub unsafe fn is_gl2() -> bool {
         || version_string.starts_with("2")
         || version_string.starts_with("OpenGL ES 2")
 }
+
+/// Whether everything the queries of this type call is there, core or EXT.
+/// GLES 2 drivers may advertise the query extensions without some of the entry points.
+pub fn query_functions_loaded(query_type: crate::graphics::QueryType) -> bool {
+    // the braces copy the pointer out instead of referencing the static mut
+    unsafe {
+        let loaded = { __pfns::glGenQueries }.is_some()
+            && { __pfns::glDeleteQueries }.is_some()
+            && { __pfns::glBeginQuery }.is_some()
+            && { __pfns::glEndQuery }.is_some()
+            && { __pfns::glGetQueryObjectuiv }.is_some();
+        match query_type {
+            crate::graphics::QueryType::TimeElapsed => {
+                loaded && { __pfns::glGetQueryObjectui64v }.is_some()
+            }
+            _ => loaded,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_load_query_functions_ext() {
+    unsafe extern "C" fn stub() {}
+
+    // GL functions are global, no headless runs in the meantime. The next one reloads them.
+    let _run = crate::native::linux_headless::TEST_RUN
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    // a GLES 2 driver with EXT_occlusion_query_boolean, but also EXT names of the rest
+    load_gl_funcs(|name| {
+        if name.ends_with("EXT") {
+            Some(stub as unsafe extern "C" fn())
+        } else {
+            None
+        }
+    });
+    assert!(query_functions_loaded(
+        crate::graphics::QueryType::AnySamplesPassed
+    ));
+    assert!(unsafe { __pfns::glGenFramebuffers }.is_none());
+    assert!(unsafe { __pfns::glDrawArraysInstanced }.is_none());
+}
@@ -449,6 +449,8 @@ unsafe fn create_metal_view(screen_rect: NSRect, _sample_count: i32, _high_dpi:
 
     msg_send_![mtk_view_obj, setEnableSetNeedsDisplay: YES];
     msg_send_![mtk_view_obj, setPaused: YES];
+    // read_default_framebuffer blits from the drawable
+    msg_send_![mtk_view_obj, setFramebufferOnly: NO];
     msg_send_![mtk_view_obj, setPreferredFramesPerSecond:60];
     msg_send_![mtk_view_obj, setDelegate: mtk_view_dlg_obj];
     let device = MTLCreateSystemDefaultDevice();
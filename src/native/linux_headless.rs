@@ -0,0 +1,339 @@
+//! No display server at all: EGL context on the surfaceless platform (or a pbuffer)
+//! with the default framebuffer being an offscreen FBO.
+//! Useful to run the real GL backend on llvmpipe in CI containers.
+
+use crate::{
+    event::EventHandler,
+    native::{egl, gl::*, NativeDisplayData, Request},
+};
+
+use std::ffi::CStr;
+
+struct HeadlessClipboard;
+impl crate::native::Clipboard for HeadlessClipboard {
+    fn get(&mut self) -> Option<String> {
+        None
+    }
+    fn set(&mut self, _data: &str) {}
+}
+
+/// Offscreen replacement of the window framebuffer.
+struct Framebuffer {
+    fbo: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+}
+
+impl Framebuffer {
+    unsafe fn new(width: i32, height: i32) -> Framebuffer {
+        let mut fbo = 0;
+        let mut renderbuffers = [0; 2];
+        glGenFramebuffers(1, &mut fbo as *mut _);
+        glGenRenderbuffers(2, renderbuffers.as_mut_ptr());
+        let framebuffer = Framebuffer {
+            fbo,
+            color: renderbuffers[0],
+            depth_stencil: renderbuffers[1],
+        };
+        framebuffer.resize(width, height);
+
+        glBindFramebuffer(GL_FRAMEBUFFER, fbo);
+        glFramebufferRenderbuffer(
+            GL_FRAMEBUFFER,
+            GL_COLOR_ATTACHMENT0,
+            GL_RENDERBUFFER,
+            framebuffer.color,
+        );
+        glFramebufferRenderbuffer(
+            GL_FRAMEBUFFER,
+            GL_DEPTH_STENCIL_ATTACHMENT,
+            GL_RENDERBUFFER,
+            framebuffer.depth_stencil,
+        );
+        assert_eq!(
+            glCheckFramebufferStatus(GL_FRAMEBUFFER),
+            GL_FRAMEBUFFER_COMPLETE,
+            "Headless framebuffer is incomplete"
+        );
+        framebuffer
+    }
+
+    unsafe fn resize(&self, width: i32, height: i32) {
+        glBindRenderbuffer(GL_RENDERBUFFER, self.color);
+        glRenderbufferStorage(GL_RENDERBUFFER, GL_RGBA8, width, height);
+        glBindRenderbuffer(GL_RENDERBUFFER, self.depth_stencil);
+        glRenderbufferStorage(GL_RENDERBUFFER, GL_DEPTH24_STENCIL8, width, height);
+        glBindRenderbuffer(GL_RENDERBUFFER, 0);
+    }
+}
+
+unsafe fn query_extensions(egl: &egl::LibEgl, display: egl::EGLDisplay) -> String {
+    let extensions = (egl.eglQueryString.unwrap())(display, egl::EGL_EXTENSIONS as _);
+    if extensions.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(extensions).to_string_lossy().into_owned()
+}
+
+/// Prefer EGL_MESA_platform_surfaceless, fall back to the default display.
+unsafe fn get_display(egl: &egl::LibEgl) -> Option<egl::EGLDisplay> {
+    let client_extensions = query_extensions(egl, /* EGL_NO_DISPLAY */ std::ptr::null_mut());
+    if client_extensions.contains("EGL_MESA_platform_surfaceless") {
+        let name = std::ffi::CString::new("eglGetPlatformDisplayEXT").unwrap();
+        let get_platform_display: egl::PFNEGLGETPLATFORMDISPLAYEXTPROC =
+            std::mem::transmute((egl.eglGetProcAddress.unwrap())(name.as_ptr()));
+        if let Some(get_platform_display) = get_platform_display {
+            let display = get_platform_display(
+                egl::EGL_PLATFORM_SURFACELESS_MESA as _,
+                /* EGL_DEFAULT_DISPLAY */ std::ptr::null_mut(),
+                std::ptr::null(),
+            );
+            if !display.is_null() {
+                return Some(display);
+            }
+        }
+    }
+
+    let display = (egl.eglGetDisplay?)(/* EGL_DEFAULT_DISPLAY */ std::ptr::null_mut());
+    if display.is_null() {
+        return None;
+    }
+    Some(display)
+}
+
+/// The event loop and the window functions are global, so runs from parallel tests
+/// take turns on this lock.
+#[cfg(any(test, feature = "test-support"))]
+pub(crate) static TEST_RUN: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+pub fn run<F>(conf: &crate::conf::Conf, f: &mut Option<F>) -> Option<()>
+where
+    F: 'static + FnOnce() -> Box<dyn EventHandler>,
+{
+    unsafe {
+        let egl = egl::LibEgl::try_load()?;
+        let display = get_display(&egl)?;
+        if (egl.eglInitialize.unwrap())(display, std::ptr::null_mut(), std::ptr::null_mut()) == 0 {
+            eprintln!("eglInitialize failed");
+            return None;
+        }
+        let surfaceless = query_extensions(&egl, display).contains("EGL_KHR_surfaceless_context");
+
+        let surface_type = if surfaceless { 0 } else { egl::EGL_PBUFFER_BIT };
+        #[rustfmt::skip]
+        let cfg_attributes = [
+            egl::EGL_SURFACE_TYPE, surface_type,
+            egl::EGL_RENDERABLE_TYPE, egl::EGL_OPENGL_ES2_BIT,
+            egl::EGL_RED_SIZE, 8,
+            egl::EGL_GREEN_SIZE, 8,
+            egl::EGL_BLUE_SIZE, 8,
+            egl::EGL_ALPHA_SIZE, 8,
+            egl::EGL_NONE,
+        ];
+        let mut config: egl::EGLConfig = std::ptr::null_mut();
+        let mut cfg_count = 0;
+        (egl.eglChooseConfig.unwrap())(
+            display,
+            cfg_attributes.as_ptr() as _,
+            &mut config,
+            1,
+            &mut cfg_count,
+        );
+        if cfg_count == 0 {
+            eprintln!("No suitable EGL config for headless rendering");
+            return None;
+        }
+
+        let context = egl::create_context(&egl, display, config, conf.platform.gl_debug);
+        if context.is_null() {
+            eprintln!("eglCreateContext failed");
+            return None;
+        }
+
+        let (w, h) = (conf.window_width, conf.window_height);
+        let surface = if surfaceless {
+            /* EGL_NO_SURFACE */
+            std::ptr::null_mut()
+        } else {
+            // The pbuffer is never rendered to, it is only there to make the context current
+            let pbuffer_attributes = [egl::EGL_WIDTH, 1, egl::EGL_HEIGHT, 1, egl::EGL_NONE];
+            let surface = (egl.eglCreatePbufferSurface.unwrap())(
+                display,
+                config,
+                pbuffer_attributes.as_ptr() as _,
+            );
+            if surface.is_null() {
+                eprintln!("eglCreatePbufferSurface failed");
+                return None;
+            }
+            surface
+        };
+        if (egl.eglMakeCurrent.unwrap())(display, surface, surface, context) == 0 {
+            panic!("eglMakeCurrent failed");
+        }
+
+        crate::native::gl::load_gl_funcs(|proc| {
+            let name = std::ffi::CString::new(proc).unwrap();
+            egl.eglGetProcAddress.expect("non-null function pointer")(name.as_ptr() as _)
+        });
+
+        // GlContext takes whatever framebuffer is bound on creation as the default one
+        let framebuffer = Framebuffer::new(w, h);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let clipboard = Box::new(HeadlessClipboard);
+        crate::set_display(NativeDisplayData {
+            high_dpi: conf.high_dpi,
+            ..NativeDisplayData::new(w, h, tx, clipboard)
+        });
+
+        let mut event_handler = (f.take().unwrap())();
+
+        let mut frame = 0;
+        while !crate::native_display().try_lock().unwrap().quit_ordered
+            && !matches!(conf.platform.headless_frames, Some(n) if frame >= n)
+        {
+            while let Ok(request) = rx.try_recv() {
+                // There is no window, so the only meaningful request is a resize
+                if let Request::SetWindowSize {
+                    new_width,
+                    new_height,
+                } = request
+                {
+                    framebuffer.resize(new_width as _, new_height as _);
+                    {
+                        let mut d = crate::native_display().try_lock().unwrap();
+                        d.screen_width = new_width as _;
+                        d.screen_height = new_height as _;
+                    }
+                    event_handler.resize_event(new_width as _, new_height as _);
+                }
+            }
+
+            event_handler.update();
+            event_handler.draw();
+            glFinish();
+            frame += 1;
+
+            let d = crate::native_display().try_lock().unwrap();
+            if d.quit_requested && !d.quit_ordered {
+                drop(d);
+                event_handler.quit_requested_event();
+                let mut d = crate::native_display().try_lock().unwrap();
+                if d.quit_requested {
+                    d.quit_ordered = true
+                }
+            }
+        }
+
+        drop(event_handler);
+        (egl.eglMakeCurrent.unwrap())(
+            display,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+        (egl.eglDestroyContext.unwrap())(display, context);
+        if !surface.is_null() {
+            (egl.eglDestroySurface.unwrap())(display, surface);
+        }
+        (egl.eglTerminate.unwrap())(display);
+    }
+
+    Some(())
+}
+
+/// Run `f` once with a `GlContext` on the headless backend, with a `width` x `height`
+/// default framebuffer. For unit tests of the GL backend.
+#[cfg(test)]
+pub(crate) fn with_gl_context<T, F>(width: i32, height: i32, f: F) -> T
+where
+    T: 'static,
+    F: 'static + FnOnce(&mut crate::GlContext) -> T,
+{
+    struct Stage<T, F> {
+        f: Option<F>,
+        result: std::rc::Rc<std::cell::RefCell<Option<T>>>,
+    }
+    impl<T, F: FnOnce(&mut crate::GlContext) -> T> EventHandler for Stage<T, F> {
+        fn update(&mut self) {}
+        fn draw(&mut self) {
+            if let Some(f) = self.f.take() {
+                let mut ctx = crate::GlContext::new();
+                *self.result.borrow_mut() = Some(f(&mut ctx));
+            }
+        }
+    }
+
+    // a failed test panics with the lock held, that doesn't break the next one
+    let _run = TEST_RUN.lock().unwrap_or_else(|err| err.into_inner());
+    let mut conf = crate::conf::Conf {
+        window_width: width,
+        window_height: height,
+        ..Default::default()
+    };
+    conf.platform.linux_backend = crate::conf::LinuxBackend::Headless;
+    conf.platform.headless_frames = Some(1);
+
+    let result = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let stage_result = result.clone();
+    crate::start(conf, move || {
+        Box::new(Stage {
+            f: Some(f),
+            result: stage_result,
+        })
+    });
+    let result = result.borrow_mut().take();
+    result.expect("The headless backend did not draw a frame")
+}
+
+#[test]
+fn test_headless_frames() {
+    use crate::{PassAction, RenderingBackend};
+
+    struct Stage {
+        ctx: Box<dyn RenderingBackend>,
+        frames: std::rc::Rc<std::cell::RefCell<Vec<Vec<u8>>>>,
+    }
+    impl EventHandler for Stage {
+        fn update(&mut self) {}
+        fn draw(&mut self) {
+            // a different clear color every frame
+            let red = self.frames.borrow().len() as f32 * 0.5;
+            self.ctx
+                .begin_default_pass(PassAction::clear_color(red, 0.0, 1.0, 1.0));
+            self.ctx.end_render_pass();
+            self.ctx.commit_frame();
+            let pixels = self.ctx.read_default_framebuffer((0, 0, 3, 2));
+            self.frames.borrow_mut().push(pixels);
+        }
+    }
+
+    let _run = TEST_RUN.lock().unwrap_or_else(|err| err.into_inner());
+    let mut conf = crate::conf::Conf {
+        window_width: 3,
+        window_height: 2,
+        ..Default::default()
+    };
+    conf.platform.linux_backend = crate::conf::LinuxBackend::Headless;
+    conf.platform.headless_frames = Some(3);
+
+    let frames = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    let stage_frames = frames.clone();
+    crate::start(conf, move || {
+        Box::new(Stage {
+            ctx: crate::window::new_rendering_backend(),
+            frames: stage_frames,
+        })
+    });
+
+    let frames = frames.borrow();
+    assert_eq!(frames.len(), 3);
+    for (frame, red) in frames.iter().zip([0u8, 128, 255].iter()) {
+        assert_eq!(frame.len(), 3 * 2 * 4);
+        for pixel in frame.chunks(4) {
+            assert!(pixel[0].abs_diff(*red) <= 1, "{:?}", pixel);
+            assert_eq!(pixel[1..], [0, 255, 255]);
+        }
+    }
+}
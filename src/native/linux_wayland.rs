@@ -629,6 +629,7 @@ where
             wdisplay as *mut _,
             conf.platform.framebuffer_alpha,
             conf.sample_count,
+            conf.platform.gl_debug,
         )
         .unwrap();
 
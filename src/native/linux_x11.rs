@@ -360,7 +360,8 @@ where
             .libx11
             .create_window(display.root, display.display, visual, depth, conf);
 
-    let (glx_context, glx_window) = glx.create_context(display.display, display.window);
+    let (glx_context, glx_window) =
+        glx.create_context(display.display, display.window, conf.platform.gl_debug);
     glx.swap_interval(
         display.display,
         glx_window,
@@ -458,6 +459,7 @@ where
         display.display as *mut _,
         conf.platform.framebuffer_alpha,
         conf.sample_count,
+        conf.platform.gl_debug,
     )
     .unwrap();
 
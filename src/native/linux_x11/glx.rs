@@ -29,6 +29,7 @@ pub const GLX_CONTEXT_PROFILE_MASK_ARB: libc::c_int = 0x9126 as libc::c_int;
 pub const GLX_CONTEXT_CORE_PROFILE_BIT_ARB: libc::c_int = 0x1 as libc::c_int;
 pub const GLX_CONTEXT_FLAGS_ARB: libc::c_int = 0x2094 as libc::c_int;
 pub const GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB: libc::c_int = 0x2 as libc::c_int;
+pub const GLX_CONTEXT_DEBUG_BIT_ARB: libc::c_int = 0x1 as libc::c_int;
 
 pub type GLenum = ::std::os::raw::c_uint;
 pub type GLboolean = ::std::os::raw::c_uchar;
@@ -317,6 +318,7 @@ impl Glx {
         &mut self,
         display: *mut Display,
         window: Window,
+        debug: bool,
     ) -> (GLXContext, GLXWindow) {
         if self.extensions.glxCreateContextAttribsARB.is_none() {
             panic!("GLX: ARB_create_context and ARB_create_context_profile required");
@@ -329,7 +331,7 @@ impl Glx {
             GLX_CONTEXT_MINOR_VERSION_ARB,
             1,
             GLX_CONTEXT_FLAGS_ARB,
-            GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
+            if debug { GLX_CONTEXT_DEBUG_BIT_ARB } else { 0 },
             0,
             0,
         ];
@@ -791,6 +791,8 @@ unsafe fn create_metal_view(_: NSRect, sample_count: i32, _: bool) -> ObjcId {
         setDepthStencilPixelFormat: MTLPixelFormat::Depth32Float_Stencil8
     ];
     let () = msg_send![view, setSampleCount: sample_count];
+    // read_default_framebuffer blits from the drawable
+    let () = msg_send![view, setFramebufferOnly: false];
     let () = msg_send![view, setEnableSetNeedsDisplay: true];
     let () = msg_send![view, setPaused: true];
 
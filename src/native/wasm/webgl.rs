@@ -240,15 +240,110 @@ pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
+pub const GL_PACK_ALIGNMENT: u32 = 3333;
 pub const GL_TEXTURE_SWIZZLE_R: u32 = 36418;
 pub const GL_TEXTURE_SWIZZLE_G: u32 = 36419;
 pub const GL_TEXTURE_SWIZZLE_B: u32 = 36420;
 pub const GL_TEXTURE_SWIZZLE_A: u32 = 36421;
 pub const GL_TEXTURE_SWIZZLE_RGBA: u32 = 36422;
 pub const GL_DRAW_FRAMEBUFFER_BINDING: u32 = 36006;
+pub const GL_READ_FRAMEBUFFER_BINDING: u32 = 0x8CAA;
+pub const GL_SAMPLE_BUFFERS: u32 = 0x80A8;
 pub const GL_TIME_ELAPSED: u32 = 35007;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
+pub const GL_SAMPLES_PASSED: u32 = 0x8914;
+pub const GL_ANY_SAMPLES_PASSED: u32 = 0x8C2F;
+pub const GL_UNIFORM_BUFFER: u32 = 0x8A11;
+pub const GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT: u32 = 0x8A34;
+pub const GL_UNIFORM_OFFSET: u32 = 0x8A3B;
+pub const GL_UNIFORM_ARRAY_STRIDE: u32 = 0x8A3C;
+pub const GL_UNIFORM_BLOCK_DATA_SIZE: u32 = 0x8A40;
+pub const GL_INVALID_INDEX: u32 = 0xFFFFFFFF;
+pub const GL_ACTIVE_UNIFORMS: u32 = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: u32 = 0x8B89;
+pub const GL_ACTIVE_UNIFORM_BLOCKS: u32 = 0x8A36;
+pub const GL_UNIFORM_BLOCK_INDEX: u32 = 0x8A3A;
+pub const GL_FLOAT_VEC2: u32 = 0x8B50;
+pub const GL_FLOAT_VEC3: u32 = 0x8B51;
+pub const GL_FLOAT_VEC4: u32 = 0x8B52;
+pub const GL_INT_VEC2: u32 = 0x8B53;
+pub const GL_INT_VEC3: u32 = 0x8B54;
+pub const GL_INT_VEC4: u32 = 0x8B55;
+pub const GL_FLOAT_MAT4: u32 = 0x8B5C;
+pub const GL_SAMPLER_2D: u32 = 0x8B5E;
+pub const GL_SAMPLER_3D: u32 = 0x8B5F;
+pub const GL_SAMPLER_CUBE: u32 = 0x8B60;
+pub const GL_SAMPLER_2D_SHADOW: u32 = 0x8B62;
+pub const GL_SAMPLER_2D_ARRAY: u32 = 0x8DC1;
+pub const GL_SAMPLER_2D_ARRAY_SHADOW: u32 = 0x8DC4;
+pub const GL_SAMPLER_CUBE_SHADOW: u32 = 0x8DC5;
+pub const GL_INT_SAMPLER_2D: u32 = 0x8DCA;
+pub const GL_INT_SAMPLER_3D: u32 = 0x8DCB;
+pub const GL_INT_SAMPLER_CUBE: u32 = 0x8DCC;
+pub const GL_INT_SAMPLER_2D_ARRAY: u32 = 0x8DCF;
+pub const GL_UNSIGNED_INT_SAMPLER_2D: u32 = 0x8DD2;
+pub const GL_UNSIGNED_INT_SAMPLER_3D: u32 = 0x8DD3;
+pub const GL_UNSIGNED_INT_SAMPLER_CUBE: u32 = 0x8DD4;
+pub const GL_UNSIGNED_INT_SAMPLER_2D_ARRAY: u32 = 0x8DD7;
+pub const GL_SRGB8_ALPHA8: u32 = 0x8C43;
+pub const GL_DEPTH_STENCIL: u32 = 0x84F9;
+pub const GL_UNSIGNED_INT_24_8: u32 = 0x84FA;
+pub const GL_HALF_FLOAT: u32 = 0x140B;
+pub const GL_IMPLEMENTATION_COLOR_READ_TYPE: u32 = 0x8B9A;
+pub const GL_IMPLEMENTATION_COLOR_READ_FORMAT: u32 = 0x8B9B;
+pub const GL_FRAMEBUFFER_SRGB: u32 = 0x8DB9;
+pub const GL_DRAW_INDIRECT_BUFFER: u32 = 0x8F3F;
+pub const GL_MAX_SAMPLES: u32 = 0x8D57;
+pub const GL_DEPTH_COMPONENT32F: u32 = 0x8CAC;
+pub const GL_PIXEL_PACK_BUFFER: u32 = 0x88EB;
+pub const GL_STREAM_READ: u32 = 0x88E1;
+pub const GL_MAP_READ_BIT: u32 = 0x0001;
+pub const GL_SYNC_GPU_COMMANDS_COMPLETE: u32 = 0x9117;
+pub const GL_ALREADY_SIGNALED: u32 = 0x911A;
+pub const GL_CONDITION_SATISFIED: u32 = 0x911C;
+pub const GL_CONTEXT_FLAGS: u32 = 0x821E;
+pub const GL_CONTEXT_FLAG_DEBUG_BIT: u32 = 0x00000002;
+pub const GL_DEBUG_OUTPUT: u32 = 0x92E0;
+pub const GL_DEBUG_OUTPUT_SYNCHRONOUS: u32 = 0x8242;
+pub const GL_DEBUG_SOURCE_APPLICATION: u32 = 0x824A;
+pub const GL_DEBUG_TYPE_MARKER: u32 = 0x8268;
+pub const GL_DEBUG_SEVERITY_HIGH: u32 = 0x9146;
+pub const GL_DEBUG_SEVERITY_MEDIUM: u32 = 0x9147;
+pub const GL_DEBUG_SEVERITY_LOW: u32 = 0x9148;
+pub const GL_DEBUG_SEVERITY_NOTIFICATION: u32 = 0x826B;
+pub const GL_TEXTURE: u32 = 0x1702;
+pub const GL_BUFFER: u32 = 0x82E0;
+pub const GL_PROGRAM: u32 = 0x82E2;
+pub const GL_PROGRAM_BINARY_RETRIEVABLE_HINT: u32 = 0x8257;
+pub const GL_PROGRAM_BINARY_LENGTH: u32 = 0x8741;
+pub const GL_NUM_PROGRAM_BINARY_FORMATS: u32 = 0x87FE;
+pub const GL_RENDERER: u32 = 0x1F01;
+pub const GL_DEPTH24_STENCIL8: u32 = 0x88F0;
+pub const GL_DEPTH_STENCIL_ATTACHMENT: u32 = 0x821A;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT3_EXT: u32 = 0x83F2;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+pub const GL_COMPRESSED_RED_RGTC1: u32 = 0x8DBB;
+pub const GL_COMPRESSED_RG_RGTC2: u32 = 0x8DBD;
+pub const GL_COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+pub const GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT: u32 = 0x8E8F;
+pub const GL_COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+pub const GL_COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+pub const GL_COMPRESSED_RGBA_ASTC_4x4_KHR: u32 = 0x93B0;
+pub const GL_COMPRESSED_RGBA_ASTC_5x5_KHR: u32 = 0x93B2;
+pub const GL_COMPRESSED_RGBA_ASTC_6x6_KHR: u32 = 0x93B4;
+pub const GL_COMPRESSED_RGBA_ASTC_8x8_KHR: u32 = 0x93B7;
+pub const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: u32 = 0x8C4D;
+pub const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: u32 = 0x8C4E;
+pub const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
+pub const GL_COMPRESSED_SRGB_ALPHA_BPTC_UNORM: u32 = 0x8E8D;
+pub const GL_COMPRESSED_SRGB8_ETC2: u32 = 0x9275;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC: u32 = 0x9279;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR: u32 = 0x93D0;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR: u32 = 0x93D2;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR: u32 = 0x93D4;
+pub const GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR: u32 = 0x93D7;
 pub const GL_VENDOR: u32 = 0x1F00;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
@@ -1028,6 +1123,9 @@ extern "C" {
 extern "C" {
     pub fn glGetQueryObjectiv(id: GLuint, pname: GLenum, params: *mut GLint);
 }
+extern "C" {
+    pub fn glGetQueryObjectuiv(id: GLuint, pname: GLenum, params: *mut GLuint);
+}
 extern "C" {
     pub fn glGetQueryObjectui64v(id: GLuint, pname: GLenum, params: *mut GLuint64);
 }
@@ -1329,6 +1427,40 @@ extern "C" {
         instancecount: GLsizei,
     );
 }
+extern "C" {
+    pub fn glDrawElementsInstancedBaseVertex(
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const ::std::os::raw::c_void,
+        instancecount: GLsizei,
+        basevertex: GLint,
+    );
+}
+extern "C" {
+    pub fn glDrawElementsIndirect(
+        mode: GLenum,
+        type_: GLenum,
+        indirect: *const ::std::os::raw::c_void,
+    );
+}
+extern "C" {
+    pub fn glMultiDrawElementsIndirect(
+        mode: GLenum,
+        type_: GLenum,
+        indirect: *const ::std::os::raw::c_void,
+        drawcount: GLsizei,
+        stride: GLsizei,
+    );
+}
+extern "C" {
+    pub fn glGetBufferSubData(
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *mut ::std::os::raw::c_void,
+    );
+}
 extern "C" {
     pub fn glFenceSync(condition: GLenum, flags: GLbitfield) -> GLsync;
 }
@@ -911,6 +911,7 @@ where
             &mut display,
             conf.sample_count,
             conf.platform.swap_interval.unwrap_or(1),
+            conf.platform.gl_debug,
         );
 
         super::gl::load_gl_funcs(|proc| display.get_proc_address(proc));
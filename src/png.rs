@@ -0,0 +1,473 @@
+//! Minimal PNG codec for screenshots and reference images.
+//!
+//! Encoding always produces 8-bit RGBA with uncompressed deflate blocks.
+//! Decoding supports non-interlaced 8-bit grayscale, grayscale with alpha,
+//! RGB, RGBA and palette images, the output is always RGBA8.
+//! Rows are top-down in both directions, the same as in the PNG file.
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSignature,
+    /// Valid PNG, but uses a feature this decoder does not implement
+    Unsupported(&'static str),
+    Corrupted(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error: {:?}", self)
+    }
+}
+
+impl std::error::Error for Error {}
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for chunk in chunks {
+        for byte in chunk.iter() {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&[kind, data]).to_be_bytes());
+}
+
+/// Encode `width` x `height` RGBA8 pixels, top row first.
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), (width * height * 4) as usize);
+
+    let mut ihdr = vec![];
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // bit depth 8, color type RGBA, default compression, filter and no interlace
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let row_size = width as usize * 4;
+    let mut raw = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in rgba.chunks_exact(row_size.max(1)).take(height as usize) {
+        // filter type None
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &zlib);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Decode a PNG file into (width, height, RGBA8 pixels), top row first.
+pub fn decode(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), Error> {
+    if bytes.len() < 8 || bytes[..8] != SIGNATURE {
+        return Err(Error::InvalidSignature);
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut idat = vec![];
+    let mut pos = 8;
+    loop {
+        if pos + 8 > bytes.len() {
+            return Err(Error::Corrupted("unexpected end of file"));
+        }
+        let len = u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+            as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let data = bytes
+            .get(pos + 8..pos + 8 + len)
+            .ok_or(Error::Corrupted("chunk is out of bounds"))?;
+        pos += 12 + len;
+        match kind {
+            b"IHDR" => {
+                if data.len() != 13 {
+                    return Err(Error::Corrupted("invalid IHDR"));
+                }
+                let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                if data[8] != 8 {
+                    return Err(Error::Unsupported("bit depth other than 8"));
+                }
+                if data[12] != 0 {
+                    return Err(Error::Unsupported("interlacing"));
+                }
+                header = Some((width, height, data[9]));
+            }
+            b"PLTE" => palette = data,
+            b"tRNS" => transparency = data,
+            b"IDAT" => idat.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let (width, height, color_type) = header.ok_or(Error::Corrupted("missing IHDR"))?;
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(Error::Corrupted("invalid color type")),
+    };
+
+    if idat.len() < 2 || idat[0] & 0x0f != 8 {
+        return Err(Error::Corrupted("invalid zlib header"));
+    }
+    let raw = inflate(&idat[2..])?;
+
+    let row_size = width as usize * channels;
+    if raw.len() < (row_size + 1) * height as usize {
+        return Err(Error::Corrupted("not enough image data"));
+    }
+    let mut pixels = vec![0u8; row_size * height as usize];
+    for y in 0..height as usize {
+        let filter = raw[y * (row_size + 1)];
+        let src = &raw[y * (row_size + 1) + 1..(y + 1) * (row_size + 1)];
+        let (prev, cur) = pixels.split_at_mut(y * row_size);
+        let prev = if y == 0 {
+            None
+        } else {
+            Some(&prev[(y - 1) * row_size..])
+        };
+        let cur = &mut cur[..row_size];
+        for x in 0..row_size {
+            let a = if x >= channels { cur[x - channels] } else { 0 };
+            let b = prev.map_or(0, |prev| prev[x]);
+            let c = match prev {
+                Some(prev) if x >= channels => prev[x - channels],
+                _ => 0,
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(Error::Corrupted("invalid filter type")),
+            };
+            cur[x] = src[x].wrapping_add(predictor);
+        }
+    }
+
+    let rgba = match color_type {
+        0 => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        2 => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        3 => {
+            let mut rgba = Vec::with_capacity(pixels.len() * 4);
+            for &index in &pixels {
+                let index = index as usize;
+                let color = palette
+                    .get(index * 3..index * 3 + 3)
+                    .ok_or(Error::Corrupted("palette index out of bounds"))?;
+                let alpha = transparency.get(index).copied().unwrap_or(255);
+                rgba.extend_from_slice(&[color[0], color[1], color[2], alpha]);
+            }
+            rgba
+        }
+        4 => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        _ => pixels,
+    };
+    Ok((width, height, rgba))
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32, Error> {
+        let mut res = 0;
+        for i in 0..count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or(Error::Corrupted("unexpected end of deflate stream"))?;
+            res |= ((byte as u32 >> self.bit) & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(res)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code, decoded bit by bit.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::Corrupted("invalid Huffman code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = vec![];
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or(Error::Corrupted("unexpected end of deflate stream"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                reader.pos += 4;
+                let block = data
+                    .get(reader.pos..reader.pos + len)
+                    .ok_or(Error::Corrupted("unexpected end of deflate stream"))?;
+                out.extend_from_slice(block);
+                reader.pos += len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(Error::Corrupted("invalid deflate block type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let codes = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for &index in &ORDER[..codes] {
+        lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literals + distances {
+        let symbol = code_lengths.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let prev = *lengths
+                    .last()
+                    .ok_or(Error::Corrupted("repeat with no previous length"))?;
+                (prev, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() != literals + distances {
+        return Err(Error::Corrupted("too many code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), Error> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= 29 {
+                    return Err(Error::Corrupted("invalid length symbol"));
+                }
+                let len =
+                    LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as _)? as usize;
+                let symbol = distances.decode(reader)? as usize;
+                if symbol >= 30 {
+                    return Err(Error::Corrupted("invalid distance symbol"));
+                }
+                let dist =
+                    DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as _)? as usize;
+                if dist > out.len() {
+                    return Err(Error::Corrupted("distance too far back"));
+                }
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_inflate() {
+    // zlib with Z_FIXED, a single block with the fixed Huffman codes
+    let fixed = [
+        75, 76, 74, 78, 132, 33, 29, 133, 140, 212, 156, 156, 124, 8, 169, 8, 0,
+    ];
+    assert_eq!(inflate(&fixed).unwrap(), b"abcabcabcabc, hello hello!");
+
+    // zlib defaults, a dynamic Huffman block with back references
+    #[rustfmt::skip]
+    let dynamic = [
+        229, 204, 129, 13, 192, 32, 8, 0, 48, 54, 17, 136, 130, 24, 20, 254, 63, 117, 135, 172,
+        7, 20, 184, 71, 250, 3, 22, 86, 176, 206, 110, 120, 27, 232, 98, 183, 151, 64, 36, 223,
+        100, 46, 132, 169, 56, 229, 148, 87, 14, 147, 237, 52, 186, 59, 169, 196, 213, 216, 72,
+        240, 143, 228, 3,
+    ];
+    let expected: Vec<u8> = (0..300u32)
+        .map(|i| ((i * i * 7 + i / 3) % 23) as u8)
+        .collect();
+    assert_eq!(inflate(&dynamic).unwrap(), expected);
+
+    assert!(inflate(&dynamic[..40]).is_err());
+}
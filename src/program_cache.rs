@@ -0,0 +1,330 @@
+//! Disk cache of linked GL programs, to skip compiling shaders on the next launch.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::program_cache::ProgramCache;
+//! # fn f(ctx: &mut dyn RenderingBackend, vertex: &str, fragment: &str, meta: ShaderMeta) {
+//! let cache = ProgramCache::new("cache/programs");
+//! let shader = cache
+//!     .new_shader(ctx, ShaderSource::Glsl { vertex, fragment }, meta)
+//!     .unwrap();
+//! # }
+//! ```
+//!
+//! The programs are stored with `RenderingBackend::shader_binary`, keyed by the sources
+//! and the GL vendor, renderer and version strings, so a driver update or another GPU
+//! misses the cache. A binary the driver rejects anyway is compiled from source
+//! and stored again.
+//!
+//! Needs `features.program_binary`, GL 4.1 or GLES 3.0. Otherwise `new_shader`
+//! just compiles from source.
+
+use crate::graphics::{
+    ContextInfo, RenderingBackend, ShaderBinary, ShaderError, ShaderId, ShaderMeta, ShaderSource,
+};
+
+use std::{
+    convert::TryInto,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 8] = b"MQPROG\0\0";
+const VERSION: u32 = 1;
+
+pub struct ProgramCache {
+    directory: PathBuf,
+}
+
+impl ProgramCache {
+    /// The directory is created on the first write.
+    pub fn new<P: AsRef<Path>>(directory: P) -> ProgramCache {
+        ProgramCache {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The same as `RenderingBackend::new_shader`, but loads the program from the cache
+    /// when it is there, and stores it otherwise.
+    ///
+    /// Failing to write the cache is not an error, the shader is compiled anyway.
+    pub fn new_shader(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let info = ctx.info();
+        if !info.features.program_binary {
+            return ctx.new_shader(shader, meta);
+        }
+
+        let key = key(&info, shader);
+        let path = self.path(&key);
+        if let Some(binary) = read(&path, &key) {
+            match ctx.new_shader_from_binary(&binary, meta.clone()) {
+                Ok(shader) => return Ok(shader),
+                Err(ShaderError::ProgramBinaryRejected) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let shader = ctx.new_shader(shader, meta)?;
+        if let Some(binary) = ctx.shader_binary(shader) {
+            let _ = self.write(&path, &key, &binary);
+        }
+        Ok(shader)
+    }
+
+    /// Delete all the cached programs.
+    pub fn clear(&self) -> std::io::Result<()> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "bin") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, key: &[u8]) -> PathBuf {
+        self.directory.join(format!("{:016x}.bin", fnv1a(key)))
+    }
+
+    fn write(&self, path: &Path, key: &[u8], binary: &ShaderBinary) -> std::io::Result<()> {
+        let mut file = Vec::with_capacity(20 + key.len() + binary.data.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&VERSION.to_le_bytes());
+        file.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        file.extend_from_slice(key);
+        file.extend_from_slice(&binary.format.to_le_bytes());
+        file.extend_from_slice(&binary.data);
+
+        fs::create_dir_all(&self.directory)?;
+        // another instance of the app may be reading it, never leave a half-written file
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, file)?;
+        fs::rename(&temporary, path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+}
+
+/// Everything the program depends on. Stored in the file as well,
+/// a hash collision reads as a miss.
+fn key(info: &ContextInfo, shader: ShaderSource) -> Vec<u8> {
+    let (vertex, fragment) = match shader {
+        ShaderSource::Glsl { vertex, fragment } => (vertex, fragment),
+        ShaderSource::Msl { program } => (program, ""),
+    };
+    [
+        &info.gl_vendor_string[..],
+        &info.gl_renderer_string,
+        &info.gl_version_string,
+        vertex,
+        fragment,
+    ]
+    .join("\0")
+    .into_bytes()
+}
+
+/// `None` if the file is missing, corrupt or for another key.
+fn read(path: &Path, key: &[u8]) -> Option<ShaderBinary> {
+    let file = fs::read(path).ok()?;
+    let rest = file.strip_prefix(&MAGIC[..])?;
+    let u32_at = |bytes: &[u8], at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+    };
+    if u32_at(rest, 0)? != VERSION {
+        return None;
+    }
+    let key_len = u32_at(rest, 4)? as usize;
+    let rest = rest.get(8..)?;
+    if rest.get(..key_len)? != key {
+        return None;
+    }
+    let format = u32_at(rest, key_len)?;
+    Some(ShaderBinary {
+        format,
+        data: rest.get(key_len + 4..)?.to_vec(),
+    })
+}
+
+/// Stable across runs and Rust versions, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[test]
+fn test_program_cache_file() {
+    let directory =
+        std::env::temp_dir().join(format!("miniquad-program-cache-{}", std::process::id()));
+    let cache = ProgramCache::new(&directory);
+    let path = directory.join("program.bin");
+    let binary = ShaderBinary {
+        format: 0x8E21,
+        data: vec![1, 2, 3, 4, 5],
+    };
+
+    cache.write(&path, b"vendor\0renderer", &binary).unwrap();
+    assert_eq!(read(&path, b"vendor\0renderer"), Some(binary));
+    assert_eq!(read(&path, b"vendor\0another renderer"), None);
+
+    let mut file = fs::read(&path).unwrap();
+    file.truncate(20);
+    fs::write(&path, file).unwrap();
+    assert_eq!(read(&path, b"vendor\0renderer"), None);
+
+    cache.clear().unwrap();
+    assert!(!path.exists());
+    fs::remove_dir(&directory).unwrap();
+}
+
+#[cfg(all(test, target_os = "linux"))]
+const VERTEX: &str = "#version 100
+attribute vec2 in_pos;
+void main() {
+    gl_Position = vec4(in_pos, 0.0, 1.0);
+}";
+#[cfg(all(test, target_os = "linux"))]
+const FRAGMENT: &str = "#version 100
+precision mediump float;
+void main() {
+    gl_FragColor = vec4(1.0);
+}";
+/// Fails to compile, so getting a shader for it means the binary was used.
+#[cfg(all(test, target_os = "linux"))]
+const BROKEN_FRAGMENT: &str = "#version 100
+void main() {
+    gl_FragColor = undeclared;
+}";
+
+#[cfg(all(test, target_os = "linux"))]
+fn meta() -> ShaderMeta {
+    ShaderMeta {
+        uniforms: crate::graphics::UniformBlockLayout { uniforms: vec![] },
+        uniform_blocks: vec![],
+        images: vec![],
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+fn source(fragment: &str) -> ShaderSource<'_> {
+    ShaderSource::Glsl {
+        vertex: VERTEX,
+        fragment,
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+fn temp_cache(name: &str) -> ProgramCache {
+    ProgramCache::new(std::env::temp_dir().join(format!(
+        "miniquad-program-cache-{}-{}",
+        name,
+        std::process::id()
+    )))
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_program_cache_round_trip() {
+    let cache = temp_cache("round-trip");
+    let directory = cache.directory.clone();
+    let result = crate::native::linux_headless::with_gl_context(1, 1, move |ctx| {
+        assert!(ctx.info().features.program_binary);
+        let shader = ctx.new_shader(source(FRAGMENT), meta()).unwrap();
+        let binary = ctx.shader_binary(shader).unwrap();
+        assert!(ctx.new_shader_from_binary(&binary, meta()).is_ok());
+
+        // first run compiles and stores
+        cache.new_shader(ctx, source(FRAGMENT), meta()).unwrap();
+        let fragment = key(&ctx.info(), source(FRAGMENT));
+        let stored = read(&cache.path(&fragment), &fragment).unwrap();
+        assert_eq!(stored.format, binary.format);
+
+        // the next run loads it, even for a source that would not compile anymore
+        let broken = key(&ctx.info(), source(BROKEN_FRAGMENT));
+        cache.write(&cache.path(&broken), &broken, &stored).unwrap();
+        let loaded = cache.new_shader(ctx, source(BROKEN_FRAGMENT), meta());
+        cache.clear().unwrap();
+        loaded.map(drop)
+    });
+    let _ = fs::remove_dir(&directory);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_program_cache_rejected_binary() {
+    let cache = temp_cache("rejected");
+    let directory = cache.directory.clone();
+    let result = crate::native::linux_headless::with_gl_context(1, 1, move |ctx| {
+        assert!(ctx.info().features.program_binary);
+        let shader = ctx.new_shader(source(FRAGMENT), meta()).unwrap();
+        let binary = ctx.shader_binary(shader).unwrap();
+        let corrupted = ShaderBinary {
+            format: binary.format,
+            data: binary.data.iter().map(|byte| !byte).collect(),
+        };
+        let rejected = ctx.new_shader_from_binary(&corrupted, meta()).map(drop);
+
+        // compiled from source instead, and the cache entry replaced
+        let key = key(&ctx.info(), source(FRAGMENT));
+        let path = cache.path(&key);
+        cache.write(&path, &key, &corrupted).unwrap();
+        let compiled = cache.new_shader(ctx, source(FRAGMENT), meta()).map(drop);
+        let stored = read(&path, &key);
+        cache.clear().unwrap();
+        (rejected, compiled, stored, corrupted)
+    });
+    let _ = fs::remove_dir(&directory);
+    let (rejected, compiled, stored, corrupted) = result;
+    assert!(matches!(rejected, Err(ShaderError::ProgramBinaryRejected)));
+    assert!(compiled.is_ok(), "{:?}", compiled);
+    assert_ne!(stored.unwrap(), corrupted);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_program_cache_other_driver() {
+    let cache = temp_cache("other-driver");
+    let directory = cache.directory.clone();
+    let result = crate::native::linux_headless::with_gl_context(1, 1, move |ctx| {
+        assert!(ctx.info().features.program_binary);
+        let shader = ctx.new_shader(source(FRAGMENT), meta()).unwrap();
+        let binary = ctx.shader_binary(shader).unwrap();
+
+        let info = ctx.info();
+        let mut other_vendor = info.clone();
+        other_vendor.gl_vendor_string = "Another Vendor".to_string();
+        let mut other_renderer = info.clone();
+        other_renderer.gl_renderer_string = "Another GPU".to_string();
+        let broken = key(&info, source(BROKEN_FRAGMENT));
+        for other in [other_vendor, other_renderer] {
+            // a binary stored by another driver, under its own name
+            let other = key(&other, source(BROKEN_FRAGMENT));
+            cache.write(&cache.path(&other), &other, &binary).unwrap();
+            // and the same under this driver's name, as if the hashes collided
+            cache.write(&cache.path(&broken), &other, &binary).unwrap();
+        }
+        let result = cache
+            .new_shader(ctx, source(BROKEN_FRAGMENT), meta())
+            .map(drop);
+        cache.clear().unwrap();
+        result
+    });
+    let _ = fs::remove_dir(&directory);
+    // the binary is ignored, the source compiled, and it does not compile
+    assert!(
+        matches!(result, Err(ShaderError::CompilationError { .. })),
+        "{:?}",
+        result
+    );
+}
@@ -0,0 +1,1027 @@
+//! GLSL preprocessing: `#include`, `#define`s and the `#version` header.
+//!
+//! Shader files are written without `#version`, the preprocessor picks the best
+//! version the running context supports and prepends it, together with a few defines:
+//! ```text
+//! #version 330
+//! #define MQ_GLSL_VERSION 330
+//! #define MQ_VERTEX_SHADER
+//! #define MAX_LIGHTS 4
+//! ```
+//! `MQ_GLSL_ES` is defined for "100" and "300 es", `MQ_FRAGMENT_SHADER` for fragment shaders.
+//!
+//! `#include "path"` is resolved relatively to the including file, `#pragma once`
+//! makes further includes of the file no-op.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::shader_preprocessor::*;
+//! # fn f(ctx: &mut dyn RenderingBackend, meta: ShaderMeta) {
+//! let preprocessor = Preprocessor::new()
+//!     .file("common.glsl", "uniform mat4 mvp;")
+//!     .file("sprite.vert", "#include \"common.glsl\"\nvoid main() {}")
+//!     .file("sprite.frag", "void main() {}")
+//!     .define("MAX_LIGHTS", "4");
+//! let mut variants = ShaderVariants::new(preprocessor, "sprite.vert", "sprite.frag", meta);
+//! let plain = variants.get(ctx, &[]).unwrap();
+//! let tinted = variants.get(ctx, &[("TINT", "1")]).unwrap();
+//! # }
+//! ```
+//! Files may be loaded with `fs::load_file` as well, includes included:
+//! ```no_run
+//! # use miniquad::shader_preprocessor::*;
+//! Preprocessor::new().load(&["shaders/sprite.vert", "shaders/sprite.frag"], |preprocessor| {
+//!     let preprocessor = preprocessor.unwrap();
+//! });
+//! ```
+//!
+//! Compile errors from `new_shader` and `ShaderVariants` have the line numbers mapped
+//! back to the original files, see `LineMap::annotate` and `LineMap::locate`.
+
+use crate::{
+    fs,
+    graphics::{
+        ContextInfo, GlslSupport, RenderingBackend, ShaderDiagnostic, ShaderError, ShaderId,
+        ShaderMeta, ShaderSource, ShaderType,
+    },
+};
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// `fs::load_file` failed
+    Load {
+        path: String,
+        error: fs::Error,
+    },
+    NotUtf8(String),
+    /// The file was neither added with `Preprocessor::file` nor loaded
+    NotFound {
+        path: String,
+        included_from: Option<(String, usize)>,
+    },
+    /// `#include` without a `"path"`
+    MalformedInclude {
+        file: String,
+        line: usize,
+    },
+    /// The file includes itself, directly or not
+    RecursiveInclude {
+        file: String,
+        line: usize,
+    },
+    /// The sources should not have `#version`, the preprocessor adds it
+    VersionDirective {
+        file: String,
+        line: usize,
+    },
+    /// None of `Preprocessor::versions` is supported by the context
+    NoSupportedVersion,
+    /// Line numbers of `ShaderError::CompilationError` are mapped to the original files
+    Shader(ShaderError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error: {:?}", self)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ShaderError> for Error {
+    fn from(e: ShaderError) -> Error {
+        Error::Shader(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlslVersion {
+    V100,
+    V130,
+    V300Es,
+    V330,
+}
+
+impl GlslVersion {
+    pub fn header(self) -> &'static str {
+        match self {
+            GlslVersion::V100 => "#version 100",
+            GlslVersion::V130 => "#version 130",
+            GlslVersion::V300Es => "#version 300 es",
+            GlslVersion::V330 => "#version 330",
+        }
+    }
+
+    pub fn is_es(self) -> bool {
+        matches!(self, GlslVersion::V100 | GlslVersion::V300Es)
+    }
+
+    pub fn is_supported(self, support: &GlslSupport) -> bool {
+        match self {
+            GlslVersion::V100 => support.v100,
+            GlslVersion::V130 => support.v130,
+            GlslVersion::V300Es => support.v300es,
+            GlslVersion::V330 => support.v330,
+        }
+    }
+
+    fn number(self) -> u32 {
+        match self {
+            GlslVersion::V100 => 100,
+            GlslVersion::V130 => 130,
+            GlslVersion::V300Es => 300,
+            GlslVersion::V330 => 330,
+        }
+    }
+}
+
+/// Maps the lines of a preprocessed source to the files and lines they came from.
+#[derive(Debug, Clone, Default)]
+pub struct LineMap {
+    files: Vec<String>,
+    /// `None` for the generated header
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl LineMap {
+    /// File and line of the 1-based `line` of the preprocessed source,
+    /// `None` for the generated `#version` and `#define` lines.
+    pub fn location(&self, line: usize) -> Option<(&str, usize)> {
+        let (file, line) = (*self.lines.get(line.checked_sub(1)?)?)?;
+        Some((&self.files[file], line))
+    }
+
+    /// Replace the source locations in a compile log with the original file and line.
+    ///
+    /// Understands the "0:12", "0:12(5)" and "0(12)" forms most drivers use,
+    /// 0 being the index of the only source string.
+    pub fn annotate(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.annotate_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn annotate_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+        for start in 0..bytes.len() {
+            if bytes[start] != b'0' || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+                continue;
+            }
+            let parenthesis = match bytes.get(start + 1) {
+                Some(b':') => false,
+                Some(b'(') => true,
+                _ => continue,
+            };
+            let digits = bytes[start + 2..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            let end = start + 2 + digits;
+            if digits == 0 || (parenthesis && bytes.get(end) != Some(&b')')) {
+                continue;
+            }
+            let location = line[start + 2..end]
+                .parse()
+                .ok()
+                .and_then(|n| self.location(n));
+            if let Some((file, n)) = location {
+                return if parenthesis {
+                    format!("{}{}({}){}", &line[..start], file, n, &line[end + 1..])
+                } else {
+                    format!("{}{}:{}{}", &line[..start], file, n, &line[end..])
+                };
+            }
+        }
+        line.to_string()
+    }
+
+    /// Point the diagnostic at the original file and line.
+    /// Diagnostics about the generated header are left as is.
+    pub fn locate(&self, diagnostic: &mut ShaderDiagnostic) {
+        if let Some((file, line)) = diagnostic.line.and_then(|line| self.location(line)) {
+            diagnostic.file = Some(file.to_string());
+            diagnostic.line = Some(line);
+        }
+    }
+
+    fn file_index(&mut self, path: &str) -> usize {
+        match self.files.iter().position(|file| file == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.to_string());
+                self.files.len() - 1
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    pub source: String,
+    pub lines: LineMap,
+}
+
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    files: HashMap<String, String>,
+    defines: Vec<(String, String)>,
+    versions: Vec<GlslVersion>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Preprocessor {
+        Preprocessor::new()
+    }
+}
+
+impl Preprocessor {
+    pub fn new() -> Preprocessor {
+        Preprocessor {
+            files: HashMap::new(),
+            defines: vec![],
+            versions: vec![
+                GlslVersion::V330,
+                GlslVersion::V300Es,
+                GlslVersion::V130,
+                GlslVersion::V100,
+            ],
+        }
+    }
+
+    /// Add a file to include or compile from memory.
+    pub fn file(mut self, path: &str, source: &str) -> Preprocessor {
+        self.files.insert(path.to_string(), source.to_string());
+        self
+    }
+
+    /// Define for every shader, before the per-variant defines.
+    pub fn define(mut self, name: &str, value: &str) -> Preprocessor {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Versions the sources are written for, most preferred first.
+    /// By default "330", "300 es", "130" and then "100".
+    pub fn versions(mut self, versions: &[GlslVersion]) -> Preprocessor {
+        self.versions = versions.to_vec();
+        self
+    }
+
+    /// Load `paths` and everything they include with `fs::load_file`.
+    ///
+    /// `on_loaded` is called once, when all the files are loaded or on the first error.
+    /// On desktop it is called before `load` returns.
+    pub fn load<F: FnOnce(Result<Preprocessor, Error>) + 'static>(
+        self,
+        paths: &[&str],
+        on_loaded: F,
+    ) {
+        let loading = Rc::new(RefCell::new(Loading {
+            preprocessor: Some(self),
+            // held while requesting `paths`, so a synchronous load won't finish early
+            pending: 1,
+            requested: HashSet::new(),
+            on_loaded: Some(Box::new(on_loaded)),
+        }));
+        for path in paths {
+            Loading::request(&loading, path.to_string());
+        }
+        Loading::done(&loading);
+    }
+
+    /// The first of `versions` the context supports.
+    pub fn glsl_version(&self, info: &ContextInfo) -> Option<GlslVersion> {
+        self.versions
+            .iter()
+            .copied()
+            .find(|version| version.is_supported(&info.glsl_support))
+    }
+
+    pub fn preprocess(
+        &self,
+        path: &str,
+        shader_type: ShaderType,
+        version: GlslVersion,
+        defines: &[(&str, &str)],
+    ) -> Result<PreprocessedSource, Error> {
+        let mut source = String::new();
+        let mut lines = LineMap::default();
+
+        let stage = match shader_type {
+            ShaderType::Vertex => "MQ_VERTEX_SHADER",
+            ShaderType::Fragment => "MQ_FRAGMENT_SHADER",
+        };
+        let mut header = vec![
+            version.header().to_string(),
+            format!("#define MQ_GLSL_VERSION {}", version.number()),
+            format!("#define {}", stage),
+        ];
+        if version.is_es() {
+            header.push("#define MQ_GLSL_ES".to_string());
+        }
+        let defines = self
+            .defines
+            .iter()
+            .map(|(name, value)| (&name[..], &value[..]))
+            .chain(defines.iter().copied());
+        for (name, value) in defines {
+            header.push(format!("#define {} {}", name, value).trim_end().to_string());
+        }
+        for line in header {
+            source.push_str(&line);
+            source.push('\n');
+            lines.lines.push(None);
+        }
+
+        let mut expansion = Expansion {
+            files: &self.files,
+            stack: vec![],
+            once: HashSet::new(),
+            source,
+            lines,
+        };
+        expansion.expand(path, None)?;
+        Ok(PreprocessedSource {
+            source: expansion.source,
+            lines: expansion.lines,
+        })
+    }
+
+    /// Preprocess and compile, with the compile errors mapped to the original files.
+    pub fn new_shader(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        vertex: &str,
+        fragment: &str,
+        defines: &[(&str, &str)],
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, Error> {
+        self.compile(ctx, vertex, fragment, defines, |ctx, source| {
+            ctx.new_shader(source, meta)
+        })
+    }
+
+    /// The same as `new_shader`, but swaps the program behind an existing shader,
+    /// see `RenderingBackend::replace_shader`.
+    pub fn replace_shader(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        shader: ShaderId,
+        vertex: &str,
+        fragment: &str,
+        defines: &[(&str, &str)],
+        meta: ShaderMeta,
+    ) -> Result<(), Error> {
+        self.compile(ctx, vertex, fragment, defines, |ctx, source| {
+            ctx.replace_shader(shader, source, meta)
+        })
+    }
+
+    /// Files `path` includes, directly or not, `path` first.
+    pub fn dependencies(&self, path: &str) -> Result<Vec<String>, Error> {
+        // includes do not depend on the version or defines
+        let source = self.preprocess(path, ShaderType::Vertex, GlslVersion::V100, &[])?;
+        Ok(source.lines.files)
+    }
+
+    /// `file`, for a preprocessor already in use, e.g. when the file changed.
+    pub fn set_file(&mut self, path: &str, source: String) {
+        self.files.insert(path.to_string(), source);
+    }
+
+    fn compile<T>(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        vertex: &str,
+        fragment: &str,
+        defines: &[(&str, &str)],
+        f: impl FnOnce(&mut dyn RenderingBackend, ShaderSource) -> Result<T, ShaderError>,
+    ) -> Result<T, Error> {
+        let version = self
+            .glsl_version(&ctx.info())
+            .ok_or(Error::NoSupportedVersion)?;
+        let vertex = self.preprocess(vertex, ShaderType::Vertex, version, defines)?;
+        let fragment = self.preprocess(fragment, ShaderType::Fragment, version, defines)?;
+        let source = ShaderSource::Glsl {
+            vertex: &vertex.source,
+            fragment: &fragment.source,
+        };
+        f(ctx, source).map_err(|err| match err {
+            ShaderError::CompilationError {
+                shader_type,
+                error_message,
+                mut diagnostics,
+            } => {
+                let lines = match shader_type {
+                    ShaderType::Vertex => &vertex.lines,
+                    ShaderType::Fragment => &fragment.lines,
+                };
+                for diagnostic in &mut diagnostics {
+                    lines.locate(diagnostic);
+                }
+                Error::Shader(ShaderError::CompilationError {
+                    shader_type,
+                    error_message: lines.annotate(&error_message),
+                    diagnostics,
+                })
+            }
+            err => Error::Shader(err),
+        })
+    }
+}
+
+struct Expansion<'a> {
+    files: &'a HashMap<String, String>,
+    stack: Vec<String>,
+    once: HashSet<String>,
+    source: String,
+    lines: LineMap,
+}
+
+impl<'a> Expansion<'a> {
+    fn expand(&mut self, path: &str, included_from: Option<(&str, usize)>) -> Result<(), Error> {
+        let files = self.files;
+        let source = files.get(path).ok_or_else(|| Error::NotFound {
+            path: path.to_string(),
+            included_from: included_from.map(|(file, line)| (file.to_string(), line)),
+        })?;
+        if self.once.contains(path) {
+            return Ok(());
+        }
+        let file = self.lines.file_index(path);
+        self.stack.push(path.to_string());
+        for (n, line) in source.lines().enumerate() {
+            let n = n + 1;
+            match directive(line) {
+                Some(("include", argument)) => {
+                    let include =
+                        include_path(argument).ok_or_else(|| Error::MalformedInclude {
+                            file: path.to_string(),
+                            line: n,
+                        })?;
+                    let include = resolve(path, include);
+                    if self.stack.contains(&include) {
+                        return Err(Error::RecursiveInclude {
+                            file: path.to_string(),
+                            line: n,
+                        });
+                    }
+                    self.expand(&include, Some((path, n)))?;
+                }
+                Some(("pragma", "once")) => {
+                    self.once.insert(path.to_string());
+                }
+                Some(("version", _)) => {
+                    return Err(Error::VersionDirective {
+                        file: path.to_string(),
+                        line: n,
+                    });
+                }
+                _ => {
+                    self.source.push_str(line);
+                    self.source.push('\n');
+                    self.lines.lines.push(Some((file, n)));
+                }
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+type OnLoaded = Box<dyn FnOnce(Result<Preprocessor, Error>)>;
+
+struct Loading {
+    /// `None` after a failure
+    preprocessor: Option<Preprocessor>,
+    pending: usize,
+    requested: HashSet<String>,
+    on_loaded: Option<OnLoaded>,
+}
+
+impl Loading {
+    fn request(loading: &Rc<RefCell<Loading>>, path: String) {
+        {
+            let mut loading = loading.borrow_mut();
+            if loading.preprocessor.is_none() || !loading.requested.insert(path.clone()) {
+                return;
+            }
+            loading.pending += 1;
+        }
+        let loading = loading.clone();
+        fs::load_file(&path.clone(), move |response| {
+            Loading::loaded(&loading, &path, response)
+        });
+    }
+
+    fn loaded(loading: &Rc<RefCell<Loading>>, path: &str, response: fs::Response) {
+        let source = response
+            .map_err(|error| Error::Load {
+                path: path.to_string(),
+                error,
+            })
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|_| Error::NotUtf8(path.into())));
+        let source = match source {
+            Ok(source) => source,
+            Err(err) => {
+                let on_loaded = {
+                    let mut loading = loading.borrow_mut();
+                    loading.preprocessor = None;
+                    loading.on_loaded.take()
+                };
+                if let Some(on_loaded) = on_loaded {
+                    on_loaded(Err(err));
+                }
+                return;
+            }
+        };
+
+        let includes: Vec<String> = source
+            .lines()
+            .filter_map(|line| match directive(line) {
+                Some(("include", argument)) => include_path(argument),
+                _ => None,
+            })
+            .map(|include| resolve(path, include))
+            .collect();
+        match &mut loading.borrow_mut().preprocessor {
+            Some(preprocessor) => preprocessor.files.insert(path.to_string(), source),
+            None => return,
+        };
+        for include in includes {
+            Loading::request(loading, include);
+        }
+        Loading::done(loading);
+    }
+
+    fn done(loading: &Rc<RefCell<Loading>>) {
+        let finished = {
+            let mut loading = loading.borrow_mut();
+            loading.pending -= 1;
+            match loading.preprocessor.take() {
+                Some(preprocessor) if loading.pending == 0 => {
+                    Some((preprocessor, loading.on_loaded.take().unwrap()))
+                }
+                preprocessor => {
+                    loading.preprocessor = preprocessor;
+                    None
+                }
+            }
+        };
+        if let Some((preprocessor, on_loaded)) = finished {
+            on_loaded(Ok(preprocessor));
+        }
+    }
+}
+
+/// Compiles and caches the permutations of a shader, keyed by the define set.
+pub struct ShaderVariants {
+    preprocessor: Preprocessor,
+    vertex: String,
+    fragment: String,
+    meta: ShaderMeta,
+    shaders: HashMap<Vec<(String, String)>, ShaderId>,
+}
+
+impl ShaderVariants {
+    pub fn new(
+        preprocessor: Preprocessor,
+        vertex: &str,
+        fragment: &str,
+        meta: ShaderMeta,
+    ) -> ShaderVariants {
+        ShaderVariants {
+            preprocessor,
+            vertex: vertex.to_string(),
+            fragment: fragment.to_string(),
+            meta,
+            shaders: HashMap::new(),
+        }
+    }
+
+    pub fn preprocessor(&self) -> &Preprocessor {
+        &self.preprocessor
+    }
+
+    /// The shader compiled with `defines`, in any order. Compiled on the first request.
+    /// Failures are not cached.
+    pub fn get(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        defines: &[(&str, &str)],
+    ) -> Result<ShaderId, Error> {
+        let mut key: Vec<(String, String)> = defines
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        key.sort();
+        if let Some(shader) = self.shaders.get(&key) {
+            return Ok(*shader);
+        }
+        let defines: Vec<(&str, &str)> = key
+            .iter()
+            .map(|(name, value)| (&name[..], &value[..]))
+            .collect();
+        let shader = self.preprocessor.new_shader(
+            ctx,
+            &self.vertex,
+            &self.fragment,
+            &defines,
+            self.meta.clone(),
+        )?;
+        self.shaders.insert(key, shader);
+        Ok(shader)
+    }
+
+    /// Number of compiled variants.
+    pub fn len(&self) -> usize {
+        self.shaders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shaders.is_empty()
+    }
+
+    /// Delete all the compiled variants.
+    pub fn clear(&mut self, ctx: &mut dyn RenderingBackend) {
+        for (_, shader) in self.shaders.drain() {
+            ctx.delete_shader(shader);
+        }
+    }
+}
+
+/// `"#  include "a.glsl"` to `("include", "\"a.glsl\"")`
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = line.find(|c: char| c.is_whitespace()).unwrap_or(line.len());
+    Some((&line[..end], line[end..].trim()))
+}
+
+fn include_path(argument: &str) -> Option<&str> {
+    argument.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// `include` relative to the directory of `from`.
+fn resolve(from: &str, include: &str) -> String {
+    let mut segments: Vec<&str> = match (include.starts_with('/'), from.rfind('/')) {
+        (false, Some(slash)) => from[..slash].split('/').collect(),
+        _ => vec![],
+    };
+    for segment in include.split('/') {
+        match segment {
+            "." => {}
+            ".." if segments
+                .last()
+                .is_some_and(|last| *last != ".." && !last.is_empty()) =>
+            {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+#[test]
+fn test_preprocessor() {
+    use crate::graphics::{RecordingContext, UniformBlockLayout};
+
+    let preprocessor = Preprocessor::new()
+        .file("lib/common.glsl", "#pragma once\nuniform mat4 mvp;")
+        .file(
+            "lib/light.glsl",
+            "#include \"common.glsl\"\nvec3 light() { return vec3(1.0); }",
+        )
+        .file(
+            "shaders/mesh.vert",
+            "#include \"../lib/common.glsl\"\n# include \"../lib/light.glsl\"\nvoid main() {}",
+        )
+        .file("shaders/mesh.frag", "void main() {}")
+        .define("MAX_LIGHTS", "4");
+
+    let vertex = preprocessor
+        .preprocess(
+            "shaders/mesh.vert",
+            ShaderType::Vertex,
+            GlslVersion::V300Es,
+            &[("SHADOWS", "")],
+        )
+        .unwrap();
+    assert_eq!(
+        vertex.source,
+        "#version 300 es\n\
+         #define MQ_GLSL_VERSION 300\n\
+         #define MQ_VERTEX_SHADER\n\
+         #define MQ_GLSL_ES\n\
+         #define MAX_LIGHTS 4\n\
+         #define SHADOWS\n\
+         uniform mat4 mvp;\n\
+         vec3 light() { return vec3(1.0); }\n\
+         void main() {}\n"
+    );
+    assert_eq!(vertex.lines.location(6), None);
+    assert_eq!(vertex.lines.location(7), Some(("lib/common.glsl", 2)));
+    assert_eq!(vertex.lines.location(8), Some(("lib/light.glsl", 2)));
+    assert_eq!(vertex.lines.location(9), Some(("shaders/mesh.vert", 3)));
+    assert_eq!(
+        vertex.lines.annotate(
+            "0:8(5): error: `x' undeclared\nERROR: 0:9: 'y' : syntax error\n0(7) : error C0000"
+        ),
+        "lib/light.glsl:2(5): error: `x' undeclared\n\
+         ERROR: shaders/mesh.vert:3: 'y' : syntax error\n\
+         lib/common.glsl(2) : error C0000"
+    );
+
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout { uniforms: vec![] },
+        uniform_blocks: vec![],
+        images: vec![],
+    };
+    let mut ctx = RecordingContext::new();
+    let mut variants =
+        ShaderVariants::new(preprocessor, "shaders/mesh.vert", "shaders/mesh.frag", meta);
+    let a = variants.get(&mut ctx, &[("A", "1"), ("B", "2")]).unwrap();
+    assert_eq!(
+        variants.get(&mut ctx, &[("B", "2"), ("A", "1")]).unwrap(),
+        a
+    );
+    assert_ne!(variants.get(&mut ctx, &[]).unwrap(), a);
+    assert_eq!(variants.len(), 2);
+}
+
+#[test]
+fn test_preprocessor_include_cycle() {
+    let preprocessor = Preprocessor::new()
+        .file("loop.vert", "#include \"loop.vert\"")
+        .file("a.glsl", "float a;\n#include \"b.glsl\"")
+        // `#pragma once` does not break the cycle, `a.glsl` is still being expanded
+        .file("b.glsl", "#pragma once\nfloat b;\n#include \"a.glsl\"")
+        .file("cycle.vert", "#include \"a.glsl\"\nvoid main() {}")
+        .file("x.glsl", "#include \"common.glsl\"")
+        .file("y.glsl", "#include \"common.glsl\"")
+        .file("common.glsl", "float common;")
+        .file("diamond.vert", "#include \"x.glsl\"\n#include \"y.glsl\"");
+    let preprocess =
+        |path| preprocessor.preprocess(path, ShaderType::Vertex, GlslVersion::V330, &[]);
+
+    assert!(matches!(
+        preprocess("loop.vert"),
+        Err(Error::RecursiveInclude { file, line: 1 }) if file == "loop.vert"
+    ));
+    assert!(matches!(
+        preprocess("cycle.vert"),
+        Err(Error::RecursiveInclude { file, line: 3 }) if file == "b.glsl"
+    ));
+    assert!(matches!(
+        preprocessor.dependencies("a.glsl"),
+        Err(Error::RecursiveInclude { file, line: 3 }) if file == "b.glsl"
+    ));
+
+    // included twice, but never from itself
+    let diamond = preprocess("diamond.vert").unwrap();
+    assert!(diamond
+        .source
+        .ends_with("#define MQ_VERTEX_SHADER\nfloat common;\nfloat common;\n"));
+    assert_eq!(
+        preprocessor.dependencies("diamond.vert").unwrap(),
+        ["diamond.vert", "x.glsl", "common.glsl", "y.glsl"]
+    );
+}
+
+#[test]
+fn test_preprocessor_missing_include() {
+    let preprocessor = Preprocessor::new()
+        .file(
+            "shaders/mesh.vert",
+            "void main() {}\n#include \"lib/missing.glsl\"",
+        )
+        .file("shaders/nested.vert", "#include \"../lib/light.glsl\"")
+        .file("lib/light.glsl", "float light;\n\n#include \"shadow.glsl\"")
+        .file("shaders/malformed.vert", "#include <common.glsl>");
+    let preprocess =
+        |path| preprocessor.preprocess(path, ShaderType::Vertex, GlslVersion::V330, &[]);
+
+    assert!(matches!(
+        preprocess("shaders/mesh.vert"),
+        Err(Error::NotFound { path, included_from: Some((file, 2)) })
+            if path == "shaders/lib/missing.glsl" && file == "shaders/mesh.vert"
+    ));
+    assert!(matches!(
+        preprocess("shaders/nested.vert"),
+        Err(Error::NotFound { path, included_from: Some((file, 3)) })
+            if path == "lib/shadow.glsl" && file == "lib/light.glsl"
+    ));
+    assert!(matches!(
+        preprocess("shaders/missing.vert"),
+        Err(Error::NotFound { path, included_from: None }) if path == "shaders/missing.vert"
+    ));
+    assert!(matches!(
+        preprocess("shaders/malformed.vert"),
+        Err(Error::MalformedInclude { file, line: 1 }) if file == "shaders/malformed.vert"
+    ));
+    assert!(matches!(
+        preprocessor.dependencies("shaders/nested.vert"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn test_preprocessor_line_map() {
+    use crate::graphics::DiagnosticSeverity;
+
+    let preprocessor = Preprocessor::new()
+        .file(
+            "lib/common.glsl",
+            "#pragma once\n// common\nuniform float time;",
+        )
+        .file(
+            "lib/noise.glsl",
+            "#include \"common.glsl\"\n\nfloat noise() { return time; }",
+        )
+        .file(
+            "lib/light.glsl",
+            "#pragma once\n#include \"noise.glsl\"\nvec3 light() { return vec3(noise()); }",
+        )
+        .file(
+            "mesh.frag",
+            "#include \"lib/common.glsl\"\n\
+             #include \"lib/light.glsl\"\n\
+             void main() {\n    gl_FragColor = vec4(light(), 1.0);\n}",
+        );
+    let fragment = preprocessor
+        .preprocess("mesh.frag", ShaderType::Fragment, GlslVersion::V100, &[])
+        .unwrap();
+    let lines = &fragment.lines;
+
+    // 4 header lines, the directives take no lines of their own
+    let locations: Vec<_> = (0..=13).map(|line| lines.location(line)).collect();
+    assert_eq!(
+        locations,
+        [
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(("lib/common.glsl", 2)),
+            Some(("lib/common.glsl", 3)),
+            Some(("lib/noise.glsl", 2)),
+            Some(("lib/noise.glsl", 3)),
+            Some(("lib/light.glsl", 3)),
+            Some(("mesh.frag", 3)),
+            Some(("mesh.frag", 4)),
+            Some(("mesh.frag", 5)),
+            None,
+        ]
+    );
+
+    let log = "0:8(24): error: `time' undeclared\n\
+               0(11) : error C1008: undefined variable \"light\"\n\
+               ERROR: 0:9: 'noise' : no matching overloaded function found\n\
+               ERROR: 0:2: 'MQ_GLSL_VERSION' : macro redefined\n\
+               ERROR: 0:99: past the end";
+    assert_eq!(
+        lines.annotate(log),
+        "lib/noise.glsl:3(24): error: `time' undeclared\n\
+         mesh.frag(4) : error C1008: undefined variable \"light\"\n\
+         ERROR: lib/light.glsl:3: 'noise' : no matching overloaded function found\n\
+         ERROR: 0:2: 'MQ_GLSL_VERSION' : macro redefined\n\
+         ERROR: 0:99: past the end"
+    );
+
+    let mut diagnostics = ShaderDiagnostic::parse_log(ShaderType::Fragment, log, &fragment.source);
+    for diagnostic in &mut diagnostics {
+        lines.locate(diagnostic);
+    }
+    let located: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.file.as_deref(), d.line, d.column, d.excerpt.as_deref()))
+        .collect();
+    assert_eq!(
+        located,
+        [
+            (
+                Some("lib/noise.glsl"),
+                Some(3),
+                Some(24),
+                Some("float noise() { return time; }")
+            ),
+            (
+                Some("mesh.frag"),
+                Some(4),
+                None,
+                Some("    gl_FragColor = vec4(light(), 1.0);")
+            ),
+            (
+                Some("lib/light.glsl"),
+                Some(3),
+                None,
+                Some("vec3 light() { return vec3(noise()); }")
+            ),
+            // the generated header and lines past the end are left as is
+            (None, Some(2), None, Some("#define MQ_GLSL_VERSION 100")),
+            (None, Some(99), None, None),
+        ]
+    );
+    assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "lib/noise.glsl:3:24: error: `time' undeclared"
+    );
+}
+
+#[test]
+fn test_preprocessor_defines() {
+    let preprocessor = Preprocessor::new()
+        .file("light.frag", "void main() {}")
+        .define("MAX_LIGHTS", "4")
+        .define("PI", "3.14159");
+    let header = |version, defines| {
+        let source = preprocessor
+            .preprocess("light.frag", ShaderType::Fragment, version, defines)
+            .unwrap()
+            .source;
+        source.strip_suffix("void main() {}\n").unwrap().to_string()
+    };
+
+    // the preprocessor-wide defines go first, empty values leave no trailing space
+    assert_eq!(
+        header(GlslVersion::V330, &[("SHADOWS", ""), ("TINT", "vec4(1.0)")]),
+        "#version 330\n\
+         #define MQ_GLSL_VERSION 330\n\
+         #define MQ_FRAGMENT_SHADER\n\
+         #define MAX_LIGHTS 4\n\
+         #define PI 3.14159\n\
+         #define SHADOWS\n\
+         #define TINT vec4(1.0)\n"
+    );
+    assert_eq!(
+        header(GlslVersion::V100, &[]),
+        "#version 100\n\
+         #define MQ_GLSL_VERSION 100\n\
+         #define MQ_FRAGMENT_SHADER\n\
+         #define MQ_GLSL_ES\n\
+         #define MAX_LIGHTS 4\n\
+         #define PI 3.14159\n"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_preprocessor_defines() {
+    use crate::graphics::UniformBlockLayout;
+
+    let preprocessor = Preprocessor::new()
+        .versions(&[GlslVersion::V100])
+        .file(
+            "common.glsl",
+            "#pragma once\nuniform highp vec4 lights[MAX_LIGHTS];",
+        )
+        .file(
+            "mesh.vert",
+            "#include \"common.glsl\"\n\
+             attribute vec2 in_pos;\n\
+             void main() {\n    gl_Position = vec4(in_pos, 0.0, 1.0) + lights[0];\n}",
+        )
+        .file(
+            "mesh.frag",
+            "#ifdef MQ_GLSL_ES\nprecision mediump float;\n#endif\n\
+             #include \"common.glsl\"\n\
+             void main() {\n    gl_FragColor = lights[MAX_LIGHTS - 1] * TINT;\n}",
+        )
+        .define("MAX_LIGHTS", "4");
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout { uniforms: vec![] },
+        uniform_blocks: vec![],
+        images: vec![],
+    };
+
+    let (tinted, untinted) = crate::native::linux_headless::with_gl_context(1, 1, move |ctx| {
+        let mut compile = |defines: &[(&str, &str)]| {
+            preprocessor
+                .new_shader(ctx, "mesh.vert", "mesh.frag", defines, meta.clone())
+                .map(drop)
+        };
+        (compile(&[("TINT", "vec4(0.5)")]), compile(&[]))
+    });
+    assert!(tinted.is_ok(), "{:?}", tinted);
+    match untinted {
+        Err(Error::Shader(ShaderError::CompilationError {
+            shader_type: ShaderType::Fragment,
+            error_message,
+            diagnostics,
+        })) => {
+            assert!(error_message.contains("mesh.frag:6"), "{}", error_message);
+            assert_eq!(diagnostics[0].file.as_deref(), Some("mesh.frag"));
+            assert_eq!(diagnostics[0].line, Some(6));
+        }
+        result => panic!("{:?}", result),
+    }
+}
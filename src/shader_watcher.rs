@@ -0,0 +1,451 @@
+//! Shader hot-reload, with the "shader-hot-reload" feature, on desktop.
+//!
+//! Shaders made through `ShaderWatcher` are recompiled when their files, or the files
+//! they include, change on disk. The new program replaces the old one behind the same
+//! `ShaderId`, so the pipelines pick it up without being recreated.
+//! A shader that fails to compile keeps its old program and the error goes to the callback.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::shader_preprocessor::Preprocessor;
+//! # use miniquad::shader_watcher::ShaderWatcher;
+//! # fn f(ctx: &mut dyn RenderingBackend, meta: ShaderMeta) {
+//! let mut watcher = ShaderWatcher::new(Preprocessor::new(), |shader, err| {
+//!     eprintln!("{:?} was not reloaded: {}", shader, err)
+//! });
+//! let shader = watcher
+//!     .new_shader(ctx, "shaders/sprite.vert", "shaders/sprite.frag", &[], meta)
+//!     .unwrap();
+//! // once a frame
+//! watcher.update(ctx);
+//! # }
+//! ```
+//!
+//! Linux is notified through inotify, other platforms poll the modification times
+//! twice a second.
+
+use crate::{
+    graphics::{RenderingBackend, ShaderId, ShaderMeta},
+    shader_preprocessor::{Error, Preprocessor},
+};
+
+use std::{collections::HashMap, time::SystemTime};
+
+const POLL_INTERVAL: f64 = 0.5;
+
+struct WatchedShader {
+    id: ShaderId,
+    vertex: String,
+    fragment: String,
+    defines: Vec<(String, String)>,
+    meta: ShaderMeta,
+    /// The vertex and fragment files and all their includes
+    files: Vec<String>,
+}
+
+pub struct ShaderWatcher {
+    preprocessor: Preprocessor,
+    shaders: Vec<WatchedShader>,
+    /// Modification time of every watched file, when it was last read
+    files: HashMap<String, Option<SystemTime>>,
+    on_error: Box<dyn FnMut(ShaderId, Error)>,
+    #[cfg(target_os = "linux")]
+    inotify: Option<inotify::Inotify>,
+    last_poll: f64,
+}
+
+impl ShaderWatcher {
+    /// Files missing from `preprocessor` are read from disk.
+    pub fn new<F: FnMut(ShaderId, Error) + 'static>(
+        preprocessor: Preprocessor,
+        on_error: F,
+    ) -> ShaderWatcher {
+        ShaderWatcher {
+            preprocessor,
+            shaders: vec![],
+            files: HashMap::new(),
+            on_error: Box::new(on_error),
+            #[cfg(target_os = "linux")]
+            inotify: inotify::Inotify::new(),
+            last_poll: 0.,
+        }
+    }
+
+    /// `Preprocessor::new_shader`, with the shader watched from now on.
+    pub fn new_shader(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        vertex: &str,
+        fragment: &str,
+        defines: &[(&str, &str)],
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, Error> {
+        let mut files = vec![];
+        self.read_missing(vertex, fragment, &mut files)?;
+        let id = self
+            .preprocessor
+            .new_shader(ctx, vertex, fragment, defines, meta.clone())?;
+        self.shaders.push(WatchedShader {
+            id,
+            vertex: vertex.to_string(),
+            fragment: fragment.to_string(),
+            defines: defines
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            meta,
+            files,
+        });
+        Ok(id)
+    }
+
+    /// Stop watching the shader, e.g. before deleting it.
+    pub fn unwatch(&mut self, shader: ShaderId) {
+        self.shaders.retain(|watched| watched.id != shader);
+        let shaders = &self.shaders;
+        self.files
+            .retain(|path, _| shaders.iter().any(|shader| shader.files.contains(path)));
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(inotify) = &mut self.inotify {
+                inotify.retain_directories_of(self.files.keys());
+            }
+        }
+    }
+
+    /// Recompile the shaders whose files changed since the last call. Call it once a frame.
+    ///
+    /// A file that can't be read, e.g. in the middle of being saved, is picked up
+    /// with its next change.
+    pub fn update(&mut self, ctx: &mut dyn RenderingBackend) {
+        if !self.should_check() {
+            return;
+        }
+
+        let mut changed = vec![];
+        for (path, modified) in &mut self.files {
+            let now = modified_time(path);
+            if now != *modified {
+                *modified = now;
+                if let Ok(source) = std::fs::read_to_string(path) {
+                    self.preprocessor.set_file(path, source);
+                    changed.push(path.clone());
+                }
+            }
+        }
+        if changed.is_empty() {
+            return;
+        }
+
+        let mut shaders = std::mem::take(&mut self.shaders);
+        for shader in &mut shaders {
+            if shader.files.iter().any(|file| changed.contains(file)) {
+                if let Err(err) = self.reload(ctx, shader) {
+                    (self.on_error)(shader.id, err);
+                }
+            }
+        }
+        self.shaders = shaders;
+    }
+
+    fn reload(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        shader: &mut WatchedShader,
+    ) -> Result<(), Error> {
+        self.read_missing(&shader.vertex, &shader.fragment, &mut shader.files)?;
+        let defines: Vec<(&str, &str)> = shader
+            .defines
+            .iter()
+            .map(|(name, value)| (&name[..], &value[..]))
+            .collect();
+        self.preprocessor.replace_shader(
+            ctx,
+            shader.id,
+            &shader.vertex,
+            &shader.fragment,
+            &defines,
+            shader.meta.clone(),
+        )
+    }
+
+    /// Read the files the shader needs and the preprocessor does not have yet,
+    /// watch all of them and add them to `files`.
+    ///
+    /// A missing file is watched and added as well, so creating it triggers a reload.
+    fn read_missing(
+        &mut self,
+        vertex: &str,
+        fragment: &str,
+        files: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        for root in [vertex, fragment].iter() {
+            loop {
+                let (dependencies, missing) = match self.preprocessor.dependencies(root) {
+                    Ok(dependencies) => (dependencies, None),
+                    Err(Error::NotFound { path, .. }) if !self.files.contains_key(&path) => {
+                        (vec![path.clone()], Some(path))
+                    }
+                    Err(err) => return Err(err),
+                };
+                for file in dependencies {
+                    self.watch(&file);
+                    if !files.contains(&file) {
+                        files.push(file);
+                    }
+                }
+                let path = match missing {
+                    Some(path) => path,
+                    None => break,
+                };
+                // the modification time was taken before reading,
+                // a change in between is read again on the next update
+                let source = std::fs::read_to_string(&path).map_err(|error| Error::Load {
+                    path: path.clone(),
+                    error: error.into(),
+                })?;
+                self.preprocessor.set_file(&path, source);
+            }
+        }
+        Ok(())
+    }
+
+    fn watch(&mut self, path: &str) {
+        if self.files.contains_key(path) {
+            return;
+        }
+        self.files.insert(path.to_string(), modified_time(path));
+        #[cfg(target_os = "linux")]
+        {
+            let watching = match &mut self.inotify {
+                Some(inotify) => inotify.watch_directory_of(path),
+                None => true,
+            };
+            if !watching {
+                self.inotify = None;
+            }
+        }
+    }
+
+    /// With inotify, whether something changed in a watched directory, otherwise
+    /// whether it's time to poll.
+    fn should_check(&mut self) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(inotify) = &mut self.inotify {
+                return inotify.has_events();
+            }
+        }
+        let now = crate::date::now();
+        if now - self.last_poll < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = now;
+        true
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::{collections::HashMap, ffi::CString, path::Path};
+
+    /// Watches directories rather than files, editors often save by writing
+    /// a new file and renaming it over the old one.
+    pub struct Inotify {
+        fd: libc::c_int,
+        /// Watch descriptor of every watched directory
+        pub(super) directories: HashMap<String, libc::c_int>,
+    }
+
+    fn directory_of(path: &str) -> &str {
+        match Path::new(path).parent().and_then(Path::to_str) {
+            Some("") | None => ".",
+            Some(directory) => directory,
+        }
+    }
+
+    impl Inotify {
+        pub fn new() -> Option<Inotify> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return None;
+            }
+            Some(Inotify {
+                fd,
+                directories: HashMap::new(),
+            })
+        }
+
+        /// `false` if the directory can't be watched.
+        pub fn watch_directory_of(&mut self, path: &str) -> bool {
+            let directory = directory_of(path);
+            if self.directories.contains_key(directory) {
+                return true;
+            }
+            let cdirectory = match CString::new(directory) {
+                Ok(cdirectory) => cdirectory,
+                Err(_) => return false,
+            };
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+            let wd = unsafe { libc::inotify_add_watch(self.fd, cdirectory.as_ptr(), mask) };
+            if wd < 0 {
+                return false;
+            }
+            self.directories.insert(directory.to_string(), wd);
+            true
+        }
+
+        /// Stop watching the directories none of `paths` is in.
+        pub fn retain_directories_of<'a>(&mut self, paths: impl Iterator<Item = &'a String>) {
+            let keep: Vec<&str> = paths.map(|path| directory_of(path)).collect();
+            let fd = self.fd;
+            self.directories.retain(|directory, wd| {
+                let watched = keep.contains(&&directory[..]);
+                if !watched {
+                    unsafe { libc::inotify_rm_watch(fd, *wd) };
+                }
+                watched
+            });
+        }
+
+        /// Drain the pending events, `true` if there were any.
+        pub fn has_events(&mut self) -> bool {
+            let mut events = false;
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read =
+                    unsafe { libc::read(self.fd, buffer.as_mut_ptr() as *mut _, buffer.len()) };
+                if read <= 0 {
+                    return events;
+                }
+                events = true;
+            }
+        }
+    }
+
+    impl Drop for Inotify {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.fd) };
+        }
+    }
+}
+
+/// Write `source` with a distinct modification time, so the change is seen even
+/// on file systems with coarse timestamps.
+#[cfg(test)]
+fn write_shader_file(path: &std::path::Path, source: &str, modified: u64) {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    std::fs::write(path, source).unwrap();
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(modified))
+        .unwrap();
+}
+
+#[test]
+fn test_shader_watcher_reload() {
+    use crate::graphics::{RecordingContext, UniformBlockLayout};
+    use std::{cell::RefCell, rc::Rc};
+
+    let directory =
+        std::env::temp_dir().join(format!("miniquad-shader-watcher-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = |name: &str| directory.join(name).to_str().unwrap().to_string();
+    write_shader_file(directory.join("common.glsl").as_ref(), "float a;", 1);
+    write_shader_file(directory.join("sprite.vert").as_ref(), "void main() {}", 1);
+    let fragment = "#include \"common.glsl\"\nvoid main() {}";
+    write_shader_file(directory.join("sprite.frag").as_ref(), fragment, 1);
+
+    let errors = Rc::new(RefCell::new(vec![]));
+    let on_error = {
+        let errors = errors.clone();
+        move |shader, err| errors.borrow_mut().push((shader, err))
+    };
+    let mut watcher = ShaderWatcher::new(Preprocessor::new(), on_error);
+    // the polling path, everywhere
+    #[cfg(target_os = "linux")]
+    {
+        watcher.inotify = None;
+    }
+    let update = |watcher: &mut ShaderWatcher, ctx: &mut RecordingContext| {
+        watcher.last_poll = 0.;
+        watcher.update(ctx);
+    };
+
+    let mut ctx = RecordingContext::new();
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout { uniforms: vec![] },
+        uniform_blocks: vec![],
+        images: vec![],
+    };
+    let shader = watcher
+        .new_shader(
+            &mut ctx,
+            &path("sprite.vert"),
+            &path("sprite.frag"),
+            &[],
+            meta,
+        )
+        .unwrap();
+    assert!(ctx.shader(shader).fragment.contains("float a;"));
+    assert!(ctx.shader(shader).vertex.ends_with("void main() {}\n"));
+
+    // nothing changed
+    update(&mut watcher, &mut ctx);
+    assert!(ctx.shader(shader).vertex.ends_with("void main() {}\n"));
+
+    write_shader_file(directory.join("sprite.vert").as_ref(), "void main() { }", 2);
+    update(&mut watcher, &mut ctx);
+    assert!(ctx.shader(shader).vertex.ends_with("void main() { }\n"));
+
+    // an included file
+    write_shader_file(directory.join("common.glsl").as_ref(), "float b;", 2);
+    update(&mut watcher, &mut ctx);
+    assert!(ctx.shader(shader).fragment.contains("float b;"));
+
+    // RecordingContext compiles anything, the preprocessor is what fails here
+    let broken = "#version 330\nvoid main() {}";
+    write_shader_file(directory.join("sprite.frag").as_ref(), broken, 2);
+    update(&mut watcher, &mut ctx);
+    assert!(ctx.shader(shader).fragment.contains("float b;"));
+    assert!(matches!(
+        &errors.borrow()[..],
+        [(id, Error::VersionDirective { line: 1, .. })] if *id == shader
+    ));
+
+    // and it comes back with the fix
+    write_shader_file(directory.join("sprite.frag").as_ref(), "void main() {}", 3);
+    update(&mut watcher, &mut ctx);
+    assert!(!ctx.shader(shader).fragment.contains("float b;"));
+    assert_eq!(errors.borrow().len(), 1);
+
+    watcher.unwatch(shader);
+    assert!(watcher.files.is_empty());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_shader_watcher_unwatch_directories() {
+    let mut inotify = match inotify::Inotify::new() {
+        Some(inotify) => inotify,
+        None => return,
+    };
+    let directory = std::env::temp_dir();
+    let directory = directory.to_str().unwrap();
+    assert!(inotify.watch_directory_of(&format!("{}/a.glsl", directory)));
+    assert!(inotify.watch_directory_of("b.glsl"));
+    assert_eq!(inotify.directories.len(), 2);
+
+    let remaining = ["c.glsl".to_string()];
+    inotify.retain_directories_of(remaining.iter());
+    assert_eq!(inotify.directories.keys().collect::<Vec<_>>(), ["."]);
+    inotify.retain_directories_of([].iter());
+    assert!(inotify.directories.is_empty());
+}
@@ -0,0 +1,355 @@
+//! Golden-image regression testing.
+//!
+//! Run an `EventHandler` for a few frames without a display server, grab the final
+//! framebuffer and compare it against a reference PNG:
+//! ```no_run
+//! # use miniquad::*;
+//! # struct Stage;
+//! # impl EventHandler for Stage { fn update(&mut self) {} fn draw(&mut self) {} }
+//! use miniquad::test_support::{assert_golden, capture_frames};
+//!
+//! let conf = conf::Conf {
+//!     window_width: 128,
+//!     window_height: 128,
+//!     ..Default::default()
+//! };
+//! let image = capture_frames(conf, 3, || Box::new(Stage));
+//! assert_golden(&image, "tests/golden/stage.png", 2);
+//! ```
+//!
+//! Set `MINIQUAD_UPDATE_GOLDEN=1` to (re)write the reference images instead of comparing.
+
+use crate::{png, EventHandler};
+
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum Error {
+    IOError(std::io::Error),
+    PngError(png::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error: {:?}", self)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::IOError(e)
+    }
+}
+
+impl From<png::Error> for Error {
+    fn from(e: png::Error) -> Error {
+        Error::PngError(e)
+    }
+}
+
+/// RGBA8 image, top row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Build an image from RGBA8 rows in OpenGL order, bottom row first,
+    /// like `texture_read_pixels` and `glReadPixels` return them.
+    pub fn from_bottom_up(width: u32, height: u32, pixels: &[u8]) -> Image {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        let pixels = pixels
+            .chunks_exact(width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Image, Error> {
+        let bytes = std::fs::read(path)?;
+        let (width, height, pixels) = png::decode(&bytes)?;
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, png::encode(self.width, self.height, &self.pixels))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Mismatch {
+    Size {
+        actual: (u32, u32),
+        reference: (u32, u32),
+    },
+    Pixels {
+        /// Amount of pixels with any channel differing by more than the tolerance
+        differing_pixels: usize,
+        /// Largest per-channel difference over the whole image
+        max_difference: u8,
+        /// Differing pixels in red, everything else is a dimmed grayscale of the reference
+        diff: Image,
+    },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mismatch::Size { actual, reference } => write!(
+                f,
+                "image size {}x{} does not match reference size {}x{}",
+                actual.0, actual.1, reference.0, reference.1
+            ),
+            Mismatch::Pixels {
+                differing_pixels,
+                max_difference,
+                ..
+            } => write!(
+                f,
+                "{} pixels differ, max channel difference is {}",
+                differing_pixels, max_difference
+            ),
+        }
+    }
+}
+
+/// Compare two images. A pixel matches when no channel differs by more than `tolerance`.
+pub fn compare(actual: &Image, reference: &Image, tolerance: u8) -> Result<(), Mismatch> {
+    if (actual.width, actual.height) != (reference.width, reference.height) {
+        return Err(Mismatch::Size {
+            actual: (actual.width, actual.height),
+            reference: (reference.width, reference.height),
+        });
+    }
+
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(actual.pixels.len());
+    for (a, b) in actual
+        .pixels
+        .chunks_exact(4)
+        .zip(reference.pixels.chunks_exact(4))
+    {
+        let difference = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap();
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            differing_pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (b[0] as u32 * 299 + b[1] as u32 * 587 + b[2] as u32 * 114) / 1000;
+            let dimmed = (luma / 3) as u8;
+            diff.extend_from_slice(&[dimmed, dimmed, dimmed, 255]);
+        }
+    }
+
+    if differing_pixels == 0 {
+        return Ok(());
+    }
+    Err(Mismatch::Pixels {
+        differing_pixels,
+        max_difference,
+        diff: Image {
+            width: actual.width,
+            height: actual.height,
+            pixels: diff,
+        },
+    })
+}
+
+/// Compare `actual` against the PNG at `reference`, panic on mismatch.
+///
+/// On failure `<reference>.actual.png` and `<reference>.diff.png` are written next to the
+/// reference. A missing reference is written from `actual` and the assert fails, so it
+/// can't silently pass in CI; with `MINIQUAD_UPDATE_GOLDEN` set the reference is
+/// overwritten and the assert passes.
+pub fn assert_golden<P: AsRef<Path>>(actual: &Image, reference: P, tolerance: u8) {
+    let reference = reference.as_ref();
+    let save = |image: &Image, path: &Path| {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        image
+            .save_png(path)
+            .unwrap_or_else(|err| panic!("Failed to write {}: {}", path.display(), err));
+    };
+
+    if std::env::var_os("MINIQUAD_UPDATE_GOLDEN").is_some() {
+        save(actual, reference);
+        return;
+    }
+    if !reference.exists() {
+        save(actual, reference);
+        panic!(
+            "Reference image {} did not exist and was written, check it and re-run",
+            reference.display()
+        );
+    }
+
+    let expected = Image::load_png(reference)
+        .unwrap_or_else(|err| panic!("Failed to load {}: {}", reference.display(), err));
+    if let Err(mismatch) = compare(actual, &expected, tolerance) {
+        let actual_path = reference.with_extension("actual.png");
+        save(actual, &actual_path);
+        if let Mismatch::Pixels { diff, .. } = &mismatch {
+            save(diff, &reference.with_extension("diff.png"));
+        }
+        panic!(
+            "{} does not match the reference: {}. Actual image written to {}",
+            reference.display(),
+            mismatch,
+            actual_path.display()
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct CaptureHandler {
+    handler: Box<dyn EventHandler>,
+    frames: usize,
+    frame: usize,
+    capture: std::rc::Rc<std::cell::RefCell<Option<Image>>>,
+}
+
+#[cfg(target_os = "linux")]
+impl EventHandler for CaptureHandler {
+    fn update(&mut self) {
+        self.handler.update();
+    }
+
+    fn draw(&mut self) {
+        self.handler.draw();
+        self.frame += 1;
+        if self.frame == self.frames {
+            let (width, height) = crate::window::screen_size();
+            let (width, height) = (width as u32, height as u32);
+            let mut pixels = vec![0u8; (width * height * 4) as usize];
+            unsafe {
+                use crate::native::gl::*;
+                glPixelStorei(GL_PACK_ALIGNMENT, 1);
+                glReadPixels(
+                    0,
+                    0,
+                    width as _,
+                    height as _,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    pixels.as_mut_ptr() as _,
+                );
+            }
+            *self.capture.borrow_mut() = Some(Image::from_bottom_up(width, height, &pixels));
+        }
+    }
+
+    fn resize_event(&mut self, width: f32, height: f32) {
+        self.handler.resize_event(width, height);
+    }
+
+    fn quit_requested_event(&mut self) {
+        self.handler.quit_requested_event();
+    }
+}
+
+/// Run the event handler for `frames` frames with `LinuxBackend::Headless`
+/// and return the content of the default framebuffer after the last `draw`.
+///
+/// May be called any number of times in a process. The event loop and the window
+/// functions are global, so captures from parallel tests run one after another.
+#[cfg(target_os = "linux")]
+pub fn capture_frames<F>(mut conf: crate::conf::Conf, frames: usize, f: F) -> Image
+where
+    F: 'static + FnOnce() -> Box<dyn EventHandler>,
+{
+    // a failed capture panics with the lock held, that doesn't break the next one
+    let _capture = crate::native::linux_headless::TEST_RUN
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+
+    assert!(frames > 0);
+    conf.platform.linux_backend = crate::conf::LinuxBackend::Headless;
+    conf.platform.headless_frames = Some(frames);
+
+    let capture = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let handler_capture = capture.clone();
+    crate::start(conf, move || {
+        Box::new(CaptureHandler {
+            handler: f(),
+            frames,
+            frame: 0,
+            capture: handler_capture,
+        })
+    });
+    let image = capture.borrow_mut().take();
+    image.expect("The event loop stopped before the last frame")
+}
+
+#[test]
+fn test_golden_compare() {
+    let reference = Image {
+        width: 2,
+        height: 1,
+        pixels: vec![0, 0, 0, 255, 100, 100, 100, 255],
+    };
+    let encoded = png::encode(2, 1, &reference.pixels);
+    assert_eq!(
+        png::decode(&encoded).unwrap(),
+        (2, 1, reference.pixels.clone())
+    );
+
+    let mut actual = reference.clone();
+    actual.pixels[4] = 103;
+    assert!(compare(&actual, &reference, 3).is_ok());
+    match compare(&actual, &reference, 2) {
+        Err(Mismatch::Pixels {
+            differing_pixels: 1,
+            max_difference: 3,
+            diff,
+        }) => assert_eq!(&diff.pixels[4..], &[255, 0, 0, 255]),
+        res => panic!("unexpected comparison result: {:?}", res),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_capture_frames_twice() {
+    struct Clear(Box<dyn crate::RenderingBackend>, f32);
+    impl EventHandler for Clear {
+        fn update(&mut self) {}
+        fn draw(&mut self) {
+            self.0
+                .begin_default_pass(crate::PassAction::clear_color(self.1, 0.0, 0.0, 1.0));
+            self.0.end_render_pass();
+            self.0.commit_frame();
+        }
+    }
+
+    for red in [0u8, 255].iter() {
+        let conf = crate::conf::Conf {
+            window_width: 4,
+            window_height: 4,
+            ..Default::default()
+        };
+        let clear = *red as f32 / 255.0;
+        let image = capture_frames(conf, 2, move || {
+            Box::new(Clear(crate::window::new_rendering_backend(), clear))
+        });
+        assert_eq!(image.pixels[..4], [*red, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,589 @@
+//! KTX2 and DDS texture containers.
+//!
+//! Both give the whole mip chain and all the cubemap faces or array layers at once,
+//! in a format ready for `TextureSource::Array`:
+//! ```no_run
+//! # use miniquad::*;
+//! # fn f(ctx: &mut dyn RenderingBackend) {
+//! let file = std::fs::read("terrain.ktx2").unwrap();
+//! let texture = texture_loader::load(&file).unwrap();
+//! assert!(ctx.info().features.texture_format_supported(texture.params.format));
+//! let texture = texture.new_texture(ctx);
+//! # }
+//! ```
+//!
+//! Supercompressed KTX2 files (Basis Universal, zstd) are not supported.
+
+use crate::graphics::{
+    FilterMode, MipmapFilterMode, RenderingBackend, TextureAccess, TextureFormat, TextureId,
+    TextureKind, TextureParams, TextureSource,
+};
+
+use std::convert::TryFrom;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Neither KTX2 nor DDS
+    UnknownContainer,
+    /// The file is shorter than its header says
+    UnexpectedEof,
+    /// vkFormat of a KTX2 file, or DXGI format or FourCC of a DDS file
+    UnsupportedFormat(u32),
+    /// Valid file, but uses a feature this loader does not implement
+    Unsupported(&'static str),
+    /// The header describes a texture that can't exist
+    InvalidHeader(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error: {:?}", self)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug)]
+pub struct LoadedTexture {
+    /// Kind, format and size from the file, the rest is default, with linear
+    /// mipmap filtering when there is more than one level.
+    pub params: TextureParams,
+    /// `[face or layer][mipmap_level][bytes]`, the same as `TextureSource::Array`
+    pub data: Vec<Vec<Vec<u8>>>,
+}
+
+impl LoadedTexture {
+    pub fn levels(&self) -> usize {
+        self.data.first().map_or(0, |mipmaps| mipmaps.len())
+    }
+
+    /// Call `f` with `data` borrowed as a `TextureSource::Array`.
+    pub fn with_source<R, F: FnOnce(TextureSource) -> R>(&self, f: F) -> R {
+        let mipmaps: Vec<Vec<&[u8]>> = self
+            .data
+            .iter()
+            .map(|mipmaps| mipmaps.iter().map(|level| &level[..]).collect())
+            .collect();
+        let layers: Vec<&[&[u8]]> = mipmaps.iter().map(|mipmaps| &mipmaps[..]).collect();
+        f(TextureSource::Array(&layers))
+    }
+
+    pub fn new_texture(&self, ctx: &mut dyn RenderingBackend) -> TextureId {
+        self.with_source(|source| ctx.new_texture(TextureAccess::Static, source, self.params))
+    }
+}
+
+/// Load a KTX2 or DDS file, depending on its signature.
+pub fn load(bytes: &[u8]) -> Result<LoadedTexture, Error> {
+    if bytes.starts_with(&KTX2_IDENTIFIER) {
+        load_ktx2(bytes)
+    } else if bytes.starts_with(b"DDS ") {
+        load_dds(bytes)
+    } else {
+        Err(Error::UnknownContainer)
+    }
+}
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = bytes.get(offset..offset + 4).ok_or(Error::UnexpectedEof)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+    bytes
+        .get(offset..offset.checked_add(len).ok_or(Error::UnexpectedEof)?)
+        .ok_or(Error::UnexpectedEof)
+}
+
+fn mip_size(size: u32, level: usize) -> u32 {
+    (size >> level).max(1)
+}
+
+/// `TextureFormat::size`, but fails instead of overflowing on sizes from a broken header.
+fn image_size(format: TextureFormat, width: u32, height: u32) -> Result<usize, Error> {
+    let (width, height, unit) = match format.block() {
+        Some((block_width, block_height, block_size)) => (
+            width.div_ceil(block_width),
+            height.div_ceil(block_height),
+            block_size,
+        ),
+        None => (width, height, format.size(1, 1)),
+    };
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|size| size.checked_mul(unit as usize))
+        .ok_or(Error::UnexpectedEof)
+}
+
+/// Check the header against the file before allocating anything for it:
+/// the mip chain can't be longer than the largest side allows, and the base
+/// level of every image has to fit in the file.
+fn validate_size(
+    bytes: &[u8],
+    format: TextureFormat,
+    (width, height, depth): (u32, u32, u32),
+    images: u32,
+    levels: usize,
+) -> Result<(), Error> {
+    let max_levels = 32 - width.max(height).max(depth).leading_zeros() as usize;
+    if levels > max_levels {
+        return Err(Error::InvalidHeader(
+            "more mipmap levels than the size allows",
+        ));
+    }
+    let base_size = image_size(format, width, height)?
+        .checked_mul(images as usize)
+        .ok_or(Error::UnexpectedEof)?;
+    if base_size > bytes.len() {
+        return Err(Error::UnexpectedEof);
+    }
+    Ok(())
+}
+
+fn texture_params(
+    kind: TextureKind,
+    format: TextureFormat,
+    (width, height, depth): (u32, u32, u32),
+    levels: usize,
+) -> TextureParams {
+    let mipmapped = levels > 1;
+    TextureParams {
+        kind,
+        format,
+        width,
+        height,
+        depth,
+        min_filter: FilterMode::Linear,
+        mag_filter: FilterMode::Linear,
+        mipmap_filter: if mipmapped {
+            MipmapFilterMode::Linear
+        } else {
+            MipmapFilterMode::None
+        },
+        allocate_mipmaps: mipmapped,
+        ..Default::default()
+    }
+}
+
+fn ktx2_format(vk_format: u32) -> Option<TextureFormat> {
+    Some(match vk_format {
+        23 => TextureFormat::RGB8,    // VK_FORMAT_R8G8B8_UNORM
+        37 => TextureFormat::RGBA8,   // VK_FORMAT_R8G8B8A8_UNORM
+        43 => TextureFormat::SRGBA8,  // VK_FORMAT_R8G8B8A8_SRGB
+        97 => TextureFormat::RGBA16F, // VK_FORMAT_R16G16B16A16_SFLOAT
+        // BC1_RGB has no punch-through alpha, decoding it as BC1_RGBA only differs
+        // for 3-color blocks that no opaque encoder emits
+        131 | 133 => TextureFormat::BC1,
+        132 | 134 => TextureFormat::BC1SRGB,
+        135 => TextureFormat::BC2,
+        136 => TextureFormat::BC2SRGB,
+        137 => TextureFormat::BC3,
+        138 => TextureFormat::BC3SRGB,
+        139 => TextureFormat::BC4,
+        141 => TextureFormat::BC5,
+        143 => TextureFormat::BC6H,
+        145 => TextureFormat::BC7,
+        146 => TextureFormat::BC7SRGB,
+        147 => TextureFormat::ETC2RGB,
+        148 => TextureFormat::ETC2SRGB,
+        151 => TextureFormat::ETC2RGBA,
+        152 => TextureFormat::ETC2SRGBA,
+        157 => TextureFormat::ASTC4x4,
+        158 => TextureFormat::ASTC4x4SRGB,
+        161 => TextureFormat::ASTC5x5,
+        162 => TextureFormat::ASTC5x5SRGB,
+        165 => TextureFormat::ASTC6x6,
+        166 => TextureFormat::ASTC6x6SRGB,
+        171 => TextureFormat::ASTC8x8,
+        172 => TextureFormat::ASTC8x8SRGB,
+        _ => return None,
+    })
+}
+
+/// Parse a KTX2 file.
+pub fn load_ktx2(bytes: &[u8]) -> Result<LoadedTexture, Error> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(Error::UnknownContainer);
+    }
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    let depth = read_u32(bytes, 28)?;
+    let layers = read_u32(bytes, 32)?;
+    let faces = read_u32(bytes, 36)?;
+    // 0 asks for the mipmaps to be generated at runtime
+    let levels = read_u32(bytes, 40)?.max(1) as usize;
+    let supercompression = read_u32(bytes, 44)?;
+
+    let format = ktx2_format(vk_format).ok_or(Error::UnsupportedFormat(vk_format))?;
+    if supercompression != 0 {
+        return Err(Error::Unsupported("supercompression"));
+    }
+    if width == 0 {
+        return Err(Error::InvalidHeader("zero width"));
+    }
+    let (kind, images) = match (depth, layers, faces) {
+        (0, 0, 1) => (TextureKind::Texture2D, 1),
+        (0, 0, 6) => (TextureKind::CubeMap, 6),
+        (0, layers, 1) => (TextureKind::Texture2DArray, layers),
+        (depth, 0, 1) => (TextureKind::Texture3D, depth),
+        (0, _, 6) => return Err(Error::Unsupported("cubemap arrays")),
+        _ => return Err(Error::Unsupported("array of 3D textures")),
+    };
+    validate_size(bytes, format, (width, height, depth), images, levels)?;
+
+    let mut data = vec![vec![]; images as usize];
+    for level in 0..levels {
+        // level index comes right after the 80 byte header
+        let offset = read_u64(bytes, 80 + level * 24)?;
+        let length = read_u64(bytes, 80 + level * 24 + 8)?;
+        let level_data = slice(
+            bytes,
+            usize::try_from(offset).map_err(|_| Error::UnexpectedEof)?,
+            usize::try_from(length).map_err(|_| Error::UnexpectedEof)?,
+        )?;
+
+        let image_size = image_size(format, mip_size(width, level), mip_size(height, level))?;
+        // 3D textures have less slices in the smaller levels
+        let level_images = match kind {
+            TextureKind::Texture3D => mip_size(depth, level),
+            _ => images,
+        };
+        if level_data.len() < image_size * level_images as usize {
+            return Err(Error::UnexpectedEof);
+        }
+        for (image, bytes) in level_data
+            .chunks_exact(image_size)
+            .take(level_images as usize)
+            .enumerate()
+        {
+            data[image].push(bytes.to_vec());
+        }
+    }
+
+    Ok(LoadedTexture {
+        params: texture_params(kind, format, (width, height, images), levels),
+        data,
+    })
+}
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE3D: u32 = 4;
+
+fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+fn dxgi_format(dxgi_format: u32) -> Option<TextureFormat> {
+    Some(match dxgi_format {
+        10 => TextureFormat::RGBA16F, // DXGI_FORMAT_R16G16B16A16_FLOAT
+        28 => TextureFormat::RGBA8,   // DXGI_FORMAT_R8G8B8A8_UNORM
+        29 => TextureFormat::SRGBA8,  // DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+        71 => TextureFormat::BC1,
+        72 => TextureFormat::BC1SRGB,
+        74 => TextureFormat::BC2,
+        75 => TextureFormat::BC2SRGB,
+        77 => TextureFormat::BC3,
+        78 => TextureFormat::BC3SRGB,
+        80 => TextureFormat::BC4,
+        83 => TextureFormat::BC5,
+        95 => TextureFormat::BC6H,
+        98 => TextureFormat::BC7,
+        99 => TextureFormat::BC7SRGB,
+        _ => return None,
+    })
+}
+
+/// Parse a DDS file, with or without the DX10 header extension.
+pub fn load_dds(bytes: &[u8]) -> Result<LoadedTexture, Error> {
+    if !bytes.starts_with(b"DDS ") {
+        return Err(Error::UnknownContainer);
+    }
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?.max(1);
+    let width = read_u32(bytes, 16)?.max(1);
+    let depth = read_u32(bytes, 24)?.max(1);
+    let levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1) as usize
+    } else {
+        1
+    };
+    let pixel_flags = read_u32(bytes, 80)?;
+    let code = read_u32(bytes, 84)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    let mut data_offset = 128;
+    let mut cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+    let mut volume = caps2 & DDSCAPS2_VOLUME != 0;
+    let mut array_size = 1;
+    let format = if pixel_flags & DDPF_FOURCC != 0 {
+        if code == fourcc(b"DX10") {
+            let dxgi = read_u32(bytes, 128)?;
+            let dimension = read_u32(bytes, 132)?;
+            let misc = read_u32(bytes, 136)?;
+            array_size = read_u32(bytes, 140)?.max(1);
+            data_offset += 20;
+            cubemap = misc & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+            volume = dimension == DDS_DIMENSION_TEXTURE3D;
+            dxgi_format(dxgi).ok_or(Error::UnsupportedFormat(dxgi))?
+        } else if code == fourcc(b"DXT1") {
+            TextureFormat::BC1
+        } else if code == fourcc(b"DXT2") || code == fourcc(b"DXT3") {
+            TextureFormat::BC2
+        } else if code == fourcc(b"DXT4") || code == fourcc(b"DXT5") {
+            TextureFormat::BC3
+        } else if code == fourcc(b"ATI1") || code == fourcc(b"BC4U") {
+            TextureFormat::BC4
+        } else if code == fourcc(b"ATI2") || code == fourcc(b"BC5U") {
+            TextureFormat::BC5
+        } else {
+            return Err(Error::UnsupportedFormat(code));
+        }
+    } else if pixel_flags & DDPF_RGB != 0
+        && read_u32(bytes, 88)? == 32
+        && read_u32(bytes, 92)? == 0xff
+        && read_u32(bytes, 96)? == 0xff00
+        && read_u32(bytes, 100)? == 0xff0000
+    {
+        TextureFormat::RGBA8
+    } else {
+        return Err(Error::Unsupported("uncompressed formats other than RGBA8"));
+    };
+
+    let (kind, images) = match (cubemap, volume, array_size) {
+        (false, false, 1) => (TextureKind::Texture2D, 1),
+        (true, false, 1) => (TextureKind::CubeMap, 6),
+        (false, false, layers) => (TextureKind::Texture2DArray, layers),
+        (false, true, 1) => (TextureKind::Texture3D, depth),
+        (true, false, _) => return Err(Error::Unsupported("cubemap arrays")),
+        _ => return Err(Error::Unsupported("array of 3D textures")),
+    };
+    let size = (width, height, if volume { depth } else { 1 });
+    validate_size(bytes, format, size, images, levels)?;
+
+    // Faces and array layers are stored one after another, each with its own mip chain.
+    // A 3D texture is one mip chain with every level holding all of its slices.
+    let mut data = vec![vec![]; images as usize];
+    let mut offset = data_offset;
+    let surfaces = if kind == TextureKind::Texture3D {
+        1
+    } else {
+        images
+    };
+    for surface in 0..surfaces as usize {
+        for level in 0..levels {
+            let image_size = image_size(format, mip_size(width, level), mip_size(height, level))?;
+            let slices = match kind {
+                TextureKind::Texture3D => mip_size(depth, level),
+                _ => 1,
+            } as usize;
+            let level_data = slice(bytes, offset, image_size * slices)?;
+            offset += image_size * slices;
+            for (slice, bytes) in level_data.chunks_exact(image_size).enumerate() {
+                data[surface + slice].push(bytes.to_vec());
+            }
+        }
+    }
+
+    Ok(LoadedTexture {
+        params: texture_params(kind, format, (width, height, images), levels),
+        data,
+    })
+}
+
+#[test]
+fn test_load_containers() {
+    // 8x4 BC1 with 2 mip levels: 2 blocks, then 1 block
+    let mut dds = b"DDS ".to_vec();
+    dds.resize(128, 0);
+    let header = [
+        (4, 124),
+        (8, 0x1007 | DDSD_MIPMAPCOUNT),
+        (12, 4),
+        (16, 8),
+        (28, 2),
+        (76, 32),
+        (80, DDPF_FOURCC),
+        (84, fourcc(b"DXT1")),
+    ];
+    for (offset, value) in header.iter() {
+        dds[*offset..*offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    dds.extend((0..24).map(|i| i as u8));
+    let texture = load(&dds).unwrap();
+    assert_eq!(texture.params.format, TextureFormat::BC1);
+    assert_eq!(texture.params.kind, TextureKind::Texture2D);
+    assert_eq!((texture.params.width, texture.params.height), (8, 4));
+    assert_eq!(texture.levels(), 2);
+    assert_eq!(texture.data[0][0], (0..16).collect::<Vec<u8>>());
+    assert_eq!(texture.data[0][1], (16..24).collect::<Vec<u8>>());
+    assert!(matches!(load(&dds[..140]), Err(Error::UnexpectedEof)));
+
+    // 1x1 RGBA8 array of 2 layers
+    let mut ktx2 = KTX2_IDENTIFIER.to_vec();
+    ktx2.resize(80 + 24, 0);
+    let header = [(12, 37), (20, 1), (24, 1), (32, 2), (36, 1), (40, 1)];
+    for (offset, value) in header.iter() {
+        ktx2[*offset..*offset + 4].copy_from_slice(&(*value as u32).to_le_bytes());
+    }
+    ktx2[80..88].copy_from_slice(&104u64.to_le_bytes());
+    ktx2[88..96].copy_from_slice(&8u64.to_le_bytes());
+    ktx2.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    let texture = load(&ktx2).unwrap();
+    assert_eq!(texture.params.kind, TextureKind::Texture2DArray);
+    assert_eq!(texture.params.depth, 2);
+    assert_eq!(
+        texture.data,
+        vec![vec![vec![1, 2, 3, 4]], vec![vec![5, 6, 7, 8]]]
+    );
+}
+
+#[cfg(test)]
+fn write_header(bytes: &mut [u8], header: &[(usize, u32)]) {
+    for (offset, value) in header.iter() {
+        bytes[*offset..*offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[test]
+fn test_load_cubemap_faces() {
+    // 2x2 RGBA8 cubemap with 2 mip levels, KTX2 stores the smallest level first
+    let mut ktx2 = KTX2_IDENTIFIER.to_vec();
+    ktx2.resize(80 + 2 * 24, 0);
+    write_header(&mut ktx2, &[(12, 37), (20, 2), (24, 2), (36, 6), (40, 2)]);
+    ktx2[80..88].copy_from_slice(&152u64.to_le_bytes());
+    ktx2[88..96].copy_from_slice(&96u64.to_le_bytes());
+    ktx2[104..112].copy_from_slice(&128u64.to_le_bytes());
+    ktx2[112..120].copy_from_slice(&24u64.to_le_bytes());
+    ktx2.extend(100..124);
+    ktx2.extend(0..96);
+    let texture = load(&ktx2).unwrap();
+    assert_eq!(texture.params.kind, TextureKind::CubeMap);
+    assert_eq!(texture.levels(), 2);
+    assert_eq!(texture.data.len(), 6);
+    for (face, mipmaps) in texture.data.iter().enumerate() {
+        let face = face as u8;
+        assert_eq!(mipmaps[0], (face * 16..face * 16 + 16).collect::<Vec<u8>>());
+        assert_eq!(
+            mipmaps[1],
+            (100 + face * 4..104 + face * 4).collect::<Vec<u8>>()
+        );
+    }
+
+    // the same in a DDS with the DX10 header, every face has its own mip chain
+    let mut dds = b"DDS ".to_vec();
+    dds.resize(148, 0);
+    write_header(
+        &mut dds,
+        &[
+            (4, 124),
+            (8, 0x1007 | DDSD_MIPMAPCOUNT),
+            (12, 2),
+            (16, 2),
+            (28, 2),
+            (76, 32),
+            (80, DDPF_FOURCC),
+            (84, fourcc(b"DX10")),
+            (128, 29),
+            (132, 3),
+            (136, DDS_RESOURCE_MISC_TEXTURECUBE),
+            (140, 1),
+        ],
+    );
+    dds.extend(0..120);
+    let texture = load(&dds).unwrap();
+    assert_eq!(texture.params.kind, TextureKind::CubeMap);
+    assert_eq!(texture.params.format, TextureFormat::SRGBA8);
+    assert_eq!(texture.levels(), 2);
+    assert_eq!(texture.data.len(), 6);
+    for (face, mipmaps) in texture.data.iter().enumerate() {
+        let face = face as u8;
+        assert_eq!(mipmaps[0], (face * 20..face * 20 + 16).collect::<Vec<u8>>());
+        assert_eq!(
+            mipmaps[1],
+            (face * 20 + 16..face * 20 + 20).collect::<Vec<u8>>()
+        );
+    }
+    assert!(matches!(load(&dds[..247]), Err(Error::UnexpectedEof)));
+}
+
+#[test]
+fn test_load_invalid_headers() {
+    let ktx2 = |header: &[(usize, u32)]| {
+        let mut ktx2 = KTX2_IDENTIFIER.to_vec();
+        ktx2.resize(80 + 24 + 64, 0);
+        // level 0 is the 64 bytes after the level index
+        let base = [
+            (12, 37),
+            (20, 1),
+            (24, 1),
+            (36, 1),
+            (40, 1),
+            (80, 104),
+            (88, 64),
+        ];
+        write_header(&mut ktx2, &base);
+        write_header(&mut ktx2, header);
+        load(&ktx2)
+    };
+    assert!(ktx2(&[]).is_ok());
+    assert!(matches!(
+        ktx2(&[(12, 43)]).map(|texture| texture.params.format),
+        Ok(TextureFormat::SRGBA8)
+    ));
+    assert!(matches!(ktx2(&[(20, 0)]), Err(Error::InvalidHeader(_))));
+    assert!(matches!(ktx2(&[(40, 40)]), Err(Error::InvalidHeader(_))));
+    assert!(matches!(
+        ktx2(&[(20, u32::MAX), (24, u32::MAX)]),
+        Err(Error::UnexpectedEof)
+    ));
+    assert!(matches!(ktx2(&[(32, u32::MAX)]), Err(Error::UnexpectedEof)));
+    assert!(matches!(
+        ktx2(&[(28, u32::MAX), (20, 2)]),
+        Err(Error::UnexpectedEof)
+    ));
+
+    let dds = |header: &[(usize, u32)]| {
+        let mut dds = b"DDS ".to_vec();
+        dds.resize(148 + 64, 0);
+        write_header(
+            &mut dds,
+            &[
+                (8, 0x1007 | DDSD_MIPMAPCOUNT),
+                (12, 1),
+                (16, 1),
+                (28, 1),
+                (80, DDPF_FOURCC),
+                (84, fourcc(b"DX10")),
+                (128, 72),
+                (132, 3),
+                (140, 1),
+            ],
+        );
+        write_header(&mut dds, header);
+        load(&dds)
+    };
+    assert!(matches!(
+        dds(&[]).map(|texture| texture.params.format),
+        Ok(TextureFormat::BC1SRGB)
+    ));
+    assert!(matches!(dds(&[(28, 33)]), Err(Error::InvalidHeader(_))));
+    assert!(matches!(
+        dds(&[(12, u32::MAX), (16, u32::MAX)]),
+        Err(Error::UnexpectedEof)
+    ));
+    assert!(matches!(dds(&[(140, u32::MAX)]), Err(Error::UnexpectedEof)));
+}